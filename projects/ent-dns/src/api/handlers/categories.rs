@@ -0,0 +1,34 @@
+use axum::{extract::State, Json};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::api::middleware::auth::AuthUser;
+use crate::api::AppState;
+use crate::dns::categories::CATEGORIES;
+use crate::error::AppResult;
+
+/// List parental-control categories with their current rule counts
+/// (bundled + subscribed lists) and whether they are blocked globally.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+) -> AppResult<Json<Value>> {
+    let rule_counts = state.filter.category_rule_counts().await;
+    let global = state.filter.parental_categories().await;
+
+    let data: Vec<Value> = CATEGORIES
+        .iter()
+        .map(|c| {
+            json!({
+                "id": c.id,
+                "name": c.name,
+                "description": c.description,
+                "rule_count": rule_counts.get(c.id).copied().unwrap_or(0),
+                "enabled_globally": global.iter().any(|g| g == c.id),
+            })
+        })
+        .collect();
+    let total = data.len();
+
+    Ok(Json(json!({ "data": data, "total": total })))
+}
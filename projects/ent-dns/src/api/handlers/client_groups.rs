@@ -5,24 +5,50 @@ use axum::{
 use chrono::Utc;
 use serde::Deserialize;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::api::middleware::auth::AuthUser;
 use crate::api::middleware::rbac::AdminUser;
 use crate::api::AppState;
+use crate::api::handlers::schedules::ensure_schedule_exists;
 use crate::db::models::client_group::*;
+use crate::dns::client_settings::SettingsOverrides;
+use crate::dns::qtype_policy::{self, QtypeAction};
 use crate::error::{AppError, AppResult};
 
+type GroupListRow = (i64, String, String, Option<String>, i32, Option<String>, Option<String>, String, String, i64, i64);
+
+/// Validate API query-type policies and encode them for `client_groups.qtype_policies`
+/// (NULL when empty).
+fn encode_qtype_policies(policies: &HashMap<String, QtypeAction>) -> AppResult<Option<String>> {
+    let normalized = qtype_policy::normalize(policies).map_err(AppError::Validation)?;
+    if normalized.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(&normalized)?))
+}
+
+fn decode_qtype_policies(json: Option<&str>) -> Value {
+    json.and_then(|s| serde_json::from_str(s).ok()).unwrap_or_else(|| json!({}))
+}
+
+/// Validate API setting overrides and encode them for `client_groups.settings`
+/// (NULL when empty).
+fn encode_settings(settings: &SettingsOverrides) -> AppResult<Option<String>> {
+    settings.encode().map_err(AppError::Validation)
+}
+
 /// List all client groups (with client_count and rule_count via JOIN)
 pub async fn list_groups(
     State(state): State<Arc<AppState>>,
     _auth: AuthUser,
 ) -> AppResult<Json<Value>> {
-    let groups: Vec<(i64, String, String, Option<String>, i32, String, String, i64, i64)> =
+    let groups: Vec<GroupListRow> =
         sqlx::query_as(
             r#"
             SELECT
-                g.id, g.name, g.color, g.description, g.priority,
+                g.id, g.name, g.color, g.description, g.priority, g.qtype_policies, g.settings,
                 g.created_at, g.updated_at,
                 COUNT(DISTINCT m.client_id) AS client_count,
                 COUNT(DISTINCT r.id) AS rule_count
@@ -39,13 +65,15 @@ pub async fn list_groups(
     let data: Vec<Value> = groups
         .into_iter()
         .map(
-            |(id, name, color, description, priority, created_at, updated_at, client_count, rule_count)| {
+            |(id, name, color, description, priority, qtype_policies, settings, created_at, updated_at, client_count, rule_count)| {
                 json!({
                     "id": id,
                     "name": name,
                     "color": color,
                     "description": description,
                     "priority": priority,
+                    "qtype_policies": decode_qtype_policies(qtype_policies.as_deref()),
+                    "settings": SettingsOverrides::parse(settings.as_deref()),
                     "client_count": client_count,
                     "rule_count": rule_count,
                     "created_at": created_at,
@@ -86,16 +114,26 @@ pub async fn create_group(
     let color = body.color.unwrap_or_else(|| "#6366f1".to_string());
     let description = body.description;
     let priority = body.priority.unwrap_or(0);
+    let qtype_policies = match &body.qtype_policies {
+        Some(p) => encode_qtype_policies(p)?,
+        None => None,
+    };
+    let settings = match &body.settings {
+        Some(s) => encode_settings(s)?,
+        None => None,
+    };
     let now = Utc::now().to_rfc3339();
 
     let id: i64 = sqlx::query_scalar(
-        "INSERT INTO client_groups (name, color, description, priority, created_at, updated_at)
-         VALUES (?, ?, ?, ?, ?, ?) RETURNING id",
+        "INSERT INTO client_groups (name, color, description, priority, qtype_policies, settings, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
     )
     .bind(&name)
     .bind(&color)
     .bind(&description)
     .bind(priority)
+    .bind(&qtype_policies)
+    .bind(&settings)
     .bind(&now)
     .bind(&now)
     .fetch_one(&state.db)
@@ -107,6 +145,8 @@ pub async fn create_group(
         "color": color,
         "description": description,
         "priority": priority,
+        "qtype_policies": decode_qtype_policies(qtype_policies.as_deref()),
+        "settings": SettingsOverrides::parse(settings.as_deref()),
         "client_count": 0,
         "rule_count": 0,
         "created_at": now,
@@ -122,15 +162,15 @@ pub async fn update_group(
     Json(body): Json<UpdateClientGroupRequest>,
 ) -> AppResult<Json<Value>> {
     // Check if group exists
-    let existing: Option<(i64, String, String, Option<String>, i32, String)> =
+    let existing: Option<(String, String, Option<String>, i32, Option<String>, Option<String>, String)> =
         sqlx::query_as(
-            "SELECT id, name, color, description, priority, created_at FROM client_groups WHERE id = ?",
+            "SELECT name, color, description, priority, qtype_policies, settings, created_at FROM client_groups WHERE id = ?",
         )
         .bind(id)
         .fetch_optional(&state.db)
         .await?;
 
-    let (_, old_name, old_color, old_description, old_priority, created_at) = existing
+    let (old_name, old_color, old_description, old_priority, old_qtype_policies, old_settings, created_at) = existing
         .ok_or_else(|| AppError::NotFound(format!("Client group {} not found", id)))?;
 
     let name = if let Some(new_name) = body.name {
@@ -163,15 +203,25 @@ pub async fn update_group(
     let color = body.color.unwrap_or(old_color);
     let description = body.description.or(old_description);
     let priority = body.priority.unwrap_or(old_priority);
+    let qtype_policies = match &body.qtype_policies {
+        Some(p) => encode_qtype_policies(p)?,
+        None => old_qtype_policies,
+    };
+    let settings = match &body.settings {
+        Some(s) => encode_settings(s)?,
+        None => old_settings,
+    };
     let now = Utc::now().to_rfc3339();
 
     sqlx::query(
-        "UPDATE client_groups SET name = ?, color = ?, description = ?, priority = ?, updated_at = ? WHERE id = ?",
+        "UPDATE client_groups SET name = ?, color = ?, description = ?, priority = ?, qtype_policies = ?, settings = ?, updated_at = ? WHERE id = ?",
     )
     .bind(&name)
     .bind(&color)
     .bind(&description)
     .bind(priority)
+    .bind(&qtype_policies)
+    .bind(&settings)
     .bind(&now)
     .bind(id)
     .execute(&state.db)
@@ -190,6 +240,11 @@ pub async fn update_group(
             cache.invalidate(&client_id).await;
         }
     }
+    // Priority, query-type policies and setting overrides are resolved into
+    // the DNS handler's per-IP client configs
+    if body.priority.is_some() || body.qtype_policies.is_some() || body.settings.is_some() {
+        state.dns_handler.invalidate_client_configs();
+    }
 
     // Get updated counts
     let client_count: i64 = sqlx::query_scalar(
@@ -213,6 +268,8 @@ pub async fn update_group(
         "color": color,
         "description": description,
         "priority": priority,
+        "qtype_policies": decode_qtype_policies(qtype_policies.as_deref()),
+        "settings": SettingsOverrides::parse(settings.as_deref()),
         "client_count": client_count,
         "rule_count": rule_count,
         "created_at": created_at,
@@ -273,6 +330,12 @@ pub async fn delete_group(
         .await?;
 
     // Delete rules
+    let list_count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM client_group_rules WHERE group_id = ? AND rule_type = 'filter_list'",
+    )
+    .bind(id)
+    .fetch_one(&state.db)
+    .await?;
     sqlx::query("DELETE FROM client_group_rules WHERE group_id = ?")
         .bind(id)
         .execute(&state.db)
@@ -284,6 +347,12 @@ pub async fn delete_group(
         .execute(&state.db)
         .await?;
 
+    // Lists no longer bound to any group apply globally again
+    if list_count > 0 {
+        state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+    state.dns_handler.invalidate_group_rules(id);
+
     Ok(Json(json!({
         "message": format!("Group '{}' deleted successfully", name),
         "affected_clients": client_count,
@@ -442,6 +511,11 @@ pub async fn batch_add_clients(
         }
     }
 
+    // Membership changes apply to the next query
+    if added_count > 0 {
+        state.dns_handler.invalidate_client_configs();
+    }
+
     Ok(Json(json!({
         "message": format!("Added {} clients to group", added_count),
         "added_count": added_count,
@@ -489,6 +563,10 @@ pub async fn batch_remove_clients(
         }
     }
 
+    if removed_count > 0 {
+        state.dns_handler.invalidate_client_configs();
+    }
+
     Ok(Json(json!({
         "message": format!("Removed {} clients from group", removed_count),
         "removed_count": removed_count,
@@ -579,6 +657,8 @@ pub async fn batch_move_clients(
         }
     }
 
+    state.dns_handler.invalidate_client_configs();
+
     Ok(Json(json!({
         "message": format!("Moved {} clients to group", moved_count),
         "moved_count": moved_count,
@@ -587,6 +667,20 @@ pub async fn batch_move_clients(
     })))
 }
 
+/// (rule_id, rule, comment, is_enabled, priority, schedule_id, created_at)
+type GroupCustomRuleRow = (String, String, Option<String>, i64, i32, Option<String>, String);
+
+#[derive(sqlx::FromRow)]
+struct GroupFilterListRow {
+    id: String,
+    name: String,
+    is_enabled: i64,
+    rule_count: i64,
+    priority: i32,
+    schedule_id: Option<String>,
+    created_at: String,
+}
+
 /// Get rules for a group
 pub async fn get_group_rules(
     State(state): State<Arc<AppState>>,
@@ -608,9 +702,9 @@ pub async fn get_group_rules(
     let rule_type = params.rule_type.as_deref().unwrap_or("all");
 
     let data: Vec<Value> = if rule_type == "custom_rule" {
-        let rules: Vec<(String, String, Option<String>, i64, i32, String)> = sqlx::query_as(
+        let rules: Vec<GroupCustomRuleRow> = sqlx::query_as(
             r#"
-            SELECT cr.id, cr.rule, cr.comment, cr.is_enabled, gr.priority, cr.created_at
+            SELECT cr.id, cr.rule, cr.comment, cr.is_enabled, gr.priority, gr.schedule_id, cr.created_at
             FROM custom_rules cr
             INNER JOIN client_group_rules gr ON cr.id = gr.rule_id
             WHERE gr.group_id = ? AND gr.rule_type = 'custom_rule'
@@ -623,7 +717,7 @@ pub async fn get_group_rules(
 
         rules
             .into_iter()
-            .map(|(rule_id, rule, comment, is_enabled, priority, created_at)| {
+            .map(|(rule_id, rule, comment, is_enabled, priority, schedule_id, created_at)| {
                 json!({
                     "rule_id": rule_id,
                     "rule_type": "custom_rule",
@@ -631,6 +725,7 @@ pub async fn get_group_rules(
                     "comment": comment,
                     "is_enabled": is_enabled == 1,
                     "priority": priority,
+                    "schedule_id": schedule_id,
                     "created_at": created_at,
                 })
             })
@@ -663,6 +758,65 @@ pub async fn get_group_rules(
                 })
             })
             .collect()
+    } else if rule_type == "category" || rule_type == "blocked_service" {
+        let rows: Vec<(String, i32, Option<String>, String)> = sqlx::query_as(
+            r#"
+            SELECT CAST(rule_id AS TEXT), priority, schedule_id, created_at
+            FROM client_group_rules
+            WHERE group_id = ? AND rule_type = ?
+            ORDER BY priority ASC
+            "#,
+        )
+        .bind(id)
+        .bind(rule_type)
+        .fetch_all(&state.db)
+        .await?;
+
+        rows.into_iter()
+            .map(|(rule_id, priority, schedule_id, created_at)| {
+                let name = if rule_type == "category" {
+                    crate::dns::categories::find(&rule_id).map(|c| c.name)
+                } else {
+                    crate::dns::services::find(&rule_id).map(|s| s.name)
+                };
+                json!({
+                    "rule_id": rule_id,
+                    "rule_type": rule_type,
+                    "name": name,
+                    "priority": priority,
+                    "schedule_id": schedule_id,
+                    "created_at": created_at,
+                })
+            })
+            .collect()
+    } else if rule_type == "filter_list" {
+        let rows: Vec<GroupFilterListRow> = sqlx::query_as(
+            r#"
+            SELECT fl.id, fl.name, fl.is_enabled, fl.rule_count, gr.priority, gr.schedule_id, gr.created_at
+            FROM filter_lists fl
+            INNER JOIN client_group_rules gr ON fl.id = gr.rule_id
+            WHERE gr.group_id = ? AND gr.rule_type = 'filter_list'
+            ORDER BY gr.priority ASC
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                json!({
+                    "rule_id": row.id,
+                    "rule_type": "filter_list",
+                    "name": row.name,
+                    "is_enabled": row.is_enabled == 1,
+                    "rule_count": row.rule_count,
+                    "priority": row.priority,
+                    "schedule_id": row.schedule_id,
+                    "created_at": row.created_at,
+                })
+            })
+            .collect()
     } else {
         Vec::new()
     };
@@ -698,28 +852,53 @@ pub async fn batch_bind_rules(
     let mut bound_count = 0i64;
     let mut skipped_count = 0i64;
     let mut skipped_rules: Vec<Value> = Vec::new();
+    let mut lists_bound = false;
     let now = Utc::now().to_rfc3339();
 
     for rule in &body.rules {
         // Validate rule type
-        if rule.rule_type != "custom_rule" && rule.rule_type != "rewrite" {
+        if !matches!(rule.rule_type.as_str(), "custom_rule" | "rewrite" | "category" | "blocked_service" | "filter_list") {
             return Err(AppError::Validation(format!(
-                "Invalid rule type: {} (must be 'custom_rule' or 'rewrite')",
+                "Invalid rule type: {} (must be 'custom_rule', 'rewrite', 'category', 'blocked_service' or 'filter_list')",
                 rule.rule_type
             )));
         }
 
-        // Check if rule exists in the appropriate table
-        let rule_exists: Option<(String,)> = if rule.rule_type == "custom_rule" {
-            sqlx::query_as("SELECT id FROM custom_rules WHERE id = ?")
-                .bind(&rule.rule_id)
-                .fetch_optional(&state.db)
-                .await?
-        } else {
-            sqlx::query_as("SELECT id FROM dns_rewrites WHERE id = ?")
+        // Check if rule exists in the appropriate table (categories and services are built in)
+        let rule_exists: Option<(String,)> = match rule.rule_type.as_str() {
+            "custom_rule" => {
+                sqlx::query_as("SELECT id FROM custom_rules WHERE id = ?")
+                    .bind(&rule.rule_id)
+                    .fetch_optional(&state.db)
+                    .await?
+            }
+            "rewrite" => {
+                sqlx::query_as("SELECT id FROM dns_rewrites WHERE id = ?")
+                    .bind(&rule.rule_id)
+                    .fetch_optional(&state.db)
+                    .await?
+            }
+            "filter_list" => {
+                // Category and threat lists already have their own scoping
+                let list: Option<(String, bool)> = sqlx::query_as(
+                    "SELECT id, category IS NOT NULL OR threat_category IS NOT NULL FROM filter_lists WHERE id = ?",
+                )
                 .bind(&rule.rule_id)
                 .fetch_optional(&state.db)
-                .await?
+                .await?;
+                if list.as_ref().is_some_and(|(_, tagged)| *tagged) {
+                    skipped_rules.push(json!({
+                        "rule_id": rule.rule_id,
+                        "rule_type": rule.rule_type,
+                        "reason": "Category and threat lists cannot be bound to groups",
+                    }));
+                    skipped_count += 1;
+                    continue;
+                }
+                list.map(|(id, _)| (id,))
+            }
+            "category" => crate::dns::categories::find(&rule.rule_id).map(|c| (c.id.to_string(),)),
+            _ => crate::dns::services::find(&rule.rule_id).map(|s| (s.id.to_string(),)),
         };
 
         if rule_exists.is_none() {
@@ -752,20 +931,34 @@ pub async fn batch_bind_rules(
             continue;
         }
 
+        if let Some(ref schedule_id) = rule.schedule_id {
+            ensure_schedule_exists(&state, schedule_id).await?;
+        }
+
         // Bind rule
         let priority = rule.priority.unwrap_or(0);
         sqlx::query(
-            "INSERT INTO client_group_rules (group_id, rule_id, rule_type, priority, created_at) VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO client_group_rules (group_id, rule_id, rule_type, priority, schedule_id, created_at) VALUES (?, ?, ?, ?, ?, ?)",
         )
         .bind(id)
         .bind(&rule.rule_id)
         .bind(&rule.rule_type)
         .bind(priority)
+        .bind(&rule.schedule_id)
         .bind(&now)
         .execute(&state.db)
         .await?;
 
         bound_count += 1;
+        lists_bound |= rule.rule_type == "filter_list";
+    }
+
+    // Bound lists move from the global rule set to their groups
+    if lists_bound {
+        state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+    if bound_count > 0 {
+        state.dns_handler.invalidate_group_rules(id);
     }
 
     // Invalidate cache for all clients in this group
@@ -831,6 +1024,14 @@ pub async fn batch_unbind_rules(
         }
     }
 
+    // Lists no longer bound to any group apply globally again
+    if rule_type == "filter_list" && unbound_count > 0 {
+        state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+    if unbound_count > 0 {
+        state.dns_handler.invalidate_group_rules(id);
+    }
+
     // Invalidate cache for all clients in this group
     let client_ids: Vec<String> = sqlx::query_scalar(
         "SELECT client_id FROM client_group_memberships WHERE group_id = ?",
@@ -851,3 +1052,54 @@ pub async fn batch_unbind_rules(
     })))
 }
 
+/// Attach or detach the schedule of an existing rule binding
+pub async fn update_binding_schedule(
+    State(state): State<Arc<AppState>>,
+    _auth: AdminUser,
+    Path(id): Path<i64>,
+    Json(body): Json<UpdateBindingScheduleRequest>,
+) -> AppResult<Json<Value>> {
+    if let Some(ref schedule_id) = body.schedule_id {
+        ensure_schedule_exists(&state, schedule_id).await?;
+    }
+
+    let result = sqlx::query(
+        "UPDATE client_group_rules SET schedule_id = ? WHERE group_id = ? AND rule_id = ? AND rule_type = ?",
+    )
+    .bind(&body.schedule_id)
+    .bind(id)
+    .bind(&body.rule_id)
+    .bind(&body.rule_type)
+    .execute(&state.db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!(
+            "Rule {} ({}) is not bound to group {}",
+            body.rule_id, body.rule_type, id
+        )));
+    }
+
+    // Invalidate cache for all clients in this group
+    let client_ids: Vec<String> = sqlx::query_scalar(
+        "SELECT client_id FROM client_group_memberships WHERE group_id = ?",
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+
+    for client_id in client_ids {
+        if let Some(cache) = state.client_config_cache.as_ref() {
+            cache.invalidate(&client_id).await;
+        }
+    }
+
+    state.dns_handler.invalidate_group_rules(id);
+
+    Ok(Json(json!({
+        "rule_id": body.rule_id,
+        "rule_type": body.rule_type,
+        "schedule_id": body.schedule_id,
+    })))
+}
+
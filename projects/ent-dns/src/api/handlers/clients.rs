@@ -10,6 +10,8 @@ use uuid::Uuid;
 
 use crate::api::middleware::auth::AuthUser;
 use crate::api::AppState;
+use crate::api::handlers::schedules::ensure_schedule_exists;
+use crate::dns::client_settings::{self, ClientSettings, SettingsOverrides};
 use crate::error::{AppError, AppResult};
 
 #[derive(Debug, Deserialize)]
@@ -20,6 +22,14 @@ pub struct CreateClientRequest {
     #[serde(default = "default_filter_enabled")]
     pub filter_enabled: bool,
     pub tags: Option<serde_json::Value>,
+    /// Built-in service ids to block for this client (see `/api/v1/blocked-services`).
+    pub blocked_services: Option<Vec<String>>,
+    /// Only block `blocked_services` while this schedule is active.
+    pub blocked_services_schedule_id: Option<String>,
+    /// Only apply filtering to this client while this schedule is active.
+    pub filter_schedule_id: Option<String>,
+    /// Overrides of the global/group settings (blocking mode, SafeSearch, ...).
+    pub settings: Option<SettingsOverrides>,
 }
 
 fn default_filter_enabled() -> bool {
@@ -33,6 +43,74 @@ pub struct UpdateClientRequest {
     pub upstreams: Option<serde_json::Value>,
     pub filter_enabled: Option<bool>,
     pub tags: Option<serde_json::Value>,
+    pub blocked_services: Option<Vec<String>>,
+    /// Empty string detaches the schedule.
+    pub blocked_services_schedule_id: Option<String>,
+    /// Empty string detaches the schedule.
+    pub filter_schedule_id: Option<String>,
+    /// Replaces all overrides; `{}` clears them.
+    pub settings: Option<SettingsOverrides>,
+}
+
+/// Columns returned by the client list / update queries.
+#[derive(sqlx::FromRow)]
+struct ClientRecord {
+    id: String,
+    name: String,
+    identifiers: String,
+    upstreams: Option<String>,
+    filter_enabled: i64,
+    tags: Option<String>,
+    created_at: String,
+    updated_at: String,
+    blocked_services: Option<String>,
+    blocked_services_schedule_id: Option<String>,
+    filter_schedule_id: Option<String>,
+    settings: Option<String>,
+}
+
+const CLIENT_COLUMNS: &str = "id, name, identifiers, upstreams, filter_enabled, tags, created_at, updated_at, \
+     blocked_services, blocked_services_schedule_id, filter_schedule_id, settings";
+
+fn validate_blocked_services(ids: &[String]) -> AppResult<()> {
+    if let Some(unknown) = ids.iter().find(|id| crate::dns::services::find(id).is_none()) {
+        return Err(AppError::Validation(format!("Unknown blocked service: {}", unknown)));
+    }
+    Ok(())
+}
+
+/// Validate an optional schedule reference; `Some("")` means "detach".
+async fn validate_schedule_ref(state: &AppState, id: &Option<String>) -> AppResult<Option<String>> {
+    match id.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(id) => {
+            ensure_schedule_exists(state, id).await?;
+            Ok(Some(id.to_string()))
+        }
+    }
+}
+
+/// Validate client overrides and encode them for `clients.settings`.  A
+/// client's upstreams are set with its `upstreams` field instead.
+fn encode_settings(settings: &SettingsOverrides) -> AppResult<Option<String>> {
+    if settings.upstreams.is_some() {
+        return Err(AppError::Validation("Set client upstreams with the upstreams field".to_string()));
+    }
+    settings.encode().map_err(AppError::Validation)
+}
+
+/// Settings in effect for a client, with the source of each value.
+async fn effective_settings(state: &AppState, defaults: &ClientSettings, client_id: &str) -> AppResult<Value> {
+    let layers = client_settings::load_layers(&state.db, client_id)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to load client settings: {}", e)))?;
+    Ok(client_settings::merge(&layers).effective_json(defaults))
+}
+
+async fn load_default_settings(state: &AppState) -> AppResult<ClientSettings> {
+    ClientSettings::load_defaults(&state.db)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to load default settings: {}", e)))
 }
 
 fn validate_json_array(value: &serde_json::Value) -> AppResult<()> {
@@ -54,28 +132,32 @@ pub async fn list(
     State(state): State<Arc<AppState>>,
     _auth: AuthUser,
 ) -> AppResult<Json<Value>> {
-    let rows: Vec<(String, String, String, Option<String>, i64, Option<String>, String, String)> = sqlx::query_as(
-        "SELECT id, name, identifiers, upstreams, filter_enabled, tags, created_at, updated_at
-         FROM clients ORDER BY created_at DESC"
-    )
+    let rows: Vec<ClientRecord> = sqlx::query_as(&format!(
+        "SELECT {} FROM clients ORDER BY created_at DESC", CLIENT_COLUMNS
+    ))
     .fetch_all(&state.db)
     .await?;
 
-    let data: Vec<Value> = rows
-        .into_iter()
-        .map(|(id, name, identifiers, upstreams, filter_enabled, tags, created_at, updated_at)| {
-            json!({
-                "id": id,
-                "name": name,
-                "identifiers": parse_json_value(&Some(identifiers)),
-                "upstreams": parse_json_value(&upstreams),
-                "filter_enabled": filter_enabled == 1,
-                "tags": parse_json_value(&tags),
-                "created_at": created_at,
-                "updated_at": updated_at,
-            })
-        })
-        .collect();
+    let defaults = load_default_settings(&state).await?;
+    let mut data: Vec<Value> = Vec::with_capacity(rows.len());
+    for c in rows {
+        let effective = effective_settings(&state, &defaults, &c.id).await?;
+        data.push(json!({
+            "id": c.id,
+            "name": c.name,
+            "identifiers": parse_json_value(&Some(c.identifiers)),
+            "upstreams": parse_json_value(&c.upstreams),
+            "filter_enabled": c.filter_enabled == 1,
+            "tags": parse_json_value(&c.tags),
+            "blocked_services": parse_json_value(&c.blocked_services).unwrap_or_else(|| json!([])),
+            "blocked_services_schedule_id": c.blocked_services_schedule_id,
+            "filter_schedule_id": c.filter_schedule_id,
+            "settings": SettingsOverrides::parse(c.settings.as_deref()),
+            "effective_settings": effective,
+            "created_at": c.created_at,
+            "updated_at": c.updated_at,
+        }));
+    }
     let count = data.len();
     Ok(Json(json!({ "data": data, "total": count })))
 }
@@ -92,6 +174,12 @@ pub async fn create(
 
     // Validate identifiers is a non-empty array
     validate_json_array(&body.identifiers)?;
+    let blocked_services = body.blocked_services.clone().unwrap_or_default();
+    validate_blocked_services(&blocked_services)?;
+    let blocked_services_schedule_id = validate_schedule_ref(&state, &body.blocked_services_schedule_id).await?;
+    let filter_schedule_id = validate_schedule_ref(&state, &body.filter_schedule_id).await?;
+    let settings = body.settings.clone().unwrap_or_default();
+    let settings_str = encode_settings(&settings)?;
 
     let id = Uuid::new_v4().to_string();
     let now = Utc::now().to_rfc3339();
@@ -104,10 +192,12 @@ pub async fn create(
         .map(|v| serde_json::to_string(v).map_err(|e| AppError::Internal(format!("Failed to serialize tags: {}", e))))
         .transpose()?;
     let filter_enabled = if body.filter_enabled { 1 } else { 0 };
+    let blocked_services_str = serde_json::to_string(&blocked_services)?;
 
     sqlx::query(
-        "INSERT INTO clients (id, name, identifiers, upstreams, filter_enabled, tags, created_at, updated_at)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        "INSERT INTO clients (id, name, identifiers, upstreams, filter_enabled, tags, created_at, updated_at,
+                              blocked_services, blocked_services_schedule_id, filter_schedule_id, settings)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
     )
     .bind(&id)
     .bind(&name)
@@ -117,8 +207,15 @@ pub async fn create(
     .bind(&tags_str)
     .bind(&now)
     .bind(&now)
+    .bind(&blocked_services_str)
+    .bind(&blocked_services_schedule_id)
+    .bind(&filter_schedule_id)
+    .bind(&settings_str)
     .execute(&state.db)
     .await?;
+    state.dns_handler.reload_clients().await;
+    let defaults = load_default_settings(&state).await?;
+    let effective = effective_settings(&state, &defaults, &id).await?;
 
     Ok(Json(json!({
         "id": id,
@@ -127,6 +224,11 @@ pub async fn create(
         "upstreams": body.upstreams,
         "filter_enabled": body.filter_enabled,
         "tags": body.tags,
+        "blocked_services": blocked_services,
+        "blocked_services_schedule_id": blocked_services_schedule_id,
+        "filter_schedule_id": filter_schedule_id,
+        "settings": settings,
+        "effective_settings": effective,
         "created_at": now,
         "updated_at": now,
     })))
@@ -139,16 +241,26 @@ pub async fn update(
     Json(body): Json<UpdateClientRequest>,
 ) -> AppResult<Json<Value>> {
     // Check if client exists
-    let existing: Option<(String, String, String, Option<String>, i64, Option<String>, String, String)> = sqlx::query_as(
-        "SELECT id, name, identifiers, upstreams, filter_enabled, tags, created_at, updated_at
-         FROM clients WHERE id = ?"
-    )
+    let existing: Option<ClientRecord> = sqlx::query_as(&format!(
+        "SELECT {} FROM clients WHERE id = ?", CLIENT_COLUMNS
+    ))
     .bind(&id)
     .fetch_optional(&state.db)
     .await?;
 
-    let (_, old_name, old_identifiers, old_upstreams, old_filter_enabled, old_tags, created_at, _updated_at) = existing
-        .ok_or_else(|| AppError::NotFound(format!("Client {} not found", id)))?;
+    let ClientRecord {
+        name: old_name,
+        identifiers: old_identifiers,
+        upstreams: old_upstreams,
+        filter_enabled: old_filter_enabled,
+        tags: old_tags,
+        created_at,
+        blocked_services: old_blocked_services,
+        blocked_services_schedule_id: old_services_schedule,
+        filter_schedule_id: old_filter_schedule,
+        settings: old_settings,
+        ..
+    } = existing.ok_or_else(|| AppError::NotFound(format!("Client {} not found", id)))?;
 
     // Prepare new values
     let name = body.name.unwrap_or(old_name);
@@ -180,10 +292,36 @@ pub async fn update(
 
     let filter_enabled = body.filter_enabled.map(|b| if b { 1 } else { 0 }).unwrap_or(old_filter_enabled);
 
+    // Handle blocked services
+    let blocked_services = if let Some(ref new_services) = body.blocked_services {
+        validate_blocked_services(new_services)?;
+        Some(serde_json::to_string(new_services)?)
+    } else {
+        old_blocked_services
+    };
+
+    // Handle schedules (empty string detaches)
+    let blocked_services_schedule_id = if body.blocked_services_schedule_id.is_some() {
+        validate_schedule_ref(&state, &body.blocked_services_schedule_id).await?
+    } else {
+        old_services_schedule
+    };
+    let filter_schedule_id = if body.filter_schedule_id.is_some() {
+        validate_schedule_ref(&state, &body.filter_schedule_id).await?
+    } else {
+        old_filter_schedule
+    };
+
+    let settings = match body.settings {
+        Some(ref new_settings) => encode_settings(new_settings)?,
+        None => old_settings,
+    };
+
     let now = Utc::now().to_rfc3339();
 
     sqlx::query(
-        "UPDATE clients SET name = ?, identifiers = ?, upstreams = ?, filter_enabled = ?, tags = ?, updated_at = ?
+        "UPDATE clients SET name = ?, identifiers = ?, upstreams = ?, filter_enabled = ?, tags = ?, blocked_services = ?,
+                            blocked_services_schedule_id = ?, filter_schedule_id = ?, settings = ?, updated_at = ?
          WHERE id = ?"
     )
     .bind(&name)
@@ -191,15 +329,23 @@ pub async fn update(
     .bind(&upstreams)
     .bind(filter_enabled)
     .bind(&tags)
+    .bind(&blocked_services)
+    .bind(&blocked_services_schedule_id)
+    .bind(&filter_schedule_id)
+    .bind(&settings)
     .bind(&now)
     .bind(&id)
     .execute(&state.db)
     .await?;
+    state.dns_handler.reload_clients().await;
 
     // Parse for response
     let identifiers_json = parse_json_value(&Some(identifiers));
     let tags_json = parse_json_value(&tags);
     let upstreams_json = parse_json_value(&upstreams);
+    let blocked_services_json = parse_json_value(&blocked_services).unwrap_or_else(|| json!([]));
+    let defaults = load_default_settings(&state).await?;
+    let effective = effective_settings(&state, &defaults, &id).await?;
 
     Ok(Json(json!({
         "id": id,
@@ -208,6 +354,11 @@ pub async fn update(
         "upstreams": upstreams_json,
         "filter_enabled": filter_enabled == 1,
         "tags": tags_json,
+        "blocked_services": blocked_services_json,
+        "blocked_services_schedule_id": blocked_services_schedule_id,
+        "filter_schedule_id": filter_schedule_id,
+        "settings": SettingsOverrides::parse(settings.as_deref()),
+        "effective_settings": effective,
         "created_at": created_at,
         "updated_at": now,
     })))
@@ -226,6 +377,7 @@ pub async fn delete(
     if result.rows_affected() == 0 {
         return Err(AppError::NotFound(format!("Client {} not found", id)));
     }
+    state.dns_handler.reload_clients().await;
 
     Ok(Json(json!({"success": true})))
 }
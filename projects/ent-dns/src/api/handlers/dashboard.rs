@@ -135,6 +135,86 @@ pub async fn get_top_clients(
     Ok(Json(json!(data)))
 }
 
+/// Blocked query counts per parental-control category (log reason `category:<id>`).
+pub async fn get_blocked_categories(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Query(params): Query<TrendParams>,
+) -> AppResult<Json<Value>> {
+    let hours = params.hours.unwrap_or(24).clamp(1, 168);
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT substr(reason, 10) AS category, COUNT(*) as cnt FROM query_log
+         WHERE status = 'blocked' AND reason LIKE 'category:%'
+           AND time >= datetime('now', printf('-%d hours', ?))
+         GROUP BY reason ORDER BY cnt DESC"
+    )
+    .bind(hours)
+    .fetch_all(&state.db)
+    .await?;
+
+    let data: Vec<Value> = rows
+        .into_iter()
+        .map(|(category, count)| json!({"category": category, "count": count}))
+        .collect();
+
+    Ok(Json(json!(data)))
+}
+
+/// Summary of suspicious-domain findings (log reason `suspicious:<kind>`):
+/// counts per kind, split into blocked and logged-only, and the most
+/// frequently flagged domains.
+pub async fn get_suspicious_domains(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Query(params): Query<TrendParams>,
+) -> AppResult<Json<Value>> {
+    let hours = params.hours.unwrap_or(24).clamp(1, 168);
+    let kinds: Vec<(String, i64, i64)> = sqlx::query_as(
+        "SELECT substr(reason, 12) AS kind, COUNT(*) AS cnt,
+                SUM(CASE WHEN status = 'blocked' THEN 1 ELSE 0 END) AS blocked
+         FROM query_log
+         WHERE reason LIKE 'suspicious:%'
+           AND time >= datetime('now', printf('-%d hours', ?))
+         GROUP BY reason ORDER BY cnt DESC"
+    )
+    .bind(hours)
+    .fetch_all(&state.db)
+    .await?;
+
+    let domains: Vec<(String, String, i64, i64, i64, String)> = sqlx::query_as(
+        "SELECT question, substr(reason, 12) AS kind, COUNT(*) AS cnt,
+                SUM(CASE WHEN status = 'blocked' THEN 1 ELSE 0 END) AS blocked,
+                COUNT(DISTINCT client_ip) AS clients, MAX(time) AS last_seen
+         FROM query_log
+         WHERE reason LIKE 'suspicious:%'
+           AND time >= datetime('now', printf('-%d hours', ?))
+         GROUP BY question, reason ORDER BY cnt DESC LIMIT 20"
+    )
+    .bind(hours)
+    .fetch_all(&state.db)
+    .await?;
+
+    let by_kind: Vec<Value> = kinds
+        .into_iter()
+        .map(|(kind, count, blocked)| json!({"kind": kind, "count": count, "blocked": blocked}))
+        .collect();
+    let top_domains: Vec<Value> = domains
+        .into_iter()
+        .map(|(domain, kind, count, blocked, clients, last_seen)| {
+            json!({
+                "domain": domain,
+                "kind": kind,
+                "count": count,
+                "blocked": blocked,
+                "clients": clients,
+                "last_seen": last_seen,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({"by_kind": by_kind, "top_domains": top_domains})))
+}
+
 pub async fn get_query_trend(
     State(state): State<Arc<AppState>>,
     _auth: AuthUser,
@@ -4,16 +4,23 @@
 ///   GET  /dns-query?dns=<base64url>   — base64url-encoded DNS wire format
 ///   POST /dns-query                   — body is raw DNS wire format
 ///
+/// Both are also served under `/dns-query/{client_id}`: the client ID maps the
+/// query to the `clients` row it names (its id or a client-ID identifier),
+/// taking precedence over source-address matching.
+///
 /// Response: `Content-Type: application/dns-message`, body is DNS wire format.
 ///
+/// Behind a reverse proxy listed in `api.forwarded_for_from`, the client
+/// address comes from `X-Forwarded-For`.
+///
 /// This endpoint is public (no authentication required) — typical DoH servers are
 /// open resolvers.  Rate-limiting at the reverse-proxy layer is recommended in
 /// production.  The underlying DnsHandler applies the same filter/cache/rewrite
 /// logic as UDP/TCP DNS queries.
 use axum::{
     body::Bytes,
-    extract::{ConnectInfo, Query, State},
-    http::{HeaderValue, StatusCode, header},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
@@ -21,6 +28,7 @@ use serde::Deserialize;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use crate::api::AppState;
+use crate::dns::client_index::is_valid_client_id;
 
 const DNS_MESSAGE_CONTENT_TYPE: &str = "application/dns-message";
 /// RFC 8484 §6: maximum wire-format message size for DoH.
@@ -35,6 +43,7 @@ pub struct DohGetParams {
 pub async fn get_query(
     State(state): State<Arc<AppState>>,
     ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Query(params): Query<DohGetParams>,
 ) -> Response {
     let data = match URL_SAFE_NO_PAD.decode(&params.dns) {
@@ -44,24 +53,86 @@ pub async fn get_query(
         }
     };
 
-    resolve_doh(state, data, peer.ip().to_string()).await
+    resolve_doh(state, data, peer, &headers, None).await
 }
 
 /// POST /dns-query  (Content-Type: application/dns-message)
 pub async fn post_query(
     State(state): State<Arc<AppState>>,
     ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if body.len() > MAX_DNS_MESSAGE_BYTES {
+        return (StatusCode::PAYLOAD_TOO_LARGE, "DNS message too large").into_response();
+    }
+
+    resolve_doh(state, body.to_vec(), peer, &headers, None).await
+}
+
+/// GET /dns-query/{client_id}?dns=<base64url>
+pub async fn get_query_with_client_id(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(client_id): Path<String>,
+    Query(params): Query<DohGetParams>,
+) -> Response {
+    let Some(client_id) = normalize_client_id(&client_id) else {
+        return (StatusCode::BAD_REQUEST, "Invalid client ID").into_response();
+    };
+    let data = match URL_SAFE_NO_PAD.decode(&params.dns) {
+        Ok(d) => d,
+        Err(_) => {
+            return (StatusCode::BAD_REQUEST, "Invalid base64url encoding").into_response();
+        }
+    };
+
+    resolve_doh(state, data, peer, &headers, Some(&client_id)).await
+}
+
+/// POST /dns-query/{client_id}  (Content-Type: application/dns-message)
+pub async fn post_query_with_client_id(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(client_id): Path<String>,
     body: Bytes,
 ) -> Response {
+    let Some(client_id) = normalize_client_id(&client_id) else {
+        return (StatusCode::BAD_REQUEST, "Invalid client ID").into_response();
+    };
     if body.len() > MAX_DNS_MESSAGE_BYTES {
         return (StatusCode::PAYLOAD_TOO_LARGE, "DNS message too large").into_response();
     }
 
-    resolve_doh(state, body.to_vec(), peer.ip().to_string()).await
+    resolve_doh(state, body.to_vec(), peer, &headers, Some(&client_id)).await
+}
+
+/// The peer, or the client it forwards for when it is a trusted reverse proxy.
+fn client_ip(state: &AppState, peer: SocketAddr, headers: &HeaderMap) -> String {
+    let forwarded_for = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok());
+    state.forwarded_for_proxies.forwarded_client(peer.ip(), forwarded_for).to_string()
+}
+
+/// Client IDs are matched case-insensitively.
+fn normalize_client_id(client_id: &str) -> Option<String> {
+    let client_id = client_id.to_ascii_lowercase();
+    is_valid_client_id(&client_id).then_some(client_id)
 }
 
-async fn resolve_doh(state: Arc<AppState>, data: Vec<u8>, client_ip: String) -> Response {
-    match state.dns_handler.handle(data, client_ip).await {
+async fn resolve_doh(
+    state: Arc<AppState>,
+    data: Vec<u8>,
+    peer: SocketAddr,
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+) -> Response {
+    let client_ip = client_ip(&state, peer, headers);
+    match state.dns_handler.handle_with_client_id(data, client_ip, client_id).await {
         Ok(response_bytes) => {
             let mut res = Response::new(axum::body::Body::from(response_bytes));
             res.headers_mut().insert(
@@ -0,0 +1,376 @@
+//! Effective configuration of a client: what the DNS engine applies to its
+//! queries, with the source of every setting and rule.
+//!
+//! Everything is read from the database on each request, so the view
+//! reflects edits immediately.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use crate::api::middleware::auth::AuthUser;
+use crate::api::AppState;
+use crate::db::models::client_group::DnsRuleWithSource;
+use crate::dns::client_settings::{self, ClientSettings, MergedOverrides, SOURCE_GLOBAL};
+use crate::dns::neighbors::MacAddr;
+use crate::dns::{categories, services};
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Deserialize)]
+pub struct EffectiveConfigParams {
+    /// Source address of the queries.
+    pub ip: String,
+    /// MAC of the source; looked up in the neighbour table when omitted.
+    pub mac: Option<String>,
+    /// Client ID sent over DoH or DoT.
+    pub client_id: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ClientRecord {
+    id: String,
+    name: String,
+    identifiers: String,
+    filter_enabled: i64,
+    filter_schedule_id: Option<String>,
+    blocked_services: Option<String>,
+    blocked_services_schedule_id: Option<String>,
+}
+
+/// A rule binding of one of the client's groups, with the bound item.
+#[derive(sqlx::FromRow)]
+struct GroupBindingRow {
+    group_id: i64,
+    group_name: String,
+    rule_type: String,
+    rule_id: String,
+    priority: i32,
+    schedule_id: Option<String>,
+    created_at: String,
+    rule: Option<String>,
+    rule_enabled: Option<i64>,
+    list_name: Option<String>,
+    list_enabled: Option<i64>,
+    list_rule_count: Option<i64>,
+}
+
+async fn load_client(state: &AppState, id: &str) -> AppResult<Option<ClientRecord>> {
+    Ok(sqlx::query_as(
+        "SELECT id, name, identifiers, filter_enabled, filter_schedule_id,
+                blocked_services, blocked_services_schedule_id
+         FROM clients WHERE id = ?"
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+/// `GET /api/v1/clients/{id}/effective-config`
+pub async fn for_client(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<Value>> {
+    let client = load_client(&state, &id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Client {} not found", id)))?;
+    let mut config = effective_config(&state, Some(&client)).await?;
+    config["matched_identifier"] = Value::Null;
+    Ok(Json(config))
+}
+
+/// `GET /api/v1/clients/effective-config?ip=...`: the configuration queries
+/// from this source get, identifying the client as the DNS server would.
+pub async fn for_source(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Query(params): Query<EffectiveConfigParams>,
+) -> AppResult<Json<Value>> {
+    let ip: IpAddr = params
+        .ip
+        .trim()
+        .parse()
+        .map_err(|_| AppError::Validation(format!("Invalid IP address: {}", params.ip)))?;
+    let mac = match params.mac.as_deref().map(str::trim).filter(|m| !m.is_empty()) {
+        Some(m) => Some(MacAddr::parse(m).ok_or_else(|| AppError::Validation(format!("Invalid MAC address: {}", m)))?),
+        None => None,
+    };
+    let client_id = params.client_id.map(|id| id.trim().to_ascii_lowercase()).filter(|id| !id.is_empty());
+
+    let matched = state.dns_handler.match_client(ip, mac, client_id.as_deref()).await;
+    let client = match &matched {
+        Some((id, _)) => load_client(&state, id).await?,
+        None => None,
+    };
+    let mut config = effective_config(&state, client.as_ref()).await?;
+    config["ip"] = json!(ip.to_string());
+    config["matched_identifier"] = match (&client, matched) {
+        (Some(_), Some((_, identifier))) => json!({ "value": identifier.to_string(), "kind": identifier.kind() }),
+        _ => Value::Null,
+    };
+    Ok(Json(config))
+}
+
+/// Configuration applied to `client`'s queries, or to unidentified sources
+/// for `None`.
+async fn effective_config(state: &AppState, client: Option<&ClientRecord>) -> AppResult<Value> {
+    let client_id = client.map(|c| c.id.as_str());
+
+    let groups: Vec<(i64, String, i32)> = match client_id {
+        Some(id) => sqlx::query_as(
+            "SELECT cg.id, cg.name, cg.priority
+             FROM client_group_memberships m
+             JOIN client_groups cg ON cg.id = m.group_id
+             WHERE m.client_id = ?
+             ORDER BY cg.priority ASC, cg.id ASC"
+        )
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?,
+        None => Vec::new(),
+    };
+
+    let defaults = ClientSettings::load_defaults(&state.db)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to load default settings: {}", e)))?;
+    let merged = match client_id {
+        Some(id) => {
+            let layers = client_settings::load_layers(&state.db, id)
+                .await
+                .map_err(|e| AppError::Internal(format!("Failed to load client settings: {}", e)))?;
+            client_settings::merge(&layers)
+        }
+        None => MergedOverrides::default(),
+    };
+    let settings = merged.effective_json(&defaults);
+    let upstreams = match &merged.overrides.upstreams {
+        Some(servers) => json!({
+            "servers": servers,
+            "source": merged.sources.get("upstreams").map_or(SOURCE_GLOBAL, String::as_str),
+        }),
+        None => json!({ "servers": state.dns_handler.default_upstreams(), "source": SOURCE_GLOBAL }),
+    };
+
+    let filtering = state.dns_handler.filtering_state(client_id).await;
+
+    let mut rules = match client {
+        Some(client) => client_rules(state, client).await?,
+        None => Vec::new(),
+    };
+    // Group custom rules replace the global rule set, see DnsHandler::check_domain
+    rules.extend(global_rules(state).await?.into_iter().filter(|rule| {
+        !filtering.group_rules_only || !matches!(rule.rule_type.as_str(), "custom_rule" | "filter_list")
+    }));
+
+    Ok(json!({
+        "client": client.map(|c| json!({
+            "id": c.id,
+            "name": c.name,
+            "identifiers": serde_json::from_str::<Value>(&c.identifiers).unwrap_or_else(|_| json!([])),
+            "filter_enabled": c.filter_enabled == 1,
+        })),
+        "groups": groups.iter().map(|(id, name, priority)| json!({
+            "id": id,
+            "name": name,
+            "priority": priority,
+        })).collect::<Vec<_>>(),
+        "upstreams": upstreams,
+        "filtering": {
+            "enabled": filtering.enabled,
+            "schedule_id": client.and_then(|c| c.filter_schedule_id.clone()),
+            "schedule_active": filtering.schedule_active,
+            "paused_until": filtering.paused_until.map(|t| t.to_rfc3339()),
+            "active": filtering.active,
+            "group_rules_only": filtering.group_rules_only,
+        },
+        "settings": settings,
+        "total_rules": rules.len(),
+        "rules": rules,
+    }))
+}
+
+/// Blocking action of an AdGuard-syntax rule.
+fn rule_action(rule: &str) -> &'static str {
+    if rule.trim_start().starts_with("@@") { "allow" } else { "block" }
+}
+
+/// Rules bound to the client itself and to its groups, in group priority
+/// order.
+async fn client_rules(state: &AppState, client: &ClientRecord) -> AppResult<Vec<DnsRuleWithSource>> {
+    let mut rules = Vec::new();
+
+    // The client's own blocked services
+    let own_services: Vec<String> = client
+        .blocked_services
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default();
+    for id in own_services {
+        let Some(service) = services::find(&id) else {
+            continue;
+        };
+        rules.push(DnsRuleWithSource {
+            id,
+            rule_type: "blocked_service".to_string(),
+            action: Some("block".to_string()),
+            source: "client".to_string(),
+            name: Some(service.name.to_string()),
+            schedule_id: client.blocked_services_schedule_id.clone(),
+            ..Default::default()
+        });
+    }
+
+    let rows: Vec<GroupBindingRow> = sqlx::query_as(
+        r#"
+        SELECT cg.id AS group_id, cg.name AS group_name, cgr.rule_type,
+               CAST(cgr.rule_id AS TEXT) AS rule_id, cgr.priority, cgr.schedule_id, cgr.created_at,
+               cr.rule, cr.is_enabled AS rule_enabled,
+               fl.name AS list_name, fl.is_enabled AS list_enabled, fl.rule_count AS list_rule_count
+        FROM client_group_memberships m
+        JOIN client_groups cg ON cg.id = m.group_id
+        JOIN client_group_rules cgr ON cgr.group_id = m.group_id
+        LEFT JOIN custom_rules cr ON cgr.rule_type = 'custom_rule' AND cr.id = cgr.rule_id
+        LEFT JOIN filter_lists fl ON cgr.rule_type = 'filter_list' AND fl.id = cgr.rule_id
+        WHERE m.client_id = ?
+        ORDER BY cg.priority ASC, cg.id ASC, cgr.priority ASC
+        "#
+    )
+    .bind(&client.id)
+    .fetch_all(&state.db)
+    .await?;
+
+    for row in rows {
+        let mut rule = DnsRuleWithSource {
+            id: row.rule_id,
+            rule_type: row.rule_type,
+            source: "group".to_string(),
+            priority: row.priority,
+            group_id: Some(row.group_id),
+            group_name: Some(row.group_name),
+            schedule_id: row.schedule_id,
+            created_at: Some(row.created_at),
+            ..Default::default()
+        };
+        // Bindings of deleted or disabled items do not apply; rewrites are
+        // resolved from the global list only, so group rewrite bindings are skipped
+        match rule.rule_type.as_str() {
+            "custom_rule" => {
+                let (Some(text), Some(1)) = (row.rule, row.rule_enabled) else {
+                    continue;
+                };
+                rule.action = Some(rule_action(&text).to_string());
+                rule.pattern = Some(text);
+            }
+            "filter_list" => {
+                let (Some(name), Some(1)) = (row.list_name, row.list_enabled) else {
+                    continue;
+                };
+                rule.action = Some("block".to_string());
+                rule.name = Some(name);
+                rule.rule_count = row.list_rule_count;
+            }
+            "category" => {
+                let Some(category) = categories::find(&rule.id) else {
+                    continue;
+                };
+                rule.action = Some("block".to_string());
+                rule.name = Some(category.name.to_string());
+            }
+            "blocked_service" => {
+                let Some(service) = services::find(&rule.id) else {
+                    continue;
+                };
+                rule.action = Some("block".to_string());
+                rule.name = Some(service.name.to_string());
+            }
+            _ => continue,
+        }
+        rules.push(rule);
+    }
+
+    Ok(rules)
+}
+
+/// Rules applying to every source: custom rules, filter lists and threat
+/// feeds not bound to a group, rewrites and the parental-control categories.
+async fn global_rules(state: &AppState) -> AppResult<Vec<DnsRuleWithSource>> {
+    let mut rules = Vec::new();
+    let global = |rule_type: &str| DnsRuleWithSource {
+        rule_type: rule_type.to_string(),
+        source: "global".to_string(),
+        ..Default::default()
+    };
+
+    // Rules synced from filter lists are represented by their list
+    let custom: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT id, rule, created_at FROM custom_rules
+         WHERE is_enabled = 1 AND created_by NOT LIKE 'filter:%'
+         ORDER BY created_at ASC, id ASC"
+    )
+    .fetch_all(&state.db)
+    .await?;
+    for (id, text, created_at) in custom {
+        rules.push(DnsRuleWithSource {
+            id,
+            action: Some(rule_action(&text).to_string()),
+            pattern: Some(text),
+            created_at: Some(created_at),
+            ..global("custom_rule")
+        });
+    }
+
+    // Category lists feed their category; group-bound lists only apply to the group
+    let lists: Vec<(String, String, i64, bool, String)> = sqlx::query_as(
+        "SELECT id, name, rule_count, threat_category IS NOT NULL, created_at FROM filter_lists
+         WHERE is_enabled = 1 AND category IS NULL
+           AND id NOT IN (SELECT CAST(rule_id AS TEXT) FROM client_group_rules WHERE rule_type = 'filter_list')
+         ORDER BY created_at ASC, id ASC"
+    )
+    .fetch_all(&state.db)
+    .await?;
+    for (id, name, rule_count, is_threat_feed, created_at) in lists {
+        rules.push(DnsRuleWithSource {
+            id,
+            action: Some("block".to_string()),
+            name: Some(name),
+            rule_count: Some(rule_count),
+            created_at: Some(created_at),
+            ..global(if is_threat_feed { "threat_feed" } else { "filter_list" })
+        });
+    }
+
+    let rewrites: Vec<(String, String, String, String)> = sqlx::query_as(
+        "SELECT id, domain, answer, created_at FROM dns_rewrites ORDER BY created_at ASC, id ASC"
+    )
+    .fetch_all(&state.db)
+    .await?;
+    for (id, domain, answer, created_at) in rewrites {
+        rules.push(DnsRuleWithSource {
+            id,
+            action: Some("rewrite".to_string()),
+            domain: Some(domain),
+            replacement: Some(answer),
+            created_at: Some(created_at),
+            ..global("rewrite")
+        });
+    }
+
+    for id in state.filter.parental_categories().await {
+        let Some(category) = categories::find(&id) else {
+            continue;
+        };
+        rules.push(DnsRuleWithSource {
+            id,
+            action: Some("block".to_string()),
+            name: Some(category.name.to_string()),
+            ..global("category")
+        });
+    }
+
+    Ok(rules)
+}
@@ -1,15 +1,19 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Multipart, Path, Query, State},
     Json,
 };
 use chrono::Utc;
 use serde::Deserialize;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::api::middleware::auth::AuthUser;
 use crate::api::AppState;
+use crate::auth::jwt::Claims;
+use crate::dns::{list_versions, subscription};
+use crate::dns::list_formats::{ListFormat, FORMAT_NAMES};
 use crate::error::{AppError, AppResult};
 
 #[derive(Debug, Deserialize)]
@@ -18,6 +22,15 @@ pub struct CreateFilterRequest {
     pub url: Option<String>,
     #[serde(default = "default_enabled")]
     pub is_enabled: bool,
+    /// Parental-control category fed by this list (None = global blocklist).
+    pub category: Option<String>,
+    /// Marks the list as a threat feed: "malware", "phishing", "c2" or "other".
+    pub threat_category: Option<String>,
+    /// Threat feed severity: "low", "medium", "high" (default) or "critical".
+    pub severity: Option<String>,
+    /// List format: "auto" (default), "adguard", "hosts", "domains", "dnsmasq",
+    /// "unbound" or "rpz".
+    pub format: Option<String>,
 }
 
 fn default_enabled() -> bool {
@@ -29,69 +42,219 @@ pub struct UpdateFilterRequest {
     pub name: Option<String>,
     pub url: Option<String>,
     pub is_enabled: Option<bool>,
+    /// Set a category, or "" to turn the list back into a global blocklist.
+    pub category: Option<String>,
+    /// Set a threat category, or "" to turn a threat feed back into a normal list.
+    pub threat_category: Option<String>,
+    pub severity: Option<String>,
+    pub format: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct FilterListRow {
+    id: String,
+    name: String,
+    url: Option<String>,
+    is_enabled: i64,
+    rule_count: i64,
+    last_updated: Option<String>,
+    created_at: String,
+    category: Option<String>,
+    threat_category: Option<String>,
+    severity: Option<String>,
+    format: String,
+    expires_hours: Option<i64>,
+    pinned_version: Option<i64>,
+    sync_status: Option<String>,
+    sync_error: Option<String>,
+}
+
+const FILTER_COLUMNS: &str = "id, name, url, is_enabled, rule_count, last_updated, created_at, category, \
+     threat_category, severity, format, expires_hours, pinned_version, sync_status, sync_error";
+
+fn filter_json(row: FilterListRow) -> Value {
+    json!({
+        "id": row.id,
+        "name": row.name,
+        "url": row.url,
+        "is_enabled": row.is_enabled == 1,
+        "rule_count": row.rule_count,
+        "last_updated": row.last_updated,
+        "created_at": row.created_at,
+        "category": row.category,
+        "threat_category": row.threat_category,
+        "severity": row.severity,
+        "format": row.format,
+        "expires_hours": row.expires_hours,
+        "pinned_version": row.pinned_version,
+        "sync_status": row.sync_status,
+        "sync_error": row.sync_error,
+    })
+}
+
+/// Lists are fetched from http(s) URLs or read from `file:///path`.  Local
+/// files are readable by the server process, so only admins may configure them.
+fn validate_source(url: &str, claims: &Claims) -> AppResult<()> {
+    if let Some(path) = subscription::local_path(url) {
+        if !matches!(claims.role.as_str(), "admin" | "super_admin") {
+            return Err(AppError::Unauthorized(
+                "Admin or super_admin role required for file:// lists".to_string(),
+            ));
+        }
+        if !path.is_absolute() {
+            return Err(AppError::Validation(format!(
+                "file:// URL must name an absolute path (file:///path/to/list.txt): {}",
+                url
+            )));
+        }
+        return Ok(());
+    }
+    if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(AppError::Validation(format!(
+            "Invalid URL: {} (must start with http://, https:// or file://)",
+            url
+        )));
+    }
+    Ok(())
+}
+
+fn validate_format(format: &str) -> AppResult<()> {
+    if ListFormat::parse(format).is_none() {
+        return Err(AppError::Validation(format!(
+            "Invalid format: {} (must be one of {})",
+            format,
+            FORMAT_NAMES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+fn validate_category(category: &str) -> AppResult<()> {
+    if crate::dns::categories::find(category).is_none() {
+        return Err(AppError::Validation(format!("Unknown category: {}", category)));
+    }
+    Ok(())
+}
+
+/// Validate a threat feed's category and severity.  A list is either a
+/// parental-control category list or a threat feed, never both.
+fn validate_threat(
+    category: Option<&str>,
+    threat_category: Option<&str>,
+    severity: Option<&str>,
+) -> AppResult<()> {
+    let Some(threat_category) = threat_category else {
+        if severity.is_some() {
+            return Err(AppError::Validation("severity requires a threat_category".to_string()));
+        }
+        return Ok(());
+    };
+    if !crate::dns::threat::is_threat_category(threat_category) {
+        return Err(AppError::Validation(format!(
+            "Unknown threat category: {} (expected one of {})",
+            threat_category,
+            crate::dns::threat::THREAT_CATEGORIES.join(", ")
+        )));
+    }
+    if let Some(severity) = severity {
+        if crate::dns::threat::Severity::parse(severity).is_none() {
+            return Err(AppError::Validation(format!(
+                "Invalid severity: {} (must be low, medium, high or critical)",
+                severity
+            )));
+        }
+    }
+    if category.is_some() {
+        return Err(AppError::Validation(
+            "A filter list cannot be both a category list and a threat feed".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 pub async fn list(
     State(state): State<Arc<AppState>>,
     _auth: AuthUser,
 ) -> AppResult<Json<Value>> {
-    let rows: Vec<(String, String, Option<String>, i64, i64, Option<String>, String)> = sqlx::query_as(
-        "SELECT id, name, url, is_enabled, rule_count, last_updated, created_at
-         FROM filter_lists ORDER BY created_at DESC"
-    )
+    let rows: Vec<FilterListRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM filter_lists ORDER BY created_at DESC",
+        FILTER_COLUMNS
+    ))
     .fetch_all(&state.db)
     .await?;
 
-    let data: Vec<Value> = rows
-        .into_iter()
-        .map(|(id, name, url, is_enabled, rule_count, last_updated, created_at)| {
-            json!({
-                "id": id,
-                "name": name,
-                "url": url,
-                "is_enabled": is_enabled == 1,
-                "rule_count": rule_count,
-                "last_updated": last_updated,
-                "created_at": created_at,
-            })
-        })
-        .collect();
+    let data: Vec<Value> = rows.into_iter().map(filter_json).collect();
     let count = data.len();
     Ok(Json(json!({ "data": data, "total": count })))
 }
 
-pub async fn create(
-    State(state): State<Arc<AppState>>,
-    _auth: AuthUser,
-    Json(body): Json<CreateFilterRequest>,
-) -> AppResult<Json<Value>> {
+/// Validate and insert a new filter list (without syncing it).
+async fn insert_list(state: &AppState, claims: &Claims, body: &CreateFilterRequest) -> AppResult<FilterListRow> {
     let name = body.name.trim().to_string();
     if name.is_empty() {
         return Err(AppError::Validation("Filter name cannot be empty".to_string()));
     }
+    if let Some(ref url) = body.url {
+        validate_source(url, claims)?;
+    }
+    if let Some(ref category) = body.category {
+        validate_category(category)?;
+    }
+    validate_threat(body.category.as_deref(), body.threat_category.as_deref(), body.severity.as_deref())?;
+    let severity = body.threat_category.as_ref().map(|_| body.severity.clone().unwrap_or_else(|| "high".to_string()));
+    let format = body.format.clone().unwrap_or_else(|| "auto".to_string());
+    validate_format(&format)?;
 
-    let id = Uuid::new_v4().to_string();
-    let now = Utc::now().to_rfc3339();
-    let is_enabled = if body.is_enabled { 1 } else { 0 };
+    let row = FilterListRow {
+        id: Uuid::new_v4().to_string(),
+        name,
+        url: body.url.clone(),
+        is_enabled: if body.is_enabled { 1 } else { 0 },
+        rule_count: 0,
+        last_updated: None,
+        created_at: Utc::now().to_rfc3339(),
+        category: body.category.clone(),
+        threat_category: body.threat_category.clone(),
+        severity,
+        format,
+        expires_hours: None,
+        pinned_version: None,
+        sync_status: None,
+        sync_error: None,
+    };
 
     sqlx::query(
-        "INSERT INTO filter_lists (id, name, url, is_enabled, rule_count, last_updated, created_at)
-         VALUES (?, ?, ?, ?, 0, NULL, ?)"
+        "INSERT INTO filter_lists (id, name, url, is_enabled, rule_count, last_updated, created_at, category, threat_category, severity, format)
+         VALUES (?, ?, ?, ?, 0, NULL, ?, ?, ?, ?, ?)"
     )
-    .bind(&id)
-    .bind(&name)
-    .bind(&body.url)
-    .bind(is_enabled)
-    .bind(&now)
+    .bind(&row.id)
+    .bind(&row.name)
+    .bind(&row.url)
+    .bind(row.is_enabled)
+    .bind(&row.created_at)
+    .bind(&row.category)
+    .bind(&row.threat_category)
+    .bind(&row.severity)
+    .bind(&row.format)
     .execute(&state.db)
     .await?;
 
+    Ok(row)
+}
+
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    AuthUser(claims): AuthUser,
+    Json(body): Json<CreateFilterRequest>,
+) -> AppResult<Json<Value>> {
+    let row = insert_list(&state, &claims, &body).await?;
+
     // If URL provided, spawn background sync so the HTTP response returns immediately.
     // Large filter lists (AdGuard, 50k+ rules) can take minutes to fetch—do NOT block here.
     let syncing = if let Some(ref url) = body.url {
         let db = state.db.clone();
         let filter_engine = state.filter.clone();
-        let filter_id = id.clone();
+        let filter_id = row.id.clone();
         let url = url.clone();
         tokio::spawn(async move {
             match crate::dns::subscription::sync_filter_list(&db, &filter_id, &url).await {
@@ -107,62 +270,192 @@ pub async fn create(
         false
     };
 
-    Ok(Json(json!({
-        "id": id,
-        "name": name,
-        "url": body.url,
-        "is_enabled": body.is_enabled,
-        "rule_count": 0,
-        "last_updated": null,
-        "created_at": now,
-        "syncing": syncing,
-    })))
+    let mut data = filter_json(row);
+    data["syncing"] = json!(syncing);
+    Ok(Json(data))
 }
 
-pub async fn update(
+/// Text fields and list body of a multipart upload.
+struct UploadForm {
+    fields: HashMap<String, String>,
+    content: Option<String>,
+}
+
+/// Read a multipart form; the list body is the `file` (or `content`) part.
+async fn read_upload(mut multipart: Multipart) -> AppResult<UploadForm> {
+    let mut form = UploadForm { fields: HashMap::new(), content: None };
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::Validation(format!("Invalid multipart body: {}", e)))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        let bytes = field
+            .bytes()
+            .await
+            .map_err(|e| AppError::Validation(format!("Invalid multipart field {}: {}", name, e)))?;
+        if name == "file" || name == "content" {
+            if bytes.len() > subscription::MAX_LIST_SIZE {
+                return Err(AppError::Validation(format!("List too large: {} bytes", bytes.len())));
+            }
+            let text = String::from_utf8(bytes.to_vec())
+                .map_err(|_| AppError::Validation("List body is not valid UTF-8".to_string()))?;
+            form.content = Some(text);
+        } else {
+            form.fields.insert(name, String::from_utf8_lossy(&bytes).into_owned());
+        }
+    }
+    Ok(form)
+}
+
+/// Create a list from an uploaded body (multipart: `name`, `file`, and
+/// optionally `format`, `category`, `threat_category`, `severity`, `is_enabled`).
+pub async fn upload(
+    State(state): State<Arc<AppState>>,
+    AuthUser(claims): AuthUser,
+    multipart: Multipart,
+) -> AppResult<Json<Value>> {
+    let mut form = read_upload(multipart).await?;
+    let content = form
+        .content
+        .take()
+        .ok_or_else(|| AppError::Validation("Missing list body (multipart field \"file\")".to_string()))?;
+    let body = CreateFilterRequest {
+        name: form.fields.remove("name").unwrap_or_default(),
+        url: None,
+        is_enabled: form.fields.get("is_enabled").is_none_or(|v| v != "false" && v != "0"),
+        category: form.fields.remove("category").filter(|v| !v.is_empty()),
+        threat_category: form.fields.remove("threat_category").filter(|v| !v.is_empty()),
+        severity: form.fields.remove("severity").filter(|v| !v.is_empty()),
+        format: form.fields.remove("format").filter(|v| !v.is_empty()),
+    };
+    let row = insert_list(&state, &claims, &body).await?;
+
+    subscription::import_filter_content(&state.db, &row.id, &content).await?;
+    state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(Json(filter_json(fetch_list(&state, &row.id).await?)))
+}
+
+/// Replace the body of an inline (uploaded) list; multipart field `file`.
+pub async fn replace_content(
     State(state): State<Arc<AppState>>,
     _auth: AuthUser,
     Path(id): Path<String>,
+    multipart: Multipart,
+) -> AppResult<Json<Value>> {
+    let row = fetch_list(&state, &id).await?;
+    if row.url.as_deref().is_some_and(|u| !u.is_empty()) {
+        return Err(AppError::Validation(
+            "List has a URL source; update the source instead of uploading content".to_string(),
+        ));
+    }
+    let content = read_upload(multipart)
+        .await?
+        .content
+        .ok_or_else(|| AppError::Validation("Missing list body (multipart field \"file\")".to_string()))?;
+
+    subscription::import_filter_content(&state.db, &id, &content).await?;
+    state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(Json(filter_json(fetch_list(&state, &id).await?)))
+}
+
+async fn fetch_list(state: &AppState, id: &str) -> AppResult<FilterListRow> {
+    sqlx::query_as(&format!("SELECT {} FROM filter_lists WHERE id = ?", FILTER_COLUMNS))
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Filter list {} not found", id)))
+}
+
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    AuthUser(claims): AuthUser,
+    Path(id): Path<String>,
     Json(body): Json<UpdateFilterRequest>,
 ) -> AppResult<Json<Value>> {
     // Check if filter exists
-    let existing: Option<(String, String, Option<String>, i64, i64, Option<String>, String)> = sqlx::query_as(
-        "SELECT id, name, url, is_enabled, rule_count, last_updated, created_at
-         FROM filter_lists WHERE id = ?"
-    )
+    let existing: Option<FilterListRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM filter_lists WHERE id = ?",
+        FILTER_COLUMNS
+    ))
     .bind(&id)
     .fetch_optional(&state.db)
     .await?;
 
-    let (_, old_name, old_url, old_enabled, old_rule_count, old_last_updated, created_at) = existing
+    let mut row = existing
         .ok_or_else(|| AppError::NotFound(format!("Filter list {} not found", id)))?;
 
-    let name = body.name.unwrap_or(old_name);
-    let url = body.url.or(old_url);
-    let is_enabled = body.is_enabled.map(|b| if b { 1 } else { 0 }).unwrap_or(old_enabled);
+    if let Some(name) = body.name {
+        row.name = name;
+    }
+    if let Some(ref url) = body.url {
+        validate_source(url, &claims)?;
+    }
+    let url_changed = body.url.is_some() && body.url != row.url;
+    if body.url.is_some() {
+        row.url = body.url;
+    }
+    if let Some(enabled) = body.is_enabled {
+        row.is_enabled = if enabled { 1 } else { 0 };
+    }
+    match body.category {
+        Some(c) if c.is_empty() => row.category = None,
+        Some(c) => {
+            validate_category(&c)?;
+            row.category = Some(c);
+        }
+        None => {}
+    }
+    match body.threat_category {
+        Some(c) if c.is_empty() => {
+            row.threat_category = None;
+            row.severity = None;
+        }
+        Some(c) => {
+            row.severity = row.severity.or_else(|| Some("high".to_string()));
+            row.threat_category = Some(c);
+        }
+        None => {}
+    }
+    if body.severity.is_some() {
+        row.severity = body.severity;
+    }
+    validate_threat(row.category.as_deref(), row.threat_category.as_deref(), row.severity.as_deref())?;
+    // A new source or format must be downloaded and parsed again in full
+    let mut refetch = url_changed;
+    if let Some(format) = body.format {
+        validate_format(&format)?;
+        refetch |= format != row.format;
+        row.format = format;
+    }
 
     sqlx::query(
-        "UPDATE filter_lists SET name = ?, url = ?, is_enabled = ? WHERE id = ?"
+        "UPDATE filter_lists SET name = ?, url = ?, is_enabled = ?, category = ?, threat_category = ?, severity = ?,
+         format = ? WHERE id = ?"
     )
-    .bind(&name)
-    .bind(&url)
-    .bind(is_enabled)
+    .bind(&row.name)
+    .bind(&row.url)
+    .bind(row.is_enabled)
+    .bind(&row.category)
+    .bind(&row.threat_category)
+    .bind(&row.severity)
+    .bind(&row.format)
     .bind(&id)
     .execute(&state.db)
     .await?;
+    if refetch {
+        sqlx::query("UPDATE filter_lists SET etag = NULL, last_modified = NULL WHERE id = ?")
+            .bind(&id)
+            .execute(&state.db)
+            .await?;
+    }
 
     // Hot-reload filter engine
     state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
 
-    Ok(Json(json!({
-        "id": id,
-        "name": name,
-        "url": url,
-        "is_enabled": is_enabled == 1,
-        "rule_count": old_rule_count,
-        "last_updated": old_last_updated,
-        "created_at": created_at,
-    })))
+    Ok(Json(filter_json(row)))
 }
 
 pub async fn delete(
@@ -175,6 +468,18 @@ pub async fn delete(
         .bind(format!("filter:{}", id))
         .execute(&state.db)
         .await?;
+    sqlx::query("DELETE FROM filter_list_versions WHERE filter_id = ?")
+        .bind(&id)
+        .execute(&state.db)
+        .await?;
+    sqlx::query("DELETE FROM filter_sync_history WHERE filter_id = ?")
+        .bind(&id)
+        .execute(&state.db)
+        .await?;
+    let unbound = sqlx::query("DELETE FROM client_group_rules WHERE rule_id = ? AND rule_type = 'filter_list'")
+        .bind(&id)
+        .execute(&state.db)
+        .await?;
 
     let result = sqlx::query("DELETE FROM filter_lists WHERE id = ?")
         .bind(&id)
@@ -187,15 +492,26 @@ pub async fn delete(
 
     // Hot-reload filter engine
     state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    if unbound.rows_affected() > 0 {
+        state.dns_handler.invalidate_client_configs();
+    }
 
     Ok(Json(json!({"success": true})))
 }
 
+#[derive(Deserialize)]
+pub struct RefreshParams {
+    /// Apply the update even if it fails the sanity checks.
+    #[serde(default)]
+    force: bool,
+}
+
 /// Manually refresh a remote filter list
 pub async fn refresh(
     State(state): State<Arc<AppState>>,
     _auth: AuthUser,
     Path(id): Path<String>,
+    Query(params): Query<RefreshParams>,
 ) -> AppResult<Json<Value>> {
     // Get filter list info
     let filter: Option<(String, Option<String>)> = sqlx::query_as(
@@ -218,7 +534,12 @@ pub async fn refresh(
     let filter_engine = state.filter.clone();
     let filter_id = id.clone();
     tokio::spawn(async move {
-        match crate::dns::subscription::sync_filter_list(&db, &filter_id, &url).await {
+        let result = if params.force {
+            subscription::force_sync_filter_list(&db, &filter_id, &url).await
+        } else {
+            subscription::sync_filter_list(&db, &filter_id, &url).await
+        };
+        match result {
             Ok(n) => {
                 tracing::info!("Background refresh filter {}: {} rules", filter_id, n);
                 let _ = filter_engine.reload().await;
@@ -235,3 +556,150 @@ pub async fn refresh(
         "message": "同步已在后台启动，请稍后刷新查看结果"
     })))
 }
+
+#[derive(Deserialize)]
+pub struct HistoryParams {
+    #[serde(default = "default_history_limit")]
+    limit: i64,
+}
+
+fn default_history_limit() -> i64 {
+    50
+}
+
+#[derive(sqlx::FromRow)]
+struct SyncHistoryRow {
+    id: i64,
+    started_at: String,
+    duration_ms: i64,
+    bytes: i64,
+    status: String,
+    rule_count: Option<i64>,
+    error: Option<String>,
+}
+
+/// Sync attempts of a list, newest first.
+pub async fn history(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path(id): Path<String>,
+    Query(params): Query<HistoryParams>,
+) -> AppResult<Json<Value>> {
+    fetch_list(&state, &id).await?;
+    let rows: Vec<SyncHistoryRow> = sqlx::query_as(
+        "SELECT id, started_at, duration_ms, bytes, status, rule_count, error
+         FROM filter_sync_history WHERE filter_id = ? ORDER BY id DESC LIMIT ?"
+    )
+    .bind(&id)
+    .bind(params.limit.clamp(1, 500))
+    .fetch_all(&state.db)
+    .await?;
+
+    let data: Vec<Value> = rows
+        .into_iter()
+        .map(|r| {
+            json!({
+                "id": r.id,
+                "started_at": r.started_at,
+                "duration_ms": r.duration_ms,
+                "bytes": r.bytes,
+                "status": r.status,
+                "rule_count": r.rule_count,
+                "error": r.error,
+            })
+        })
+        .collect();
+    let total = data.len();
+    Ok(Json(json!({ "data": data, "total": total })))
+}
+
+/// Stored versions of a list, newest first.
+pub async fn versions(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<Value>> {
+    let list = fetch_list(&state, &id).await?;
+    let versions = list_versions::list_versions(&state.db, &id).await?;
+    let data: Vec<Value> = versions
+        .into_iter()
+        .map(|v| {
+            let pinned = list.pinned_version == Some(v.id);
+            let mut item = json!(v);
+            item["pinned"] = json!(pinned);
+            item
+        })
+        .collect();
+    let total = data.len();
+    Ok(Json(json!({ "data": data, "total": total, "pinned_version": list.pinned_version })))
+}
+
+#[derive(Deserialize)]
+pub struct DiffParams {
+    /// Version to compare against (default: the previous version).
+    base: Option<i64>,
+    #[serde(default = "default_diff_limit")]
+    limit: usize,
+}
+
+fn default_diff_limit() -> usize {
+    1000
+}
+
+/// Rules added and removed by a version relative to `base`.
+pub async fn version_diff(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path((id, version)): Path<(String, i64)>,
+    Query(params): Query<DiffParams>,
+) -> AppResult<Json<Value>> {
+    let not_found = |v: i64| AppError::NotFound(format!("Version {} of filter list {} not found", v, id));
+    let target = list_versions::version_rules(&state.db, &id, version)
+        .await?
+        .ok_or_else(|| not_found(version))?;
+    let base = match params.base {
+        Some(base) => Some(base),
+        None => list_versions::previous_version(&state.db, &id, version).await?,
+    };
+    let base_rules = match base {
+        Some(b) => list_versions::version_rules(&state.db, &id, b).await?.ok_or_else(|| not_found(b))?,
+        None => Vec::new(),
+    };
+
+    let diff = list_versions::diff(&base_rules, &target);
+    let limit = params.limit.clamp(1, 100_000);
+    Ok(Json(json!({
+        "version": version,
+        "base": base,
+        "added_count": diff.added.len(),
+        "removed_count": diff.removed.len(),
+        "added": &diff.added[..diff.added.len().min(limit)],
+        "removed": &diff.removed[..diff.removed.len().min(limit)],
+    })))
+}
+
+/// Roll a list back to a version and pin it there until unpinned.
+pub async fn pin_version(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path((id, version)): Path<(String, i64)>,
+) -> AppResult<Json<Value>> {
+    fetch_list(&state, &id).await?;
+    list_versions::pin(&state.db, &id, version)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Version {} of filter list {} not found", version, id)))?;
+    state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(Json(filter_json(fetch_list(&state, &id).await?)))
+}
+
+/// Unpin a list; its latest version becomes active.
+pub async fn unpin(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<Value>> {
+    fetch_list(&state, &id).await?;
+    list_versions::unpin(&state.db, &id).await?;
+    state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(Json(filter_json(fetch_list(&state, &id).await?)))
+}
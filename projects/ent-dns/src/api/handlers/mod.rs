@@ -5,6 +5,8 @@ pub mod query_log;
 pub mod filters;
 pub mod rules;
 pub mod clients;
+pub mod runtime_clients;
+pub mod effective_config;
 pub mod client_groups;
 pub mod settings;
 pub mod users;
@@ -18,3 +20,8 @@ pub mod doh;
 pub mod rule_validation;
 pub mod query_log_advanced;
 pub mod query_log_templates;
+pub mod categories;
+pub mod services;
+pub mod schedules;
+pub mod pauses;
+pub mod security_events;
@@ -0,0 +1,191 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::api::middleware::auth::AuthUser;
+use crate::api::middleware::rbac::AdminUser;
+use crate::api::AppState;
+use crate::error::{AppError, AppResult};
+
+/// Longest pause accepted by the API (one week).
+const MAX_PAUSE_MINUTES: i64 = 7 * 24 * 60;
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePauseRequest {
+    /// "global", "client" or "group"
+    pub scope: String,
+    /// Client id or group id; omitted for global pauses.
+    pub target_id: Option<String>,
+    pub duration_minutes: i64,
+    pub reason: Option<String>,
+}
+
+type PauseRow = (String, String, String, String, Option<String>, String, String);
+
+fn pause_json(row: PauseRow) -> Value {
+    let (id, scope, target_id, expires_at, reason, created_by, created_at) = row;
+    let remaining = chrono::DateTime::parse_from_rfc3339(&expires_at)
+        .map(|t| (t.with_timezone(&Utc) - Utc::now()).num_seconds().max(0))
+        .unwrap_or(0);
+
+    json!({
+        "id": id,
+        "scope": scope,
+        "target_id": if target_id.is_empty() { None } else { Some(target_id) },
+        "expires_at": expires_at,
+        "remaining_seconds": remaining,
+        "reason": reason,
+        "created_by": created_by,
+        "created_at": created_at,
+    })
+}
+
+/// List pauses that are currently in effect.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+) -> AppResult<Json<Value>> {
+    let rows: Vec<PauseRow> = sqlx::query_as(
+        "SELECT id, scope, target_id, expires_at, reason, created_by, created_at
+         FROM filter_pauses WHERE expires_at > ? ORDER BY expires_at ASC"
+    )
+    .bind(Utc::now().to_rfc3339())
+    .fetch_all(&state.db)
+    .await?;
+
+    let data: Vec<Value> = rows.into_iter().map(pause_json).collect();
+    let total = data.len();
+    Ok(Json(json!({ "data": data, "total": total })))
+}
+
+/// Pause filtering for a scope until now + `duration_minutes`.  Re-pausing the
+/// same scope replaces the previous expiry.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Json(body): Json<CreatePauseRequest>,
+) -> AppResult<Json<Value>> {
+    if !(1..=MAX_PAUSE_MINUTES).contains(&body.duration_minutes) {
+        return Err(AppError::Validation(format!(
+            "duration_minutes must be between 1 and {}",
+            MAX_PAUSE_MINUTES
+        )));
+    }
+
+    let target_id = body.target_id.as_deref().map(str::trim).unwrap_or("").to_string();
+    match body.scope.as_str() {
+        "global" => {
+            if !target_id.is_empty() {
+                return Err(AppError::Validation("Global pauses take no target_id".to_string()));
+            }
+        }
+        "client" | "group" => {
+            if target_id.is_empty() {
+                return Err(AppError::Validation(format!("target_id is required for {} pauses", body.scope)));
+            }
+            let exists: Option<String> = if body.scope == "client" {
+                sqlx::query_scalar("SELECT id FROM clients WHERE id = ?")
+                    .bind(&target_id)
+                    .fetch_optional(&state.db)
+                    .await?
+            } else {
+                sqlx::query_scalar("SELECT CAST(id AS TEXT) FROM client_groups WHERE id = ?")
+                    .bind(&target_id)
+                    .fetch_optional(&state.db)
+                    .await?
+            };
+            if exists.is_none() {
+                return Err(AppError::NotFound(format!("{} {} not found", body.scope, target_id)));
+            }
+        }
+        other => {
+            return Err(AppError::Validation(format!(
+                "Invalid scope: {} (must be 'global', 'client' or 'group')",
+                other
+            )));
+        }
+    }
+
+    let now = Utc::now();
+    let now_str = now.to_rfc3339();
+    let expires_at = (now + Duration::minutes(body.duration_minutes)).to_rfc3339();
+    let id = Uuid::new_v4().to_string();
+    let claims = &admin.0;
+
+    // Prune expired pauses; they no longer have any effect
+    sqlx::query("DELETE FROM filter_pauses WHERE expires_at <= ?")
+        .bind(&now_str)
+        .execute(&state.db)
+        .await?;
+
+    sqlx::query(
+        "INSERT OR REPLACE INTO filter_pauses (id, scope, target_id, expires_at, reason, created_by, created_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&id)
+    .bind(&body.scope)
+    .bind(&target_id)
+    .bind(&expires_at)
+    .bind(&body.reason)
+    .bind(&claims.username)
+    .bind(&now_str)
+    .execute(&state.db)
+    .await?;
+
+    state.dns_handler.reload_pauses().await.map_err(|e| AppError::Internal(e.to_string()))?;
+
+    crate::db::audit::log_action(
+        state.db.clone(),
+        claims.sub.clone(),
+        claims.username.clone(),
+        "pause_filtering",
+        body.scope.clone(),
+        (!target_id.is_empty()).then(|| target_id.clone()),
+        Some(format!("until {} ({} min)", expires_at, body.duration_minutes)),
+        "unknown".to_string(),
+    );
+
+    Ok(Json(pause_json((id, body.scope, target_id, expires_at, body.reason, claims.username.clone(), now_str))))
+}
+
+/// Resume filtering before the pause expires.
+pub async fn delete(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<Value>> {
+    let existing: Option<(String, String)> =
+        sqlx::query_as("SELECT scope, target_id FROM filter_pauses WHERE id = ?")
+            .bind(&id)
+            .fetch_optional(&state.db)
+            .await?;
+    let (scope, target_id) = existing
+        .ok_or_else(|| AppError::NotFound(format!("Pause {} not found", id)))?;
+
+    sqlx::query("DELETE FROM filter_pauses WHERE id = ?")
+        .bind(&id)
+        .execute(&state.db)
+        .await?;
+
+    state.dns_handler.reload_pauses().await.map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let claims = &admin.0;
+    crate::db::audit::log_action(
+        state.db.clone(),
+        claims.sub.clone(),
+        claims.username.clone(),
+        "resume_filtering",
+        scope,
+        (!target_id.is_empty()).then_some(target_id),
+        None,
+        "unknown".to_string(),
+    );
+
+    Ok(Json(json!({"success": true})))
+}
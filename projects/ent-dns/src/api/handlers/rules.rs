@@ -138,11 +138,26 @@ pub async fn create(
     })))
 }
 
+/// Client groups binding any of the rules `ids`.
+async fn bound_groups(state: &AppState, ids: &[String]) -> AppResult<Vec<i64>> {
+    let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let sql = format!(
+        "SELECT DISTINCT group_id FROM client_group_rules WHERE rule_type = 'custom_rule' AND rule_id IN ({})",
+        placeholders
+    );
+    let mut q = sqlx::query_scalar(&sql);
+    for id in ids {
+        q = q.bind(id);
+    }
+    Ok(q.fetch_all(&state.db).await?)
+}
+
 pub async fn delete(
     State(state): State<Arc<AppState>>,
     _auth: AuthUser,
     Path(id): Path<String>,
 ) -> AppResult<Json<Value>> {
+    let groups = bound_groups(&state, std::slice::from_ref(&id)).await?;
     let result = sqlx::query("DELETE FROM custom_rules WHERE id = ?")
         .bind(&id)
         .execute(&state.db)
@@ -153,6 +168,9 @@ pub async fn delete(
     }
 
     state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    for group_id in groups {
+        state.dns_handler.invalidate_group_rules(group_id);
+    }
 
     Ok(Json(json!({"success": true})))
 }
@@ -173,6 +191,7 @@ pub async fn bulk_action(
     }
 
     let placeholders = req.ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let groups = bound_groups(&state, &req.ids).await?;
 
     let affected: u64 = match req.action.as_str() {
         "enable" => {
@@ -212,6 +231,11 @@ pub async fn bulk_action(
     };
 
     state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    if affected > 0 {
+        for group_id in groups {
+            state.dns_handler.invalidate_group_rules(group_id);
+        }
+    }
 
     Ok(Json(json!({"affected": affected})))
 }
@@ -0,0 +1,153 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use crate::api::handlers::clients::{self, CreateClientRequest};
+use crate::api::middleware::auth::AuthUser;
+use crate::api::AppState;
+use crate::dns::runtime_clients::{self, LeaseFormat};
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Deserialize)]
+pub struct ImportLeasesRequest {
+    pub format: LeaseFormat,
+    /// Contents of the lease file.
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PromoteRequest {
+    /// Defaults to the runtime client's host name, else its address.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct RuntimeClientRecord {
+    ip: String,
+    first_seen: Option<String>,
+    last_seen: Option<String>,
+    query_count: i64,
+    rdns_hostname: Option<String>,
+    dhcp_hostname: Option<String>,
+    mac: Option<String>,
+}
+
+const RUNTIME_CLIENT_COLUMNS: &str = "ip, first_seen, last_seen, query_count, rdns_hostname, dhcp_hostname, mac";
+
+impl RuntimeClientRecord {
+    /// Lease names win over PTR names.
+    fn hostname(&self) -> (Option<&str>, Option<&'static str>) {
+        match (&self.dhcp_hostname, &self.rdns_hostname) {
+            (Some(name), _) => (Some(name), Some("dhcp")),
+            (None, Some(name)) => (Some(name), Some("rdns")),
+            (None, None) => (None, None),
+        }
+    }
+}
+
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+) -> AppResult<Json<Value>> {
+    let rows: Vec<RuntimeClientRecord> = sqlx::query_as(&format!(
+        "SELECT {} FROM runtime_clients ORDER BY last_seen IS NULL, last_seen DESC, ip ASC",
+        RUNTIME_CLIENT_COLUMNS
+    ))
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut data = Vec::with_capacity(rows.len());
+    for row in &rows {
+        // Persistent client the address is already attributed to, if any
+        let client_id = match row.ip.parse::<IpAddr>() {
+            Ok(ip) => state.dns_handler.client_for_ip(ip).await,
+            Err(_) => None,
+        };
+        let (hostname, hostname_source) = row.hostname();
+        data.push(json!({
+            "ip": row.ip,
+            "hostname": hostname,
+            "hostname_source": hostname_source,
+            "mac": row.mac,
+            "first_seen": row.first_seen,
+            "last_seen": row.last_seen,
+            "query_count": row.query_count,
+            "client_id": client_id,
+        }));
+    }
+    let count = data.len();
+    Ok(Json(json!({ "data": data, "total": count })))
+}
+
+pub async fn import_leases(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Json(body): Json<ImportLeasesRequest>,
+) -> AppResult<Json<Value>> {
+    let leases = body.format.parse(&body.content);
+    if leases.is_empty() {
+        return Err(AppError::Validation("No leases found in content".to_string()));
+    }
+    let imported = runtime_clients::import_leases(&state.db, &leases)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to import leases: {}", e)))?;
+    Ok(Json(json!({ "imported": imported })))
+}
+
+/// Create a persistent client identified by the runtime client's address,
+/// and its MAC when a lease provided one.
+pub async fn promote(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    Path(ip): Path<String>,
+    Json(body): Json<PromoteRequest>,
+) -> AppResult<Json<Value>> {
+    let addr: IpAddr = ip
+        .parse()
+        .map_err(|_| AppError::Validation(format!("Invalid IP address: {}", ip)))?;
+    let row: RuntimeClientRecord = sqlx::query_as(&format!(
+        "SELECT {} FROM runtime_clients WHERE ip = ?", RUNTIME_CLIENT_COLUMNS
+    ))
+    .bind(addr.to_string())
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Runtime client {} not found", ip)))?;
+
+    if let Some(client_id) = state.dns_handler.client_for_ip(addr).await {
+        return Err(AppError::Conflict(format!("{} already belongs to client {}", row.ip, client_id)));
+    }
+
+    let name = body
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .or(row.hostname().0)
+        .unwrap_or(&row.ip)
+        .to_string();
+    let mut identifiers = vec![row.ip.clone()];
+    identifiers.extend(row.mac.clone());
+
+    clients::create(
+        State(state),
+        auth,
+        Json(CreateClientRequest {
+            name,
+            identifiers: json!(identifiers),
+            upstreams: None,
+            filter_enabled: true,
+            tags: None,
+            blocked_services: None,
+            blocked_services_schedule_id: None,
+            filter_schedule_id: None,
+            settings: None,
+        }),
+    )
+    .await
+}
@@ -0,0 +1,241 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::api::middleware::auth::AuthUser;
+use crate::api::middleware::rbac::AdminUser;
+use crate::api::AppState;
+use crate::dns::schedule::{Schedule, TimeRange};
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateScheduleRequest {
+    pub name: String,
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// Weekday names, e.g. `["mon", "tue", "wed", "thu", "fri"]`.
+    pub weekdays: Vec<String>,
+    pub time_ranges: Vec<TimeRange>,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateScheduleRequest {
+    pub name: Option<String>,
+    pub timezone: Option<String>,
+    pub weekdays: Option<Vec<String>>,
+    pub time_ranges: Option<Vec<TimeRange>>,
+}
+
+type ScheduleRow = (String, String, String, String, String, String, String);
+
+/// Serialize a schedule row, including its current state and next transition.
+fn schedule_json(row: ScheduleRow) -> Value {
+    let (id, name, timezone, weekdays, time_ranges, created_at, updated_at) = row;
+    let compiled = Schedule::from_row(&id, &timezone, &weekdays, &time_ranges).ok();
+    let now = Utc::now();
+
+    json!({
+        "id": id,
+        "name": name,
+        "timezone": timezone,
+        "weekdays": serde_json::from_str::<Value>(&weekdays).unwrap_or_else(|_| json!([])),
+        "time_ranges": serde_json::from_str::<Value>(&time_ranges).unwrap_or_else(|_| json!([])),
+        "active_now": compiled.as_ref().map(|s| s.is_active_at(now)),
+        "next_transition": compiled.as_ref().and_then(|s| s.next_transition(now)).map(|t| t.to_rfc3339()),
+        "created_at": created_at,
+        "updated_at": updated_at,
+    })
+}
+
+async fn fetch_schedule(state: &AppState, id: &str) -> AppResult<ScheduleRow> {
+    sqlx::query_as(
+        "SELECT id, name, timezone, weekdays, time_ranges, created_at, updated_at
+         FROM schedules WHERE id = ?"
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Schedule {} not found", id)))
+}
+
+/// Validation error unless `id` names an existing schedule.  Used when
+/// attaching schedules to group bindings and clients.
+pub(crate) async fn ensure_schedule_exists(state: &AppState, id: &str) -> AppResult<()> {
+    let exists: Option<String> = sqlx::query_scalar("SELECT id FROM schedules WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?;
+    if exists.is_none() {
+        return Err(AppError::Validation(format!("Unknown schedule: {}", id)));
+    }
+    Ok(())
+}
+
+async fn ensure_name_free(state: &AppState, name: &str, except_id: Option<&str>) -> AppResult<()> {
+    let existing: Option<String> = sqlx::query_scalar("SELECT id FROM schedules WHERE name = ?")
+        .bind(name)
+        .fetch_optional(&state.db)
+        .await?;
+    match existing {
+        Some(id) if Some(id.as_str()) != except_id => {
+            Err(AppError::Conflict(format!("Schedule '{}' already exists", name)))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+) -> AppResult<Json<Value>> {
+    let rows: Vec<ScheduleRow> = sqlx::query_as(
+        "SELECT id, name, timezone, weekdays, time_ranges, created_at, updated_at
+         FROM schedules ORDER BY name ASC"
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let data: Vec<Value> = rows.into_iter().map(schedule_json).collect();
+    let total = data.len();
+    Ok(Json(json!({ "data": data, "total": total })))
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<Value>> {
+    Ok(Json(schedule_json(fetch_schedule(&state, &id).await?)))
+}
+
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    _auth: AdminUser,
+    Json(body): Json<CreateScheduleRequest>,
+) -> AppResult<Json<Value>> {
+    let name = body.name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::Validation("Schedule name cannot be empty".to_string()));
+    }
+    let id = Uuid::new_v4().to_string();
+    let timezone = body.timezone.trim().to_string();
+    let compiled = Schedule::new(&id, &timezone, &body.weekdays, &body.time_ranges)
+        .map_err(AppError::Validation)?;
+    ensure_name_free(&state, &name, None).await?;
+
+    let now = Utc::now().to_rfc3339();
+    let weekdays = serde_json::to_string(&compiled.weekday_names())?;
+    let time_ranges = serde_json::to_string(&body.time_ranges)?;
+
+    sqlx::query(
+        "INSERT INTO schedules (id, name, timezone, weekdays, time_ranges, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&id)
+    .bind(&name)
+    .bind(&timezone)
+    .bind(&weekdays)
+    .bind(&time_ranges)
+    .bind(&now)
+    .bind(&now)
+    .execute(&state.db)
+    .await?;
+
+    Ok(Json(schedule_json((id, name, timezone, weekdays, time_ranges, now.clone(), now))))
+}
+
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    _auth: AdminUser,
+    Path(id): Path<String>,
+    Json(body): Json<UpdateScheduleRequest>,
+) -> AppResult<Json<Value>> {
+    let (_, old_name, old_timezone, old_weekdays, old_ranges, created_at, _) =
+        fetch_schedule(&state, &id).await?;
+
+    let name = match body.name {
+        Some(n) => {
+            let n = n.trim().to_string();
+            if n.is_empty() {
+                return Err(AppError::Validation("Schedule name cannot be empty".to_string()));
+            }
+            ensure_name_free(&state, &n, Some(&id)).await?;
+            n
+        }
+        None => old_name,
+    };
+    let timezone = body.timezone.map(|t| t.trim().to_string()).unwrap_or(old_timezone);
+    let weekdays: Vec<String> = match body.weekdays {
+        Some(w) => w,
+        None => serde_json::from_str(&old_weekdays)?,
+    };
+    let time_ranges: Vec<TimeRange> = match body.time_ranges {
+        Some(r) => r,
+        None => serde_json::from_str(&old_ranges)?,
+    };
+    let compiled = Schedule::new(&id, &timezone, &weekdays, &time_ranges)
+        .map_err(AppError::Validation)?;
+
+    let now = Utc::now().to_rfc3339();
+    let weekdays = serde_json::to_string(&compiled.weekday_names())?;
+    let time_ranges = serde_json::to_string(&time_ranges)?;
+
+    sqlx::query(
+        "UPDATE schedules SET name = ?, timezone = ?, weekdays = ?, time_ranges = ?, updated_at = ?
+         WHERE id = ?"
+    )
+    .bind(&name)
+    .bind(&timezone)
+    .bind(&weekdays)
+    .bind(&time_ranges)
+    .bind(&now)
+    .bind(&id)
+    .execute(&state.db)
+    .await?;
+    // Compiled schedules are held in the cached client configs
+    state.dns_handler.invalidate_client_configs();
+
+    Ok(Json(schedule_json((id, name, timezone, weekdays, time_ranges, created_at, now))))
+}
+
+/// Delete a schedule.  Refused while bindings or clients still reference it,
+/// since silently dropping the schedule would make their blocking permanent.
+pub async fn delete(
+    State(state): State<Arc<AppState>>,
+    _auth: AdminUser,
+    Path(id): Path<String>,
+) -> AppResult<Json<Value>> {
+    fetch_schedule(&state, &id).await?;
+
+    let in_use: i64 = sqlx::query_scalar(
+        "SELECT (SELECT COUNT(*) FROM client_group_rules WHERE schedule_id = ?1)
+              + (SELECT COUNT(*) FROM clients
+                 WHERE blocked_services_schedule_id = ?1 OR filter_schedule_id = ?1)"
+    )
+    .bind(&id)
+    .fetch_one(&state.db)
+    .await?;
+    if in_use > 0 {
+        return Err(AppError::Conflict(format!(
+            "Schedule {} is still attached to {} binding(s) or client(s)",
+            id, in_use
+        )));
+    }
+
+    sqlx::query("DELETE FROM schedules WHERE id = ?")
+        .bind(&id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(json!({"success": true})))
+}
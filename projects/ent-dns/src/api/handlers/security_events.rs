@@ -0,0 +1,133 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::api::middleware::auth::AuthUser;
+use crate::api::AppState;
+use crate::dns::threat::Severity;
+use crate::error::{AppError, AppResult};
+
+#[derive(Deserialize)]
+pub struct SecurityEventParams {
+    #[serde(default = "default_limit")]
+    limit: i64,
+    #[serde(default)]
+    offset: i64,
+    /// Only events at or above this severity.
+    min_severity: Option<String>,
+    /// Threat category ("malware", "phishing", ...).
+    category: Option<String>,
+    client: Option<String>,
+    /// Only events that raised an alert.
+    alerted: Option<bool>,
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+#[derive(sqlx::FromRow)]
+struct SecurityEventRow {
+    id: i64,
+    time: String,
+    client_ip: String,
+    domain: String,
+    qtype: String,
+    filter_id: String,
+    feed_name: String,
+    threat_category: String,
+    severity: String,
+    alerted: bool,
+}
+
+/// List threat-feed hits, newest first.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Query(params): Query<SecurityEventParams>,
+) -> AppResult<Json<Value>> {
+    let limit = params.limit.clamp(1, 1000);
+
+    let severities: Option<Vec<&'static str>> = match params.min_severity.as_deref() {
+        Some(s) => {
+            let min = Severity::parse(s).ok_or_else(|| {
+                AppError::Validation(format!("Invalid min_severity: {} (must be low, medium, high or critical)", s))
+            })?;
+            Some(
+                [Severity::Low, Severity::Medium, Severity::High, Severity::Critical]
+                    .into_iter()
+                    .filter(|sev| *sev >= min)
+                    .map(|sev| sev.as_str())
+                    .collect(),
+            )
+        }
+        None => None,
+    };
+
+    let mut conditions = Vec::<String>::new();
+    if let Some(ref sevs) = severities {
+        conditions.push(format!("severity IN ({})", vec!["?"; sevs.len()].join(", ")));
+    }
+    if params.category.is_some() {
+        conditions.push("threat_category = ?".to_string());
+    }
+    if params.client.is_some() {
+        conditions.push("client_ip LIKE ?".to_string());
+    }
+    if params.alerted.is_some() {
+        conditions.push("alerted = ?".to_string());
+    }
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+
+    let data_sql = format!(
+        "SELECT id, time, client_ip, domain, qtype, filter_id, feed_name, threat_category, severity, alerted
+         FROM security_events {where_clause} ORDER BY time DESC LIMIT ? OFFSET ?"
+    );
+    let count_sql = format!("SELECT COUNT(*) FROM security_events {where_clause}");
+
+    let rows: Vec<SecurityEventRow> = {
+        let mut q = sqlx::query_as::<_, SecurityEventRow>(&data_sql);
+        for sev in severities.iter().flatten() { q = q.bind(*sev); }
+        if let Some(ref c) = params.category { q = q.bind(c); }
+        if let Some(ref c) = params.client { q = q.bind(format!("%{c}%")); }
+        if let Some(a) = params.alerted { q = q.bind(a); }
+        q.bind(limit).bind(params.offset).fetch_all(&state.db).await?
+    };
+
+    let total: i64 = {
+        let mut q = sqlx::query_scalar::<_, i64>(&count_sql);
+        for sev in severities.iter().flatten() { q = q.bind(*sev); }
+        if let Some(ref c) = params.category { q = q.bind(c); }
+        if let Some(ref c) = params.client { q = q.bind(format!("%{c}%")); }
+        if let Some(a) = params.alerted { q = q.bind(a); }
+        q.fetch_one(&state.db).await?
+    };
+
+    let data: Vec<Value> = rows
+        .into_iter()
+        .map(|r| {
+            json!({
+                "id": r.id,
+                "time": r.time,
+                "client_ip": r.client_ip,
+                "domain": r.domain,
+                "qtype": r.qtype,
+                "filter_id": r.filter_id,
+                "feed_name": r.feed_name,
+                "threat_category": r.threat_category,
+                "severity": r.severity,
+                "alerted": r.alerted,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({ "data": data, "total": total })))
+}
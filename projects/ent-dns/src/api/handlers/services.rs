@@ -0,0 +1,23 @@
+use axum::Json;
+use serde_json::{json, Value};
+
+use crate::api::middleware::auth::AuthUser;
+use crate::dns::services::SERVICES;
+use crate::error::AppResult;
+
+/// List the built-in blocked-services catalog.
+pub async fn list(_auth: AuthUser) -> AppResult<Json<Value>> {
+    let data: Vec<Value> = SERVICES
+        .iter()
+        .map(|s| {
+            json!({
+                "id": s.id,
+                "name": s.name,
+                "rules": s.rules,
+            })
+        })
+        .collect();
+    let total = data.len();
+
+    Ok(Json(json!({ "data": data, "total": total })))
+}
@@ -1,10 +1,16 @@
 use axum::{extract::State, Json};
 use serde::Deserialize;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::api::middleware::rbac::AdminUser;
+use crate::api::validators::domain::{DomainValidator, Validator};
 use crate::api::AppState;
+use crate::dns::client_settings::{BlockingMode, ClientSettings, MAX_RATE_LIMIT};
+use crate::dns::ecs::{self, EcsSettings};
+use crate::dns::qtype_policy::{self, QtypeAction};
+use crate::dns::threat::Severity;
 use crate::error::{AppError, AppResult};
 
 #[derive(Debug, Deserialize)]
@@ -15,6 +21,43 @@ pub struct UpdateDnsSettingsRequest {
     pub stats_retention_days: Option<u64>,
     pub safe_search_enabled: Option<bool>,
     pub parental_control_enabled: Option<bool>,
+    /// Category ids blocked for all clients while parental control is enabled.
+    pub parental_control_categories: Option<Vec<String>>,
+    pub rebind_protection_enabled: Option<bool>,
+    /// Split-horizon domains (and subdomains) allowed to resolve to internal addresses.
+    pub rebind_protection_allowlist: Option<Vec<String>>,
+    /// Global query-type policies, e.g. `{"ANY": "refuse", "HTTPS": "empty"}`.
+    pub qtype_policies: Option<HashMap<String, QtypeAction>>,
+    /// Suspicious-domain detection (DGA, homographs, tunneling); logs findings.
+    pub heuristics_enabled: Option<bool>,
+    /// Block suspicious queries scoring at least `heuristics_block_threshold`.
+    pub heuristics_block_enabled: Option<bool>,
+    pub heuristics_block_threshold: Option<f64>,
+    /// Minimum threat severity that raises a security alert.
+    pub security_alert_min_severity: Option<String>,
+    /// Webhook receiving security alerts as JSON POSTs; "" disables it.
+    pub security_alert_webhook_url: Option<String>,
+    /// List updates with fewer rules are rejected (0 disables the check).
+    pub filter_sync_min_rules: Option<i64>,
+    /// List updates shrinking by more than this percentage are rejected
+    /// (100 disables the check).
+    pub filter_sync_max_shrink_percent: Option<i64>,
+    /// Identify clients by the MAC a forwarder sends in EDNS0 (dnsmasq `--add-mac`).
+    pub edns_mac_enabled: Option<bool>,
+    /// Pass the client's EDNS Client Subnet option upstream instead of stripping it.
+    pub ecs_forward_client: Option<bool>,
+    /// Send the client's subnet upstream when no ECS option is forwarded.
+    pub ecs_add_subnet: Option<bool>,
+    pub ecs_ipv4_prefix: Option<u8>,
+    pub ecs_ipv6_prefix: Option<u8>,
+    /// Forwarders (addresses or CIDRs) whose ECS address identifies the client.
+    pub ecs_trusted_forwarders: Option<Vec<String>>,
+    /// Default answer for blocked queries; clients and groups may override it.
+    pub blocking_mode: Option<BlockingMode>,
+    /// Default queries per second per client (0 = unlimited).
+    pub rate_limit_qps: Option<u32>,
+    /// Default for recording queries in the query log.
+    pub query_log_enabled: Option<bool>,
 }
 
 /// Get current DNS settings
@@ -48,12 +91,62 @@ pub async fn get_dns(
         .await
         .unwrap_or(("false".to_string(),));
 
+    let parental_categories: (String,) = sqlx::query_as("SELECT value FROM settings WHERE key = 'parental_control_categories'")
+        .fetch_one(&state.db)
+        .await
+        .unwrap_or(("[]".to_string(),));
+
+    let rebind_protection: (String,) = sqlx::query_as("SELECT value FROM settings WHERE key = 'rebind_protection_enabled'")
+        .fetch_one(&state.db)
+        .await
+        .unwrap_or(("false".to_string(),));
+
+    let rebind_allowlist: (String,) = sqlx::query_as("SELECT value FROM settings WHERE key = 'rebind_protection_allowlist'")
+        .fetch_one(&state.db)
+        .await
+        .unwrap_or(("[]".to_string(),));
+
+    let qtype_policies: (String,) = sqlx::query_as("SELECT value FROM settings WHERE key = 'qtype_policies'")
+        .fetch_one(&state.db)
+        .await
+        .unwrap_or(("{}".to_string(),));
+
+    let heuristics: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM settings WHERE key LIKE 'heuristics_%'")
+        .fetch_all(&state.db)
+        .await
+        .unwrap_or_default();
+    let heuristics: HashMap<String, String> = heuristics.into_iter().collect();
+
+    let security_alerts: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM settings WHERE key LIKE 'security_alert_%'")
+        .fetch_all(&state.db)
+        .await
+        .unwrap_or_default();
+    let security_alerts: HashMap<String, String> = security_alerts.into_iter().collect();
+
+    let filter_sync: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM settings WHERE key LIKE 'filter_sync_%'")
+        .fetch_all(&state.db)
+        .await
+        .unwrap_or_default();
+    let filter_sync: HashMap<String, String> = filter_sync.into_iter().collect();
+
+    let edns_mac: (String,) = sqlx::query_as("SELECT value FROM settings WHERE key = 'edns_mac_enabled'")
+        .fetch_one(&state.db)
+        .await
+        .unwrap_or(("false".to_string(),));
+
+    let ecs = EcsSettings::load(&state.db).await.unwrap_or_default();
+    let client_defaults = ClientSettings::load_defaults(&state.db).await.unwrap_or_default();
+
     // Parse values
     let cache_ttl = cache_ttl.0.parse::<u64>().unwrap_or(300);
     let query_log_retention = query_log_retention.0.parse::<u64>().unwrap_or(30);
     let stats_retention = stats_retention.0.parse::<u64>().unwrap_or(90);
     let safe_search_enabled = safe_search.0 == "true";
     let parental_control_enabled = parental_control.0 == "true";
+    let parental_control_categories: Vec<String> = serde_json::from_str(&parental_categories.0).unwrap_or_default();
+    let rebind_protection_enabled = rebind_protection.0 == "true";
+    let rebind_protection_allowlist: Vec<String> = serde_json::from_str(&rebind_allowlist.0).unwrap_or_default();
+    let qtype_policies: HashMap<String, QtypeAction> = serde_json::from_str(&qtype_policies.0).unwrap_or_default();
 
     // Get upstreams from config (or database if implemented)
     // For now, return empty array as default
@@ -69,6 +162,38 @@ pub async fn get_dns(
         "stats_retention_days": stats_retention,
         "safe_search_enabled": safe_search_enabled,
         "parental_control_enabled": parental_control_enabled,
+        "parental_control_categories": parental_control_categories,
+        "rebind_protection_enabled": rebind_protection_enabled,
+        "rebind_protection_allowlist": rebind_protection_allowlist,
+        "qtype_policies": qtype_policies,
+        "heuristics_enabled": heuristics.get("heuristics_enabled").is_some_and(|v| v == "true"),
+        "heuristics_block_enabled": heuristics.get("heuristics_block_enabled").is_some_and(|v| v == "true"),
+        "heuristics_block_threshold": heuristics
+            .get("heuristics_block_threshold")
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(0.9),
+        "security_alert_min_severity": security_alerts
+            .get("security_alert_min_severity")
+            .cloned()
+            .unwrap_or_else(|| "high".to_string()),
+        "security_alert_webhook_url": security_alerts.get("security_alert_webhook_url").cloned().unwrap_or_default(),
+        "filter_sync_min_rules": filter_sync
+            .get("filter_sync_min_rules")
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(1),
+        "filter_sync_max_shrink_percent": filter_sync
+            .get("filter_sync_max_shrink_percent")
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(50),
+        "edns_mac_enabled": edns_mac.0 == "true",
+        "ecs_forward_client": ecs.forward_client,
+        "ecs_add_subnet": ecs.add_subnet,
+        "ecs_ipv4_prefix": ecs.ipv4_prefix,
+        "ecs_ipv6_prefix": ecs.ipv6_prefix,
+        "ecs_trusted_forwarders": ecs.trusted_forwarders.iter().map(|n| n.to_string()).collect::<Vec<_>>(),
+        "blocking_mode": client_defaults.blocking_mode,
+        "rate_limit_qps": client_defaults.rate_limit,
+        "query_log_enabled": client_defaults.query_log_enabled,
     })))
 }
 
@@ -127,6 +252,223 @@ pub async fn update_dns(
             .await?;
     }
 
+    // Update parental_control_categories if provided
+    if let Some(ref categories) = body.parental_control_categories {
+        if let Some(unknown) = categories.iter().find(|c| crate::dns::categories::find(c).is_none()) {
+            return Err(AppError::Validation(format!("Unknown parental control category: {}", unknown)));
+        }
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('parental_control_categories', ?)")
+            .bind(serde_json::to_string(categories)?)
+            .execute(&state.db)
+            .await?;
+    }
+
+    // Update rebind_protection_enabled if provided
+    if let Some(enabled) = body.rebind_protection_enabled {
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('rebind_protection_enabled', ?)")
+            .bind(if enabled { "true" } else { "false" })
+            .execute(&state.db)
+            .await?;
+    }
+
+    // Update rebind_protection_allowlist if provided
+    if let Some(ref allowlist) = body.rebind_protection_allowlist {
+        let mut normalized: Vec<String> = Vec::with_capacity(allowlist.len());
+        for entry in allowlist {
+            let domain = crate::dns::rebind::normalize_allowlist_entry(entry);
+            DomainValidator::new().validate(&domain).map_err(|e| {
+                AppError::Validation(format!("Invalid rebind allowlist entry '{}': {}", entry, e.message))
+            })?;
+            if !normalized.contains(&domain) {
+                normalized.push(domain);
+            }
+        }
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('rebind_protection_allowlist', ?)")
+            .bind(serde_json::to_string(&normalized)?)
+            .execute(&state.db)
+            .await?;
+    }
+
+    // Update qtype_policies if provided
+    if let Some(ref policies) = body.qtype_policies {
+        let normalized = qtype_policy::normalize(policies).map_err(AppError::Validation)?;
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('qtype_policies', ?)")
+            .bind(serde_json::to_string(&normalized)?)
+            .execute(&state.db)
+            .await?;
+    }
+
+    // Update suspicious-domain heuristics if provided
+    if let Some(threshold) = body.heuristics_block_threshold {
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(AppError::Validation("heuristics_block_threshold must be between 0.0 and 1.0".to_string()));
+        }
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('heuristics_block_threshold', ?)")
+            .bind(threshold.to_string())
+            .execute(&state.db)
+            .await?;
+    }
+    for (key, value) in [
+        ("heuristics_enabled", body.heuristics_enabled),
+        ("heuristics_block_enabled", body.heuristics_block_enabled),
+    ] {
+        if let Some(enabled) = value {
+            sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)")
+                .bind(key)
+                .bind(if enabled { "true" } else { "false" })
+                .execute(&state.db)
+                .await?;
+        }
+    }
+    if body.heuristics_enabled.is_some()
+        || body.heuristics_block_enabled.is_some()
+        || body.heuristics_block_threshold.is_some()
+    {
+        state.dns_handler.reload_heuristics().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+
+    // Update security alert settings if provided
+    if let Some(ref severity) = body.security_alert_min_severity {
+        if Severity::parse(severity).is_none() {
+            return Err(AppError::Validation(format!(
+                "Invalid security_alert_min_severity: {} (must be low, medium, high or critical)",
+                severity
+            )));
+        }
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('security_alert_min_severity', ?)")
+            .bind(severity)
+            .execute(&state.db)
+            .await?;
+    }
+    if let Some(ref url) = body.security_alert_webhook_url {
+        let url = url.trim();
+        if !url.is_empty() && !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(AppError::Validation("security_alert_webhook_url must be an http(s) URL".to_string()));
+        }
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('security_alert_webhook_url', ?)")
+            .bind(url)
+            .execute(&state.db)
+            .await?;
+    }
+    if body.security_alert_min_severity.is_some() || body.security_alert_webhook_url.is_some() {
+        state.dns_handler.security_events().reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+
+    // Update filter list sanity-check thresholds if provided (read at each sync)
+    if let Some(min_rules) = body.filter_sync_min_rules {
+        if min_rules < 0 {
+            return Err(AppError::Validation("filter_sync_min_rules must not be negative".to_string()));
+        }
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('filter_sync_min_rules', ?)")
+            .bind(min_rules.to_string())
+            .execute(&state.db)
+            .await?;
+    }
+    if let Some(percent) = body.filter_sync_max_shrink_percent {
+        if !(0..=100).contains(&percent) {
+            return Err(AppError::Validation("filter_sync_max_shrink_percent must be between 0 and 100".to_string()));
+        }
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('filter_sync_max_shrink_percent', ?)")
+            .bind(percent.to_string())
+            .execute(&state.db)
+            .await?;
+    }
+
+    // Update client identification settings if provided
+    if let Some(enabled) = body.edns_mac_enabled {
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('edns_mac_enabled', ?)")
+            .bind(if enabled { "true" } else { "false" })
+            .execute(&state.db)
+            .await?;
+        state.dns_handler.reload_identification().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+
+    // Update EDNS Client Subnet settings if provided
+    if let Some(prefix) = body.ecs_ipv4_prefix {
+        if prefix > 32 {
+            return Err(AppError::Validation("ecs_ipv4_prefix must be between 0 and 32".to_string()));
+        }
+    }
+    if let Some(prefix) = body.ecs_ipv6_prefix {
+        if prefix > 128 {
+            return Err(AppError::Validation("ecs_ipv6_prefix must be between 0 and 128".to_string()));
+        }
+    }
+    let trusted_forwarders = match body.ecs_trusted_forwarders {
+        Some(ref forwarders) => {
+            let mut nets = Vec::new();
+            for forwarder in forwarders {
+                let net = ecs::parse_forwarder(forwarder).ok_or_else(|| {
+                    AppError::Validation(format!("Invalid ECS trusted forwarder: {}", forwarder))
+                })?;
+                nets.push(net.to_string());
+            }
+            Some(serde_json::to_string(&nets).map_err(|e| AppError::Internal(e.to_string()))?)
+        }
+        None => None,
+    };
+    let ecs_updates = [
+        ("ecs_forward_client", body.ecs_forward_client.map(|v| v.to_string())),
+        ("ecs_add_subnet", body.ecs_add_subnet.map(|v| v.to_string())),
+        ("ecs_ipv4_prefix", body.ecs_ipv4_prefix.map(|v| v.to_string())),
+        ("ecs_ipv6_prefix", body.ecs_ipv6_prefix.map(|v| v.to_string())),
+        ("ecs_trusted_forwarders", trusted_forwarders),
+    ];
+    let ecs_changed = ecs_updates.iter().any(|(_, value)| value.is_some());
+    for (key, value) in ecs_updates {
+        if let Some(value) = value {
+            sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)")
+                .bind(key)
+                .bind(value)
+                .execute(&state.db)
+                .await?;
+        }
+    }
+    if ecs_changed {
+        state.dns_handler.reload_ecs().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+
+    // Defaults of the per-client overridable settings
+    if let Some(mode) = body.blocking_mode {
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('blocking_mode', ?)")
+            .bind(mode.as_str())
+            .execute(&state.db)
+            .await?;
+    }
+    if let Some(limit) = body.rate_limit_qps {
+        if limit > MAX_RATE_LIMIT {
+            return Err(AppError::Validation(format!("rate_limit_qps must be between 0 and {}", MAX_RATE_LIMIT)));
+        }
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('rate_limit_qps', ?)")
+            .bind(limit.to_string())
+            .execute(&state.db)
+            .await?;
+    }
+    if let Some(enabled) = body.query_log_enabled {
+        sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('query_log_enabled', ?)")
+            .bind(if enabled { "true" } else { "false" })
+            .execute(&state.db)
+            .await?;
+    }
+    if body.blocking_mode.is_some()
+        || body.rate_limit_qps.is_some()
+        || body.query_log_enabled.is_some()
+        || body.safe_search_enabled.is_some()
+    {
+        state.dns_handler.reload_client_settings().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+
+    // Parental control, rebinding protection and query-type policies are
+    // enforced by the filter engine — pick up the new settings
+    if body.parental_control_enabled.is_some()
+        || body.parental_control_categories.is_some()
+        || body.rebind_protection_enabled.is_some()
+        || body.rebind_protection_allowlist.is_some()
+        || body.qtype_policies.is_some()
+    {
+        state.filter.reload().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+
     // Note: Upstreams would require either a settings table update or config file reload
     // For this implementation, we acknowledge the update but don't persist upstreams
     if body.upstreams.is_some() {
@@ -41,6 +41,20 @@ pub struct WsParams {
     ticket: String,
 }
 
+/// Validate and consume a one-time ticket.  Returns the rejection response
+/// when the ticket is unknown, already used or expired.
+fn reject_ticket(state: &AppState, ticket: &str) -> Option<Response> {
+    // Validate and consume the ticket atomically
+    match state.ws_tickets.remove(ticket) {
+        Some((_, issued_at)) if issued_at.elapsed() < WS_TICKET_TTL => None,
+        Some(_) => {
+            // Ticket found but expired — already removed above
+            Some((StatusCode::UNAUTHORIZED, "WebSocket ticket expired").into_response())
+        }
+        None => Some((StatusCode::UNAUTHORIZED, "Invalid or already-used WebSocket ticket").into_response()),
+    }
+}
+
 /// WebSocket endpoint for real-time query log streaming.
 /// Authenticated via one-time ticket (see `issue_ticket`); ticket is consumed on use.
 pub async fn query_log_ws(
@@ -48,18 +62,8 @@ pub async fn query_log_ws(
     Query(params): Query<WsParams>,
     ws: WebSocketUpgrade,
 ) -> Response {
-    // Validate and consume the ticket atomically
-    match state.ws_tickets.remove(&params.ticket) {
-        Some((_, issued_at)) if issued_at.elapsed() < WS_TICKET_TTL => {
-            // Ticket valid — proceed with upgrade
-        }
-        Some(_) => {
-            // Ticket found but expired — already removed above
-            return (StatusCode::UNAUTHORIZED, "WebSocket ticket expired").into_response();
-        }
-        None => {
-            return (StatusCode::UNAUTHORIZED, "Invalid or already-used WebSocket ticket").into_response();
-        }
+    if let Some(resp) = reject_ticket(&state, &params.ticket) {
+        return resp;
     }
 
     let tx = state.query_log_tx.clone();
@@ -67,6 +71,22 @@ pub async fn query_log_ws(
         .into_response()
 }
 
+/// WebSocket endpoint streaming security events (threat-feed hits) only,
+/// separate from the query log stream.  Same ticket authentication.
+pub async fn security_events_ws(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<WsParams>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if let Some(resp) = reject_ticket(&state, &params.ticket) {
+        return resp;
+    }
+
+    let tx = state.dns_handler.security_events().sender();
+    ws.on_upgrade(move |socket| handle_socket(socket, tx))
+        .into_response()
+}
+
 async fn handle_socket(mut socket: WebSocket, tx: broadcast::Sender<serde_json::Value>) {
     let mut rx = tx.subscribe();
 
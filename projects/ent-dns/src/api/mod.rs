@@ -1,5 +1,6 @@
 use anyhow::Result;
 use axum::Router;
+use axum::serve::ListenerExt;
 use axum::http::{HeaderValue, Method, header};
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
@@ -16,6 +17,7 @@ use crate::db::DbPool;
 use crate::dns::filter::FilterEngine;
 use crate::dns::DnsHandler;
 use crate::metrics::DnsMetrics;
+use crate::proxy_protocol::{ProxyProtocolListener, TrustedProxies};
 
 pub mod router;
 pub mod middleware;
@@ -39,6 +41,8 @@ pub struct AppState {
     pub rule_validation_cache: Arc<Cache<String, RuleValidationResponse>>,
     /// Client configuration cache: client_id → Vec<DnsRuleWithSource> (Task 12)
     pub client_config_cache: Option<Arc<Cache<String, Vec<DnsRuleWithSource>>>>,
+    /// Reverse proxies whose `X-Forwarded-For` names the DoH client.
+    pub forwarded_for_proxies: TrustedProxies,
 }
 
 pub async fn serve(
@@ -79,6 +83,7 @@ pub async fn serve(
         dns_handler,
         rule_validation_cache,
         client_config_cache: Some(client_config_cache),
+        forwarded_for_proxies: TrustedProxies::parse(&cfg.api.forwarded_for_from)?,
     });
     let cors = build_cors_layer(&cfg.api.cors_allowed_origins);
     let app = build_app(state, cors);
@@ -87,10 +92,21 @@ pub async fn serve(
     let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
     tracing::info!("Management API listening on http://{}", bind_addr);
 
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    ).await?;
+    let proxies = TrustedProxies::parse(&cfg.api.proxy_protocol_from)?;
+    if proxies.is_empty() {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        ).await?;
+    } else {
+        tracing::info!("Accepting PROXY protocol headers from {:?}", cfg.api.proxy_protocol_from);
+        // The peer address is the one from the PROXY header; tap_io gives the
+        // listener axum's ConnectInfo<SocketAddr> support
+        axum::serve(
+            ProxyProtocolListener::new(listener, proxies)?.tap_io(|_| {}),
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        ).await?;
+    }
     Ok(())
 }
 
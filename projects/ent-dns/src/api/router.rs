@@ -1,4 +1,4 @@
-use axum::{routing::{get, post, put, delete}, Router};
+use axum::{extract::DefaultBodyLimit, routing::{get, post, put, delete}, Router};
 use std::sync::Arc;
 use tower_http::services::{ServeDir, ServeFile};
 use super::AppState;
@@ -18,6 +18,8 @@ pub fn routes(state: Arc<AppState>) -> Router {
         .route("/api/v1/dashboard/query-trend", get(handlers::dashboard::get_query_trend))
         .route("/api/v1/dashboard/top-blocked-domains", get(handlers::dashboard::get_top_blocked_domains))
         .route("/api/v1/dashboard/top-clients", get(handlers::dashboard::get_top_clients))
+        .route("/api/v1/dashboard/blocked-categories", get(handlers::dashboard::get_blocked_categories))
+        .route("/api/v1/dashboard/suspicious-domains", get(handlers::dashboard::get_suspicious_domains))
         // Query log (protected)
         .route("/api/v1/query-log", get(handlers::query_log::list))
         .route("/api/v1/query-log/export", get(handlers::query_log::export))
@@ -36,6 +38,16 @@ pub fn routes(state: Arc<AppState>) -> Router {
         .route("/api/v1/filters", get(handlers::filters::list).post(handlers::filters::create))
         .route("/api/v1/filters/{id}", put(handlers::filters::update).delete(handlers::filters::delete))
         .route("/api/v1/filters/{id}/refresh", post(handlers::filters::refresh))
+        .route("/api/v1/filters/{id}/history", get(handlers::filters::history))
+        .route("/api/v1/filters/{id}/versions", get(handlers::filters::versions))
+        .route("/api/v1/filters/{id}/versions/{version}/diff", get(handlers::filters::version_diff))
+        .route("/api/v1/filters/{id}/versions/{version}/pin", post(handlers::filters::pin_version))
+        .route("/api/v1/filters/{id}/pin", delete(handlers::filters::unpin))
+        // Inline list bodies (multipart), up to the list size limit plus form overhead
+        .route("/api/v1/filters/upload", post(handlers::filters::upload)
+            .layer(DefaultBodyLimit::max(crate::dns::subscription::MAX_LIST_SIZE + 64 * 1024)))
+        .route("/api/v1/filters/{id}/content", put(handlers::filters::replace_content)
+            .layer(DefaultBodyLimit::max(crate::dns::subscription::MAX_LIST_SIZE + 64 * 1024)))
         // Rules (protected)
         .route("/api/v1/rules", get(handlers::rules::list).post(handlers::rules::create))
         .route("/api/v1/rules/bulk", post(handlers::rules::bulk_action))
@@ -47,12 +59,20 @@ pub fn routes(state: Arc<AppState>) -> Router {
         // Clients (protected)
         .route("/api/v1/clients", get(handlers::clients::list).post(handlers::clients::create))
         .route("/api/v1/clients/{id}", put(handlers::clients::update).delete(handlers::clients::delete))
+        // Effective configuration of a client or source address (protected)
+        .route("/api/v1/clients/effective-config", get(handlers::effective_config::for_source))
+        .route("/api/v1/clients/{id}/effective-config", get(handlers::effective_config::for_client))
+        // Runtime clients: addresses seen in the query log (protected)
+        .route("/api/v1/clients/runtime", get(handlers::runtime_clients::list))
+        .route("/api/v1/clients/runtime/leases", post(handlers::runtime_clients::import_leases))
+        .route("/api/v1/clients/runtime/{ip}/promote", post(handlers::runtime_clients::promote))
         // Client Groups (protected)
         .route("/api/v1/client-groups", get(handlers::client_groups::list_groups).post(handlers::client_groups::create_group))
         .route("/api/v1/client-groups/{id}", put(handlers::client_groups::update_group).delete(handlers::client_groups::delete_group))
         .route("/api/v1/client-groups/{id}/members", get(handlers::client_groups::get_group_members).post(handlers::client_groups::batch_add_clients).delete(handlers::client_groups::batch_remove_clients))
         .route("/api/v1/clients/batch-move", post(handlers::client_groups::batch_move_clients))
         .route("/api/v1/client-groups/{id}/rules", get(handlers::client_groups::get_group_rules).post(handlers::client_groups::batch_bind_rules).delete(handlers::client_groups::batch_unbind_rules))
+        .route("/api/v1/client-groups/{id}/rules/schedule", put(handlers::client_groups::update_binding_schedule))
         // Upstreams (protected)
         .route("/api/v1/settings/upstreams", get(handlers::upstreams::list).post(handlers::upstreams::create))
         .route("/api/v1/settings/upstreams/{id}", get(handlers::upstreams::get))
@@ -61,11 +81,22 @@ pub fn routes(state: Arc<AppState>) -> Router {
         .route("/api/v1/settings/upstreams/failover", post(handlers::upstreams::trigger_failover))
         // Settings (protected)
         .route("/api/v1/settings/dns", get(handlers::settings::get_dns).put(handlers::settings::update_dns))
+        // Parental control categories (protected)
+        .route("/api/v1/categories", get(handlers::categories::list))
+        // Blocked services catalog (protected)
+        .route("/api/v1/blocked-services", get(handlers::services::list))
+        // Schedules (protected)
+        .route("/api/v1/schedules", get(handlers::schedules::list).post(handlers::schedules::create))
+        .route("/api/v1/schedules/{id}", get(handlers::schedules::get).put(handlers::schedules::update).delete(handlers::schedules::delete))
+        // Filtering pauses (protected; create/delete admin only)
+        .route("/api/v1/pauses", get(handlers::pauses::list).post(handlers::pauses::create))
+        .route("/api/v1/pauses/{id}", delete(handlers::pauses::delete))
         // Users (admin only)
         .route("/api/v1/users", get(handlers::users::list).post(handlers::users::create))
         .route("/api/v1/users/{id}/role", put(handlers::users::update_role))
         // Audit log (admin only)
         .route("/api/v1/audit-log", get(handlers::audit_log::list))
+        .route("/api/v1/security-events", get(handlers::security_events::list))
         .route("/api/v1/settings/upstreams/failover-log", get(handlers::upstreams::failover_log))
         // Prometheus metrics (admin only - security fix)
         .route("/metrics", get(handlers::metrics::prometheus_metrics))
@@ -74,8 +105,13 @@ pub fn routes(state: Arc<AppState>) -> Router {
         // WebSocket: issue one-time ticket (authenticated), then connect via ticket
         .route("/api/v1/ws/ticket", post(handlers::ws::issue_ticket))
         .route("/api/v1/ws/query-log", get(handlers::ws::query_log_ws))
+        .route("/api/v1/ws/security-events", get(handlers::ws::security_events_ws))
         // DNS-over-HTTPS (RFC 8484) — public endpoint, no auth required
         .route("/dns-query", get(handlers::doh::get_query).post(handlers::doh::post_query))
+        .route(
+            "/dns-query/{client_id}",
+            get(handlers::doh::get_query_with_client_id).post(handlers::doh::post_query_with_client_id),
+        )
         .with_state(state)
         // 前端静态文件 + SPA fallback（必须在 with_state 之后）
         // ENT_DNS_STATIC_DIR overrides the default relative path (fixes L-2)
@@ -60,6 +60,16 @@ impl RuleValidator {
 
         let domain_validator = DomainValidator::new();
 
+        // Response-address rules: IP, CIDR or ||IP^, optionally @@-prefixed
+        let ip_part = rule.strip_prefix("@@").unwrap_or(rule);
+        let ip_part = ip_part
+            .strip_prefix("||")
+            .map(|r| r.trim_end_matches('^'))
+            .unwrap_or(ip_part);
+        if ip_part.parse::<std::net::IpAddr>().is_ok() || ip_part.parse::<ipnet::IpNet>().is_ok() {
+            return Ok(());
+        }
+
         // AdGuard format: ||domain^
         if rule.starts_with("||") {
             let domain_part = rule.trim_start_matches("||").trim_end_matches('^');
@@ -173,6 +183,11 @@ mod tests {
 
         // Plain domain
         assert!(validator.validate_rule("filter", "example.com").is_ok());
+
+        // Response-address rules
+        assert!(validator.validate_rule("filter", "198.51.100.0/24").is_ok());
+        assert!(validator.validate_rule("filter", "||203.0.113.9^").is_ok());
+        assert!(validator.validate_rule("filter", "@@2001:db8::1").is_ok());
     }
 
     #[test]
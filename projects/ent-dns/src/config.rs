@@ -1,6 +1,8 @@
 use anyhow::Result;
 use serde::Deserialize;
 
+use crate::proxy_protocol::TrustedProxies;
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub dns: DnsConfig,
@@ -19,8 +21,24 @@ pub struct DnsConfig {
     pub upstreams: Vec<String>,
     #[allow(dead_code)]
     pub doh_enabled: bool,
-    #[allow(dead_code)]
+    /// DNS-over-TLS listener (RFC 7858); requires `tls_cert_path` and `tls_key_path`.
     pub dot_enabled: bool,
+    #[serde(default = "default_dot_port")]
+    pub dot_port: u16,
+    /// PEM certificate chain and private key for DoT.
+    #[serde(default)]
+    pub tls_cert_path: Option<String>,
+    #[serde(default)]
+    pub tls_key_path: Option<String>,
+    /// Server name under which DoT clients send their client ID as the first
+    /// SNI label (`{client_id}.dns.example.com`).
+    #[serde(default)]
+    pub client_id_domain: Option<String>,
+    /// Load balancers (addresses or CIDRs) that prefix DNS/TCP and DoT
+    /// connections, and DNS/UDP datagrams (v2 only), with a PROXY protocol
+    /// header.  Mandatory for them, ignored for everyone else.
+    #[serde(default)]
+    pub proxy_protocol_from: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -33,6 +51,12 @@ pub struct ApiConfig {
     /// Set ENT_DNS__API__CORS_ALLOWED_ORIGINS in production.
     #[serde(default = "default_cors_allowed_origins")]
     pub cors_allowed_origins: Vec<String>,
+    /// Load balancers that prefix API/DoH connections with a PROXY protocol header.
+    #[serde(default)]
+    pub proxy_protocol_from: Vec<String>,
+    /// Reverse proxies whose `X-Forwarded-For` is trusted for DoH queries.
+    #[serde(default)]
+    pub forwarded_for_from: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -51,6 +75,7 @@ pub struct AuthConfig {
 }
 
 fn default_dns_port() -> u16 { 5353 }  // Use 5353 in dev (53 requires root)
+fn default_dot_port() -> u16 { 853 }
 fn default_bind() -> String { "0.0.0.0".to_string() }
 fn default_api_port() -> u16 { 8080 }
 fn default_db_path() -> String { "./ent-dns.db".to_string() }
@@ -94,6 +119,20 @@ pub fn validate(cfg: &Config) -> Result<()> {
         }
     }
 
+    for (key, entries) in [
+        ("dns.proxy_protocol_from", &cfg.dns.proxy_protocol_from),
+        ("api.proxy_protocol_from", &cfg.api.proxy_protocol_from),
+        ("api.forwarded_for_from", &cfg.api.forwarded_for_from),
+    ] {
+        if let Err(e) = TrustedProxies::parse(entries) {
+            anyhow::bail!("CONFIG ERROR: {}: {}", key, e);
+        }
+    }
+
+    if cfg.dns.dot_enabled && (cfg.dns.tls_cert_path.is_none() || cfg.dns.tls_key_path.is_none()) {
+        anyhow::bail!("CONFIG ERROR: dns.dot_enabled requires dns.tls_cert_path and dns.tls_key_path");
+    }
+
     tracing::info!("Configuration validation passed");
     Ok(())
 }
@@ -107,6 +146,7 @@ pub fn load() -> Result<Config> {
         .set_default("dns.upstreams", vec!["1.1.1.1:53", "8.8.8.8:53"])?
         .set_default("dns.doh_enabled", false)?
         .set_default("dns.dot_enabled", false)?
+        .set_default("dns.dot_port", 853)?
         .set_default("api.bind", "0.0.0.0")?
         .set_default("api.port", 8080)?
         .set_default("database.path", "./ent-dns.db")?
@@ -0,0 +1,10 @@
+-- Migration 008: Parental control categories
+-- Filter lists may be tagged with a content category (adult, gambling, ...).
+-- Rules from a tagged list feed that category instead of the global blocklist.
+ALTER TABLE filter_lists ADD COLUMN category TEXT;
+
+-- Categories blocked for all clients when parental_control_enabled = 'true'.
+-- Client groups select additional categories via client_group_rules
+-- (rule_type = 'category', rule_id = category id).
+INSERT OR IGNORE INTO settings (key, value) VALUES
+    ('parental_control_categories', '["adult"]');
@@ -0,0 +1,5 @@
+-- Migration 009: Blocked services
+-- Per-client selection of built-in services to block (JSON array of service ids).
+-- Per-group selection uses client_group_rules (rule_type = 'blocked_service',
+-- rule_id = service id).
+ALTER TABLE clients ADD COLUMN blocked_services TEXT;
@@ -0,0 +1,19 @@
+-- Migration 010: Time-based schedules
+-- weekdays:    JSON array of weekday names, e.g. ["mon","tue","wed","thu","fri"]
+-- time_ranges: JSON array of local ranges, e.g. [{"start":"08:00","end":"15:00"}]
+CREATE TABLE IF NOT EXISTS schedules (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    timezone TEXT NOT NULL DEFAULT 'UTC',
+    weekdays TEXT NOT NULL,
+    time_ranges TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- A binding with a schedule only applies while the schedule is active.
+ALTER TABLE client_group_rules ADD COLUMN schedule_id TEXT;
+
+-- Restrict a client's own blocked services / its whole filtering to a schedule.
+ALTER TABLE clients ADD COLUMN blocked_services_schedule_id TEXT;
+ALTER TABLE clients ADD COLUMN filter_schedule_id TEXT;
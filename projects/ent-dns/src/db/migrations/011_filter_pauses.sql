@@ -0,0 +1,16 @@
+-- Migration 011: Temporary filtering pauses
+-- scope = 'global' (target_id = ''), 'client' (clients.id) or 'group' (client_groups.id).
+-- A pause is in effect while expires_at (RFC 3339, UTC) is in the future; expired
+-- rows are ignored and pruned when new pauses are created.
+CREATE TABLE IF NOT EXISTS filter_pauses (
+    id TEXT PRIMARY KEY,
+    scope TEXT NOT NULL CHECK (scope IN ('global', 'client', 'group')),
+    target_id TEXT NOT NULL DEFAULT '',
+    expires_at TEXT NOT NULL,
+    reason TEXT,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    UNIQUE(scope, target_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_filter_pauses_expires ON filter_pauses(expires_at);
@@ -0,0 +1,7 @@
+-- Migration 012: DNS rebinding protection
+-- When enabled, answers with private/loopback/link-local addresses are blocked
+-- unless the queried name is in the allowlist (JSON array of domains; each
+-- entry also covers its subdomains).
+INSERT OR IGNORE INTO settings (key, value) VALUES
+    ('rebind_protection_enabled', 'false'),
+    ('rebind_protection_allowlist', '[]');
@@ -0,0 +1,6 @@
+-- Migration 013: Query-type policies
+-- JSON object mapping record types to "refuse" | "empty" | "allow",
+-- e.g. {"ANY": "refuse", "HTTPS": "empty"}.  Group policies take precedence
+-- over the global setting (first group by priority that names the type wins).
+INSERT OR IGNORE INTO settings (key, value) VALUES ('qtype_policies', '{}');
+ALTER TABLE client_groups ADD COLUMN qtype_policies TEXT;
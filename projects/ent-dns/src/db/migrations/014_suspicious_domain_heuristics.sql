@@ -0,0 +1,6 @@
+-- Migration 014: Suspicious-domain heuristics (DGA, IDN homographs, tunneling)
+-- Detection is opt-in and only logs (reason 'suspicious:<kind>'); blocking is
+-- a further opt-in and applies to findings scoring at least the threshold (0.0–1.0).
+INSERT OR IGNORE INTO settings (key, value) VALUES ('heuristics_enabled', 'false');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('heuristics_block_enabled', 'false');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('heuristics_block_threshold', '0.9');
@@ -0,0 +1,26 @@
+-- Migration 015: Threat-intelligence feeds and security events
+-- A filter list with a threat_category is a threat feed: its rules block like
+-- any other list, but hits are recorded as security events with the feed's
+-- category and severity instead of being treated as ad blocking.
+ALTER TABLE filter_lists ADD COLUMN threat_category TEXT;
+ALTER TABLE filter_lists ADD COLUMN severity TEXT;
+
+CREATE TABLE IF NOT EXISTS security_events (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    time            TEXT NOT NULL,
+    client_ip       TEXT NOT NULL,
+    domain          TEXT NOT NULL,
+    qtype           TEXT NOT NULL,
+    filter_id       TEXT NOT NULL,
+    feed_name       TEXT NOT NULL,
+    threat_category TEXT NOT NULL,
+    severity        TEXT NOT NULL CHECK (severity IN ('low','medium','high','critical')),
+    alerted         INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS idx_security_events_time ON security_events(time DESC);
+CREATE INDEX IF NOT EXISTS idx_security_events_client ON security_events(client_ip, time DESC);
+
+-- Alerting: events at or above the minimum severity are pushed to the webhook
+-- (if set), at most once per client and domain per cooldown period.
+INSERT OR IGNORE INTO settings (key, value) VALUES ('security_alert_min_severity', 'high');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('security_alert_webhook_url', '');
@@ -0,0 +1,4 @@
+-- Migration 016: Explicit filter list format
+-- 'auto' detects the format from the downloaded content; other values are
+-- adguard, hosts, domains, dnsmasq, unbound and rpz.
+ALTER TABLE filter_lists ADD COLUMN format TEXT NOT NULL DEFAULT 'auto';
@@ -0,0 +1,4 @@
+-- Migration 017: Local (file://) filter lists
+-- Modification time of the file at the last sync; the list is re-synced when
+-- the file's mtime differs.
+ALTER TABLE filter_lists ADD COLUMN source_mtime TEXT;
@@ -0,0 +1,22 @@
+-- Migration 018: Conditional filter list downloads and sync history
+-- Refresh interval used by the auto-refresh task (NULL: the list's
+-- `! Expires:` header, else 24 hours).
+ALTER TABLE filter_lists ADD COLUMN update_interval_hours INTEGER;
+-- Cache validators from the last successful download, sent back as
+-- If-None-Match / If-Modified-Since.
+ALTER TABLE filter_lists ADD COLUMN etag TEXT;
+ALTER TABLE filter_lists ADD COLUMN last_modified TEXT;
+-- Interval announced by the list itself (`! Expires: 4 days` → 96).
+ALTER TABLE filter_lists ADD COLUMN expires_hours INTEGER;
+
+CREATE TABLE IF NOT EXISTS filter_sync_history (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    filter_id   TEXT NOT NULL,
+    started_at  TEXT NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    bytes       INTEGER NOT NULL DEFAULT 0,
+    status      TEXT NOT NULL CHECK (status IN ('updated','not_modified','failed')),
+    rule_count  INTEGER,
+    error       TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_filter_sync_history_filter ON filter_sync_history(filter_id, id DESC);
@@ -0,0 +1,17 @@
+-- Migration 019: Filter list versions and pinning
+-- Complete rule set of each distinct list version (sorted, newline-joined),
+-- with counts of rules added/removed relative to the previous version.
+CREATE TABLE IF NOT EXISTS filter_list_versions (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    filter_id   TEXT NOT NULL,
+    created_at  TEXT NOT NULL,
+    hash        TEXT NOT NULL,
+    rule_count  INTEGER NOT NULL,
+    added       INTEGER NOT NULL,
+    removed     INTEGER NOT NULL,
+    rules       TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_filter_list_versions_filter ON filter_list_versions(filter_id, id DESC);
+
+-- A pinned list keeps this version's rules active until unpinned.
+ALTER TABLE filter_lists ADD COLUMN pinned_version INTEGER;
@@ -0,0 +1,26 @@
+-- Migration 020: Sanity checks on filter list updates
+-- An update with fewer rules than filter_sync_min_rules, or shrinking by more
+-- than filter_sync_max_shrink_percent relative to the last good version, or
+-- that looks like an HTML page, is rejected and the previous rules stay active.
+INSERT OR IGNORE INTO settings (key, value) VALUES ('filter_sync_min_rules', '1');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('filter_sync_max_shrink_percent', '50');
+
+-- Outcome of the latest sync: ok, rejected or failed
+ALTER TABLE filter_lists ADD COLUMN sync_status TEXT;
+ALTER TABLE filter_lists ADD COLUMN sync_error TEXT;
+
+-- Allow 'rejected' in the sync history
+CREATE TABLE filter_sync_history_new (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    filter_id   TEXT NOT NULL,
+    started_at  TEXT NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    bytes       INTEGER NOT NULL DEFAULT 0,
+    status      TEXT NOT NULL CHECK (status IN ('updated','not_modified','rejected','failed')),
+    rule_count  INTEGER,
+    error       TEXT
+);
+INSERT INTO filter_sync_history_new SELECT * FROM filter_sync_history;
+DROP TABLE filter_sync_history;
+ALTER TABLE filter_sync_history_new RENAME TO filter_sync_history;
+CREATE INDEX IF NOT EXISTS idx_filter_sync_history_filter ON filter_sync_history(filter_id, id DESC);
@@ -0,0 +1,5 @@
+-- Migration 021: Client identification by MAC address
+-- clients.identifiers may contain MAC addresses.  The MAC of a query's source
+-- comes from the kernel neighbour table, or, when edns_mac_enabled is 'true',
+-- from the EDNS0 option a forwarder such as dnsmasq (--add-mac) attaches.
+INSERT OR IGNORE INTO settings (key, value) VALUES ('edns_mac_enabled', 'false');
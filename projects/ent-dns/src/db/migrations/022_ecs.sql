@@ -0,0 +1,11 @@
+-- Migration 022: EDNS Client Subnet (RFC 7871)
+-- ecs_forward_client: pass the client's ECS option upstream instead of stripping it.
+-- ecs_add_subnet:     send the client's own subnet, truncated to ecs_ipv4_prefix /
+--                     ecs_ipv6_prefix bits, when no option is forwarded.
+-- ecs_trusted_forwarders: JSON list of forwarder addresses/CIDRs whose ECS
+--                     address identifies the client.
+INSERT OR IGNORE INTO settings (key, value) VALUES ('ecs_forward_client', 'false');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('ecs_add_subnet', 'false');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('ecs_ipv4_prefix', '24');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('ecs_ipv6_prefix', '56');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('ecs_trusted_forwarders', '[]');
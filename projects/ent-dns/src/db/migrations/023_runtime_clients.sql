@@ -0,0 +1,17 @@
+-- Migration 023: Runtime clients
+-- Every source address seen in the query log, with first/last query time and
+-- a running query count.  Hostnames come from reverse lookups (rdns_*) and
+-- from imported DHCP leases (dhcp_*); lease-only rows have no queries yet.
+CREATE TABLE IF NOT EXISTS runtime_clients (
+    ip               TEXT PRIMARY KEY,
+    first_seen       TEXT,
+    last_seen        TEXT,
+    query_count      INTEGER NOT NULL DEFAULT 0,
+    rdns_hostname    TEXT,
+    rdns_checked_at  TEXT,
+    dhcp_hostname    TEXT,
+    mac              TEXT,
+    lease_updated_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_runtime_clients_last_seen ON runtime_clients(last_seen);
@@ -0,0 +1,13 @@
+-- Migration 024: Per-client and per-group setting overrides
+-- clients.settings / client_groups.settings: JSON object overriding the global
+-- defaults below (blocking_mode, safe_search, rate_limit, query_log_enabled,
+-- cache_enabled, upstreams).  A client's own value wins, then its groups' in
+-- priority order.
+-- blocking_mode:     nxdomain | refused | nodata | null_ip
+-- rate_limit_qps:    queries per second per client; 0 disables the limit
+-- query_log_enabled: 'false' keeps queries out of the query log
+ALTER TABLE clients ADD COLUMN settings TEXT;
+ALTER TABLE client_groups ADD COLUMN settings TEXT;
+INSERT OR IGNORE INTO settings (key, value) VALUES ('blocking_mode', 'nxdomain');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('rate_limit_qps', '0');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('query_log_enabled', 'true');
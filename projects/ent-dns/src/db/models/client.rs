@@ -11,6 +11,9 @@ pub struct Client {
     pub upstreams: Option<String>,
     pub filter_enabled: bool,
     pub tags: Option<String>,
+    pub blocked_services: Option<String>,
+    pub blocked_services_schedule_id: Option<String>,
+    pub filter_schedule_id: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -1,5 +1,9 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::dns::client_settings::SettingsOverrides;
+use crate::dns::qtype_policy::QtypeAction;
 
 /// Client group model
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -41,9 +45,10 @@ pub struct ClientGroupMembership {
 pub struct ClientGroupRule {
     pub id: i64,
     pub group_id: i64,
-    pub rule_id: String,   // TEXT: custom_rules.id or dns_rewrites.id
-    pub rule_type: String, // "custom_rule" | "rewrite"
+    pub rule_id: String,   // TEXT: custom_rules.id, dns_rewrites.id, category or service id
+    pub rule_type: String, // "custom_rule" | "rewrite" | "category" | "blocked_service" | "filter_list"
     pub priority: i32,
+    pub schedule_id: Option<String>, // schedules.id; binding only applies while active
     pub created_at: DateTime<Utc>,
 }
 
@@ -68,6 +73,10 @@ pub struct CreateClientGroupRequest {
     pub color: Option<String>,
     pub description: Option<String>,
     pub priority: Option<i32>,
+    /// Query-type policies overriding the global ones, e.g. `{"HTTPS": "empty"}`.
+    pub qtype_policies: Option<HashMap<String, QtypeAction>>,
+    /// Setting overrides inherited by members, e.g. `{"blocking_mode": "refused"}`.
+    pub settings: Option<SettingsOverrides>,
 }
 
 /// Update client group request
@@ -77,6 +86,10 @@ pub struct UpdateClientGroupRequest {
     pub color: Option<String>,
     pub description: Option<String>,
     pub priority: Option<i32>,
+    /// Replaces the group's query-type policies; `{}` clears them.
+    pub qtype_policies: Option<HashMap<String, QtypeAction>>,
+    /// Replaces the group's setting overrides; `{}` clears them.
+    pub settings: Option<SettingsOverrides>,
 }
 
 /// Reorder groups request
@@ -113,9 +126,18 @@ pub struct BatchBindRulesRequest {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BindRuleRequest {
-    pub rule_id: String,   // custom_rules.id or dns_rewrites.id (TEXT UUID)
-    pub rule_type: String, // "custom_rule" | "rewrite"
+    pub rule_id: String,   // custom_rules.id or dns_rewrites.id (TEXT UUID), category/service id or filter_lists.id
+    pub rule_type: String, // "custom_rule" | "rewrite" | "category" | "blocked_service" | "filter_list"
     pub priority: Option<i32>,
+    pub schedule_id: Option<String>, // schedules.id; binding only applies while active
+}
+
+/// Attach (or with `schedule_id: None`, detach) a schedule on an existing binding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateBindingScheduleRequest {
+    pub rule_id: String,
+    pub rule_type: String,
+    pub schedule_id: Option<String>,
 }
 
 /// Batch unbind rules request
@@ -145,18 +167,25 @@ pub struct ClientWithGroups {
 }
 
 /// Rule with source info (for DNS engine)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DnsRuleWithSource {
-    pub id: i64,
-    pub rule_type: String,
+    pub id: String,
+    pub rule_type: String, // "custom_rule" | "rewrite" | "filter_list" | "threat_feed" | "category" | "blocked_service"
     pub pattern: Option<String>,
     pub domain: Option<String>,
     pub replacement: Option<String>,
-    pub action: Option<String>,
+    pub action: Option<String>, // "block" | "allow" | "rewrite"
     pub source: String, // "client" | "group" | "global"
     pub priority: i32,
+    pub group_id: Option<i64>,
     pub group_name: Option<String>,
-    pub created_at: DateTime<Utc>,
+    /// Name of a filter list, category or service.
+    pub name: Option<String>,
+    /// Rules in a filter list.
+    pub rule_count: Option<i64>,
+    /// Only applies while this schedule is active.
+    pub schedule_id: Option<String>,
+    pub created_at: Option<String>,
 }
 
 /// Preview rules request
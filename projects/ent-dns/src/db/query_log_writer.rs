@@ -3,8 +3,10 @@
 /// DnsHandler sends log entries via an UnboundedSender (non-blocking, zero latency
 /// on the DNS hot path). This background task drains the channel every second or
 /// when a batch of 100 entries accumulates, then writes them in a single SQLite
-/// transaction — dramatically reducing write amplification.
+/// transaction — dramatically reducing write amplification.  The same
+/// transaction records each source address as a runtime client.
 use crate::db::DbPool;
+use std::collections::HashMap;
 use tokio::sync::mpsc;
 use tokio::time::{Duration, interval};
 
@@ -101,6 +103,32 @@ async fn write_batch(db: &DbPool, batch: &[QueryLogEntry]) -> Result<(), sqlx::E
         .await?;
     }
 
+    // Runtime clients: per address, (first time, last time, queries) in this batch
+    let mut seen: HashMap<&str, (&str, &str, i64)> = HashMap::new();
+    for entry in batch {
+        let (first, last, count) = seen
+            .entry(entry.client_ip.as_str())
+            .or_insert((entry.time.as_str(), entry.time.as_str(), 0));
+        *first = (*first).min(entry.time.as_str());
+        *last = (*last).max(entry.time.as_str());
+        *count += 1;
+    }
+    for (ip, (first, last, count)) in seen {
+        sqlx::query(
+            "INSERT INTO runtime_clients (ip, first_seen, last_seen, query_count) VALUES (?, ?, ?, ?)
+             ON CONFLICT(ip) DO UPDATE SET
+                 first_seen = MIN(COALESCE(first_seen, excluded.first_seen), excluded.first_seen),
+                 last_seen = MAX(COALESCE(last_seen, ''), excluded.last_seen),
+                 query_count = query_count + excluded.query_count",
+        )
+        .bind(ip)
+        .bind(first)
+        .bind(last)
+        .bind(count)
+        .execute(&mut *tx)
+        .await?;
+    }
+
     tx.commit().await?;
     Ok(())
 }
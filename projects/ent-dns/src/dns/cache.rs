@@ -1,4 +1,5 @@
 use hickory_proto::rr::RecordType;
+use ipnet::IpNet;
 use moka::future::Cache;
 use moka::Expiry;
 use std::time::{Duration, Instant};
@@ -45,13 +46,27 @@ impl DnsCache {
         Self { inner }
     }
 
-    fn cache_key(domain: &str, qtype: RecordType) -> String {
-        format!("{}:{:?}", domain.to_lowercase(), qtype)
+    fn cache_key(domain: &str, qtype: RecordType, subnet: Option<IpNet>) -> String {
+        match subnet {
+            Some(net) => format!("{}:{:?}@{}", domain.to_lowercase(), qtype, net),
+            None => format!("{}:{:?}", domain.to_lowercase(), qtype),
+        }
     }
 
     pub async fn get(&self, domain: &str, qtype: RecordType) -> Option<Vec<u8>> {
+        self.get_for_subnet(domain, qtype, None).await
+    }
+
+    /// Look up an answer for a query sent upstream with an EDNS Client Subnet:
+    /// one cached for that subnet, else one valid for every subnet.
+    pub async fn get_for_subnet(&self, domain: &str, qtype: RecordType, subnet: Option<IpNet>) -> Option<Vec<u8>> {
+        if subnet.is_some() {
+            if let Some(entry) = self.inner.get(&Self::cache_key(domain, qtype, subnet)).await {
+                return Some(entry.data);
+            }
+        }
         self.inner
-            .get(&Self::cache_key(domain, qtype))
+            .get(&Self::cache_key(domain, qtype, None))
             .await
             .map(|e| e.data)
     }
@@ -61,6 +76,21 @@ impl DnsCache {
     /// `min_ttl` is the minimum TTL (seconds) across all answer records.
     /// Pass `None` to use the default TTL.
     pub async fn set_with_ttl(&self, domain: &str, qtype: RecordType, data: Vec<u8>, min_ttl: Option<u32>) {
+        self.set_for_subnet(domain, qtype, None, data, min_ttl).await;
+    }
+
+    /// Store an answer that only applies to `subnet` (the ECS source subnet
+    /// of a query whose answer had a non-zero scope), or to everyone (`None`).
+    /// Scoped answers are keyed by the source subnet even when the scope is
+    /// shorter, which only costs extra upstream queries.
+    pub async fn set_for_subnet(
+        &self,
+        domain: &str,
+        qtype: RecordType,
+        subnet: Option<IpNet>,
+        data: Vec<u8>,
+        min_ttl: Option<u32>,
+    ) {
         let ttl_secs = min_ttl
             .map(|t| t as u64)
             .unwrap_or(TTL_DEFAULT_SECS)
@@ -70,7 +100,7 @@ impl DnsCache {
             ttl: Duration::from_secs(ttl_secs),
         };
         self.inner
-            .insert(Self::cache_key(domain, qtype), entry)
+            .insert(Self::cache_key(domain, qtype, subnet), entry)
             .await;
     }
 
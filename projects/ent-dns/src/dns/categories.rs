@@ -0,0 +1,113 @@
+//! Parental-control content categories.
+//!
+//! Each category is backed by a bundled domain list (compiled into the binary)
+//! and may be extended by subscribed filter lists whose `category` column is set.
+//! Categories are enabled globally via the `parental_control_enabled` /
+//! `parental_control_categories` settings, or per client group through
+//! `client_group_rules` rows with `rule_type = 'category'`.
+
+use super::rules::RuleSet;
+
+/// A built-in content category.
+#[derive(Debug, Clone, Copy)]
+pub struct Category {
+    /// Stable identifier used in settings, group bindings and log reasons.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Bundled list in plain-domain format.
+    bundled: &'static str,
+}
+
+pub const CATEGORIES: &[Category] = &[
+    Category {
+        id: "adult",
+        name: "Adult content",
+        description: "Pornography and other sexually explicit sites",
+        bundled: include_str!("categories/adult.txt"),
+    },
+    Category {
+        id: "gambling",
+        name: "Gambling",
+        description: "Online casinos, sports betting and poker",
+        bundled: include_str!("categories/gambling.txt"),
+    },
+    Category {
+        id: "social_media",
+        name: "Social media",
+        description: "Social networks and their content CDNs",
+        bundled: include_str!("categories/social_media.txt"),
+    },
+    Category {
+        id: "gaming",
+        name: "Gaming",
+        description: "Game stores, launchers and online gaming platforms",
+        bundled: include_str!("categories/gaming.txt"),
+    },
+    Category {
+        id: "dating",
+        name: "Dating",
+        description: "Dating sites and apps",
+        bundled: include_str!("categories/dating.txt"),
+    },
+    Category {
+        id: "video_streaming",
+        name: "Video streaming",
+        description: "Video platforms and streaming services",
+        bundled: include_str!("categories/video_streaming.txt"),
+    },
+];
+
+/// Look up a category by id.
+pub fn find(id: &str) -> Option<&'static Category> {
+    CATEGORIES.iter().find(|c| c.id == id)
+}
+
+impl Category {
+    /// Build a rule set from the bundled list only.
+    pub fn bundled_ruleset(&self) -> RuleSet {
+        let mut rs = RuleSet::new();
+        rs.add_rules_from_str(self.bundled);
+        rs
+    }
+}
+
+/// Log reason recorded for a query blocked by `category_id`.
+pub fn block_reason(category_id: &str) -> String {
+    format!("category:{}", category_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_category_ids_unique() {
+        for (i, a) in CATEGORIES.iter().enumerate() {
+            for b in &CATEGORIES[i + 1..] {
+                assert_ne!(a.id, b.id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bundled_lists_parse() {
+        for category in CATEGORIES {
+            let rs = category.bundled_ruleset();
+            assert!(rs.blocked_count() > 0, "bundled list for {} is empty", category.id);
+        }
+    }
+
+    #[test]
+    fn test_bundled_list_blocks_subdomains() {
+        let rs = find("gambling").unwrap().bundled_ruleset();
+        assert!(rs.is_blocked("bet365.com"));
+        assert!(rs.is_blocked("www.bet365.com"));
+        assert!(!rs.is_blocked("example.com"));
+    }
+
+    #[test]
+    fn test_unknown_category() {
+        assert!(find("nonexistent").is_none());
+    }
+}
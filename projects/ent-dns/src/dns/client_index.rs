@@ -0,0 +1,278 @@
+//! In-memory index from source address to client.
+//!
+//! Built from `clients.identifiers`: client IDs, MAC addresses and plain IP
+//! addresses go into exact-match hashes, CIDR identifiers into
+//! per-prefix-length tables searched longest prefix first.  A client's own id
+//! is also accepted as client ID.
+//!
+//! A client ID sent over the transport (DoH path, DoT SNI) beats a MAC, a MAC
+//! beats any IP identifier, an exact address beats any CIDR and a longer
+//! prefix beats a shorter one; when two clients claim the same identifier, the
+//! one created first wins.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+
+use super::neighbors::MacAddr;
+
+/// The identifier a source matched, in canonical form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Identifier<'a> {
+    ClientId(&'a str),
+    Mac(MacAddr),
+    Ip(IpAddr),
+    Net(IpNet),
+}
+
+impl Identifier<'_> {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ClientId(_) => "client_id",
+            Self::Mac(_) => "mac",
+            Self::Ip(_) => "ip",
+            Self::Net(_) => "cidr",
+        }
+    }
+}
+
+impl std::fmt::Display for Identifier<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ClientId(id) => f.write_str(id),
+            Self::Mac(mac) => mac.fmt(f),
+            Self::Ip(ip) => ip.fmt(f),
+            Self::Net(net) => net.fmt(f),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ClientIndex {
+    client_ids: HashMap<String, String>,
+    macs: HashMap<MacAddr, String>,
+    exact: HashMap<IpAddr, String>,
+    /// Network address → client id, per prefix length, longest prefix first.
+    v4: Vec<(u8, HashMap<Ipv4Addr, String>)>,
+    v6: Vec<(u8, HashMap<Ipv6Addr, String>)>,
+}
+
+/// Treat IPv4-mapped IPv6 sources (dual-stack sockets) as IPv4.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    }
+}
+
+/// Client IDs are single DNS labels: 1–63 lowercase letters, digits or
+/// hyphens.
+pub fn is_valid_client_id(id: &str) -> bool {
+    (1..=63).contains(&id.len())
+        && id.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+/// The table for `prefix` in a longest-prefix-first list of per-prefix-length
+/// tables, created if missing.
+pub(crate) fn prefix_table<T: Default>(tables: &mut Vec<(u8, T)>, prefix: u8) -> &mut T {
+    let idx = match tables.iter().position(|(len, _)| *len <= prefix) {
+        Some(i) if tables[i].0 == prefix => i,
+        Some(i) => {
+            tables.insert(i, (prefix, T::default()));
+            i
+        }
+        None => {
+            tables.push((prefix, T::default()));
+            tables.len() - 1
+        }
+    };
+    &mut tables[idx].1
+}
+
+impl ClientIndex {
+    /// Build the index from `(client id, identifiers JSON)` rows, oldest client
+    /// first.  Unrecognised identifiers are ignored.
+    pub fn build(clients: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut index = Self::default();
+        for (id, identifiers) in clients {
+            let Ok(identifiers) = serde_json::from_str::<Vec<serde_json::Value>>(&identifiers) else {
+                tracing::warn!("Client {} has malformed identifiers", id);
+                continue;
+            };
+            for identifier in identifiers.iter().filter_map(|v| v.as_str()) {
+                index.insert(identifier.trim(), &id);
+            }
+            let own_id = id.to_ascii_lowercase();
+            if is_valid_client_id(&own_id) {
+                index.client_ids.entry(own_id).or_insert_with(|| id.clone());
+            }
+        }
+        index
+    }
+
+    fn insert(&mut self, identifier: &str, client_id: &str) {
+        if let Ok(ip) = identifier.parse::<IpAddr>() {
+            self.exact.entry(canonical(ip)).or_insert_with(|| client_id.to_string());
+            return;
+        }
+        if let Some(mac) = MacAddr::parse(identifier) {
+            self.macs.entry(mac).or_insert_with(|| client_id.to_string());
+            return;
+        }
+        match identifier.parse::<IpNet>() {
+            Ok(IpNet::V4(net)) => {
+                prefix_table(&mut self.v4, net.prefix_len())
+                    .entry(net.network())
+                    .or_insert_with(|| client_id.to_string());
+            }
+            Ok(IpNet::V6(net)) => {
+                prefix_table(&mut self.v6, net.prefix_len())
+                    .entry(net.network())
+                    .or_insert_with(|| client_id.to_string());
+            }
+            Err(_) => {
+                let lowered = identifier.to_ascii_lowercase();
+                if is_valid_client_id(&lowered) {
+                    self.client_ids.entry(lowered).or_insert_with(|| client_id.to_string());
+                }
+            }
+        }
+    }
+
+    /// Id of the client matching the source most specifically.  An unknown
+    /// client ID falls back to MAC and address matching.
+    pub fn lookup(&self, ip: IpAddr, mac: Option<MacAddr>, client_id: Option<&str>) -> Option<&str> {
+        self.lookup_match(ip, mac, client_id).map(|(id, _)| id)
+    }
+
+    /// Like `lookup`, also returning the identifier that matched.
+    pub fn lookup_match<'a>(
+        &self,
+        ip: IpAddr,
+        mac: Option<MacAddr>,
+        client_id: Option<&'a str>,
+    ) -> Option<(&str, Identifier<'a>)> {
+        if let Some(cid) = client_id {
+            if let Some(id) = self.client_ids.get(cid) {
+                return Some((id, Identifier::ClientId(cid)));
+            }
+        }
+        if let Some(mac) = mac {
+            if let Some(id) = self.macs.get(&mac) {
+                return Some((id, Identifier::Mac(mac)));
+            }
+        }
+        let ip = canonical(ip);
+        if let Some(id) = self.exact.get(&ip) {
+            return Some((id, Identifier::Ip(ip)));
+        }
+        match ip {
+            IpAddr::V4(v4) => self.v4.iter().find_map(|(len, nets)| {
+                let net = Ipv4Net::new(v4, *len).ok()?.trunc();
+                nets.get(&net.network()).map(|id| (id.as_str(), Identifier::Net(IpNet::V4(net))))
+            }),
+            IpAddr::V6(v6) => self.v6.iter().find_map(|(len, nets)| {
+                let net = Ipv6Net::new(v6, *len).ok()?.trunc();
+                nets.get(&net.network()).map(|id| (id.as_str(), Identifier::Net(IpNet::V6(net))))
+            }),
+        }
+    }
+
+    /// True if some client is identified by MAC address.
+    pub fn has_macs(&self) -> bool {
+        !self.macs.is_empty()
+    }
+
+    /// Number of indexed identifiers.
+    pub fn len(&self) -> usize {
+        self.client_ids.len()
+            + self.macs.len()
+            + self.exact.len()
+            + self.v4.iter().map(|(_, t)| t.len()).sum::<usize>()
+            + self.v6.iter().map(|(_, t)| t.len()).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(rows: &[(&str, &str)]) -> ClientIndex {
+        ClientIndex::build(rows.iter().map(|(id, ids)| (id.to_string(), ids.to_string())))
+    }
+
+    fn lookup<'a>(index: &'a ClientIndex, ip: &str) -> Option<&'a str> {
+        index.lookup(ip.parse().unwrap(), None, None)
+    }
+
+    #[test]
+    fn test_most_specific_match_wins() {
+        let index = index(&[
+            ("office", r#"["10.0.0.0/8"]"#),
+            ("lab", r#"["10.1.0.0/16", "2001:db8::/32"]"#),
+            ("printer", r#"["10.1.2.3"]"#),
+            ("wifi", r#"["10.1.2.0/24", "2001:db8:1::/48"]"#),
+        ]);
+        assert_eq!(index.len(), 10);
+        assert_eq!(lookup(&index, "10.1.2.3"), Some("printer"));
+        assert_eq!(lookup(&index, "10.1.2.4"), Some("wifi"));
+        assert_eq!(lookup(&index, "10.1.3.1"), Some("lab"));
+        assert_eq!(lookup(&index, "10.200.0.1"), Some("office"));
+        assert_eq!(lookup(&index, "192.168.0.1"), None);
+        assert_eq!(lookup(&index, "2001:db8:1::5"), Some("wifi"));
+        assert_eq!(lookup(&index, "2001:db8:2::5"), Some("lab"));
+        // IPv4-mapped source on a dual-stack socket
+        assert_eq!(lookup(&index, "::ffff:10.1.2.3"), Some("printer"));
+
+        let matched = |ip: &str| index.lookup_match(ip.parse().unwrap(), None, None).map(|(_, m)| (m.kind(), m.to_string()));
+        assert_eq!(matched("10.1.2.3"), Some(("ip", "10.1.2.3".to_string())));
+        assert_eq!(matched("10.1.3.1"), Some(("cidr", "10.1.0.0/16".to_string())));
+        assert_eq!(matched("2001:db8:1::5"), Some(("cidr", "2001:db8:1::/48".to_string())));
+    }
+
+    #[test]
+    fn test_first_client_wins_ties() {
+        let index = index(&[
+            ("first", r#"["192.168.1.0/24", "192.168.1.10"]"#),
+            ("second", r#"["192.168.1.0/24", "192.168.1.10", "not-an-address"]"#),
+            ("broken", "not json"),
+        ]);
+        assert_eq!(lookup(&index, "192.168.1.10"), Some("first"));
+        assert_eq!(lookup(&index, "192.168.1.11"), Some("first"));
+    }
+
+    #[test]
+    fn test_mac_beats_address() {
+        let index = index(&[
+            ("desk", r#"["192.168.1.10"]"#),
+            ("phone", r#"["AA-BB-CC-DD-EE-FF"]"#),
+        ]);
+        assert!(index.has_macs());
+        let ip = "192.168.1.10".parse().unwrap();
+        assert_eq!(index.lookup(ip, MacAddr::parse("aa:bb:cc:dd:ee:ff"), None), Some("phone"));
+        assert_eq!(index.lookup(ip, MacAddr::parse("aa:bb:cc:dd:ee:00"), None), Some("desk"));
+    }
+
+    #[test]
+    fn test_client_id_beats_everything() {
+        let index = index(&[
+            ("desk", r#"["192.168.1.10", "AA-BB-CC-DD-EE-FF"]"#),
+            ("laptop", r#"["Roaming-Laptop"]"#),
+        ]);
+        let ip = "192.168.1.10".parse().unwrap();
+        let mac = MacAddr::parse("aa:bb:cc:dd:ee:ff");
+        assert_eq!(index.lookup(ip, mac, Some("roaming-laptop")), Some("laptop"));
+        assert_eq!(index.lookup(ip, mac, Some("desk")), Some("desk"));
+        assert_eq!(index.lookup(ip, None, Some("unknown")), Some("desk"));
+        assert_eq!(index.lookup_match(ip, mac, Some("roaming-laptop")), Some(("laptop", Identifier::ClientId("roaming-laptop"))));
+        assert!(is_valid_client_id("kiosk-1"));
+        assert!(!is_valid_client_id("Kiosk"));
+        assert!(!is_valid_client_id("a.b"));
+        assert!(!is_valid_client_id(""));
+    }
+}
@@ -0,0 +1,390 @@
+//! Per-client and per-group setting overrides.
+//!
+//! Clients and groups carry a `settings` JSON object whose fields override
+//! the global defaults: blocking mode, SafeSearch, rate limit, query logging,
+//! response caching and upstreams (a client's own upstreams stay in its
+//! `upstreams` column).  Unset fields inherit: the client's own value wins,
+//! then its groups' in priority order, then the global setting.
+
+use anyhow::Result;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::db::DbPool;
+
+/// Source of a setting no client or group overrides.
+pub const SOURCE_GLOBAL: &str = "global";
+/// Upper bound for `rate_limit` (queries per second).
+pub const MAX_RATE_LIMIT: u32 = 100_000;
+/// Rate limiter windows kept before idle ones are pruned.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Answer sent for a blocked query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockingMode {
+    /// NXDOMAIN
+    #[default]
+    Nxdomain,
+    /// REFUSED
+    Refused,
+    /// NOERROR without answers
+    Nodata,
+    /// `0.0.0.0` / `::` for A/AAAA queries, NOERROR without answers otherwise
+    NullIp,
+}
+
+impl BlockingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockingMode::Nxdomain => "nxdomain",
+            BlockingMode::Refused => "refused",
+            BlockingMode::Nodata => "nodata",
+            BlockingMode::NullIp => "null_ip",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(s.to_string())).ok()
+    }
+}
+
+/// Overrides set on a client or group; `None` inherits.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocking_mode: Option<BlockingMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safe_search: Option<bool>,
+    /// Queries per second; 0 means unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_log_enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_enabled: Option<bool>,
+    /// Upstream addresses (`ip` or `ip:port`, plain DNS).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstreams: Option<Vec<String>>,
+}
+
+impl SettingsOverrides {
+    /// Stored overrides; a missing or unreadable object overrides nothing.
+    pub fn parse(json: Option<&str>) -> Self {
+        json.and_then(|s| serde_json::from_str(s).ok()).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Validate overrides from the API and encode them for storage (NULL
+    /// when nothing is overridden).
+    pub fn encode(&self) -> Result<Option<String>, String> {
+        if let Some(limit) = self.rate_limit {
+            if limit > MAX_RATE_LIMIT {
+                return Err(format!("rate_limit must be between 0 and {}", MAX_RATE_LIMIT));
+            }
+        }
+        if let Some(upstreams) = &self.upstreams {
+            if upstreams.is_empty() {
+                return Err("upstreams cannot be empty".to_string());
+            }
+            if let Some(bad) = upstreams.iter().find(|u| !is_upstream_addr(u)) {
+                return Err(format!("Invalid upstream address: {}", bad));
+            }
+        }
+        if self.is_empty() {
+            return Ok(None);
+        }
+        serde_json::to_string(self).map(Some).map_err(|e| e.to_string())
+    }
+}
+
+/// Upstream forms accepted by `DnsResolver::with_upstreams`.
+fn is_upstream_addr(s: &str) -> bool {
+    let s = s.trim();
+    s.parse::<SocketAddr>().is_ok() || s.parse::<IpAddr>().is_ok()
+}
+
+/// Settings in effect for a client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClientSettings {
+    pub blocking_mode: BlockingMode,
+    pub safe_search: bool,
+    pub rate_limit: u32,
+    pub query_log_enabled: bool,
+    pub cache_enabled: bool,
+    /// `None` uses the global resolver.
+    pub upstreams: Option<Vec<String>>,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            blocking_mode: BlockingMode::default(),
+            safe_search: false,
+            rate_limit: 0,
+            query_log_enabled: true,
+            cache_enabled: true,
+            upstreams: None,
+        }
+    }
+}
+
+impl ClientSettings {
+    /// Global defaults from the `blocking_mode`, `safe_search_enabled`,
+    /// `rate_limit_qps` and `query_log_enabled` settings.
+    pub async fn load_defaults(db: &DbPool) -> Result<Self> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT key, value FROM settings
+             WHERE key IN ('blocking_mode', 'safe_search_enabled', 'rate_limit_qps', 'query_log_enabled')"
+        )
+        .fetch_all(db)
+        .await?;
+        let mut settings = Self::default();
+        for (key, value) in rows {
+            match key.as_str() {
+                "blocking_mode" => settings.blocking_mode = BlockingMode::parse(&value).unwrap_or_default(),
+                "safe_search_enabled" => settings.safe_search = value == "true",
+                "rate_limit_qps" => settings.rate_limit = value.parse::<u32>().unwrap_or(0).min(MAX_RATE_LIMIT),
+                "query_log_enabled" => settings.query_log_enabled = value != "false",
+                _ => {}
+            }
+        }
+        Ok(settings)
+    }
+
+    /// These settings with `overrides` applied.
+    pub fn with_overrides(&self, overrides: &SettingsOverrides) -> Self {
+        Self {
+            blocking_mode: overrides.blocking_mode.unwrap_or(self.blocking_mode),
+            safe_search: overrides.safe_search.unwrap_or(self.safe_search),
+            rate_limit: overrides.rate_limit.unwrap_or(self.rate_limit),
+            query_log_enabled: overrides.query_log_enabled.unwrap_or(self.query_log_enabled),
+            cache_enabled: overrides.cache_enabled.unwrap_or(self.cache_enabled),
+            upstreams: overrides.upstreams.clone().or_else(|| self.upstreams.clone()),
+        }
+    }
+}
+
+/// Overrides of one client or group, labelled `client` or `group:<id>`.
+#[derive(Debug, Clone)]
+pub struct SettingsLayer {
+    pub source: String,
+    pub overrides: SettingsOverrides,
+}
+
+/// Overrides of a client's layers combined, with the layer each field came from.
+#[derive(Debug, Clone, Default)]
+pub struct MergedOverrides {
+    pub overrides: SettingsOverrides,
+    pub sources: BTreeMap<&'static str, String>,
+}
+
+impl MergedOverrides {
+    /// Effective settings on top of `defaults`, as returned by the API:
+    /// each value plus a `sources` map naming where it comes from.
+    pub fn effective_json(&self, defaults: &ClientSettings) -> serde_json::Value {
+        let settings = defaults.with_overrides(&self.overrides);
+        let mut value = serde_json::to_value(&settings).unwrap_or_default();
+        let sources: BTreeMap<&str, &str> = ["blocking_mode", "safe_search", "rate_limit", "query_log_enabled", "cache_enabled", "upstreams"]
+            .into_iter()
+            .map(|field| (field, self.sources.get(field).map_or(SOURCE_GLOBAL, String::as_str)))
+            .collect();
+        value["sources"] = serde_json::json!(sources);
+        value
+    }
+}
+
+/// Combine layers ordered from most to least specific: the first layer
+/// setting a field wins.
+pub fn merge(layers: &[SettingsLayer]) -> MergedOverrides {
+    let mut merged = MergedOverrides::default();
+    macro_rules! inherit {
+        ($($field:ident),*) => {$(
+            if let Some(layer) = layers.iter().find(|l| l.overrides.$field.is_some()) {
+                merged.overrides.$field = layer.overrides.$field.clone();
+                merged.sources.insert(stringify!($field), layer.source.clone());
+            }
+        )*};
+    }
+    inherit!(blocking_mode, safe_search, rate_limit, query_log_enabled, cache_enabled, upstreams);
+    merged
+}
+
+/// A client's layers: its own overrides (with its `upstreams` column), then
+/// its groups' in priority order.
+pub async fn load_layers(db: &DbPool, client_id: &str) -> Result<Vec<SettingsLayer>> {
+    let mut layers = Vec::new();
+    let client: Option<(Option<String>, Option<String>)> = sqlx::query_as(
+        "SELECT settings, upstreams FROM clients WHERE id = ?"
+    )
+    .bind(client_id)
+    .fetch_optional(db)
+    .await?;
+    if let Some((settings, upstreams)) = client {
+        let mut overrides = SettingsOverrides::parse(settings.as_deref());
+        overrides.upstreams = upstreams
+            .and_then(|s| serde_json::from_str::<Vec<String>>(&s).ok())
+            .filter(|v| !v.is_empty());
+        layers.push(SettingsLayer { source: "client".to_string(), overrides });
+    }
+
+    let groups: Vec<(i64, String)> = sqlx::query_as(
+        r#"
+        SELECT cg.id, cg.settings
+        FROM client_group_memberships m
+        JOIN client_groups cg ON cg.id = m.group_id
+        WHERE m.client_id = ? AND cg.settings IS NOT NULL
+        ORDER BY cg.priority ASC, cg.id ASC
+        "#
+    )
+    .bind(client_id)
+    .fetch_all(db)
+    .await?;
+    layers.extend(groups.into_iter().map(|(id, settings)| SettingsLayer {
+        source: format!("group:{}", id),
+        overrides: SettingsOverrides::parse(Some(&settings)),
+    }));
+    Ok(layers)
+}
+
+/// Fixed one-second windows of query counts per client.
+pub struct RateLimiter {
+    windows: DashMap<String, (Instant, u32)>,
+    /// Reference point for `last_prune_ms`.
+    epoch: Instant,
+    /// When expired windows were last pruned, in ms since `epoch`.
+    last_prune_ms: AtomicU64,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            windows: DashMap::new(),
+            epoch: Instant::now(),
+            last_prune_ms: AtomicU64::new(0),
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a query from `key`; false once it exceeds `limit` queries in
+    /// the current second.  A limit of 0 never limits.
+    pub fn allow(&self, key: &str, limit: u32) -> bool {
+        if limit == 0 {
+            return true;
+        }
+        let now = Instant::now();
+        let allowed = {
+            let mut window = self.windows.entry(key.to_string()).or_insert((now, 0));
+            if now.duration_since(window.0) >= Duration::from_secs(1) {
+                *window = (now, 0);
+            }
+            window.1 += 1;
+            window.1 <= limit
+        };
+        if self.windows.len() > MAX_TRACKED_CLIENTS {
+            self.prune(now);
+        }
+        allowed
+    }
+
+    /// Drop expired windows, at most once per second so a flood of sources
+    /// does not turn every query into a full scan.
+    fn prune(&self, now: Instant) {
+        let now_ms = now.duration_since(self.epoch).as_millis() as u64;
+        let last = self.last_prune_ms.load(Ordering::Relaxed);
+        if now_ms.saturating_sub(last) < 1000
+            || self.last_prune_ms.compare_exchange(last, now_ms, Ordering::Relaxed, Ordering::Relaxed).is_err()
+        {
+            return;
+        }
+        self.windows.retain(|_, (start, _)| now.duration_since(*start) < Duration::from_secs(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(source: &str, json: &str) -> SettingsLayer {
+        SettingsLayer { source: source.to_string(), overrides: SettingsOverrides::parse(Some(json)) }
+    }
+
+    #[test]
+    fn test_merge_by_priority() {
+        let layers = [
+            layer("client", r#"{"query_log_enabled": false}"#),
+            layer("group:2", r#"{"blocking_mode": "refused", "query_log_enabled": true, "rate_limit": 20}"#),
+            layer("group:1", r#"{"blocking_mode": "null_ip", "cache_enabled": false}"#),
+        ];
+        let merged = merge(&layers);
+        assert_eq!(merged.overrides.blocking_mode, Some(BlockingMode::Refused));
+        assert_eq!(merged.overrides.query_log_enabled, Some(false));
+        assert_eq!(merged.overrides.rate_limit, Some(20));
+        assert_eq!(merged.overrides.cache_enabled, Some(false));
+        assert_eq!(merged.sources.get("blocking_mode").map(String::as_str), Some("group:2"));
+        assert_eq!(merged.sources.get("query_log_enabled").map(String::as_str), Some("client"));
+
+        let defaults = ClientSettings { safe_search: true, ..ClientSettings::default() };
+        let effective = defaults.with_overrides(&merged.overrides);
+        assert!(effective.safe_search);
+        assert!(!effective.query_log_enabled);
+        let json = merged.effective_json(&defaults);
+        assert_eq!(json["blocking_mode"], "refused");
+        assert_eq!(json["sources"]["safe_search"], SOURCE_GLOBAL);
+        assert_eq!(json["sources"]["cache_enabled"], "group:1");
+    }
+
+    #[test]
+    fn test_encode_validates() {
+        assert_eq!(SettingsOverrides::default().encode(), Ok(None));
+        let overrides = SettingsOverrides { upstreams: Some(vec!["9.9.9.9".into(), "10.0.0.1:5353".into()]), ..Default::default() };
+        assert!(overrides.encode().unwrap().is_some());
+        let overrides = SettingsOverrides { upstreams: Some(vec!["tls://dns.example".into()]), ..Default::default() };
+        assert!(overrides.encode().is_err());
+        let overrides = SettingsOverrides { rate_limit: Some(MAX_RATE_LIMIT + 1), ..Default::default() };
+        assert!(overrides.encode().is_err());
+        assert!(serde_json::from_str::<SettingsOverrides>(r#"{"blocking_mode": "drop"}"#).is_err());
+        assert!(serde_json::from_str::<SettingsOverrides>(r#"{"unknown": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new();
+        assert!((0..3).all(|_| limiter.allow("a", 3)));
+        assert!(!limiter.allow("a", 3));
+        assert!(limiter.allow("b", 3));
+        assert!(limiter.allow("a", 0));
+    }
+
+    #[test]
+    fn test_rate_limiter_prunes_once_per_second() {
+        let limiter = RateLimiter::new();
+        let start = Instant::now();
+        let expired = start - Duration::from_secs(2);
+        for i in 0..=MAX_TRACKED_CLIENTS {
+            limiter.windows.insert(format!("idle-{}", i), (expired, 1));
+        }
+        limiter.prune(start + Duration::from_secs(2));
+        assert!(limiter.windows.is_empty());
+
+        // Within the same second the next prune is skipped
+        limiter.windows.insert("idle".to_string(), (expired, 1));
+        limiter.prune(start + Duration::from_millis(2500));
+        assert_eq!(limiter.windows.len(), 1);
+        limiter.prune(start + Duration::from_secs(3));
+        assert!(limiter.windows.is_empty());
+    }
+}
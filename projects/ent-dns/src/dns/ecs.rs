@@ -0,0 +1,293 @@
+//! EDNS Client Subnet (RFC 7871).
+//!
+//! The ECS option a client sends is stripped by default, or passed upstream
+//! with `ecs_forward_client`.  With `ecs_add_subnet`, queries that carry no
+//! forwarded option get one for the client's own subnet, truncated to
+//! `ecs_ipv4_prefix` / `ecs_ipv6_prefix` bits; non-global addresses (private,
+//! loopback, link-local, …) are never sent, and a client sending a /0 option
+//! opts out.  Queries from one of `ecs_trusted_forwarders` are identified by
+//! the ECS address instead of the forwarder's.
+
+use anyhow::Result;
+use hickory_proto::op::Message;
+use hickory_proto::rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption};
+use ipnet::IpNet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::db::DbPool;
+
+const DEFAULT_IPV4_PREFIX: u8 = 24;
+const DEFAULT_IPV6_PREFIX: u8 = 56;
+
+/// An ECS option: the address truncated to `source_prefix` bits, and the
+/// prefix the answer covers (`scope_prefix`, 0 in queries).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcsSubnet {
+    pub addr: IpAddr,
+    pub source_prefix: u8,
+    pub scope_prefix: u8,
+}
+
+impl EcsSubnet {
+    /// Subnet of `addr` truncated to `prefix` bits (capped at the address length).
+    pub fn new(addr: IpAddr, prefix: u8) -> Self {
+        let net = match addr {
+            IpAddr::V4(v4) => IpNet::new(IpAddr::V4(v4), prefix.min(32)),
+            IpAddr::V6(v6) => IpNet::new(IpAddr::V6(v6), prefix.min(128)),
+        }
+        .expect("prefix is capped to the address length");
+        Self { addr: net.network(), source_prefix: net.prefix_len(), scope_prefix: 0 }
+    }
+
+    /// The ECS option of a message, if it carries a well-formed one.
+    pub fn from_message(message: &Message) -> Option<Self> {
+        let option = message.extensions().as_ref()?.option(EdnsCode::Subnet)?;
+        let bytes = Vec::<u8>::try_from(option).ok()?;
+        Self::parse(&bytes)
+    }
+
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let (&[f0, f1, source_prefix, scope_prefix], address) = bytes.split_first_chunk::<4>()?;
+        let addr = match u16::from_be_bytes([f0, f1]) {
+            1 if source_prefix <= 32 => {
+                let mut octets = [0u8; 4];
+                octets.get_mut(..address.len())?.copy_from_slice(address);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            2 if source_prefix <= 128 => {
+                let mut octets = [0u8; 16];
+                octets.get_mut(..address.len())?.copy_from_slice(address);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => return None,
+        };
+        let subnet = Self::new(addr, source_prefix);
+        // RFC 7871 §6: address bits beyond the source prefix must be zero
+        (subnet.addr == addr).then_some(Self { scope_prefix, ..subnet })
+    }
+
+    pub fn to_option(self) -> EdnsOption {
+        EdnsOption::Subnet(ClientSubnet::new(self.addr, self.source_prefix, 0))
+    }
+
+    pub fn network(&self) -> IpNet {
+        IpNet::new(self.addr, self.source_prefix).expect("source prefix is validated")
+    }
+}
+
+/// True for addresses routable on the public internet.
+fn is_global(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)) // shared address space
+                || a >= 240)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_global(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    || (first & 0xfe00) == 0xfc00 // unique local
+                    || (first & 0xffc0) == 0xfe80 // link-local
+                    || first == 0x2001 && v6.segments()[1] == 0xdb8) // documentation
+            }
+        },
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EcsSettings {
+    pub forward_client: bool,
+    pub add_subnet: bool,
+    pub ipv4_prefix: u8,
+    pub ipv6_prefix: u8,
+    pub trusted_forwarders: Vec<IpNet>,
+}
+
+impl Default for EcsSettings {
+    fn default() -> Self {
+        Self {
+            forward_client: false,
+            add_subnet: false,
+            ipv4_prefix: DEFAULT_IPV4_PREFIX,
+            ipv6_prefix: DEFAULT_IPV6_PREFIX,
+            trusted_forwarders: Vec::new(),
+        }
+    }
+}
+
+/// Parse a trusted forwarder: a CIDR or a single address.
+pub fn parse_forwarder(s: &str) -> Option<IpNet> {
+    let s = s.trim();
+    s.parse::<IpNet>().ok().or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+impl EcsSettings {
+    /// Read the `ecs_*` settings.
+    pub async fn load(db: &DbPool) -> Result<Self> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT key, value FROM settings WHERE key LIKE 'ecs_%'"
+        )
+        .fetch_all(db)
+        .await?;
+        let mut settings = Self::default();
+        for (key, value) in rows {
+            match key.as_str() {
+                "ecs_forward_client" => settings.forward_client = value == "true",
+                "ecs_add_subnet" => settings.add_subnet = value == "true",
+                "ecs_ipv4_prefix" => {
+                    settings.ipv4_prefix = value.parse::<u8>().unwrap_or(DEFAULT_IPV4_PREFIX).min(32)
+                }
+                "ecs_ipv6_prefix" => {
+                    settings.ipv6_prefix = value.parse::<u8>().unwrap_or(DEFAULT_IPV6_PREFIX).min(128)
+                }
+                "ecs_trusted_forwarders" => {
+                    settings.trusted_forwarders = serde_json::from_str::<Vec<String>>(&value)
+                        .unwrap_or_default()
+                        .iter()
+                        .filter_map(|s| parse_forwarder(s))
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        Ok(settings)
+    }
+
+    fn is_trusted_forwarder(&self, source: IpAddr) -> bool {
+        self.trusted_forwarders.iter().any(|net| net.contains(&source))
+    }
+
+    /// Address identifying the client: the ECS address when the query comes
+    /// from a trusted forwarder, else the source address.
+    pub fn client_addr(&self, source: IpAddr, client_subnet: Option<EcsSubnet>) -> IpAddr {
+        match client_subnet {
+            Some(subnet) if subnet.source_prefix > 0 && self.is_trusted_forwarder(source) => subnet.addr,
+            _ => source,
+        }
+    }
+
+    /// ECS option to send upstream for a query from `source`.
+    pub fn upstream_subnet(&self, source: IpAddr, client_subnet: Option<EcsSubnet>) -> Option<EcsSubnet> {
+        if let Some(subnet) = client_subnet {
+            if self.forward_client {
+                return Some(EcsSubnet { scope_prefix: 0, ..subnet });
+            }
+            if subnet.source_prefix == 0 {
+                // RFC 7871 §7.1.2: the client asked for its address not to be used
+                return None;
+            }
+        }
+        if !self.add_subnet {
+            return None;
+        }
+        let addr = self.client_addr(source, client_subnet);
+        if !is_global(addr) {
+            return None;
+        }
+        let prefix = if addr.is_ipv4() { self.ipv4_prefix } else { self.ipv6_prefix };
+        let prefix = match client_subnet {
+            Some(subnet) if addr == subnet.addr => prefix.min(subnet.source_prefix),
+            _ => prefix,
+        };
+        Some(EcsSubnet::new(addr, prefix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::op::Edns;
+
+    fn message_with(subnet: EcsSubnet) -> Message {
+        let mut edns = Edns::new();
+        edns.options_mut().insert(subnet.to_option());
+        let mut msg = Message::new();
+        msg.set_edns(edns);
+        Message::from_vec(&msg.to_vec().unwrap()).unwrap()
+    }
+
+    fn subnet(s: &str) -> EcsSubnet {
+        let net: IpNet = s.parse().unwrap();
+        EcsSubnet::new(net.addr(), net.prefix_len())
+    }
+
+    #[test]
+    fn test_subnet_round_trip() {
+        let v4 = EcsSubnet::new("203.0.113.77".parse().unwrap(), 24);
+        assert_eq!(v4.network().to_string(), "203.0.113.0/24");
+        assert_eq!(EcsSubnet::from_message(&message_with(v4)), Some(v4));
+
+        let v6 = EcsSubnet::new("2001:db8:abcd:12ff::1".parse().unwrap(), 56);
+        assert_eq!(v6.network().to_string(), "2001:db8:abcd:1200::/56");
+        assert_eq!(EcsSubnet::from_message(&message_with(v6)), Some(v6));
+
+        assert_eq!(EcsSubnet::from_message(&Message::new()), None);
+        // Bits set beyond the source prefix
+        assert_eq!(EcsSubnet::parse(&[0, 1, 16, 0, 10, 1, 2]), None);
+        assert_eq!(EcsSubnet::parse(&[0, 1, 33, 0, 10, 1, 2, 3, 4]), None);
+        assert_eq!(EcsSubnet::parse(&[0, 1, 0, 0]).map(|s| s.source_prefix), Some(0));
+    }
+
+    #[test]
+    fn test_upstream_subnet() {
+        let public: IpAddr = "81.2.69.142".parse().unwrap();
+        let private: IpAddr = "192.168.1.9".parse().unwrap();
+        let client = subnet("93.184.216.0/24");
+
+        // Strip by default
+        let mut settings = EcsSettings::default();
+        assert_eq!(settings.upstream_subnet(public, Some(client)), None);
+
+        settings.forward_client = true;
+        assert_eq!(settings.upstream_subnet(public, Some(client)), Some(client));
+        assert_eq!(settings.upstream_subnet(public, None), None);
+
+        settings.forward_client = false;
+        settings.add_subnet = true;
+        settings.ipv4_prefix = 20;
+        assert_eq!(settings.upstream_subnet(public, None), Some(subnet("81.2.64.0/20")));
+        assert_eq!(settings.upstream_subnet(private, None), None);
+        // The client opted out
+        assert_eq!(settings.upstream_subnet(public, Some(subnet("0.0.0.0/0"))), None);
+
+        // Behind a trusted forwarder, the forwarded subnet is the client's
+        settings.trusted_forwarders = vec!["192.168.0.0/16".parse().unwrap()];
+        assert_eq!(settings.upstream_subnet(private, Some(client)), Some(subnet("93.184.208.0/20")));
+        settings.ipv4_prefix = 32;
+        assert_eq!(settings.upstream_subnet(private, Some(client)), Some(client));
+    }
+
+    #[test]
+    fn test_client_addr_requires_trusted_forwarder() {
+        let mut settings = EcsSettings::default();
+        let forwarder: IpAddr = "10.0.0.53".parse().unwrap();
+        let client = subnet("192.168.7.20/32");
+        assert_eq!(settings.client_addr(forwarder, Some(client)), forwarder);
+
+        settings.trusted_forwarders = vec![parse_forwarder("10.0.0.53").unwrap()];
+        assert_eq!(settings.client_addr(forwarder, Some(client)), client.addr);
+        assert_eq!(settings.client_addr(forwarder, None), forwarder);
+        assert_eq!(settings.client_addr("10.0.0.54".parse().unwrap(), Some(client)), "10.0.0.54".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_is_global() {
+        for ip in ["8.8.8.8", "2606:4700::1111", "::ffff:1.1.1.1"] {
+            assert!(is_global(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["10.1.2.3", "172.16.0.1", "100.64.0.1", "127.0.0.1", "169.254.1.1", "fd00::1", "fe80::1", "::1", "::ffff:192.168.1.1"] {
+            assert!(!is_global(ip.parse().unwrap()), "{ip}");
+        }
+    }
+}
@@ -2,13 +2,33 @@
 
 use anyhow::Result;
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
 use tokio::sync::RwLock;
 use crate::db::DbPool;
+use super::categories;
+use super::qtype_policy::{self, QtypeAction, QtypePolicies};
+use super::rebind::{self, RebindPolicy};
+use hickory_proto::rr::RecordType;
 use super::rules::RuleSet;
+use super::threat::{self, Severity, ThreatFeed, ThreatMatch};
 
 pub struct FilterEngine {
     rules: RwLock<RuleSet>,
     rewrites: RwLock<HashMap<String, String>>,
+    /// Parental-control category rule sets: category id → bundled + subscribed rules.
+    categories: RwLock<HashMap<String, Arc<RuleSet>>>,
+    /// Categories blocked for every client (empty when parental control is disabled).
+    parental_categories: RwLock<Vec<String>>,
+    /// DNS rebinding protection settings.
+    rebind: RwLock<RebindPolicy>,
+    /// Global query-type policies (`qtype_policies` setting).
+    qtype_policies: RwLock<QtypePolicies>,
+    /// Threat-intelligence feeds (filter lists with a threat category).
+    threat_feeds: RwLock<Vec<ThreatFeed>>,
+    /// Filter lists bound to client groups: list id → its rules.  These lists
+    /// only apply to members of the bound groups, not globally.
+    group_lists: RwLock<HashMap<String, Arc<RuleSet>>>,
     db: DbPool,
 }
 
@@ -17,6 +37,12 @@ impl FilterEngine {
         let engine = Self {
             rules: RwLock::new(RuleSet::new()),
             rewrites: RwLock::new(HashMap::new()),
+            categories: RwLock::new(HashMap::new()),
+            parental_categories: RwLock::new(Vec::new()),
+            rebind: RwLock::new(RebindPolicy::default()),
+            qtype_policies: RwLock::new(QtypePolicies::new()),
+            threat_feeds: RwLock::new(Vec::new()),
+            group_lists: RwLock::new(HashMap::new()),
             db,
         };
         engine.reload().await?;
@@ -28,14 +54,67 @@ impl FilterEngine {
         let mut new_rules = RuleSet::new();
         let mut total = 0usize;
 
-        // Load custom rules (AdGuard syntax stored in DB)
-        let rows: Vec<(String,)> = sqlx::query_as(
-            "SELECT rule FROM custom_rules WHERE is_enabled = 1"
+        // Category rule sets start from the bundled lists
+        let mut category_rules: HashMap<String, RuleSet> = categories::CATEGORIES
+            .iter()
+            .map(|c| (c.id.to_string(), c.bundled_ruleset()))
+            .collect();
+
+        // Threat feeds: enabled filter lists tagged with a threat category
+        let feed_rows: Vec<(String, String, String, Option<String>)> = sqlx::query_as(
+            "SELECT id, name, threat_category, severity FROM filter_lists
+             WHERE threat_category IS NOT NULL AND is_enabled = 1"
+        )
+        .fetch_all(&self.db)
+        .await?;
+        let mut new_feeds: Vec<ThreatFeed> = feed_rows
+            .into_iter()
+            .map(|(filter_id, name, category, severity)| ThreatFeed {
+                filter_id,
+                name,
+                category,
+                severity: severity.as_deref().and_then(Severity::parse).unwrap_or(Severity::High),
+                rules: RuleSet::new(),
+            })
+            .collect();
+
+        // Filter lists bound to client groups (`rule_type = 'filter_list'`)
+        let bound_ids: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT CAST(rule_id AS TEXT) FROM client_group_rules WHERE rule_type = 'filter_list'"
+        )
+        .fetch_all(&self.db)
+        .await?;
+        let mut group_lists: HashMap<String, RuleSet> = bound_ids
+            .into_iter()
+            .map(|id| (id, RuleSet::new()))
+            .collect();
+
+        // Load custom rules (AdGuard syntax stored in DB).  Rules synced from a
+        // filter list tagged with a category feed that category instead of the
+        // global rule set; rules of a threat feed go to that feed and rules of
+        // a group-bound list to that list.
+        let rows: Vec<(String, Option<String>, Option<String>)> = sqlx::query_as(
+            "SELECT cr.rule, fl.category, fl.id
+             FROM custom_rules cr
+             LEFT JOIN filter_lists fl ON cr.created_by = 'filter:' || fl.id
+             WHERE cr.is_enabled = 1"
         )
         .fetch_all(&self.db)
         .await?;
 
-        for (rule,) in rows {
+        for (rule, category, list_id) in rows {
+            if let Some(feed) = list_id.as_ref().and_then(|id| new_feeds.iter_mut().find(|f| &f.filter_id == id)) {
+                feed.rules.add_rule(&rule);
+                continue;
+            }
+            if let Some(set) = category.and_then(|c| category_rules.get_mut(&c)) {
+                set.add_rule(&rule);
+                continue;
+            }
+            if let Some(set) = list_id.as_ref().and_then(|id| group_lists.get_mut(id)) {
+                set.add_rule(&rule);
+                continue;
+            }
             if new_rules.add_rule(&rule) {
                 total += 1;
             }
@@ -72,6 +151,57 @@ impl FilterEngine {
 
         let rewrite_count = new_rewrites.len();
 
+        // Load global parental control settings
+        let parental_enabled: Option<String> = sqlx::query_scalar(
+            "SELECT value FROM settings WHERE key = 'parental_control_enabled'"
+        )
+        .fetch_optional(&self.db)
+        .await?;
+        let new_parental = if parental_enabled.as_deref() == Some("true") {
+            let configured: Option<String> = sqlx::query_scalar(
+                "SELECT value FROM settings WHERE key = 'parental_control_categories'"
+            )
+            .fetch_optional(&self.db)
+            .await?;
+            configured
+                .and_then(|s| serde_json::from_str::<Vec<String>>(&s).ok())
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|c| categories::find(c).is_some())
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // Load DNS rebinding protection settings
+        let rebind_enabled: Option<String> = sqlx::query_scalar(
+            "SELECT value FROM settings WHERE key = 'rebind_protection_enabled'"
+        )
+        .fetch_optional(&self.db)
+        .await?;
+        let rebind_allowlist: Option<String> = sqlx::query_scalar(
+            "SELECT value FROM settings WHERE key = 'rebind_protection_allowlist'"
+        )
+        .fetch_optional(&self.db)
+        .await?;
+        let new_rebind = RebindPolicy {
+            enabled: rebind_enabled.as_deref() == Some("true"),
+            allowlist: rebind_allowlist
+                .and_then(|s| serde_json::from_str::<Vec<String>>(&s).ok())
+                .unwrap_or_default()
+                .iter()
+                .map(|d| rebind::normalize_allowlist_entry(d))
+                .collect(),
+        };
+
+        // Load global query-type policies
+        let qtype_json: Option<String> = sqlx::query_scalar(
+            "SELECT value FROM settings WHERE key = 'qtype_policies'"
+        )
+        .fetch_optional(&self.db)
+        .await?;
+        let new_qtype_policies = qtype_json.map(|s| qtype_policy::parse(&s)).unwrap_or_default();
+
         // Update rules
         {
             let mut rules = self.rules.write().await;
@@ -84,10 +214,39 @@ impl FilterEngine {
             *rewrites = new_rewrites;
         }
 
+        // Update categories
+        {
+            let mut cats = self.categories.write().await;
+            *cats = category_rules.into_iter().map(|(k, v)| (k, Arc::new(v))).collect();
+        }
+        {
+            let mut parental = self.parental_categories.write().await;
+            *parental = new_parental;
+        }
+        {
+            let mut rebind = self.rebind.write().await;
+            *rebind = new_rebind;
+        }
+        {
+            let mut policies = self.qtype_policies.write().await;
+            *policies = new_qtype_policies;
+        }
+        new_feeds.retain(|f| f.rules.blocked_count() > 0);
+        let group_list_count = group_lists.len();
+        {
+            let mut lists = self.group_lists.write().await;
+            *lists = group_lists.into_iter().map(|(k, v)| (k, Arc::new(v))).collect();
+        }
+        {
+            let mut feeds = self.threat_feeds.write().await;
+            *feeds = new_feeds;
+        }
+
         tracing::info!(
-            "Filter engine reloaded: {} custom rules, {} filter lists, {} rewrites",
+            "Filter engine reloaded: {} custom rules, {} filter lists ({} group-bound), {} rewrites",
             total,
             list_count,
+            group_list_count,
             rewrite_count,
         );
         Ok(())
@@ -99,12 +258,106 @@ impl FilterEngine {
         rules.is_blocked(domain)
     }
 
+    /// True if a global allow rule matches `domain`.
+    pub async fn is_allowlisted(&self, domain: &str) -> bool {
+        self.rules.read().await.is_allowlisted(domain)
+    }
+
+    /// First of the group-bound filter lists `ids` blocking `domain`.  Each
+    /// list's own allow rules apply within that list.
+    pub async fn check_group_lists(&self, domain: &str, ids: &[String]) -> Option<String> {
+        if ids.is_empty() {
+            return None;
+        }
+        let lists = self.group_lists.read().await;
+        ids.iter()
+            .find(|id| lists.get(id.as_str()).is_some_and(|rs| rs.is_blocked(domain)))
+            .cloned()
+    }
+
+    /// First of `ips` matched by a response-address rule of the group-bound
+    /// filter lists `ids`.
+    pub async fn check_group_list_ips(&self, ips: &[IpAddr], ids: &[String]) -> Option<IpAddr> {
+        if ids.is_empty() {
+            return None;
+        }
+        let lists = self.group_lists.read().await;
+        let active: Vec<&Arc<RuleSet>> = ids.iter().filter_map(|id| lists.get(id.as_str())).collect();
+        ips.iter().find(|ip| active.iter().any(|rs| rs.is_ip_blocked(ip))).copied()
+    }
+
+    /// Return the first of `ips` (addresses from a response's answer section)
+    /// matched by a global response-address rule.
+    pub async fn check_response_ips(&self, ips: &[IpAddr]) -> Option<IpAddr> {
+        let rules = self.rules.read().await;
+        ips.iter().find(|ip| rules.is_ip_blocked(ip)).copied()
+    }
+
+    /// Rebinding check for an answer to `domain`: the first internal address in
+    /// `ips` when protection is enabled and `domain` is not allowlisted.
+    pub async fn check_rebind(&self, domain: &str, ips: &[IpAddr]) -> Option<IpAddr> {
+        self.rebind.read().await.check(domain, ips)
+    }
+
+    /// Threat feed blocking `domain`, if any.  Global allow rules take
+    /// precedence so false positives can be overridden.
+    pub async fn check_threat(&self, domain: &str) -> Option<ThreatMatch> {
+        let feeds = self.threat_feeds.read().await;
+        if feeds.is_empty() || self.rules.read().await.is_allowlisted(domain) {
+            return None;
+        }
+        threat::check_feeds(&feeds, domain)
+    }
+
+    /// Global policy for a query type, if one is configured.
+    pub async fn qtype_action(&self, qtype: RecordType) -> Option<QtypeAction> {
+        self.qtype_policies.read().await.get(&qtype).copied()
+    }
+
+    /// Return the first category blocking `domain`, checking the globally enabled
+    /// parental-control categories plus `extra` (e.g. categories selected by the
+    /// client's groups).
+    pub async fn check_categories(&self, domain: &str, extra: &[String]) -> Option<String> {
+        let parental = self.parental_categories.read().await;
+        if parental.is_empty() && extra.is_empty() {
+            return None;
+        }
+        let cats = self.categories.read().await;
+        parental
+            .iter()
+            .chain(extra.iter())
+            .find(|id| cats.get(id.as_str()).is_some_and(|rs| rs.is_blocked(domain)))
+            .cloned()
+    }
+
+    /// Categories currently blocked for every client.
+    pub async fn parental_categories(&self) -> Vec<String> {
+        self.parental_categories.read().await.clone()
+    }
+
+    /// Number of blocking rules per category (bundled + subscribed).
+    pub async fn category_rule_counts(&self) -> HashMap<String, usize> {
+        let cats = self.categories.read().await;
+        cats.iter().map(|(id, rs)| (id.clone(), rs.blocked_count())).collect()
+    }
+
     /// Check if a domain has a rewrite rule. Returns the target IP if found.
     pub async fn check_rewrite(&self, domain: &str) -> Option<String> {
         let rewrites = self.rewrites.read().await;
         rewrites.get(&domain.to_lowercase()).cloned()
     }
 
+    /// Local-data addresses from filter lists (`$dnsrewrite=<address>`).
+    pub async fn local_data(&self, domain: &str) -> Option<Vec<IpAddr>> {
+        self.rules.read().await.local_data(domain).map(<[IpAddr]>::to_vec)
+    }
+
+    /// True if the global rule blocking `domain` asks for an empty answer
+    /// instead of NXDOMAIN.
+    pub async fn is_nodata(&self, domain: &str) -> bool {
+        self.rules.read().await.is_nodata(domain)
+    }
+
     /// Add a single rule at runtime (without DB persistence — use API for persistence).
     pub async fn add_rule_live(&self, rule: &str) {
         let mut rules = self.rules.write().await;
@@ -0,0 +1,114 @@
+//! Compiled custom rules of client groups.
+//!
+//! The custom rules bound to a group (`client_group_rules` rows with
+//! `rule_type = 'custom_rule'`) are compiled once per group, partitioned by
+//! binding schedule, and shared by every member.  Compiled sets are keyed by
+//! group id and version: `invalidate` gives the group a new version, so the
+//! next lookup recompiles it while members still holding the old set keep
+//! using it until their client config is rebuilt.
+
+use anyhow::Result;
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::rules::RuleSet;
+use crate::db::DbPool;
+
+/// A group's compiled rules.
+pub struct CompiledGroup {
+    pub group_id: i64,
+    pub version: u64,
+    /// Rules per binding schedule id (`None` = unscheduled).
+    pub parts: Vec<(Option<String>, Arc<RuleSet>)>,
+}
+
+pub struct GroupRuleCache {
+    db: DbPool,
+    /// Current version per group; groups never invalidated are at version 0.
+    versions: DashMap<i64, u64>,
+    compiled: DashMap<i64, Arc<CompiledGroup>>,
+    next_version: AtomicU64,
+    /// Number of compilations, for metrics and tests.
+    compilations: AtomicU64,
+}
+
+impl GroupRuleCache {
+    pub fn new(db: DbPool) -> Self {
+        Self {
+            db,
+            versions: DashMap::new(),
+            compiled: DashMap::new(),
+            next_version: AtomicU64::new(1),
+            compilations: AtomicU64::new(0),
+        }
+    }
+
+    fn version(&self, group_id: i64) -> u64 {
+        self.versions.get(&group_id).map(|v| *v).unwrap_or(0)
+    }
+
+    /// Compiled rules of a group, compiling them if the group changed since
+    /// the last compilation.
+    pub async fn get(&self, group_id: i64) -> Result<Arc<CompiledGroup>> {
+        let version = self.version(group_id);
+        if let Some(group) = self.compiled.get(&group_id).filter(|g| g.version == version) {
+            return Ok(group.clone());
+        }
+
+        let group = Arc::new(self.compile(group_id, version).await?);
+        // A concurrent invalidation may have bumped the version meanwhile;
+        // the stale set is then replaced by the next lookup
+        self.compiled.insert(group_id, group.clone());
+        Ok(group)
+    }
+
+    async fn compile(&self, group_id: i64, version: u64) -> Result<CompiledGroup> {
+        let rows: Vec<(String, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT cr.rule, cgr.schedule_id
+            FROM client_group_rules cgr
+            JOIN custom_rules cr ON cr.id = cgr.rule_id
+            WHERE cgr.group_id = ?
+              AND cgr.rule_type = 'custom_rule'
+              AND cr.is_enabled = 1
+            ORDER BY cgr.priority ASC
+            "#
+        )
+        .bind(group_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut parts: Vec<(Option<String>, RuleSet)> = Vec::new();
+        for (rule, schedule_id) in rows {
+            let idx = match parts.iter().position(|(s, _)| *s == schedule_id) {
+                Some(i) => i,
+                None => {
+                    parts.push((schedule_id, RuleSet::new()));
+                    parts.len() - 1
+                }
+            };
+            parts[idx].1.add_rule(&rule);
+        }
+
+        self.compilations.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!("Compiled rules of client group {} (version {}, {} parts)", group_id, version, parts.len());
+        Ok(CompiledGroup {
+            group_id,
+            version,
+            parts: parts.into_iter().map(|(s, r)| (s, Arc::new(r))).collect(),
+        })
+    }
+
+    /// Mark a group's rules as changed (bindings edited, bound rules edited,
+    /// group deleted).
+    pub fn invalidate(&self, group_id: i64) {
+        let version = self.next_version.fetch_add(1, Ordering::Relaxed);
+        self.versions.insert(group_id, version);
+        self.compiled.remove(&group_id);
+    }
+
+    pub fn compilations(&self) -> u64 {
+        self.compilations.load(Ordering::Relaxed)
+    }
+}
@@ -1,10 +1,11 @@
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use hickory_proto::op::{Message, MessageType, OpCode, ResponseCode};
-use hickory_proto::rr::{RData, Record, RecordType, rdata::{A, AAAA}};
+use hickory_proto::rr::{Name, RData, Record, RecordType, rdata::{A, AAAA, CNAME}};
 use moka::future::Cache as MokaCache;
 use std::collections::HashMap;
 use std::net::IpAddr;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{broadcast, mpsc, RwLock};
@@ -12,27 +13,160 @@ use crate::config::Config;
 use crate::db::DbPool;
 use crate::db::query_log_writer::QueryLogEntry;
 use crate::metrics::DnsMetrics;
-use super::{filter::FilterEngine, resolver::DnsResolver, cache::DnsCache, rules::RuleSet};
+use super::heuristics::{HeuristicsConfig, SuspicionDetector};
+use super::threat::SecurityEvents;
+use super::client_settings::{self, BlockingMode, ClientSettings, RateLimiter, SettingsOverrides};
+use super::{categories, safe_search, client_index::{ClientIndex, Identifier}, ecs::{EcsSettings, EcsSubnet}, group_rules::GroupRuleCache, neighbors::{self, MacAddr, NeighborTable}, rebind, qtype_policy::{self, QtypeAction, QtypePolicies}, services, schedule::Schedule, filter::FilterEngine, resolver::DnsResolver, cache::DnsCache, rules::RuleSet};
 
 /// TTL for the client-config cache.  Client configs change rarely; 60 s is safe. (M-4 fix)
 const CLIENT_CACHE_TTL: Duration = Duration::from_secs(60);
 
 /// Per-client resolved configuration, cached in moka for CLIENT_CACHE_TTL.
+///
+/// Scheduled parts are stored unevaluated; schedules are checked per query so a
+/// cached config changes behaviour exactly at the schedule boundary.
 #[derive(Clone)]
 struct ClientConfig {
+    /// The identified client, if any.
+    client_id: Option<String>,
     /// Whether DNS filtering is enabled for this client.
     filter_enabled: bool,
-    /// Custom upstream resolvers, if specified by the client or its highest-priority group.
-    upstream_urls: Option<Vec<String>>,
-    /// Group-specific rule set built from client_group_rules → custom_rules.
+    /// When set, filtering only applies while this schedule is active.
+    filter_schedule: Option<Arc<Schedule>>,
+    /// Filtering is paused for this client (directly or via a group) until this instant.
+    paused_until: Option<DateTime<Utc>>,
+    /// Setting overrides of the client and its groups (including custom
+    /// upstreams); applied on top of the global defaults per query.
+    settings: SettingsOverrides,
+    /// Group-specific rules built from client_group_rules → custom_rules.
     /// When Some, replaces the global FilterEngine check for this client.
     /// When None, falls back to the global FilterEngine.
-    group_ruleset: Option<Arc<RuleSet>>,
+    group_ruleset: Option<Arc<GroupRules>>,
+    /// Filter lists bound to the client's groups, checked in addition to the
+    /// rules above.  Their rule sets are compiled once in the FilterEngine.
+    filter_lists: Vec<ScheduledBinding>,
+    /// Parental-control categories selected by the client's groups
+    /// (in addition to the globally enabled ones).
+    blocked_categories: Vec<ScheduledBinding>,
+    /// Built-in services blocked for this client (own selection + its groups').
+    blocked_services: Vec<ScheduledBinding>,
+    /// Query-type policies from the client's groups; for each type the
+    /// highest-priority group naming it wins.  Overrides the global policy.
+    qtype_policies: QtypePolicies,
+}
+
+/// Filtering state of a client at one instant (see `DnsHandler::filtering_state`).
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FilteringState {
+    /// The client's own switch; always true for unidentified sources.
+    pub enabled: bool,
+    /// False while the client's filter schedule is inactive.
+    pub schedule_active: bool,
+    /// End of the latest pause covering the client (own, group or global).
+    pub paused_until: Option<DateTime<Utc>>,
+    /// Whether queries are filtered now.
+    pub active: bool,
+    /// The client's group custom rules replace the global rules and filter
+    /// lists (threat feeds, categories and rewrites still apply).
+    pub group_rules_only: bool,
+}
+
+/// A category or service id bound to a client, optionally limited to a schedule.
+#[derive(Clone)]
+struct ScheduledBinding {
+    id: String,
+    schedule: Option<Arc<Schedule>>,
+}
+
+/// Custom rules bound to a client's groups: the shared compiled parts of
+/// each group, with their binding schedules.  Only parts whose schedule is
+/// active take part in a lookup; allow rules still override block rules
+/// across parts.
+struct GroupRules {
+    parts: Vec<(Option<Arc<Schedule>>, Arc<RuleSet>)>,
+}
+
+impl GroupRules {
+    fn is_blocked(&self, domain: &str, now: DateTime<Utc>) -> bool {
+        let active = || {
+            self.parts
+                .iter()
+                .filter(|(schedule, _)| schedule_active(schedule, now))
+                .map(|(_, rules)| rules)
+        };
+        if active().any(|rules| rules.is_allowlisted(domain)) {
+            return false;
+        }
+        active().any(|rules| rules.is_blocklisted(domain))
+    }
+
+    fn is_allowlisted(&self, domain: &str, now: DateTime<Utc>) -> bool {
+        self.parts
+            .iter()
+            .any(|(schedule, rules)| schedule_active(schedule, now) && rules.is_allowlisted(domain))
+    }
+
+    /// First of `ips` matched by an active response-address rule.
+    fn blocked_ip(&self, ips: &[IpAddr], now: DateTime<Utc>) -> Option<IpAddr> {
+        let active = || {
+            self.parts
+                .iter()
+                .filter(|(schedule, _)| schedule_active(schedule, now))
+                .map(|(_, rules)| rules)
+        };
+        ips.iter()
+            .find(|ip| {
+                active().any(|rules| rules.is_ip_blocklisted(ip))
+                    && !active().any(|rules| rules.is_ip_allowlisted(ip))
+            })
+            .copied()
+    }
+
+    fn rule_count(&self) -> usize {
+        self.parts.iter().map(|(_, r)| r.blocked_count() + r.allowed_count() + r.ip_rule_count()).sum()
+    }
+}
+
+/// An unscheduled item is always active.
+fn schedule_active(schedule: &Option<Arc<Schedule>>, now: DateTime<Utc>) -> bool {
+    schedule.as_ref().is_none_or(|s| s.is_active_at(now))
+}
+
+/// Ids of the bindings whose schedule is active at `now`.
+fn active_ids(bindings: &[ScheduledBinding], now: DateTime<Utc>) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::with_capacity(bindings.len());
+    for b in bindings {
+        if schedule_active(&b.schedule, now) && !ids.contains(&b.id) {
+            ids.push(b.id.clone());
+        }
+    }
+    ids
+}
+
+/// Columns of `clients` needed to resolve a ClientConfig.
+#[derive(sqlx::FromRow)]
+struct ClientRow {
+    id: String,
+    filter_enabled: i64,
+    blocked_services: Option<String>,
+    blocked_services_schedule_id: Option<String>,
+    filter_schedule_id: Option<String>,
 }
 
 impl Default for ClientConfig {
     fn default() -> Self {
-        Self { filter_enabled: true, upstream_urls: None, group_ruleset: None }
+        Self {
+            client_id: None,
+            filter_enabled: true,
+            filter_schedule: None,
+            paused_until: None,
+            settings: SettingsOverrides::default(),
+            group_ruleset: None,
+            filter_lists: Vec::new(),
+            blocked_categories: Vec::new(),
+            blocked_services: Vec::new(),
+            qtype_policies: QtypePolicies::new(),
+        }
     }
 }
 
@@ -44,6 +178,26 @@ pub struct DnsHandler {
     cache: Arc<DnsCache>,
     /// TTL cache for client config: IP → ClientConfig (M-4 fix)
     client_config_cache: MokaCache<String, ClientConfig>,
+    /// Compiled custom rules per client group, shared by the group's members.
+    group_rules: GroupRuleCache,
+    /// Source address → client id; built on first use after `reload_clients`.
+    client_index: RwLock<Option<Arc<ClientIndex>>>,
+    /// Source address → MAC, for clients identified by MAC.
+    neighbors: NeighborTable,
+    /// Trust the MAC a forwarder sends in EDNS0 (`edns_mac_enabled` setting).
+    edns_mac_enabled: RwLock<bool>,
+    /// EDNS Client Subnet handling (`ecs_*` settings).
+    ecs: RwLock<Arc<EcsSettings>>,
+    /// Global defaults of the settings clients and groups can override.
+    default_settings: RwLock<Arc<ClientSettings>>,
+    rate_limiter: RateLimiter,
+    /// Expiry of the global filtering pause, if any (see `reload_pauses`).
+    global_pause: RwLock<Option<DateTime<Utc>>>,
+    /// Suspicious-domain heuristics and their settings.
+    suspicion: SuspicionDetector,
+    heuristics: RwLock<HeuristicsConfig>,
+    /// Threat-feed hits: storage, streaming, metrics and alerting.
+    security_events: SecurityEvents,
     db: DbPool,
     metrics: Arc<DnsMetrics>,
     query_log_tx: broadcast::Sender<serde_json::Value>,
@@ -61,21 +215,146 @@ impl DnsHandler {
             .build();
         // Spawn batch writer; the sender is stored so log_query() is fully non-blocking
         let query_log_entry_tx = crate::db::query_log_writer::spawn(db.clone());
-        Ok(Self {
+        let handler = Self {
             filter,
             resolver,
             client_resolvers: RwLock::new(HashMap::new()),
             cache,
             client_config_cache,
+            group_rules: GroupRuleCache::new(db.clone()),
+            client_index: RwLock::new(None),
+            neighbors: NeighborTable::new(),
+            edns_mac_enabled: RwLock::new(false),
+            ecs: RwLock::new(Arc::new(EcsSettings::default())),
+            default_settings: RwLock::new(Arc::new(ClientSettings::default())),
+            rate_limiter: RateLimiter::new(),
+            global_pause: RwLock::new(None),
+            suspicion: SuspicionDetector::new(),
+            heuristics: RwLock::new(HeuristicsConfig::default()),
+            security_events: SecurityEvents::new(db.clone(), metrics.clone()),
             db,
             metrics,
             query_log_tx,
             query_log_entry_tx,
-        })
+        };
+        handler.reload_pauses().await?;
+        handler.reload_heuristics().await?;
+        handler.reload_identification().await?;
+        handler.reload_ecs().await?;
+        handler.reload_client_settings().await?;
+        handler.security_events.reload().await?;
+        Ok(handler)
+    }
+
+    pub fn security_events(&self) -> &SecurityEvents {
+        &self.security_events
+    }
+
+    /// Re-read the `heuristics_*` settings.
+    pub async fn reload_heuristics(&self) -> Result<()> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT key, value FROM settings WHERE key LIKE 'heuristics_%'"
+        )
+        .fetch_all(&self.db)
+        .await?;
+        let mut config = HeuristicsConfig::default();
+        for (key, value) in rows {
+            match key.as_str() {
+                "heuristics_enabled" => config.enabled = value == "true",
+                "heuristics_block_enabled" => config.block_enabled = value == "true",
+                "heuristics_block_threshold" => {
+                    config.block_threshold = value.parse().unwrap_or(config.block_threshold)
+                }
+                _ => {}
+            }
+        }
+        *self.heuristics.write().await = config;
+        Ok(())
+    }
+
+    /// Re-read the client identification settings.
+    pub async fn reload_identification(&self) -> Result<()> {
+        let edns_mac: Option<String> = sqlx::query_scalar(
+            "SELECT value FROM settings WHERE key = 'edns_mac_enabled'"
+        )
+        .fetch_optional(&self.db)
+        .await?;
+        *self.edns_mac_enabled.write().await = edns_mac.as_deref() == Some("true");
+        self.client_config_cache.invalidate_all();
+        Ok(())
+    }
+
+    /// Re-read the EDNS Client Subnet settings.  Trusted forwarders change
+    /// how clients are identified, so cached client configs are dropped.
+    pub async fn reload_ecs(&self) -> Result<()> {
+        *self.ecs.write().await = Arc::new(EcsSettings::load(&self.db).await?);
+        self.client_config_cache.invalidate_all();
+        Ok(())
+    }
+
+    /// Re-read the global defaults of client settings (blocking mode,
+    /// SafeSearch, rate limit, query logging).  Overrides are applied on top
+    /// per query, so cached client configs stay valid.
+    pub async fn reload_client_settings(&self) -> Result<()> {
+        *self.default_settings.write().await = Arc::new(ClientSettings::load_defaults(&self.db).await?);
+        Ok(())
+    }
+
+    /// Re-read filtering pauses after they change.  Loads the global pause and
+    /// drops cached client configs so client and group pauses apply immediately.
+    /// Expiry itself needs no reload: it is compared against the clock per query.
+    pub async fn reload_pauses(&self) -> Result<()> {
+        let expires: Option<String> = sqlx::query_scalar(
+            "SELECT expires_at FROM filter_pauses WHERE scope = 'global' AND expires_at > ?"
+        )
+        .bind(Utc::now().to_rfc3339())
+        .fetch_optional(&self.db)
+        .await?;
+        *self.global_pause.write().await = expires
+            .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+            .map(|t| t.with_timezone(&Utc));
+        self.client_config_cache.invalidate_all();
+        Ok(())
+    }
+
+    /// Drop all cached client configs so group or client changes apply to the
+    /// next query instead of after CLIENT_CACHE_TTL.
+    pub fn invalidate_client_configs(&self) {
+        self.client_config_cache.invalidate_all();
+    }
+
+    /// Rebuild the client index on next use after clients were added, changed
+    /// or removed.
+    pub async fn reload_clients(&self) {
+        *self.client_index.write().await = None;
+        self.client_config_cache.invalidate_all();
+    }
+
+    /// Recompile a group's custom rules on next use (bindings or bound rules
+    /// changed, group deleted) and drop the client configs holding the old set.
+    pub fn invalidate_group_rules(&self, group_id: i64) {
+        self.group_rules.invalidate(group_id);
+        self.client_config_cache.invalidate_all();
+    }
+
+    /// Number of group rule set compilations so far.
+    pub fn group_rule_compilations(&self) -> u64 {
+        self.group_rules.compilations()
     }
 
     /// Handle a DNS query (wire format bytes).  Used by both UDP and TCP transports.
     pub async fn handle(&self, data: Vec<u8>, client_ip: String) -> Result<Vec<u8>> {
+        self.handle_with_client_id(data, client_ip, None).await
+    }
+
+    /// Handle a DNS query carrying a client ID from the transport (DoH path,
+    /// DoT SNI).  A known client ID takes precedence over MAC and IP matching.
+    pub async fn handle_with_client_id(
+        &self,
+        data: Vec<u8>,
+        client_ip: String,
+        client_id: Option<&str>,
+    ) -> Result<Vec<u8>> {
         let request = Message::from_vec(&data)?;
 
         tracing::debug!(
@@ -105,8 +384,48 @@ impl DnsHandler {
         // Normalize domain (remove trailing dot)
         let domain_normalized = domain.trim_end_matches('.');
 
+        // EDNS Client Subnet: the client behind a trusted forwarder, and the
+        // subnet (if any) sent upstream
+        let ecs = self.ecs.read().await.clone();
+        let client_subnet = EcsSubnet::from_message(&request);
+        let source_ip = client_ip.parse::<IpAddr>().ok();
+        let identity_ip = match source_ip {
+            Some(ip) => ecs.client_addr(ip, client_subnet).to_string(),
+            None => client_ip.clone(),
+        };
+        let upstream_subnet = source_ip.and_then(|ip| ecs.upstream_subnet(ip, client_subnet));
+
         // Look up client-specific config (filter override + custom upstreams + group rules)
-        let config = self.get_client_config(&client_ip).await;
+        let mac = self.client_mac(&request, &client_ip).await;
+        let config = self.get_client_config(&identity_ip, mac, client_id).await;
+        let settings = self.default_settings.read().await.with_overrides(&config.settings);
+        self.metrics.inc_qtype(&qtype_str);
+
+        let rate_key = config.client_id.as_deref().unwrap_or(&identity_ip);
+        if !self.rate_limiter.allow(rate_key, settings.rate_limit) {
+            tracing::debug!("Rate limit of {} qps exceeded by {}", settings.rate_limit, rate_key);
+            return self.rcode_response(&request, ResponseCode::Refused);
+        }
+
+        // Query-type policy: group policy first, then the global one.  Applies
+        // regardless of filtering pauses and schedules.
+        let qtype_action = match config.qtype_policies.get(&qtype) {
+            Some(action) => Some(*action),
+            None => self.filter.qtype_action(qtype).await,
+        };
+        if let Some(action @ (QtypeAction::Refuse | QtypeAction::Empty)) = qtype_action {
+            tracing::debug!("Query type policy {}: {} {}", action.as_str(), domain, qtype_str);
+            let elapsed = start.elapsed().as_millis() as i64;
+            self.metrics.inc_blocked();
+            self.metrics.inc_qtype_policy(&qtype_str, action.as_str());
+            let reason = qtype_policy::block_reason(action);
+            self.log_query(&settings, client_ip, &domain, &qtype_str, "blocked", Some(&reason), elapsed);
+            let rcode = match action {
+                QtypeAction::Refuse => ResponseCode::Refused,
+                _ => ResponseCode::NoError,
+            };
+            return self.rcode_response(&request, rcode);
+        }
 
         // Check DNS rewrite first (always, regardless of client config)
         if let Some(answer) = self.filter.check_rewrite(domain_normalized).await {
@@ -116,53 +435,126 @@ impl DnsHandler {
             if matches!(qtype, RecordType::A | RecordType::AAAA) {
                 if let Ok(response) = self.rewrite_response(&request, &answer, qtype, &domain) {
                     self.metrics.inc_allowed();
-                    self.log_query(client_ip, &domain, &qtype_str, "allowed", Some("rewrite"), elapsed);
+                    self.log_query(&settings, client_ip, &domain, &qtype_str, "allowed", Some("rewrite"), elapsed);
                     return Ok(response);
                 }
             }
         }
 
-        // Check filter using client's filter_enabled setting (default true)
-        if config.filter_enabled {
-            let blocked = if let Some(ref ruleset) = config.group_ruleset {
-                // Client belongs to a group with specific rules — use group rules only
-                tracing::debug!("Using group-specific ruleset for {}", client_ip);
-                ruleset.is_blocked(domain_normalized)
-            } else {
-                // No group rules — use global FilterEngine
-                self.filter.is_blocked(&domain).await
-            };
+        // Check filter using client's filter_enabled setting (default true),
+        // restricted to the client's filter schedule when one is attached
+        let now = Utc::now();
+        let paused = self.global_pause.read().await.is_some_and(|t| t > now)
+            || config.paused_until.is_some_and(|t| t > now);
+        let filtering = config.filter_enabled && !paused && schedule_active(&config.filter_schedule, now);
+        if filtering {
+            // Threat-feed hits are blocked like any other rule but also
+            // recorded as security events
+            if let Some(threat) = self.filter.check_threat(domain_normalized).await {
+                tracing::debug!("Blocked threat ({} via {}): {}", threat.category, threat.feed_name, domain);
+                let elapsed = start.elapsed().as_millis() as i64;
+                self.metrics.inc_blocked();
+                self.security_events.record(&client_ip, domain_normalized, &qtype_str, &threat).await;
+                self.log_query(&settings, client_ip, &domain, &qtype_str, "blocked", Some(&threat.reason()), elapsed);
+                return self.blocked_response(&request, settings.blocking_mode, qtype);
+            }
+            // Local data from global filter lists (RPZ/Unbound/dnsmasq addresses)
+            // wins over their block rules; other types get an empty answer
+            if config.group_ruleset.is_none() && matches!(qtype, RecordType::A | RecordType::AAAA) {
+                if let Some(addrs) = self.filter.local_data(domain_normalized).await {
+                    let elapsed = start.elapsed().as_millis() as i64;
+                    self.metrics.inc_allowed();
+                    self.log_query(&settings, client_ip, &domain, &qtype_str, "allowed", Some("local_data"), elapsed);
+                    let wanted = addrs.iter().find(|ip| ip.is_ipv4() == (qtype == RecordType::A));
+                    if let Some(response) = wanted.and_then(|ip| {
+                        self.rewrite_response(&request, &ip.to_string(), qtype, &domain).ok()
+                    }) {
+                        return Ok(response);
+                    }
+                    return self.rcode_response(&request, ResponseCode::NoError);
+                }
+            }
+            if let Some(reason) = self.check_domain(&config, domain_normalized, now).await {
+                tracing::debug!("Blocked ({}): {}", reason, domain);
+                let elapsed = start.elapsed().as_millis() as i64;
+                self.metrics.inc_blocked();
+                self.log_query(&settings, client_ip, &domain, &qtype_str, "blocked", Some(&reason), elapsed);
+                // No-data rules (RPZ `CNAME *.`) answer NOERROR without records
+                if config.group_ruleset.is_none() && self.filter.is_nodata(domain_normalized).await {
+                    return self.rcode_response(&request, ResponseCode::NoError);
+                }
+                return self.blocked_response(&request, settings.blocking_mode, qtype);
+            }
+            if settings.safe_search {
+                if let Some(host) = safe_search::safe_host(domain_normalized) {
+                    tracing::debug!("SafeSearch: {} -> {}", domain, host);
+                    let resolver = self.resolver_for(&settings).await?;
+                    let response = self.safe_search_response(&request, &resolver, host, qtype).await?;
+                    let elapsed = start.elapsed().as_millis() as i64;
+                    self.metrics.inc_allowed();
+                    self.log_query(&settings, client_ip, &domain, &qtype_str, "allowed", Some("safe_search"), elapsed);
+                    return Ok(response);
+                }
+            }
+        }
 
-            if blocked {
-                tracing::debug!("Blocked: {}", domain);
+        // Suspicious-domain heuristics: findings are logged when detection is
+        // enabled, and blocked only when blocking is enabled and the score
+        // reaches the configured threshold
+        let heuristics = *self.heuristics.read().await;
+        let suspicion = if heuristics.enabled { self.suspicion.analyze(domain_normalized) } else { None };
+        let suspicion_reason = suspicion.map(|f| f.reason());
+        if let Some(finding) = suspicion {
+            tracing::debug!(
+                "Suspicious query ({}, score {:.2}): {} from {}",
+                finding.kind.as_str(), finding.score, domain, client_ip
+            );
+            if filtering && heuristics.should_block(&finding) {
                 let elapsed = start.elapsed().as_millis() as i64;
                 self.metrics.inc_blocked();
-                self.log_query(client_ip, &domain, &qtype_str, "blocked", Some("filter_rule"), elapsed);
-                return self.nxdomain(&request);
+                self.log_query(&settings, client_ip, &domain, &qtype_str, "blocked", suspicion_reason.as_deref(), elapsed);
+                return self.blocked_response(&request, settings.blocking_mode, qtype);
             }
         }
 
-        // Check cache
-        if let Some(cached) = self.cache.get(&domain, qtype).await {
+        // Check cache, unless disabled for this client
+        let cached = match settings.cache_enabled {
+            true => self.cache.get_for_subnet(&domain, qtype, upstream_subnet.map(|s| s.network())).await,
+            false => None,
+        };
+        if let Some(cached) = cached {
             let elapsed = start.elapsed().as_millis() as i64;
 
             // CRITICAL: Update cached response ID to match current request ID
             // Cached responses contain the original request ID, which must be replaced
             let mut cached_msg = Message::from_vec(&cached)?;
+
+            // Response-address rules may have changed since this entry was
+            // cached, and the cache is shared by clients with different rules
+            if filtering {
+                if let Some(reason) = self.check_response(&config, domain_normalized, &cached_msg, now).await {
+                    tracing::debug!("Blocked cached response for {} ({})", domain, reason);
+                    self.metrics.inc_blocked();
+                    self.log_query(&settings, client_ip, &domain, &qtype_str, "blocked", Some(&reason), elapsed);
+                    return self.blocked_response(&request, settings.blocking_mode, qtype);
+                }
+            }
+
             cached_msg.set_id(request.id());
             let updated_cached = cached_msg.to_vec()?;
 
             self.metrics.inc_cached();
-            self.log_query(client_ip, &domain, &qtype_str, "cached", None, elapsed);
+            self.log_query(&settings, client_ip, &domain, &qtype_str, "cached", suspicion_reason.as_deref(), elapsed);
             return Ok(updated_cached);
         }
 
-        // Resolve: use client-specific upstream if configured, else global resolver
-        let (response, min_ttl) = if let Some(ref upstreams) = config.upstream_urls {
-            let resolver = self.get_or_create_client_resolver(upstreams).await?;
-            resolver.resolve(&domain, qtype, &request).await?
-        } else {
-            self.resolver.resolve(&domain, qtype, &request).await?
+        let resolver = self.resolver_for(&settings).await?;
+        let (response, min_ttl, scope) = match upstream_subnet {
+            Some(subnet) => resolver.resolve_with_subnet(&domain, qtype, &request, subnet).await?,
+            None => {
+                let (response, min_ttl) = resolver.resolve(&domain, qtype, &request).await?;
+                (response, min_ttl, 0)
+            }
         };
 
         // Verify response ID matches request ID (CRITICAL for DNS protocol)
@@ -184,94 +576,474 @@ impl DnsHandler {
         }
 
         let elapsed = start.elapsed().as_millis() as i64;
-        // Cache with upstream-derived TTL (Task 2: respect upstream TTL)
-        self.cache.set_with_ttl(&domain, qtype, response.clone(), min_ttl).await;
+
+        // Inspect the answer (CNAME chain, addresses) before caching it:
+        // a blocked answer is replaced by NXDOMAIN and never cached
+        if filtering {
+            if let Some(reason) = self.check_response(&config, domain_normalized, &response_msg, now).await {
+                tracing::debug!("Blocked response for {} ({})", domain, reason);
+                self.metrics.inc_blocked();
+                self.log_query(&settings, client_ip, &domain, &qtype_str, "blocked", Some(&reason), elapsed);
+                return self.blocked_response(&request, settings.blocking_mode, qtype);
+            }
+        }
+
+        // Cache with upstream-derived TTL (Task 2: respect upstream TTL); an
+        // answer scoped to the client subnet is only reused for that subnet
+        if settings.cache_enabled {
+            let cache_subnet = upstream_subnet.filter(|_| scope > 0).map(|s| s.network());
+            self.cache.set_for_subnet(&domain, qtype, cache_subnet, response.clone(), min_ttl).await;
+        }
         self.metrics.inc_allowed();
-        self.log_query(client_ip, &domain, &qtype_str, "allowed", None, elapsed);
+        self.log_query(&settings, client_ip, &domain, &qtype_str, "allowed", suspicion_reason.as_deref(), elapsed);
 
         Ok(response)
     }
 
-    /// Look up client configuration by source IP.
-    /// Returns ClientConfig with filter_enabled, upstream_urls, and optional group_ruleset.
+    /// Whether an allow rule of the client (its group rules, else the global
+    /// rules) matches `domain`.
+    async fn is_allowlisted(&self, config: &ClientConfig, domain: &str, now: DateTime<Utc>) -> bool {
+        match config.group_ruleset {
+            Some(ref ruleset) => ruleset.is_allowlisted(domain, now),
+            None => self.filter.is_allowlisted(domain).await,
+        }
+    }
+
+    /// Domain-name checks for a filtered client.  Returns the log reason of the
+    /// first match: custom/list rules (group rules replace the global ones),
+    /// filter lists bound to the client's groups, blocked services, then
+    /// parental-control categories.  The client's allow rules override list,
+    /// service and category matches.
+    async fn check_domain(&self, config: &ClientConfig, domain: &str, now: DateTime<Utc>) -> Option<String> {
+        let blocked = if let Some(ref ruleset) = config.group_ruleset {
+            // Client belongs to a group with specific rules — use group rules only
+            ruleset.is_blocked(domain, now)
+        } else {
+            // No group rules — use global FilterEngine
+            self.filter.is_blocked(domain).await
+        };
+        if blocked {
+            return Some("filter_rule".to_string());
+        }
+
+        // Group-bound filter lists; the client's allow rules still take precedence
+        let lists = active_ids(&config.filter_lists, now);
+        if !lists.is_empty() && !self.is_allowlisted(config, domain, now).await {
+            if let Some(list_id) = self.filter.check_group_lists(domain, &lists).await {
+                return Some(format!("filter_list:{}", list_id));
+            }
+        }
+
+        // Blocked services selected for the client or its groups; allow rules win
+        if let Some(service) = services::check(domain, &active_ids(&config.blocked_services, now)) {
+            if !self.is_allowlisted(config, domain, now).await {
+                return Some(services::block_reason(service));
+            }
+        }
+
+        // Parental control: global categories + categories selected by the
+        // client's groups; allow rules win
+        let category = self.filter
+            .check_categories(domain, &active_ids(&config.blocked_categories, now))
+            .await?;
+        if self.is_allowlisted(config, domain, now).await {
+            return None;
+        }
+        Some(categories::block_reason(&category))
+    }
+
+    /// Answer-section checks for a filtered client.  Returns the log reason if
+    /// the response must be blocked:
+    ///   - `cname:<hop>` when a CNAME target in the chain is blocked by name
+    ///     (CNAME cloaking of trackers behind first-party subdomains);
+    ///   - `response_ip` when an A/AAAA answer matches a response-address rule
+    ///     (the client's group rules when it has them, else the global rules,
+    ///     plus its group-bound filter lists);
+    ///   - `rebind_protection` when `domain` answers with an internal address.
+    async fn check_response(&self, config: &ClientConfig, domain: &str, response: &Message, now: DateTime<Utc>) -> Option<String> {
+        // An allowlisted question name exempts the names it points to
+        let check_hops = !self.is_allowlisted(config, domain, now).await;
+        let mut ips: Vec<IpAddr> = Vec::new();
+        for record in response.answers() {
+            match record.data() {
+                Some(RData::CNAME(target)) if check_hops => {
+                    let hop = target.0.to_string();
+                    let hop = hop.trim_end_matches('.');
+                    if self.check_domain(config, hop, now).await.is_some() {
+                        return Some(format!("cname:{}", hop));
+                    }
+                }
+                Some(RData::A(a)) => ips.push(IpAddr::V4(a.0)),
+                Some(RData::AAAA(aaaa)) => ips.push(IpAddr::V6(aaaa.0)),
+                _ => {}
+            }
+        }
+        if ips.is_empty() {
+            return None;
+        }
+        let blocked_ip = match config.group_ruleset {
+            Some(ref ruleset) => ruleset.blocked_ip(&ips, now),
+            None => self.filter.check_response_ips(&ips).await,
+        };
+        let blocked_ip = match blocked_ip {
+            Some(ip) => Some(ip),
+            None => self.filter.check_group_list_ips(&ips, &active_ids(&config.filter_lists, now)).await,
+        };
+        if blocked_ip.is_some() {
+            return Some("response_ip".to_string());
+        }
+        self.filter
+            .check_rebind(domain, &ips)
+            .await
+            .map(|_| rebind::BLOCK_REASON.to_string())
+    }
+
+    /// The client index, built on first use after `reload_clients`.
+    async fn client_index(&self) -> Arc<ClientIndex> {
+        if let Some(index) = self.client_index.read().await.as_ref() {
+            return index.clone();
+        }
+        let mut slot = self.client_index.write().await;
+        match slot.as_ref() {
+            Some(index) => index.clone(),
+            None => {
+                let index = Arc::new(self.build_client_index().await);
+                *slot = Some(index.clone());
+                index
+            }
+        }
+    }
+
+    /// MAC of the query's source: from the forwarder's EDNS0 option when that
+    /// is trusted, else from the neighbour table.  Only looked up while some
+    /// client is identified by MAC.
+    async fn client_mac(&self, request: &Message, client_ip: &str) -> Option<MacAddr> {
+        if !self.client_index().await.has_macs() {
+            return None;
+        }
+        if *self.edns_mac_enabled.read().await {
+            if let Some(mac) = neighbors::edns_mac(request) {
+                return Some(mac);
+            }
+        }
+        self.neighbors.lookup(client_ip.parse().ok()?).await
+    }
+
+    /// Id of the client whose identifiers match the source most specifically.
+    async fn identify_client(&self, client_ip: &str, mac: Option<MacAddr>, client_id: Option<&str>) -> Option<String> {
+        let ip = client_ip.parse::<IpAddr>().ok()?;
+        self.client_index().await.lookup(ip, mac, client_id).map(str::to_string)
+    }
+
+    /// Persistent client a query from `ip` alone would be attributed to.
+    pub async fn client_for_ip(&self, ip: IpAddr) -> Option<String> {
+        self.client_index().await.lookup(ip, None, None).map(str::to_string)
+    }
+
+    /// Client a query from `ip` would be attributed to, with the identifier
+    /// that matched.  Without `mac`, the neighbour table is consulted.
+    pub async fn match_client<'a>(
+        &self,
+        ip: IpAddr,
+        mac: Option<MacAddr>,
+        client_id: Option<&'a str>,
+    ) -> Option<(String, Identifier<'a>)> {
+        let index = self.client_index().await;
+        let mac = match mac {
+            None if index.has_macs() => self.neighbors.lookup(ip).await,
+            mac => mac,
+        };
+        index.lookup_match(ip, mac, client_id).map(|(id, identifier)| (id.to_string(), identifier))
+    }
+
+    /// Whether queries of a client (of unidentified sources for `None`) are
+    /// filtered right now, and why not.  Read from the database rather than
+    /// the per-source config cache.
+    pub async fn filtering_state(&self, client_id: Option<&str>) -> FilteringState {
+        let config = match client_id {
+            Some(id) => self.load_client_config(id).await,
+            None => ClientConfig::default(),
+        };
+        let now = Utc::now();
+        let global_pause = self.global_pause.read().await.filter(|t| *t > now);
+        let paused_until = global_pause.max(config.paused_until.filter(|t| *t > now));
+        let in_schedule = schedule_active(&config.filter_schedule, now);
+        FilteringState {
+            enabled: config.filter_enabled,
+            schedule_active: in_schedule,
+            paused_until,
+            active: config.filter_enabled && paused_until.is_none() && in_schedule,
+            group_rules_only: config.group_ruleset.is_some(),
+        }
+    }
+
+    /// Name servers used for clients without custom upstreams.
+    pub fn default_upstreams(&self) -> Vec<String> {
+        self.resolver.name_servers().iter().map(|addr| addr.to_string()).collect()
+    }
+
+    /// Host name of `ip` from a PTR lookup through the default upstreams.
+    pub async fn reverse_lookup(&self, ip: IpAddr) -> Result<Option<String>> {
+        self.resolver.reverse_lookup(ip).await
+    }
+
+    async fn build_client_index(&self) -> ClientIndex {
+        let rows: Vec<(String, String)> = match sqlx::query_as(
+            "SELECT id, identifiers FROM clients ORDER BY created_at ASC, id ASC"
+        )
+        .fetch_all(&self.db)
+        .await {
+            Ok(r) => r,
+            Err(e) => {
+                tracing::warn!("Failed to load clients: {}", e);
+                return ClientIndex::default();
+            }
+        };
+        let clients = rows.len();
+        let index = ClientIndex::build(rows);
+        tracing::debug!("Indexed {} identifiers of {} clients", index.len(), clients);
+        index
+    }
+
+    /// Look up client configuration by source IP (and MAC or client ID, when
+    /// known).
+    /// Returns ClientConfig with filter_enabled, setting overrides, and optional group_ruleset.
     /// Results are cached for CLIENT_CACHE_TTL to avoid per-query DB scans (M-4 fix).
-    async fn get_client_config(&self, client_ip: &str) -> ClientConfig {
+    async fn get_client_config(&self, client_ip: &str, mac: Option<MacAddr>, client_id: Option<&str>) -> ClientConfig {
+        let mut key = match mac {
+            Some(mac) => format!("{}@{}", mac, client_ip),
+            None => client_ip.to_string(),
+        };
+        if let Some(client_id) = client_id {
+            key = format!("{}#{}", client_id, key);
+        }
         // Fast path: cache hit
-        if let Some(cached) = self.client_config_cache.get(client_ip).await {
+        if let Some(cached) = self.client_config_cache.get(&key).await {
             return cached;
         }
 
         // Slow path: DB lookup (only on cache miss)
-        let result = self.resolve_client_config(client_ip).await;
-        self.client_config_cache.insert(client_ip.to_string(), result.clone()).await;
+        let result = self.resolve_client_config(client_ip, mac, client_id).await;
+        self.client_config_cache.insert(key, result.clone()).await;
         result
     }
 
-    async fn resolve_client_config(&self, client_ip: &str) -> ClientConfig {
-        let full_rows: Vec<(String, String, i64, Option<String>)> = match sqlx::query_as(
-            "SELECT id, identifiers, filter_enabled, upstreams FROM clients"
+    async fn resolve_client_config(&self, client_ip: &str, mac: Option<MacAddr>, transport_id: Option<&str>) -> ClientConfig {
+        match self.identify_client(client_ip, mac, transport_id).await {
+            Some(client_id) => self.load_client_config(&client_id).await,
+            None => ClientConfig::default(),
+        }
+    }
+
+    async fn load_client_config(&self, client_id: &str) -> ClientConfig {
+        let row: ClientRow = match sqlx::query_as(
+            "SELECT id, filter_enabled, blocked_services,
+                    blocked_services_schedule_id, filter_schedule_id
+             FROM clients WHERE id = ?"
         )
+        .bind(client_id)
+        .fetch_optional(&self.db)
+        .await {
+            Ok(Some(r)) => r,
+            _ => return ClientConfig::default(),
+        };
+
+        let schedules = self.load_schedules().await;
+        let lookup = |id: Option<String>| -> Option<Arc<Schedule>> {
+            let id = id?;
+            let schedule = schedules.get(&id).cloned();
+            if schedule.is_none() {
+                // Fail closed: an unknown schedule leaves the item always active
+                tracing::warn!("Client {} references unknown schedule {}", row.id, id);
+            }
+            schedule
+        };
+
+        let filter_schedule = lookup(row.filter_schedule_id.clone());
+        let settings = match client_settings::load_layers(&self.db, &row.id).await {
+            Ok(layers) => client_settings::merge(&layers).overrides,
+            Err(e) => {
+                tracing::warn!("Failed to load settings of client {}: {}", row.id, e);
+                SettingsOverrides::default()
+            }
+        };
+        let own_services_schedule = lookup(row.blocked_services_schedule_id.clone());
+        let mut blocked_services: Vec<ScheduledBinding> = row.blocked_services.as_ref()
+            .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|id| ScheduledBinding { id, schedule: own_services_schedule.clone() })
+            .collect();
+
+        // Group-specific rules, categories and services
+        for (id, schedule_id) in self.load_group_bindings_for_client(&row.id, "blocked_service").await {
+            blocked_services.push(ScheduledBinding { id, schedule: lookup(schedule_id) });
+        }
+        blocked_services.retain(|b| services::find(&b.id).is_some());
+
+        let blocked_categories = self.load_group_bindings_for_client(&row.id, "category").await
+            .into_iter()
+            .filter(|(id, _)| categories::find(id).is_some())
+            .map(|(id, schedule_id)| ScheduledBinding { id, schedule: lookup(schedule_id) })
+            .collect();
+
+        let filter_lists = self.load_group_bindings_for_client(&row.id, "filter_list").await
+            .into_iter()
+            .map(|(id, schedule_id)| ScheduledBinding { id, schedule: lookup(schedule_id) })
+            .collect();
+
+        let group_ruleset = self.load_group_rules_for_client(&row.id, &lookup).await;
+        let paused_until = self.load_pause_for_client(&row.id).await;
+        let qtype_policies = self.load_qtype_policies_for_client(&row.id).await;
+
+        ClientConfig {
+            client_id: Some(row.id.clone()),
+            filter_enabled: row.filter_enabled == 1,
+            filter_schedule,
+            paused_until,
+            settings,
+            group_ruleset,
+            filter_lists,
+            blocked_categories,
+            blocked_services,
+            qtype_policies,
+        }
+    }
+
+    /// Merge the query-type policies of this client's groups.  Groups are
+    /// visited in priority order, so the first group naming a type wins.
+    async fn load_qtype_policies_for_client(&self, client_id: &str) -> QtypePolicies {
+        let rows: Vec<String> = match sqlx::query_scalar(
+            r#"
+            SELECT cg.qtype_policies
+            FROM client_group_memberships m
+            JOIN client_groups cg ON cg.id = m.group_id
+            WHERE m.client_id = ? AND cg.qtype_policies IS NOT NULL
+            ORDER BY cg.priority ASC
+            "#
+        )
+        .bind(client_id)
         .fetch_all(&self.db)
         .await {
             Ok(r) => r,
-            Err(_) => return ClientConfig::default(),
-        };
-
-        let mut matched_client_id: Option<String> = None;
-        let mut filter_enabled = true;
-        let mut upstream_urls: Option<Vec<String>> = None;
-
-        for (client_id, identifiers_json, fe, upstreams_json) in full_rows {
-            // Parse identifiers array (["192.168.1.10", "192.168.1.0/24", ...])
-            if let Ok(identifiers) = serde_json::from_str::<Vec<serde_json::Value>>(&identifiers_json) {
-                let matched = identifiers.iter().any(|id| {
-                    let id_str = id.as_str().unwrap_or("");
-                    // Exact IP match
-                    if id_str == client_ip { return true; }
-                    // CIDR match
-                    if let Ok(network) = id_str.parse::<ipnet::IpNet>() {
-                        if let Ok(ip) = client_ip.parse::<IpAddr>() {
-                            return network.contains(&ip);
-                        }
-                    }
-                    false
-                });
-
-                if matched {
-                    matched_client_id = Some(client_id);
-                    filter_enabled = fe == 1;
-                    upstream_urls = upstreams_json.and_then(|s| {
-                        serde_json::from_str::<Vec<String>>(&s).ok()
-                    }).filter(|v| !v.is_empty());
-                    break;
-                }
+            Err(e) => {
+                tracing::warn!("Failed to load query type policies for client {}: {}", client_id, e);
+                return QtypePolicies::new();
+            }
+        };
+
+        let mut merged = QtypePolicies::new();
+        for json in rows {
+            for (qtype, action) in qtype_policy::parse(&json) {
+                merged.entry(qtype).or_insert(action);
             }
         }
+        merged
+    }
 
-        // If client was matched, check for group-specific rules
-        let group_ruleset = if let Some(ref cid) = matched_client_id {
-            self.load_group_rules_for_client(cid).await
-        } else {
-            None
+    /// Latest unexpired pause covering this client directly or through one of its groups.
+    async fn load_pause_for_client(&self, client_id: &str) -> Option<DateTime<Utc>> {
+        let expires: Option<String> = match sqlx::query_scalar(
+            r#"
+            SELECT MAX(expires_at) FROM filter_pauses
+            WHERE expires_at > ?
+              AND ((scope = 'client' AND target_id = ?)
+                OR (scope = 'group' AND target_id IN (
+                    SELECT CAST(group_id AS TEXT) FROM client_group_memberships WHERE client_id = ?)))
+            "#
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(client_id)
+        .bind(client_id)
+        .fetch_one(&self.db)
+        .await {
+            Ok(e) => e,
+            Err(e) => {
+                tracing::warn!("Failed to load filtering pause for client {}: {}", client_id, e);
+                return None;
+            }
+        };
+        expires
+            .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+            .map(|t| t.with_timezone(&Utc))
+    }
+
+    /// Load and compile all schedules, keyed by id.  Invalid rows are skipped.
+    async fn load_schedules(&self) -> HashMap<String, Arc<Schedule>> {
+        let rows: Vec<(String, String, String, String)> = match sqlx::query_as(
+            "SELECT id, timezone, weekdays, time_ranges FROM schedules"
+        )
+        .fetch_all(&self.db)
+        .await {
+            Ok(r) => r,
+            Err(e) => {
+                tracing::warn!("Failed to load schedules: {}", e);
+                return HashMap::new();
+            }
         };
 
-        ClientConfig { filter_enabled, upstream_urls, group_ruleset }
+        rows.into_iter()
+            .filter_map(|(id, tz, weekdays, ranges)| {
+                match Schedule::from_row(&id, &tz, &weekdays, &ranges) {
+                    Ok(s) => Some((id, Arc::new(s))),
+                    Err(e) => {
+                        tracing::warn!("Ignoring invalid schedule {}: {}", id, e);
+                        None
+                    }
+                }
+            })
+            .collect()
     }
 
-    /// Load custom rule strings bound to groups this client belongs to.
-    /// Fetches rules from all groups the client is in, ordered by group priority.
-    /// Returns None if the client has no group rules (caller falls back to global FilterEngine).
-    async fn load_group_rules_for_client(&self, client_id: &str) -> Option<Arc<RuleSet>> {
-        let rule_rows: Vec<(String,)> = match sqlx::query_as(
+    /// Load the ids bound to this client's groups with the given `rule_type`
+    /// (e.g. parental-control categories or blocked services), ordered by group
+    /// priority, together with each binding's schedule id.
+    async fn load_group_bindings_for_client(&self, client_id: &str, rule_type: &str) -> Vec<(String, Option<String>)> {
+        match sqlx::query_as::<_, (String, Option<String>)>(
             r#"
-            SELECT cr.rule
+            SELECT CAST(cgr.rule_id AS TEXT), cgr.schedule_id
             FROM client_group_memberships m
             JOIN client_groups cg ON cg.id = m.group_id
             JOIN client_group_rules cgr ON cgr.group_id = m.group_id
-            JOIN custom_rules cr ON cr.id = cgr.rule_id
             WHERE m.client_id = ?
-              AND cgr.rule_type = 'custom_rule'
-              AND cr.is_enabled = 1
+              AND cgr.rule_type = ?
+            ORDER BY cg.priority ASC, cgr.priority ASC
+            "#
+        )
+        .bind(client_id)
+        .bind(rule_type)
+        .fetch_all(&self.db)
+        .await {
+            Ok(rows) => {
+                let mut bindings: Vec<(String, Option<String>)> = Vec::with_capacity(rows.len());
+                for row in rows {
+                    if !bindings.contains(&row) {
+                        bindings.push(row);
+                    }
+                }
+                bindings
+            }
+            Err(e) => {
+                tracing::warn!("Failed to load group {} bindings for client {}: {}", rule_type, client_id, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Custom rules bound to groups this client belongs to, in group priority
+    /// order.  Each group's rules are compiled once and shared by its members.
+    /// Returns None if the client has no group rules (caller falls back to global FilterEngine).
+    async fn load_group_rules_for_client(
+        &self,
+        client_id: &str,
+        lookup: &impl Fn(Option<String>) -> Option<Arc<Schedule>>,
+    ) -> Option<Arc<GroupRules>> {
+        let group_ids: Vec<i64> = match sqlx::query_scalar(
+            r#"
+            SELECT m.group_id
+            FROM client_group_memberships m
+            JOIN client_groups cg ON cg.id = m.group_id
+            WHERE m.client_id = ?
             ORDER BY cg.priority ASC
             "#
         )
@@ -280,21 +1052,40 @@ impl DnsHandler {
         .await {
             Ok(r) => r,
             Err(e) => {
-                tracing::warn!("Failed to load group rules for client {}: {}", client_id, e);
+                tracing::warn!("Failed to load groups of client {}: {}", client_id, e);
                 return None;
             }
         };
 
-        if rule_rows.is_empty() {
+        let mut parts: Vec<(Option<Arc<Schedule>>, Arc<RuleSet>)> = Vec::new();
+        for group_id in group_ids {
+            let group = match self.group_rules.get(group_id).await {
+                Ok(g) => g,
+                Err(e) => {
+                    tracing::warn!("Failed to compile rules of client group {}: {}", group_id, e);
+                    continue;
+                }
+            };
+            for (schedule_id, rules) in &group.parts {
+                parts.push((lookup(schedule_id.clone()), rules.clone()));
+            }
+        }
+
+        if parts.is_empty() {
             return None;
         }
+        let rules = GroupRules { parts };
+        tracing::debug!("Loaded {} group rules for client {}", rules.rule_count(), client_id);
+        Some(Arc::new(rules))
+    }
 
-        let mut ruleset = RuleSet::new();
-        for (rule,) in rule_rows {
-            ruleset.add_rule(&rule);
+    /// The client's custom upstreams when it (or a group) has some, else the
+    /// global resolver.
+    async fn resolver_for(&self, settings: &ClientSettings) -> Result<Arc<DnsResolver>> {
+        match settings.upstreams {
+            Some(ref upstreams) => self.get_or_create_client_resolver(upstreams).await,
+            None => Ok(self.resolver.clone()),
         }
-        tracing::debug!("Loaded {} group rules for client {}", ruleset.blocked_count() + ruleset.allowed_count(), client_id);
-        Some(Arc::new(ruleset))
     }
 
     /// Get or create a cached per-client resolver for the given upstream list.
@@ -357,11 +1148,58 @@ impl DnsHandler {
         Ok(response.to_vec()?)
     }
 
+    /// SafeSearch answer: a CNAME from the queried name to the engine's
+    /// restricted `host`, followed by `host`'s own records.  Not cached, as
+    /// the cache is shared with clients without SafeSearch.
+    async fn safe_search_response(&self, request: &Message, resolver: &DnsResolver, host: &str, qtype: RecordType) -> Result<Vec<u8>> {
+        let query = request.queries().first()
+            .ok_or_else(|| anyhow::anyhow!("SafeSearch request contains no queries"))?;
+        let target = Name::from_str(&format!("{}.", host))?;
+        let (upstream, _) = resolver.resolve(&target.to_string(), qtype, request).await?;
+        let upstream = Message::from_vec(&upstream)?;
+
+        let mut response = Message::new();
+        response.set_id(request.id());
+        response.set_message_type(MessageType::Response);
+        response.set_response_code(upstream.response_code());
+        response.set_recursion_desired(request.recursion_desired());
+        response.set_recursion_available(true);
+        response.add_query(query.clone());
+        response.add_answer(Record::from_rdata(query.name().clone(), 300, RData::CNAME(CNAME(target))));
+        for record in upstream.answers() {
+            response.add_answer(record.clone());
+        }
+        Ok(response.to_vec()?)
+    }
+
+    /// Answer for a blocked query in the client's blocking mode.
+    fn blocked_response(&self, request: &Message, mode: BlockingMode, qtype: RecordType) -> Result<Vec<u8>> {
+        let null_ip = match qtype {
+            RecordType::A => Some("0.0.0.0"),
+            RecordType::AAAA => Some("::"),
+            _ => None,
+        };
+        match (mode, null_ip) {
+            (BlockingMode::Nxdomain, _) => self.nxdomain(request),
+            (BlockingMode::Refused, _) => self.rcode_response(request, ResponseCode::Refused),
+            (BlockingMode::NullIp, Some(ip)) => {
+                let domain = request.queries().first().map(|q| q.name().to_string()).unwrap_or_default();
+                self.rewrite_response(request, ip, qtype, &domain)
+            }
+            (BlockingMode::Nodata | BlockingMode::NullIp, _) => self.rcode_response(request, ResponseCode::NoError),
+        }
+    }
+
     /// Non-blocking query log write + WebSocket broadcast.
     ///
     /// The DB write goes through the batch writer (Task 1): send() is O(1) and
     /// never blocks the DNS hot path.  The WebSocket broadcast is also fire-and-forget.
-    fn log_query(&self, client_ip: String, domain: &str, qtype: &str, status: &str, reason: Option<&str>, elapsed_ms: i64) {
+    /// Nothing is recorded for clients with query logging turned off.
+    #[allow(clippy::too_many_arguments)]
+    fn log_query(&self, settings: &ClientSettings, client_ip: String, domain: &str, qtype: &str, status: &str, reason: Option<&str>, elapsed_ms: i64) {
+        if !settings.query_log_enabled {
+            return;
+        }
         let domain = domain.to_string();
         let qtype = qtype.to_string();
         let status = status.to_string();
@@ -396,10 +1234,16 @@ impl DnsHandler {
     }
 
     fn nxdomain(&self, request: &Message) -> Result<Vec<u8>> {
+        self.rcode_response(request, ResponseCode::NXDomain)
+    }
+
+    /// Answer-less response with the given rcode (NXDOMAIN, REFUSED, or an
+    /// empty NOERROR answer).
+    fn rcode_response(&self, request: &Message, rcode: ResponseCode) -> Result<Vec<u8>> {
         let mut response = Message::new();
         response.set_id(request.id());
         response.set_message_type(MessageType::Response);
-        response.set_response_code(ResponseCode::NXDomain);
+        response.set_response_code(rcode);
         response.set_recursion_desired(request.recursion_desired());
         response.set_recursion_available(true);
         for query in request.queries() {
@@ -416,3 +1260,534 @@ impl DnsHandler {
         Ok(response.to_vec()?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiConfig, AuthConfig, DatabaseConfig, DnsConfig};
+    use hickory_proto::rr::{rdata::CNAME, Name};
+    use std::str::FromStr;
+
+    async fn test_handler(rules: &[&str]) -> DnsHandler {
+        let db = sqlx::SqlitePool::connect(":memory:").await.unwrap();
+        sqlx::migrate!("./src/db/migrations").run(&db).await.unwrap();
+        for (i, rule) in rules.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO custom_rules (id, rule, is_enabled, created_by, created_at)
+                 VALUES (?, ?, 1, 'test', datetime('now'))"
+            )
+            .bind(i.to_string())
+            .bind(rule)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        let filter = Arc::new(FilterEngine::new(db.clone()).await.unwrap());
+        let cfg = Config {
+            dns: DnsConfig {
+                port: 0,
+                bind: "127.0.0.1".to_string(),
+                upstreams: vec!["https://1.1.1.1/dns-query".to_string()],
+                doh_enabled: false,
+                dot_enabled: false,
+                dot_port: 0,
+                tls_cert_path: None,
+                tls_key_path: None,
+                client_id_domain: None,
+                proxy_protocol_from: vec![],
+            },
+            api: ApiConfig { port: 0, bind: "127.0.0.1".to_string(), cors_allowed_origins: vec![], proxy_protocol_from: vec![], forwarded_for_from: vec![] },
+            database: DatabaseConfig { path: ":memory:".to_string(), query_log_retention_days: 7 },
+            auth: AuthConfig { jwt_secret: "x".repeat(32), jwt_expiry_hours: 1 },
+        };
+        let (tx, _) = broadcast::channel(4);
+        DnsHandler::new(cfg, db, filter, Arc::new(DnsMetrics::default()), tx).await.unwrap()
+    }
+
+    fn record(name: &str, rdata: RData) -> Record {
+        Record::from_rdata(Name::from_str(name).unwrap(), 300, rdata)
+    }
+
+    fn cloaked_response() -> Message {
+        let mut msg = Message::new();
+        msg.add_answer(record("metrics.shop.example.", RData::CNAME(CNAME(Name::from_str("shop.tracker.example.").unwrap()))));
+        msg.add_answer(record("shop.tracker.example.", RData::CNAME(CNAME(Name::from_str("edge.cdn.example.").unwrap()))));
+        msg.add_answer(record("edge.cdn.example.", RData::A(A("198.51.100.7".parse().unwrap()))));
+        msg
+    }
+
+    #[tokio::test]
+    async fn test_cname_hop_blocked() {
+        let handler = test_handler(&["||tracker.example^"]).await;
+        let reason = handler.check_response(&ClientConfig::default(), "metrics.shop.example", &cloaked_response(), Utc::now()).await;
+        assert_eq!(reason.as_deref(), Some("cname:shop.tracker.example"));
+    }
+
+    #[tokio::test]
+    async fn test_cname_hop_of_allowlisted_name_not_blocked() {
+        let handler = test_handler(&["||tracker.example^", "@@||metrics.shop.example^"]).await;
+        let reason = handler.check_response(&ClientConfig::default(), "metrics.shop.example", &cloaked_response(), Utc::now()).await;
+        assert!(reason.is_none());
+        let reason = handler.check_response(&ClientConfig::default(), "other.shop.example", &cloaked_response(), Utc::now()).await;
+        assert_eq!(reason.as_deref(), Some("cname:shop.tracker.example"));
+    }
+
+    #[tokio::test]
+    async fn test_allow_rule_overrides_blocked_service() {
+        let handler = test_handler(&["@@||music.youtube.com^"]).await;
+        let config = ClientConfig {
+            blocked_services: vec![ScheduledBinding { id: "youtube".to_string(), schedule: None }],
+            ..ClientConfig::default()
+        };
+        assert!(handler.check_domain(&config, "music.youtube.com", Utc::now()).await.is_none());
+        assert_eq!(
+            handler.check_domain(&config, "www.youtube.com", Utc::now()).await,
+            Some(services::block_reason("youtube"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_allow_rule_overrides_category() {
+        let handler = test_handler(&["@@||bet365.com^"]).await;
+        let config = ClientConfig {
+            blocked_categories: vec![ScheduledBinding { id: "gambling".to_string(), schedule: None }],
+            ..ClientConfig::default()
+        };
+        assert!(handler.check_domain(&config, "www.bet365.com", Utc::now()).await.is_none());
+        assert_eq!(
+            handler.check_domain(&config, "williamhill.com", Utc::now()).await,
+            Some(categories::block_reason("gambling"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_response_ip_blocked() {
+        let (upstream, _) = fake_ecs_upstream().await;
+        for (rule, status) in [("192.0.2.0/24", "blocked"), ("198.51.100.0/24", "allowed")] {
+            let handler = test_handler(&[rule]).await;
+            sqlx::query(
+                "INSERT INTO clients (id, name, identifiers, upstreams, filter_enabled, created_at, updated_at)
+                 VALUES ('lan', 'LAN', '[\"10.3.0.0/24\"]', ?, 1, datetime('now'), datetime('now'))"
+            )
+            .bind(serde_json::json!([upstream.to_string()]).to_string())
+            .execute(&handler.db)
+            .await
+            .unwrap();
+            handler.reload_clients().await;
+            let mut log = handler.query_log_tx.subscribe();
+
+            // The upstream answers 192.0.2.1
+            let bytes = handler.handle(query_bytes("cdn.example.", RecordType::A), "10.3.0.1".to_string()).await.unwrap();
+            let resp = Message::from_vec(&bytes).unwrap();
+            let entry = log.recv().await.unwrap();
+            assert_eq!(entry["status"], status, "{}", rule);
+            if status == "blocked" {
+                assert_eq!(entry["reason"], "response_ip");
+                assert!(resp.answers().is_empty());
+            } else {
+                assert_eq!(resp.answers()[0].data(), Some(&RData::A(A("192.0.2.1".parse().unwrap()))));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rebind_protection() {
+        let handler = test_handler(&[]).await;
+        sqlx::query(
+            "UPDATE settings SET value = CASE key
+                 WHEN 'rebind_protection_enabled' THEN 'true'
+                 ELSE '[\"corp.example\"]' END
+             WHERE key IN ('rebind_protection_enabled', 'rebind_protection_allowlist')"
+        )
+        .execute(&handler.db)
+        .await
+        .unwrap();
+        handler.filter.reload().await.unwrap();
+
+        let mut msg = Message::new();
+        msg.add_answer(record("evil.example.", RData::A(A("192.168.1.1".parse().unwrap()))));
+        let config = ClientConfig::default();
+        let reason = handler.check_response(&config, "evil.example", &msg, Utc::now()).await;
+        assert_eq!(reason.as_deref(), Some("rebind_protection"));
+        assert!(handler.check_response(&config, "nas.corp.example", &msg, Utc::now()).await.is_none());
+    }
+
+    fn query_bytes(name: &str, qtype: RecordType) -> Vec<u8> {
+        let mut msg = Message::new();
+        msg.set_id(7);
+        msg.set_message_type(MessageType::Query);
+        msg.set_op_code(OpCode::Query);
+        msg.add_query(hickory_proto::op::Query::query(Name::from_str(name).unwrap(), qtype));
+        msg.to_vec().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_qtype_policies() {
+        let handler = test_handler(&[]).await;
+        sqlx::query("UPDATE settings SET value = '{\"ANY\": \"refuse\", \"HTTPS\": \"empty\"}' WHERE key = 'qtype_policies'")
+            .execute(&handler.db)
+            .await
+            .unwrap();
+        handler.filter.reload().await.unwrap();
+
+        // A group overriding the global HTTPS policy
+        sqlx::query(
+            "INSERT INTO clients (id, name, identifiers, filter_enabled, created_at, updated_at)
+             VALUES ('c1', 'Strict', '[\"10.0.0.5\"]', 1, datetime('now'), datetime('now'))"
+        )
+        .execute(&handler.db)
+        .await
+        .unwrap();
+        let group_id: i64 = sqlx::query_scalar(
+            "INSERT INTO client_groups (name, priority, qtype_policies, created_at, updated_at)
+             VALUES ('strict', 1, '{\"HTTPS\": \"refuse\"}', datetime('now'), datetime('now')) RETURNING id"
+        )
+        .fetch_one(&handler.db)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO client_group_memberships (client_id, group_id, created_at) VALUES ('c1', ?, datetime('now'))")
+            .bind(group_id)
+            .execute(&handler.db)
+            .await
+            .unwrap();
+
+        let rcode = |bytes: Vec<u8>| {
+            let msg = Message::from_vec(&bytes).unwrap();
+            assert!(msg.answers().is_empty());
+            msg.response_code()
+        };
+        let any = handler.handle(query_bytes("example.com.", RecordType::ANY), "10.0.0.9".to_string()).await.unwrap();
+        assert_eq!(rcode(any), ResponseCode::Refused);
+        let https = handler.handle(query_bytes("example.com.", RecordType::HTTPS), "10.0.0.9".to_string()).await.unwrap();
+        assert_eq!(rcode(https), ResponseCode::NoError);
+        let group_https = handler.handle(query_bytes("example.com.", RecordType::HTTPS), "10.0.0.5".to_string()).await.unwrap();
+        assert_eq!(rcode(group_https), ResponseCode::Refused);
+
+        let metrics = handler.metrics.to_prometheus_text();
+        assert!(metrics.contains("ent_dns_queries_by_qtype_total{qtype=\"HTTPS\"} 2"));
+        assert!(metrics.contains("ent_dns_qtype_policy_total{qtype=\"HTTPS\",action=\"refuse\"} 1"));
+        assert!(metrics.contains("ent_dns_qtype_policy_total{qtype=\"ANY\",action=\"refuse\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_threat_feed_hit_recorded() {
+        let handler = test_handler(&[]).await;
+        sqlx::query(
+            "INSERT INTO filter_lists (id, name, is_enabled, rule_count, created_at, threat_category, severity)
+             VALUES ('feed1', 'Phishing feed', 1, 1, datetime('now'), 'phishing', 'critical')"
+        )
+        .execute(&handler.db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO custom_rules (id, rule, is_enabled, created_by, created_at)
+             VALUES ('t1', '||login-bank.example^', 1, 'filter:feed1', datetime('now'))"
+        )
+        .execute(&handler.db)
+        .await
+        .unwrap();
+        handler.filter.reload().await.unwrap();
+        // Threat rules stay out of the global blocklist
+        assert!(!handler.filter.is_blocked("login-bank.example").await);
+
+        let mut events = handler.security_events().sender().subscribe();
+        let resp = handler.handle(query_bytes("www.login-bank.example.", RecordType::A), "10.0.0.9".to_string()).await.unwrap();
+        assert_eq!(Message::from_vec(&resp).unwrap().response_code(), ResponseCode::NXDomain);
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event["threat_category"], "phishing");
+        assert_eq!(event["severity"], "critical");
+        assert_eq!(event["alerted"], true);
+        assert!(handler.metrics.to_prometheus_text()
+            .contains("ent_dns_security_events_total{category=\"phishing\",severity=\"critical\"} 1"));
+
+        // Repeated hits within the cooldown do not alert again
+        handler.handle(query_bytes("www.login-bank.example.", RecordType::A), "10.0.0.9".to_string()).await.unwrap();
+        assert_eq!(events.try_recv().unwrap()["alerted"], false);
+    }
+
+    #[tokio::test]
+    async fn test_list_actions() {
+        let handler = test_handler(&[
+            "||nx.example^",
+            "||nodata.example^$dnsrewrite=NOERROR",
+            "||portal.example^",
+            "||portal.example^$dnsrewrite=192.0.2.10",
+        ])
+        .await;
+        let ask = |name: &'static str, qtype: RecordType| {
+            let handler = &handler;
+            async move {
+                let bytes = handler.handle(query_bytes(name, qtype), "10.0.0.9".to_string()).await.unwrap();
+                Message::from_vec(&bytes).unwrap()
+            }
+        };
+
+        let nx = ask("nx.example.", RecordType::A).await;
+        assert_eq!(nx.response_code(), ResponseCode::NXDomain);
+
+        let nodata = ask("www.nodata.example.", RecordType::A).await;
+        assert_eq!(nodata.response_code(), ResponseCode::NoError);
+        assert!(nodata.answers().is_empty());
+
+        // Local data wins over the block rule for the same name
+        let portal = ask("portal.example.", RecordType::A).await;
+        assert_eq!(portal.response_code(), ResponseCode::NoError);
+        assert_eq!(portal.answers()[0].data(), Some(&RData::A(A("192.0.2.10".parse().unwrap()))));
+        let portal_v6 = ask("portal.example.", RecordType::AAAA).await;
+        assert_eq!(portal_v6.response_code(), ResponseCode::NoError);
+        assert!(portal_v6.answers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_edns_mac_identification() {
+        use hickory_proto::op::Edns;
+        use hickory_proto::rr::rdata::opt::EdnsOption;
+
+        let handler = test_handler(&[]).await;
+        sqlx::query(
+            "INSERT INTO clients (id, name, identifiers, filter_enabled, blocked_services, created_at, updated_at)
+             VALUES ('tablet', 'Tablet', '[\"AA:BB:CC:DD:EE:05\"]', 1, '[\"tiktok\"]', datetime('now'), datetime('now'))"
+        )
+        .execute(&handler.db)
+        .await
+        .unwrap();
+        handler.reload_clients().await;
+
+        // A query forwarded by dnsmasq with --add-mac
+        let mut msg = Message::from_vec(&query_bytes("www.tiktok.com.", RecordType::A)).unwrap();
+        let mut edns = Edns::new();
+        edns.options_mut().insert(EdnsOption::Unknown(neighbors::EDNS_MAC_OPTION, vec![0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x05]));
+        msg.set_edns(edns);
+        let forwarded = msg.to_vec().unwrap();
+
+        // The EDNS MAC is ignored until trusted
+        assert_eq!(handler.client_mac(&msg, "192.0.2.53").await, None);
+
+        sqlx::query("UPDATE settings SET value = 'true' WHERE key = 'edns_mac_enabled'")
+            .execute(&handler.db)
+            .await
+            .unwrap();
+        handler.reload_identification().await.unwrap();
+        assert_eq!(handler.client_mac(&msg, "192.0.2.53").await, MacAddr::parse("aa:bb:cc:dd:ee:05"));
+
+        let bytes = handler.handle(forwarded, "192.0.2.53".to_string()).await.unwrap();
+        assert_eq!(Message::from_vec(&bytes).unwrap().response_code(), ResponseCode::NXDomain);
+    }
+
+    /// A UDP upstream answering every A query with 192.0.2.1, echoing the
+    /// query's ECS option with scope /24.  Yields the subnets it was sent.
+    async fn fake_ecs_upstream() -> (std::net::SocketAddr, mpsc::UnboundedReceiver<Option<String>>) {
+        use hickory_proto::op::Edns;
+        use hickory_proto::rr::rdata::opt::{ClientSubnet, EdnsOption};
+
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 4096];
+            while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+                let query = Message::from_vec(&buf[..len]).unwrap();
+                let subnet = EcsSubnet::from_message(&query);
+                let _ = tx.send(subnet.map(|s| s.network().to_string()));
+
+                let mut response = Message::new();
+                response.set_id(query.id());
+                response.set_message_type(MessageType::Response);
+                response.add_query(query.queries()[0].clone());
+                let name = query.queries()[0].name().clone();
+                response.add_answer(Record::from_rdata(name, 300, RData::A(A("192.0.2.1".parse().unwrap()))));
+                if let Some(subnet) = subnet {
+                    let mut edns = Edns::new();
+                    edns.options_mut().insert(EdnsOption::Subnet(ClientSubnet::new(
+                        subnet.addr,
+                        subnet.source_prefix,
+                        24,
+                    )));
+                    response.set_edns(edns);
+                }
+                socket.send_to(&response.to_vec().unwrap(), peer).await.unwrap();
+            }
+        });
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_ecs_subnet_sent_and_cached_per_subnet() {
+        let handler = test_handler(&[]).await;
+        let (upstream, mut sent) = fake_ecs_upstream().await;
+        sqlx::query(
+            "INSERT INTO clients (id, name, identifiers, upstreams, filter_enabled, created_at, updated_at)
+             VALUES ('isp', 'ISP customers', '[\"81.2.0.0/16\"]', ?, 1, datetime('now'), datetime('now'))"
+        )
+        .bind(serde_json::json!([upstream.to_string()]).to_string())
+        .execute(&handler.db)
+        .await
+        .unwrap();
+        sqlx::query("UPDATE settings SET value = 'true' WHERE key = 'ecs_add_subnet'")
+            .execute(&handler.db)
+            .await
+            .unwrap();
+        handler.reload_ecs().await.unwrap();
+
+        let ask = |ip: &'static str| {
+            let handler = &handler;
+            async move {
+                let bytes = handler.handle(query_bytes("cdn.example.", RecordType::A), ip.to_string()).await.unwrap();
+                Message::from_vec(&bytes).unwrap().response_code()
+            }
+        };
+        assert_eq!(ask("81.2.69.142").await, ResponseCode::NoError);
+        assert_eq!(sent.try_recv().unwrap().as_deref(), Some("81.2.69.0/24"));
+        // Same /24: answered from the cache
+        assert_eq!(ask("81.2.69.7").await, ResponseCode::NoError);
+        assert!(sent.try_recv().is_err());
+        // Another /24 gets its own answer
+        assert_eq!(ask("81.2.70.1").await, ResponseCode::NoError);
+        assert_eq!(sent.try_recv().unwrap().as_deref(), Some("81.2.70.0/24"));
+    }
+
+    #[tokio::test]
+    async fn test_ecs_identifies_client_behind_trusted_forwarder() {
+        use hickory_proto::op::Edns;
+
+        let handler = test_handler(&[]).await;
+        for (id, ip, service) in [("forwarder", "10.0.0.53", "youtube"), ("tablet", "192.168.7.20", "tiktok")] {
+            sqlx::query(
+                "INSERT INTO clients (id, name, identifiers, filter_enabled, blocked_services, created_at, updated_at)
+                 VALUES (?, ?, ?, 1, ?, datetime('now'), datetime('now'))"
+            )
+            .bind(id)
+            .bind(id)
+            .bind(serde_json::json!([ip]).to_string())
+            .bind(serde_json::json!([service]).to_string())
+            .execute(&handler.db)
+            .await
+            .unwrap();
+        }
+        handler.reload_clients().await;
+
+        let forwarded = |name: &str| {
+            let mut msg = Message::from_vec(&query_bytes(name, RecordType::A)).unwrap();
+            let mut edns = Edns::new();
+            edns.options_mut().insert(EcsSubnet::new("192.168.7.20".parse().unwrap(), 32).to_option());
+            msg.set_edns(edns);
+            msg.to_vec().unwrap()
+        };
+        let rcode = |bytes: Vec<u8>| Message::from_vec(&bytes).unwrap().response_code();
+
+        // Untrusted: the forwarder's own client applies
+        let resp = handler.handle(forwarded("www.youtube.com."), "10.0.0.53".to_string()).await.unwrap();
+        assert_eq!(rcode(resp), ResponseCode::NXDomain);
+
+        sqlx::query("UPDATE settings SET value = '[\"10.0.0.0/24\"]' WHERE key = 'ecs_trusted_forwarders'")
+            .execute(&handler.db)
+            .await
+            .unwrap();
+        handler.reload_ecs().await.unwrap();
+        let resp = handler.handle(forwarded("www.tiktok.com."), "10.0.0.53".to_string()).await.unwrap();
+        assert_eq!(rcode(resp), ResponseCode::NXDomain);
+    }
+
+    #[tokio::test]
+    async fn test_client_settings_inherit_by_group_priority() {
+        let handler = test_handler(&["||ads.example^"]).await;
+        let (upstream, _) = fake_ecs_upstream().await;
+        for (id, ip, settings) in [
+            ("kiosk", "10.1.0.1", None),
+            ("ceo", "10.1.0.2", Some(r#"{"blocking_mode":"refused","query_log_enabled":false}"#)),
+        ] {
+            sqlx::query(
+                "INSERT INTO clients (id, name, identifiers, upstreams, filter_enabled, settings, created_at, updated_at)
+                 VALUES (?, ?, ?, ?, 1, ?, datetime('now'), datetime('now'))"
+            )
+            .bind(id)
+            .bind(id)
+            .bind(serde_json::json!([ip]).to_string())
+            .bind(serde_json::json!([upstream.to_string()]).to_string())
+            .bind(settings)
+            .execute(&handler.db)
+            .await
+            .unwrap();
+        }
+        // The higher-priority group (lower number) wins where both set a field
+        for (id, priority, settings) in [
+            (1, 10, r#"{"blocking_mode":"nodata","rate_limit":100}"#),
+            (2, 1, r#"{"blocking_mode":"null_ip","safe_search":true}"#),
+        ] {
+            sqlx::query("INSERT INTO client_groups (id, name, priority, settings) VALUES (?, ?, ?, ?)")
+                .bind(id)
+                .bind(format!("group-{}", id))
+                .bind(priority)
+                .bind(settings)
+                .execute(&handler.db)
+                .await
+                .unwrap();
+            for client in ["kiosk", "ceo"] {
+                sqlx::query("INSERT INTO client_group_memberships (client_id, group_id) VALUES (?, ?)")
+                    .bind(client)
+                    .bind(id)
+                    .execute(&handler.db)
+                    .await
+                    .unwrap();
+            }
+        }
+        handler.reload_clients().await;
+        let mut log = handler.query_log_tx.subscribe();
+
+        let ask = |name: &'static str, ip: &'static str| {
+            let handler = &handler;
+            async move {
+                let bytes = handler.handle(query_bytes(name, RecordType::A), ip.to_string()).await.unwrap();
+                Message::from_vec(&bytes).unwrap()
+            }
+        };
+
+        let resp = ask("ads.example.", "10.1.0.1").await;
+        assert_eq!(resp.response_code(), ResponseCode::NoError);
+        assert_eq!(resp.answers()[0].data(), Some(&RData::A(A("0.0.0.0".parse().unwrap()))));
+        assert_eq!(log.recv().await.unwrap()["status"], "blocked");
+
+        let resp = ask("www.google.com.", "10.1.0.1").await;
+        let target = Name::from_str("forcesafesearch.google.com.").unwrap();
+        assert_eq!(resp.answers()[0].data(), Some(&RData::CNAME(CNAME(target))));
+        assert_eq!(resp.answers()[1].data(), Some(&RData::A(A("192.0.2.1".parse().unwrap()))));
+        assert_eq!(log.recv().await.unwrap()["reason"], "safe_search");
+
+        // Client overrides beat every group; nothing is logged for it
+        let resp = ask("ads.example.", "10.1.0.2").await;
+        assert_eq!(resp.response_code(), ResponseCode::Refused);
+        assert!(log.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_rate_limit_and_cache_bypass() {
+        let handler = test_handler(&[]).await;
+        let (upstream, mut sent) = fake_ecs_upstream().await;
+        sqlx::query(
+            "INSERT INTO clients (id, name, identifiers, upstreams, filter_enabled, settings, created_at, updated_at)
+             VALUES ('iot', 'IoT', '[\"10.2.0.0/24\"]', ?, 1, '{\"rate_limit\":3,\"cache_enabled\":false}', datetime('now'), datetime('now'))"
+        )
+        .bind(serde_json::json!([upstream.to_string()]).to_string())
+        .execute(&handler.db)
+        .await
+        .unwrap();
+        handler.reload_clients().await;
+
+        // The limit is shared by every address of the client
+        let mut rcodes = Vec::new();
+        for ip in ["10.2.0.1", "10.2.0.2", "10.2.0.1", "10.2.0.3"] {
+            let bytes = handler.handle(query_bytes("cdn.example.", RecordType::A), ip.to_string()).await.unwrap();
+            rcodes.push(Message::from_vec(&bytes).unwrap().response_code());
+        }
+        assert_eq!(rcodes[..3], [ResponseCode::NoError; 3]);
+        assert_eq!(rcodes[3], ResponseCode::Refused);
+
+        // With the cache off, every answered query went upstream
+        let mut upstream_queries = 0;
+        while sent.try_recv().is_ok() {
+            upstream_queries += 1;
+        }
+        assert_eq!(upstream_queries, 3);
+    }
+}
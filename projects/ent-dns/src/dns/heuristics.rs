@@ -0,0 +1,408 @@
+//! Suspicious-domain heuristics.
+//!
+//! A passive, in-process detection layer next to the rule lists.  Each query
+//! name is scored for three signals:
+//!
+//! * `dga` — the registrable label looks machine generated: high character
+//!   entropy, many bigrams that are rare in natural language, digits mixed
+//!   into letters.
+//! * `homograph` — a punycode label decodes to mixed Latin/Cyrillic/Greek
+//!   letters, or to a non-Latin label made only of Latin look-alikes.
+//! * `tunneling` — very long labels, or a parent domain receiving an unusual
+//!   number of distinct subdomains within a short window.
+//!
+//! Scores are in `0.0..=1.0`.  Detection is opt-in; when enabled, findings at
+//! or above `REPORT_THRESHOLD` are logged with reason `suspicious:<kind>`, and
+//! blocking above a configurable threshold is a further opt-in
+//! (`heuristics_*` settings).
+
+use dashmap::DashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Minimum score for a finding to be reported.
+pub const REPORT_THRESHOLD: f64 = 0.7;
+
+/// Labels shorter than this are too short to judge for DGA patterns.
+const DGA_MIN_LABEL_LEN: usize = 8;
+/// A label of this length scores 0 for tunneling; 63 (the DNS maximum) scores 1.
+const TUNNEL_LABEL_LEN_FLOOR: usize = 30;
+/// Distinct subdomains of one parent per window that score 1 for tunneling.
+const TUNNEL_CARDINALITY_LIMIT: usize = 300;
+const TUNNEL_WINDOW: Duration = Duration::from_secs(600);
+/// Upper bound on tracked parent domains; expired windows are pruned beyond it.
+const MAX_TRACKED_PARENTS: usize = 20_000;
+/// Minimum time between two prunes of the parent table.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The most frequent English letter bigrams.  Natural-language and brand names
+/// consist mostly of these; random strings mostly do not.
+const COMMON_BIGRAMS: &[&str] = &[
+    "th", "he", "in", "er", "an", "re", "on", "at", "en", "nd", "ti", "es", "or", "te", "of", "ed",
+    "is", "it", "al", "ar", "st", "to", "nt", "ng", "se", "ha", "as", "ou", "io", "le", "ve", "co",
+    "me", "de", "hi", "ri", "ro", "ic", "ne", "ea", "ra", "ce", "li", "ch", "ll", "be", "ma", "si",
+    "om", "ur", "ca", "el", "ta", "la", "ns", "di", "fo", "ho", "pe", "ec", "pr", "no", "ct", "us",
+    "ac", "ot", "il", "tr", "ly", "nc", "et", "ut", "ss", "so", "rs", "un", "lo", "wa", "ge", "ie",
+    "wh", "ee", "wi", "em", "ad", "ol", "rt", "po", "we", "na", "ul", "ni", "ts", "mo", "ow", "pa",
+    "im", "mi", "ai", "sh", "ir", "su", "id", "os", "iv", "ia", "am", "fi", "ci", "vi", "pl", "ig",
+    "tu", "ev", "ld", "ry", "mp", "fe", "bl", "ab", "gh", "ty", "op", "wo", "sa", "ay", "ex", "ke",
+    "fr", "oo", "av", "ag", "if", "ap", "gr", "od", "bo", "sp", "rd", "do", "uc", "bu", "ei", "ov",
+    "by", "rm", "ep", "tt", "oc", "fa", "ef", "cu", "rn", "sc", "gi", "da", "yo", "cr", "cl", "du",
+    "ga", "qu", "ue", "ff", "ba", "ey", "ls", "va", "um", "pp", "ua", "up", "lu", "go", "ht", "ru",
+    "ug", "ds", "lt", "pi", "rc", "rr", "eg", "au", "ck", "ew", "mu", "br", "bi", "pt", "ak", "pu",
+    "ok", "ft", "sk", "ki", "ob", "og", "ph", "ms", "ye", "ud", "mb", "ip", "ub", "oi", "rl",
+    "gu", "dr", "hr", "cc", "tw", "nu", "af", "hu", "nn", "eo", "vo", "rv", "nf", "xp", "gn", "sm",
+    "fl", "iz", "nk", "kn", "gs", "dy", "ks", "xt", "ze", "za", "zo", "ya", "ys", "ny",
+];
+
+/// Cyrillic and Greek letters that render like Latin letters.
+const LATIN_LOOKALIKES: &[char] = &[
+    'а', 'в', 'е', 'з', 'к', 'м', 'н', 'о', 'р', 'с', 'т', 'у', 'х', 'і', 'ј', 'ѕ', 'ԁ', 'ԛ', 'ԝ',
+    'ӏ', 'һ', 'ү', 'ɡ', 'α', 'β', 'ε', 'ι', 'κ', 'ν', 'ο', 'ρ', 'τ', 'υ', 'χ',
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SuspicionKind {
+    Dga,
+    Homograph,
+    Tunneling,
+}
+
+impl SuspicionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuspicionKind::Dga => "dga",
+            SuspicionKind::Homograph => "homograph",
+            SuspicionKind::Tunneling => "tunneling",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Finding {
+    pub kind: SuspicionKind,
+    pub score: f64,
+}
+
+impl Finding {
+    /// Query-log reason for this finding.
+    pub fn reason(&self) -> String {
+        format!("suspicious:{}", self.kind.as_str())
+    }
+}
+
+/// Settings controlling the detector (`heuristics_enabled`,
+/// `heuristics_block_enabled`, `heuristics_block_threshold`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeuristicsConfig {
+    pub enabled: bool,
+    pub block_enabled: bool,
+    pub block_threshold: f64,
+}
+
+impl Default for HeuristicsConfig {
+    fn default() -> Self {
+        Self { enabled: false, block_enabled: false, block_threshold: 0.9 }
+    }
+}
+
+impl HeuristicsConfig {
+    pub fn should_block(&self, finding: &Finding) -> bool {
+        self.block_enabled && finding.score >= self.block_threshold
+    }
+}
+
+struct ParentWindow {
+    started: Instant,
+    /// Hashes of the distinct subdomains seen in this window (capped).
+    seen: HashSet<u64>,
+}
+
+/// Scores query names; keeps per-parent subdomain cardinality for tunneling.
+pub struct SuspicionDetector {
+    parents: DashMap<String, ParentWindow>,
+    /// Reference point for `last_prune_ms`.
+    epoch: Instant,
+    /// When expired windows were last pruned, in ms since `epoch`.
+    last_prune_ms: AtomicU64,
+}
+
+impl Default for SuspicionDetector {
+    fn default() -> Self {
+        Self {
+            parents: DashMap::new(),
+            epoch: Instant::now(),
+            last_prune_ms: AtomicU64::new(0),
+        }
+    }
+}
+
+impl SuspicionDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Score `domain` (normalized, without trailing dot) and record it for the
+    /// cardinality check.  Returns the strongest finding at or above
+    /// `REPORT_THRESHOLD`.
+    pub fn analyze(&self, domain: &str) -> Option<Finding> {
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+        if domain.ends_with(".arpa") || !domain.contains('.') {
+            return None;
+        }
+        let labels: Vec<&str> = domain.split('.').collect();
+        let sld_index = registrable_index(&labels);
+
+        let mut findings = vec![
+            Finding { kind: SuspicionKind::Dga, score: dga_score(labels[sld_index]) },
+            Finding { kind: SuspicionKind::Homograph, score: homograph_score(&labels) },
+            Finding {
+                kind: SuspicionKind::Tunneling,
+                score: long_label_score(&labels[..sld_index])
+                    .max(self.cardinality_score(&labels, sld_index)),
+            },
+        ];
+        findings.retain(|f| f.score >= REPORT_THRESHOLD);
+        findings.into_iter().max_by(|a, b| a.score.total_cmp(&b.score))
+    }
+
+    /// Share of `TUNNEL_CARDINALITY_LIMIT` reached by the distinct subdomains
+    /// of this name's parent (registrable domain) in the current window.
+    fn cardinality_score(&self, labels: &[&str], sld_index: usize) -> f64 {
+        if sld_index == 0 {
+            return 0.0;
+        }
+        let parent = labels[sld_index..].join(".");
+        let mut hasher = DefaultHasher::new();
+        labels[..sld_index].hash(&mut hasher);
+        let sub_hash = hasher.finish();
+        let now = Instant::now();
+
+        if !self.parents.contains_key(&parent) && self.parents.len() >= MAX_TRACKED_PARENTS {
+            self.prune(now);
+            if self.parents.len() >= MAX_TRACKED_PARENTS {
+                return 0.0;
+            }
+        }
+
+        let mut window = self.parents.entry(parent).or_insert_with(|| ParentWindow {
+            started: now,
+            seen: HashSet::new(),
+        });
+        if now.duration_since(window.started) >= TUNNEL_WINDOW {
+            window.started = now;
+            window.seen.clear();
+        }
+        if window.seen.len() < TUNNEL_CARDINALITY_LIMIT {
+            window.seen.insert(sub_hash);
+        }
+        window.seen.len() as f64 / TUNNEL_CARDINALITY_LIMIT as f64
+    }
+
+    /// Drop expired parent windows, at most once per `PRUNE_INTERVAL` so a
+    /// full table of live parents does not turn every new parent into a scan.
+    fn prune(&self, now: Instant) {
+        let now_ms = now.duration_since(self.epoch).as_millis() as u64;
+        let last = self.last_prune_ms.load(Ordering::Relaxed);
+        if now_ms.saturating_sub(last) < PRUNE_INTERVAL.as_millis() as u64
+            || self.last_prune_ms.compare_exchange(last, now_ms, Ordering::Relaxed, Ordering::Relaxed).is_err()
+        {
+            return;
+        }
+        self.parents.retain(|_, w| now.duration_since(w.started) < TUNNEL_WINDOW);
+    }
+}
+
+/// Index of the registrable (second-level) label.  Short second-level labels
+/// under a ccTLD (`co.uk`, `com.au`) are treated as part of the suffix.
+fn registrable_index(labels: &[&str]) -> usize {
+    let n = labels.len();
+    if n >= 3 && labels[n - 1].len() == 2 && labels[n - 2].len() <= 3 {
+        n - 3
+    } else {
+        n.saturating_sub(2)
+    }
+}
+
+/// Shannon entropy of `s` in bits per character.
+fn entropy(s: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in s.bytes() {
+        counts[b as usize] += 1;
+    }
+    let len = s.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Linear map of `value` from `[low, high]` onto `[0, 1]`.
+fn scale(value: f64, low: f64, high: f64) -> f64 {
+    ((value - low) / (high - low)).clamp(0.0, 1.0)
+}
+
+fn dga_score(label: &str) -> f64 {
+    if label.len() < DGA_MIN_LABEL_LEN || label.starts_with("xn--") {
+        return 0.0;
+    }
+    let bytes = label.as_bytes();
+
+    let letter_pairs: Vec<&[u8]> = bytes
+        .windows(2)
+        .filter(|w| w[0].is_ascii_lowercase() && w[1].is_ascii_lowercase())
+        .collect();
+    let rare = if letter_pairs.is_empty() {
+        1.0
+    } else {
+        let uncommon = letter_pairs
+            .iter()
+            .filter(|w| !COMMON_BIGRAMS.iter().any(|b| b.as_bytes() == **w))
+            .count();
+        uncommon as f64 / letter_pairs.len() as f64
+    };
+
+    let digits = bytes.iter().filter(|b| b.is_ascii_digit()).count();
+    let letters = bytes.iter().filter(|b| b.is_ascii_lowercase()).count();
+    let digit_mix = if digits > 0 && letters > 0 {
+        scale(digits as f64 / label.len() as f64, 0.1, 0.4)
+    } else {
+        0.0
+    };
+
+    // Short labels cannot reach the entropy of long ones; compare against the
+    // maximum possible for this length.
+    let max_entropy = (label.len().min(36) as f64).log2();
+    let relative_entropy = scale(entropy(label) / max_entropy, 0.75, 0.95);
+
+    let length_factor = scale(label.len() as f64, 6.0, 12.0);
+    let score = 0.6 * scale(rare, 0.25, 0.6) + 0.25 * relative_entropy + 0.15 * digit_mix;
+    score * (0.5 + 0.5 * length_factor)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Other,
+}
+
+fn script_of(c: char) -> Option<Script> {
+    if !c.is_alphabetic() {
+        return None;
+    }
+    Some(match c as u32 {
+        0x0041..=0x024F => Script::Latin,
+        0x0370..=0x03FF => Script::Greek,
+        0x0400..=0x052F => Script::Cyrillic,
+        0x1D00..=0x1DBF | 0x1E00..=0x1EFF => Script::Latin,
+        // IPA extensions contain ɡ, a common Latin look-alike
+        0x0250..=0x02AF => Script::Latin,
+        _ => Script::Other,
+    })
+}
+
+fn homograph_score(labels: &[&str]) -> f64 {
+    labels
+        .iter()
+        .filter_map(|label| label.strip_prefix("xn--"))
+        .filter_map(idna::punycode::decode_to_string)
+        .map(|decoded| {
+            let scripts: Vec<Script> = decoded.chars().filter_map(script_of).collect();
+            let has = |s: Script| scripts.contains(&s);
+            let confusable = has(Script::Cyrillic) || has(Script::Greek);
+            if has(Script::Latin) && confusable {
+                1.0
+            } else if confusable
+                && !has(Script::Other)
+                && decoded.chars().filter(|c| c.is_alphabetic()).all(|c| LATIN_LOOKALIKES.contains(&c))
+            {
+                // Whole-script spoof such as Cyrillic "аррӏе"
+                0.9
+            } else {
+                0.0
+            }
+        })
+        .fold(0.0, f64::max)
+}
+
+fn long_label_score(subdomain_labels: &[&str]) -> f64 {
+    let longest = subdomain_labels.iter().map(|l| l.len()).max().unwrap_or(0);
+    scale(longest as f64, TUNNEL_LABEL_LEN_FLOOR as f64, 63.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dga_scores() {
+        for name in ["google.com", "facebook.com", "wikipedia.org", "stackoverflow.com", "microsoftonline.com", "bbc.co.uk", "weather.gov", "cloudflare-dns.com"] {
+            assert!(dga_score(name.split('.').next().unwrap()) < REPORT_THRESHOLD, "{} flagged", name);
+        }
+        let detector = SuspicionDetector::new();
+        for name in ["xjw3kq9vzt7p.com", "qxvbnmzkwrtpl.net", "kq8d7fj2nx0wbv.info", "zzxqjkvwpfgh.ru"] {
+            let finding = detector.analyze(name).unwrap_or_else(|| panic!("{} not flagged", name));
+            assert_eq!(finding.kind, SuspicionKind::Dga);
+        }
+    }
+
+    #[test]
+    fn test_homographs() {
+        let detector = SuspicionDetector::new();
+        // "pаypal" with a Cyrillic а
+        let mixed = format!("xn--{}.com", idna::punycode::encode_str("pаypal").unwrap());
+        assert_eq!(detector.analyze(&mixed).map(|f| f.kind), Some(SuspicionKind::Homograph));
+        // Whole-script Cyrillic "аррӏе"
+        let whole = format!("xn--{}.com", idna::punycode::encode_str("аррӏе").unwrap());
+        assert_eq!(detector.analyze(&whole).map(|f| f.kind), Some(SuspicionKind::Homograph));
+        // Legitimate IDNs
+        assert!(detector.analyze("xn--mnchen-3ya.de").is_none()); // münchen
+        assert!(detector.analyze("xn--e1afmkfd.xn--p1ai").is_none()); // пример.рф
+    }
+
+    #[test]
+    fn test_tunneling() {
+        let detector = SuspicionDetector::new();
+        let long = format!("{}.t.example.com", "a1b2c3d4e5".repeat(6));
+        assert_eq!(detector.analyze(&long).map(|f| f.kind), Some(SuspicionKind::Tunneling));
+
+        let mut last = None;
+        for i in 0..TUNNEL_CARDINALITY_LIMIT {
+            last = detector.analyze(&format!("s{}.exfil.example.net", i));
+        }
+        let finding = last.unwrap();
+        assert_eq!(finding.kind, SuspicionKind::Tunneling);
+        assert!(finding.score >= 1.0);
+        assert!(detector.analyze("www.example.org").is_none());
+        assert!(detector.analyze("4.3.2.1.in-addr.arpa").is_none());
+    }
+
+    #[test]
+    fn test_parent_prune_once_per_interval() {
+        let detector = SuspicionDetector::new();
+        let start = Instant::now();
+        let expired = start + TUNNEL_WINDOW;
+        let window = || ParentWindow { started: start, seen: HashSet::new() };
+        for i in 0..MAX_TRACKED_PARENTS {
+            detector.parents.insert(format!("p{}.example", i), window());
+        }
+        detector.prune(expired);
+        assert!(detector.parents.is_empty());
+
+        // Within the same interval the next prune is skipped
+        detector.parents.insert("idle.example".to_string(), window());
+        detector.prune(expired + Duration::from_millis(500));
+        assert_eq!(detector.parents.len(), 1);
+        detector.prune(expired + PRUNE_INTERVAL);
+        assert!(detector.parents.is_empty());
+    }
+}
@@ -0,0 +1,509 @@
+//! Filter list formats.
+//!
+//! Every supported format is translated into rule lines understood by
+//! [`RuleSet`](super::rules::RuleSet):
+//!
+//! | format    | example                                   |
+//! |-----------|-------------------------------------------|
+//! | `adguard` | `\|\|ads.example^`, `@@\|\|ok.example^`   |
+//! | `hosts`   | `0.0.0.0 ads.example`                     |
+//! | `domains` | `ads.example`                             |
+//! | `dnsmasq` | `address=/ads.example/0.0.0.0`            |
+//! | `unbound` | `local-zone: "ads.example." always_nxdomain` |
+//! | `rpz`     | `ads.example CNAME .`                     |
+//!
+//! RPZ actions map to rules as follows: NXDOMAIN (`CNAME .`) and DROP block,
+//! NODATA (`CNAME *.`) blocks with an empty answer (`$dnsrewrite=NOERROR`),
+//! PASSTHRU becomes an allow rule and A/AAAA local data becomes
+//! `$dnsrewrite=<address>`.  RPZ triggers are matched like AdGuard rules, i.e.
+//! a name also covers its subdomains.
+
+use regex::Regex;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::LazyLock;
+
+/// Scanned lines when detecting a format.
+const DETECT_SAMPLE_LINES: usize = 200;
+
+/// AdGuard rule patterns
+static ADGUARD_DOMAIN_RULE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\|\|([a-zA-Z0-9][a-zA-Z0-9_.-]*[a-zA-Z0-9])\^?$").expect("Invalid regex")
+});
+
+static ADGUARD_EXCEPTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^@@\|\|([a-zA-Z0-9][a-zA-Z0-9_.-]*[a-zA-Z0-9])\^?$").expect("Invalid regex")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    /// Detect from content (the default).
+    Auto,
+    Adguard,
+    Hosts,
+    Domains,
+    Dnsmasq,
+    Unbound,
+    Rpz,
+}
+
+pub const FORMAT_NAMES: &[&str] = &["auto", "adguard", "hosts", "domains", "dnsmasq", "unbound", "rpz"];
+
+impl ListFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "auto" => ListFormat::Auto,
+            "adguard" => ListFormat::Adguard,
+            "hosts" => ListFormat::Hosts,
+            "domains" => ListFormat::Domains,
+            "dnsmasq" => ListFormat::Dnsmasq,
+            "unbound" => ListFormat::Unbound,
+            "rpz" => ListFormat::Rpz,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListFormat::Auto => "auto",
+            ListFormat::Adguard => "adguard",
+            ListFormat::Hosts => "hosts",
+            ListFormat::Domains => "domains",
+            ListFormat::Dnsmasq => "dnsmasq",
+            ListFormat::Unbound => "unbound",
+            ListFormat::Rpz => "rpz",
+        }
+    }
+}
+
+/// Result of parsing a list.
+#[derive(Debug, Default)]
+pub struct ParsedList {
+    /// Rule lines in RuleSet syntax, deduplicated, in source order.
+    pub rules: Vec<String>,
+    /// Entries that were recognised but cannot be represented (e.g. RPZ IP
+    /// triggers, CNAME local data).
+    pub unsupported: usize,
+    /// Refresh interval announced by the list (`! Expires: 4 days`).
+    pub expires_hours: Option<i64>,
+    seen: HashSet<String>,
+}
+
+impl ParsedList {
+    fn push(&mut self, rule: String) {
+        if self.seen.insert(rule.clone()) {
+            self.rules.push(rule);
+        }
+    }
+
+    /// Push a rule, or count the entry as unsupported.
+    fn push_or_skip(&mut self, rule: Option<String>) {
+        match rule {
+            Some(rule) => self.push(rule),
+            None => self.unsupported += 1,
+        }
+    }
+}
+
+/// Parse `content` in `format`, detecting the format first for `Auto`.
+/// Returns the format actually used.
+pub fn parse(content: &str, format: ListFormat) -> (ListFormat, ParsedList) {
+    let mut parser = ListParser::new(format);
+    for line in content.lines() {
+        parser.push_line(line);
+    }
+    parser.finish()
+}
+
+/// Incremental parser fed one line at a time, so a download can be parsed
+/// while it streams.  With `Auto`, the first significant lines are buffered
+/// until the format can be detected.
+pub struct ListParser {
+    format: ListFormat,
+    pending: Vec<String>,
+    significant: usize,
+    rpz: RpzState,
+    parsed: ParsedList,
+}
+
+/// Zone-file state carried between RPZ lines.
+#[derive(Default)]
+struct RpzState {
+    origin: String,
+    in_parens: bool,
+    last_owner: Option<String>,
+}
+
+impl ListParser {
+    pub fn new(format: ListFormat) -> Self {
+        Self {
+            format,
+            pending: Vec::new(),
+            significant: 0,
+            rpz: RpzState::default(),
+            parsed: ParsedList::default(),
+        }
+    }
+
+    pub fn push_line(&mut self, line: &str) {
+        if let Some(hours) = parse_expires(line) {
+            self.parsed.expires_hours.get_or_insert(hours);
+        }
+        if self.format != ListFormat::Auto {
+            self.parse_line(line);
+            return;
+        }
+        if is_significant(line) {
+            self.significant += 1;
+        }
+        self.pending.push(line.to_string());
+        if self.significant >= DETECT_SAMPLE_LINES {
+            self.resolve_format();
+        }
+    }
+
+    /// Finish parsing; returns the format used and the parsed rules.
+    pub fn finish(mut self) -> (ListFormat, ParsedList) {
+        if self.format == ListFormat::Auto {
+            self.resolve_format();
+        }
+        (self.format, self.parsed)
+    }
+
+    fn resolve_format(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        self.format = detect_format(&pending.join("\n"));
+        for line in &pending {
+            self.parse_line(line);
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) {
+        match self.format {
+            ListFormat::Hosts => {
+                if let Some(rule) = hosts_line(line) {
+                    self.parsed.push(rule);
+                }
+            }
+            ListFormat::Domains => self.domains_line(line),
+            ListFormat::Dnsmasq => self.dnsmasq_line(line),
+            ListFormat::Unbound => self.unbound_line(line),
+            ListFormat::Rpz => self.rpz_line(line),
+            ListFormat::Adguard | ListFormat::Auto => {
+                if let Some(rule) = adguard_line(line) {
+                    self.parsed.push(rule);
+                }
+            }
+        }
+    }
+}
+
+fn is_significant(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && !line.starts_with('#') && !line.starts_with('!') && !line.starts_with(';')
+}
+
+/// `! Expires: 4 days` / `# Expires: 12 hours` → hours (at least 1).
+fn parse_expires(line: &str) -> Option<i64> {
+    let rest = line.trim().strip_prefix(['!', '#'])?.trim();
+    let value = rest.strip_prefix("Expires:").or_else(|| rest.strip_prefix("expires:"))?;
+    let mut words = value.split_whitespace();
+    let n: i64 = words.next()?.parse().ok()?;
+    let unit = words.next().unwrap_or("days").to_lowercase();
+    let hours = if unit.starts_with("hour") { n } else if unit.starts_with("day") { n * 24 } else { return None };
+    Some(hours.max(1))
+}
+
+/// Guess the format of a list from its first significant lines.
+pub fn detect_format(content: &str) -> ListFormat {
+    let (mut rpz, mut dnsmasq, mut unbound, mut hosts, mut adguard, mut domains) = (0, 0, 0, 0, 0, 0);
+
+    let lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with('!') && !l.starts_with(';'))
+        .take(DETECT_SAMPLE_LINES);
+    for line in lines {
+        if line.starts_with("$ORIGIN") || line.starts_with("$TTL") || line.contains(" SOA ") {
+            return ListFormat::Rpz;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if line.starts_with("address=/") || line.starts_with("server=/") || line.starts_with("local=/") {
+            dnsmasq += 1;
+        } else if line.starts_with("local-zone:") || line.starts_with("local-data:") || line == "server:" {
+            unbound += 1;
+        } else if fields.len() >= 2 && fields[0].parse::<IpAddr>().is_ok() {
+            hosts += 1;
+        } else if fields.iter().any(|f| f.eq_ignore_ascii_case("CNAME") || f.eq_ignore_ascii_case("A")) && fields.len() >= 3 {
+            rpz += 1;
+        } else if line.starts_with("||") || line.starts_with("@@") || line.starts_with('|') || line.contains('^') {
+            adguard += 1;
+        } else if fields.len() == 1 && clean_domain(line.split('#').next().unwrap_or("")).is_some() {
+            domains += 1;
+        }
+    }
+
+    // Plain domains are also valid AdGuard rules; prefer AdGuard when any
+    // AdGuard-specific syntax is present
+    let candidates = [
+        (rpz, ListFormat::Rpz),
+        (dnsmasq, ListFormat::Dnsmasq),
+        (unbound, ListFormat::Unbound),
+        (hosts, ListFormat::Hosts),
+        (adguard + if adguard > 0 { domains } else { 0 }, ListFormat::Adguard),
+        (if adguard > 0 { 0 } else { domains }, ListFormat::Domains),
+    ];
+    candidates
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .max_by_key(|(count, _)| *count)
+        .map(|(_, format)| *format)
+        .unwrap_or(ListFormat::Adguard)
+}
+
+/// Lowercased domain without trailing dot, or None if `s` is not a domain name.
+fn clean_domain(s: &str) -> Option<String> {
+    let d = s.trim().trim_matches('"').trim_end_matches('.').to_lowercase();
+    let valid = d.contains('.')
+        && d.len() <= 253
+        && d.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+    valid.then_some(d)
+}
+
+fn is_null_address(ip: &IpAddr) -> bool {
+    ip.is_unspecified()
+}
+
+/// Block rule for a null/sinkhole address, local data otherwise.
+fn address_rule(domain: &str, ip: IpAddr) -> String {
+    if is_null_address(&ip) {
+        format!("||{}^", domain)
+    } else {
+        format!("||{}^$dnsrewrite={}", domain, ip)
+    }
+}
+
+/// Parse AdGuard filter rules from content
+pub fn parse_adguard_rules(content: &str) -> (Vec<String>, Vec<String>) {
+    content
+        .lines()
+        .filter_map(adguard_line)
+        .partition(|rule| !rule.starts_with("@@"))
+}
+
+/// One AdGuard line → block or allow rule.
+fn adguard_line(line: &str) -> Option<String> {
+    let line = line.trim();
+
+    // Skip empty lines and comments
+    if line.is_empty() || line.starts_with('!') || line.starts_with('#') {
+        return None;
+    }
+
+    // Skip CSS selectors and script rules
+    if line.contains("##") || line.contains("#@#") || line.contains("#%#") {
+        return None;
+    }
+
+    // Skip regex rules (too complex for now)
+    if line.starts_with('/') && line.ends_with('/') {
+        return None;
+    }
+
+    // Parse exception rules (@@||domain^)
+    if let Some(caps) = ADGUARD_EXCEPTION.captures(line) {
+        // Bug fix: append `^` so the rule matches AdGuard syntax expected by RuleSet
+        return caps.get(1).map(|domain| format!("@@||{}^", domain.as_str()));
+    }
+
+    // Parse blocking rules (||domain^ or ||domain)
+    if let Some(caps) = ADGUARD_DOMAIN_RULE.captures(line) {
+        return caps.get(1).map(|domain| format!("||{}^", domain.as_str()));
+    }
+
+    // Simple domain blocking (domain without special chars)
+    if !line.contains(['/', ':', '*', '^', '|'])
+        && line.contains('.')
+        && !line.starts_with('.')
+        && !line.ends_with('.')
+    {
+        return Some(format!("||{}^", line));
+    }
+    None
+}
+
+/// Parse hosts file format rules
+pub fn parse_hosts_rules(content: &str) -> Vec<String> {
+    content.lines().filter_map(hosts_line).collect()
+}
+
+/// One hosts line ("IP domain") → block rule.
+fn hosts_line(line: &str) -> Option<String> {
+    let line = line.trim();
+
+    // Skip empty lines and comments
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let domain = parts.get(1)?;
+    // Validate domain format
+    let valid = domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && domain.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '-' || c == '_');
+    // Create AdGuard-style blocking rule
+    valid.then(|| format!("||{}^", domain))
+}
+
+impl ListParser {
+    /// One domain per line; `#` and `!` start comments, `*.` prefixes are accepted.
+    fn domains_line(&mut self, line: &str) {
+        let line = line.split(['#', '!']).next().unwrap_or("").trim();
+        if line.is_empty() {
+            return;
+        }
+        let rule = clean_domain(line.strip_prefix("*.").unwrap_or(line)).map(|d| format!("||{}^", d));
+        self.parsed.push_or_skip(rule);
+    }
+
+    /// dnsmasq `address=/d1/d2/target`, `server=/d/[upstream]` and `local=/d/`.
+    fn dnsmasq_line(&mut self, line: &str) {
+        let line = line.trim();
+        let Some((directive, rest)) = line.split_once('=') else {
+            return;
+        };
+        let Some(rest) = rest.strip_prefix('/') else {
+            return;
+        };
+        // "/d1/d2/target": everything before the last slash is a domain
+        let (domains, target) = rest.rsplit_once('/').unwrap_or((rest, ""));
+        let target = target.trim();
+        for domain in domains.split('/') {
+            let rule = clean_domain(domain).and_then(|domain| match (directive.trim(), target) {
+                // No address: answered locally with NXDOMAIN; `#`: null address
+                ("address", "" | "#") | ("local", "") | ("server", "") => Some(format!("||{}^", domain)),
+                // Forward to the default upstream: an explicit pass
+                ("server", "#") => Some(format!("@@||{}^", domain)),
+                ("address", addr) => addr.parse().ok().map(|ip| address_rule(&domain, ip)),
+                // Forwarding to a specific upstream is not a filtering decision
+                _ => None,
+            });
+            self.parsed.push_or_skip(rule);
+        }
+    }
+
+    /// Unbound `local-zone:` and `local-data:` statements.
+    fn unbound_line(&mut self, line: &str) {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if let Some(rest) = line.strip_prefix("local-zone:") {
+            let mut fields = rest.split_whitespace();
+            let (Some(domain), Some(kind)) = (fields.next().and_then(clean_domain), fields.next()) else {
+                self.parsed.unsupported += 1;
+                return;
+            };
+            let rule = match kind {
+                "deny" | "refuse" | "static" | "redirect" | "always_refuse" | "always_nxdomain"
+                | "always_null" | "always_deny" | "inform_deny" => format!("||{}^", domain),
+                "always_nodata" => format!("||{}^$dnsrewrite=NOERROR", domain),
+                "always_transparent" => format!("@@||{}^", domain),
+                // transparent, typetransparent, inform, nodefault: normal resolution
+                _ => return,
+            };
+            self.parsed.push(rule);
+        } else if let Some(rest) = line.strip_prefix("local-data:") {
+            let record = rest.trim().trim_matches('"');
+            let rule = parse_address_record(record).map(|(domain, ip)| address_rule(&domain, ip));
+            self.parsed.push_or_skip(rule);
+        }
+    }
+
+    /// RPZ zone file line (QNAME triggers).
+    fn rpz_line(&mut self, raw: &str) {
+        let state = &mut self.rpz;
+        let line = raw.split(';').next().unwrap_or("");
+        // Multi-line records (SOA) are wrapped in parentheses
+        if state.in_parens {
+            if line.contains(')') {
+                state.in_parens = false;
+            }
+            return;
+        }
+        if line.contains('(') && !line.contains(')') {
+            state.in_parens = true;
+            return;
+        }
+        if line.trim().is_empty() {
+            return;
+        }
+        if let Some(rest) = line.trim().strip_prefix("$ORIGIN") {
+            state.origin = rest.trim().trim_end_matches('.').to_lowercase();
+            return;
+        }
+        if line.trim().starts_with('$') {
+            return;
+        }
+
+        // A record starting with whitespace reuses the previous owner
+        let mut fields: Vec<&str> = line.split_whitespace().collect();
+        let owner = if line.starts_with([' ', '\t']) {
+            state.last_owner.clone()
+        } else {
+            let owner = fields.remove(0).to_string();
+            state.last_owner = Some(owner.clone());
+            Some(owner)
+        };
+        let Some(owner) = owner else { return };
+
+        // Skip TTL and class to reach the record type
+        while let Some(f) = fields.first() {
+            if f.parse::<u32>().is_ok() || f.eq_ignore_ascii_case("IN") {
+                fields.remove(0);
+            } else {
+                break;
+            }
+        }
+        let (Some(rtype), Some(rdata)) = (fields.first(), fields.get(1)) else {
+            return;
+        };
+        let rtype = rtype.to_ascii_uppercase();
+        if matches!(rtype.as_str(), "SOA" | "NS") || owner == "@" {
+            return;
+        }
+
+        // Trigger name: absolute names lose the zone origin
+        let mut name = owner.to_lowercase();
+        if let Some(abs) = name.strip_suffix('.') {
+            name = abs.strip_suffix(&format!(".{}", state.origin)).unwrap_or(abs).to_string();
+        }
+        let ip_trigger = [".rpz-ip", ".rpz-nsip", ".rpz-nsdname", ".rpz-client-ip"]
+            .iter()
+            .any(|suffix| name.ends_with(suffix));
+        let domain = if ip_trigger { None } else { clean_domain(name.strip_prefix("*.").unwrap_or(&name)) };
+
+        let rule = domain.and_then(|domain| match (rtype.as_str(), rdata.to_lowercase().as_str()) {
+            ("CNAME", ".") | ("CNAME", "rpz-drop.") => Some(format!("||{}^", domain)),
+            ("CNAME", "*.") => Some(format!("||{}^$dnsrewrite=NOERROR", domain)),
+            ("CNAME", "rpz-passthru.") => Some(format!("@@||{}^", domain)),
+            ("A" | "AAAA", addr) => addr.parse().ok().map(|ip| address_rule(&domain, ip)),
+            // rpz-tcp-only, CNAME local data and other record types
+            _ => None,
+        });
+        self.parsed.push_or_skip(rule);
+    }
+}
+
+/// `name [ttl] [class] A|AAAA address` → (name, address).
+fn parse_address_record(record: &str) -> Option<(String, IpAddr)> {
+    let fields: Vec<&str> = record.split_whitespace().collect();
+    let type_index = fields
+        .iter()
+        .position(|f| f.eq_ignore_ascii_case("A") || f.eq_ignore_ascii_case("AAAA"))?;
+    let ip = fields.get(type_index + 1)?.parse().ok()?;
+    Some((clean_domain(fields.first()?)?, ip))
+}
@@ -0,0 +1,234 @@
+//! Filter list versions.
+//!
+//! Every sync that changes a list's rules stores the complete rule set as a
+//! version: sorted, with a SHA-256 content hash and the number of rules added
+//! and removed relative to the previous version.  The newest
+//! `VERSIONS_PER_LIST` versions are kept, plus the pinned one.
+//!
+//! Pinning a list to a version makes that version's rules active until the
+//! list is unpinned; syncs meanwhile keep recording new versions without
+//! applying them.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+
+use crate::db::DbPool;
+use super::subscription;
+
+/// Versions kept per list (the pinned version is kept in addition).
+pub const VERSIONS_PER_LIST: i64 = 10;
+
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct VersionInfo {
+    pub id: i64,
+    pub created_at: String,
+    pub hash: String,
+    pub rule_count: i64,
+    pub added: i64,
+    pub removed: i64,
+}
+
+/// Rules added and removed going from one version to another.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Diff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Sorted, deduplicated copy of `rules`.
+fn normalize(rules: &[String]) -> Vec<String> {
+    let mut rules = rules.to_vec();
+    rules.sort_unstable();
+    rules.dedup();
+    rules
+}
+
+/// Hex SHA-256 of a normalized rule set.
+fn content_hash(sorted: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for rule in sorted {
+        hasher.update(rule.as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Diff of two sorted rule sets.
+pub fn diff(base: &[String], target: &[String]) -> Diff {
+    let mut out = Diff::default();
+    let (mut i, mut j) = (0, 0);
+    while i < base.len() || j < target.len() {
+        let order = match (base.get(i), target.get(j)) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+        match order {
+            Ordering::Less => {
+                out.removed.push(base[i].clone());
+                i += 1;
+            }
+            Ordering::Greater => {
+                out.added.push(target[j].clone());
+                j += 1;
+            }
+            Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out
+}
+
+fn split_rules(stored: &str) -> Vec<String> {
+    stored.lines().map(str::to_string).collect()
+}
+
+/// Store `rules` as a new version unless they equal the latest one.
+/// Returns the id of the version holding these rules.
+pub async fn record_version(pool: &DbPool, filter_id: &str, rules: &[String]) -> Result<i64> {
+    let sorted = normalize(rules);
+    let hash = content_hash(&sorted);
+
+    let latest: Option<(i64, String, String)> = sqlx::query_as(
+        "SELECT id, hash, rules FROM filter_list_versions WHERE filter_id = ? ORDER BY id DESC LIMIT 1"
+    )
+    .bind(filter_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to read latest list version")?;
+
+    let changes = match latest {
+        Some((id, latest_hash, _)) if latest_hash == hash => return Ok(id),
+        Some((_, _, latest_rules)) => diff(&split_rules(&latest_rules), &sorted),
+        None => Diff { added: sorted.clone(), removed: Vec::new() },
+    };
+
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO filter_list_versions (filter_id, created_at, hash, rule_count, added, removed, rules)
+         VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING id"
+    )
+    .bind(filter_id)
+    .bind(Utc::now().to_rfc3339())
+    .bind(&hash)
+    .bind(sorted.len() as i64)
+    .bind(changes.added.len() as i64)
+    .bind(changes.removed.len() as i64)
+    .bind(sorted.join("\n"))
+    .fetch_one(pool)
+    .await
+    .context("Failed to store list version")?;
+
+    sqlx::query(
+        "DELETE FROM filter_list_versions
+         WHERE filter_id = ?
+           AND id NOT IN (SELECT id FROM filter_list_versions WHERE filter_id = ? ORDER BY id DESC LIMIT ?)
+           AND id IS NOT (SELECT pinned_version FROM filter_lists WHERE id = ?)"
+    )
+    .bind(filter_id)
+    .bind(filter_id)
+    .bind(VERSIONS_PER_LIST)
+    .bind(filter_id)
+    .execute(pool)
+    .await
+    .context("Failed to prune list versions")?;
+
+    Ok(id)
+}
+
+/// Versions of a list, newest first.
+pub async fn list_versions(pool: &DbPool, filter_id: &str) -> Result<Vec<VersionInfo>> {
+    Ok(sqlx::query_as(
+        "SELECT id, created_at, hash, rule_count, added, removed
+         FROM filter_list_versions WHERE filter_id = ? ORDER BY id DESC"
+    )
+    .bind(filter_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Sorted rules of a version, or None if the list has no such version.
+pub async fn version_rules(pool: &DbPool, filter_id: &str, version_id: i64) -> Result<Option<Vec<String>>> {
+    let stored: Option<String> = sqlx::query_scalar(
+        "SELECT rules FROM filter_list_versions WHERE filter_id = ? AND id = ?"
+    )
+    .bind(filter_id)
+    .bind(version_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(stored.as_deref().map(split_rules))
+}
+
+/// The version recorded before `version_id`, if still kept.
+pub async fn previous_version(pool: &DbPool, filter_id: &str, version_id: i64) -> Result<Option<i64>> {
+    Ok(sqlx::query_scalar(
+        "SELECT id FROM filter_list_versions WHERE filter_id = ? AND id < ? ORDER BY id DESC LIMIT 1"
+    )
+    .bind(filter_id)
+    .bind(version_id)
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Make a version's rules active and keep them until unpinned.  Returns the
+/// rule count, or None if the list has no such version.
+pub async fn pin(pool: &DbPool, filter_id: &str, version_id: i64) -> Result<Option<i64>> {
+    let Some(rules) = version_rules(pool, filter_id, version_id).await? else {
+        return Ok(None);
+    };
+    let count = subscription::replace_rules(pool, filter_id, &rules).await?;
+    sqlx::query("UPDATE filter_lists SET pinned_version = ? WHERE id = ?")
+        .bind(version_id)
+        .bind(filter_id)
+        .execute(pool)
+        .await?;
+    tracing::info!("Filter {} pinned to version {} ({} rules)", filter_id, version_id, count);
+    Ok(Some(count))
+}
+
+/// Unpin a list and activate its latest version.  Returns the rule count.
+pub async fn unpin(pool: &DbPool, filter_id: &str) -> Result<i64> {
+    sqlx::query("UPDATE filter_lists SET pinned_version = NULL WHERE id = ?")
+        .bind(filter_id)
+        .execute(pool)
+        .await?;
+    let latest: Option<String> = sqlx::query_scalar(
+        "SELECT rules FROM filter_list_versions WHERE filter_id = ? ORDER BY id DESC LIMIT 1"
+    )
+    .bind(filter_id)
+    .fetch_optional(pool)
+    .await?;
+    match latest {
+        Some(rules) => subscription::replace_rules(pool, filter_id, &split_rules(&rules)).await,
+        None => Ok(sqlx::query_scalar("SELECT rule_count FROM filter_lists WHERE id = ?")
+            .bind(filter_id)
+            .fetch_one(pool)
+            .await?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(items: &[&str]) -> Vec<String> {
+        normalize(&items.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_diff_and_hash() {
+        let old = rules(&["||b.example^", "||a.example^", "||c.example^"]);
+        let new = rules(&["||c.example^", "||d.example^", "||a.example^"]);
+        let d = diff(&old, &new);
+        assert_eq!(d.added, vec!["||d.example^"]);
+        assert_eq!(d.removed, vec!["||b.example^"]);
+
+        // Order-insensitive hash
+        assert_eq!(content_hash(&rules(&["||x^", "||y^"])), content_hash(&rules(&["||y^", "||x^", "||y^"])));
+        assert_ne!(content_hash(&old), content_hash(&new));
+    }
+}
@@ -4,6 +4,7 @@ use tokio::sync::broadcast;
 use crate::config::Config;
 use crate::db::DbPool;
 use crate::metrics::DnsMetrics;
+use crate::proxy_protocol::TrustedProxies;
 use filter::FilterEngine;
 
 pub mod server;
@@ -14,6 +15,23 @@ pub mod rules;
 pub mod cache;
 pub mod acl;
 pub mod subscription;
+pub mod categories;
+pub mod services;
+pub mod schedule;
+pub mod rebind;
+pub mod qtype_policy;
+pub mod heuristics;
+pub mod threat;
+pub mod list_formats;
+pub mod list_versions;
+pub mod group_rules;
+pub mod client_index;
+pub mod neighbors;
+pub mod tls;
+pub mod ecs;
+pub mod runtime_clients;
+pub mod client_settings;
+pub mod safe_search;
 
 pub use handler::DnsHandler;
 
@@ -29,9 +47,33 @@ pub async fn build_handler(
     Ok(Arc::new(DnsHandler::new(cfg.clone(), db, filter, metrics, query_log_tx).await?))
 }
 
-/// Start the DNS server (UDP + TCP) using a previously built handler.
+/// Start the DNS server (UDP + TCP, and DoT when enabled) using a previously
+/// built handler.
 pub async fn serve(handler: Arc<DnsHandler>, cfg: &Config) -> Result<()> {
+    let proxies = Arc::new(TrustedProxies::parse(&cfg.dns.proxy_protocol_from)?);
+    if !proxies.is_empty() {
+        tracing::info!("Accepting PROXY protocol headers from {:?}", cfg.dns.proxy_protocol_from);
+    }
+
+    if cfg.dns.dot_enabled {
+        let (Some(cert), Some(key)) = (&cfg.dns.tls_cert_path, &cfg.dns.tls_key_path) else {
+            anyhow::bail!("dns.dot_enabled requires dns.tls_cert_path and dns.tls_key_path");
+        };
+        let tls_config = tls::load_tls_config(cert, key)?;
+        let dot_addr = format!("{}:{}", cfg.dns.bind, cfg.dns.dot_port);
+        let listener = tokio::net::TcpListener::bind(&dot_addr).await?;
+        tracing::info!("DNS TLS listening on {}", dot_addr);
+        let dot_handler = handler.clone();
+        let client_id_domain = cfg.dns.client_id_domain.clone();
+        let dot_proxies = proxies.clone();
+        tokio::spawn(async move {
+            if let Err(e) = tls::run(dot_handler, listener, tls_config, client_id_domain, dot_proxies).await {
+                tracing::error!("DNS TLS server stopped: {}", e);
+            }
+        });
+    }
+
     let bind_addr = format!("{}:{}", cfg.dns.bind, cfg.dns.port);
     tracing::info!("DNS server starting on {}", bind_addr);
-    server::run(handler, bind_addr).await
+    server::run(handler, bind_addr, proxies).await
 }
@@ -0,0 +1,217 @@
+//! MAC addresses of clients on directly attached networks.
+//!
+//! The kernel neighbour table maps source addresses to MACs: IPv4 entries are
+//! read from `/proc/net/arp`, IPv6 (and IPv4) entries from `ip neigh show`.
+//! The table is refreshed lazily, at most every `REFRESH_INTERVAL`, and only
+//! consulted while some client is identified by MAC.  A forwarder such as
+//! dnsmasq (`--add-mac`) can also pass the MAC in an EDNS0 option.
+
+use base64::Engine;
+use hickory_proto::op::Message;
+use hickory_proto::rr::rdata::opt::{EdnsCode, EdnsOption};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+const ARP_TABLE: &str = "/proc/net/arp";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+const IP_NEIGH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// EDNS0 option code dnsmasq uses for `--add-mac`.
+pub const EDNS_MAC_OPTION: u16 = 65001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddr([u8; 6]);
+
+impl MacAddr {
+    /// Parse `aa:bb:cc:dd:ee:ff`, `aa-bb-cc-dd-ee-ff` or `aabb.ccdd.eeff`
+    /// (any case).
+    pub fn parse(s: &str) -> Option<Self> {
+        let hex: String = s.chars().filter(|c| !matches!(c, ':' | '-' | '.')).collect();
+        let separators = s.len() - hex.len();
+        if hex.len() != 12 || !matches!(separators, 0 | 2 | 5) || !hex.is_ascii() {
+            return None;
+        }
+        let mut bytes = [0u8; 6];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+        }
+        let mac = Self(bytes);
+        (!mac.is_zero()).then_some(mac)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == [0; 6]
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", b[0], b[1], b[2], b[3], b[4], b[5])
+    }
+}
+
+/// MAC from the dnsmasq EDNS0 option, sent as 6 raw bytes, base64 or text
+/// depending on the `--add-mac` mode.
+pub fn edns_mac(request: &Message) -> Option<MacAddr> {
+    let option = request.extensions().as_ref()?.option(EdnsCode::Unknown(EDNS_MAC_OPTION))?;
+    let EdnsOption::Unknown(_, data) = option else {
+        return None;
+    };
+    if let Ok(bytes) = <[u8; 6]>::try_from(data.as_slice()) {
+        let mac = MacAddr(bytes);
+        return (!mac.is_zero()).then_some(mac);
+    }
+    let text = std::str::from_utf8(data).ok()?;
+    if let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(text) {
+        if let Ok(bytes) = <[u8; 6]>::try_from(bytes.as_slice()) {
+            let mac = MacAddr(bytes);
+            return (!mac.is_zero()).then_some(mac);
+        }
+    }
+    MacAddr::parse(text)
+}
+
+/// Entries of `/proc/net/arp`, skipping incomplete ones.
+fn parse_proc_arp(content: &str) -> Vec<(IpAddr, MacAddr)> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 4 || fields[2] == "0x0" {
+                return None;
+            }
+            Some((fields[0].parse().ok()?, MacAddr::parse(fields[3])?))
+        })
+        .collect()
+}
+
+/// Entries of `ip neigh show` output that carry a link-layer address.
+fn parse_ip_neigh(output: &str) -> Vec<(IpAddr, MacAddr)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let ip = fields.next()?.parse().ok()?;
+            if line.ends_with("FAILED") || line.ends_with("INCOMPLETE") {
+                return None;
+            }
+            fields.by_ref().find(|f| *f == "lladdr")?;
+            Some((ip, MacAddr::parse(fields.next()?)?))
+        })
+        .collect()
+}
+
+pub struct NeighborTable {
+    entries: RwLock<HashMap<IpAddr, MacAddr>>,
+    refreshed_at: RwLock<Option<Instant>>,
+    refreshing: AtomicBool,
+}
+
+impl Default for NeighborTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NeighborTable {
+    pub fn new() -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            refreshed_at: RwLock::new(None),
+            refreshing: AtomicBool::new(false),
+        }
+    }
+
+    /// MAC of `ip`, refreshing the table first when it is stale.  Concurrent
+    /// lookups use the previous table while one of them refreshes.
+    pub async fn lookup(&self, ip: IpAddr) -> Option<MacAddr> {
+        let stale = self.refreshed_at.read().await.is_none_or(|t| t.elapsed() >= REFRESH_INTERVAL);
+        if stale && !self.refreshing.swap(true, Ordering::AcqRel) {
+            self.refresh().await;
+            self.refreshing.store(false, Ordering::Release);
+        }
+        self.entries.read().await.get(&ip).copied()
+    }
+
+    async fn refresh(&self) {
+        let mut entries = HashMap::new();
+        match tokio::fs::read_to_string(ARP_TABLE).await {
+            Ok(content) => entries.extend(parse_proc_arp(&content)),
+            Err(e) => tracing::debug!("Cannot read {}: {}", ARP_TABLE, e),
+        }
+        let output = tokio::time::timeout(
+            IP_NEIGH_TIMEOUT,
+            tokio::process::Command::new("ip").args(["neigh", "show"]).kill_on_drop(true).output(),
+        )
+        .await;
+        match output {
+            Ok(Ok(out)) if out.status.success() => {
+                entries.extend(parse_ip_neigh(&String::from_utf8_lossy(&out.stdout)));
+            }
+            Ok(Ok(out)) => tracing::debug!("ip neigh show exited with {}", out.status),
+            Ok(Err(e)) => tracing::debug!("Cannot run ip neigh show: {}", e),
+            Err(_) => tracing::debug!("ip neigh show timed out"),
+        }
+        tracing::debug!("Neighbour table refreshed: {} entries", entries.len());
+        *self.entries.write().await = entries;
+        *self.refreshed_at.write().await = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::op::Edns;
+
+    #[test]
+    fn test_parse_mac() {
+        let mac = MacAddr::parse("AA:bb:CC:00:11:22").unwrap();
+        assert_eq!(mac.to_string(), "aa:bb:cc:00:11:22");
+        assert_eq!(MacAddr::parse("aa-bb-cc-00-11-22"), Some(mac));
+        assert_eq!(MacAddr::parse("aabb.cc00.1122"), Some(mac));
+        assert_eq!(MacAddr::parse("00:00:00:00:00:00"), None);
+        assert_eq!(MacAddr::parse("aa:bb:cc:00:11"), None);
+        assert_eq!(MacAddr::parse("192.168.1.1"), None);
+        assert_eq!(MacAddr::parse("zz:bb:cc:00:11:22"), None);
+    }
+
+    #[test]
+    fn test_parse_neighbour_tables() {
+        let arp = "IP address       HW type     Flags       HW address            Mask     Device\n\
+                   192.168.1.20     0x1         0x2         aa:bb:cc:dd:ee:01     *        eth0\n\
+                   192.168.1.21     0x1         0x0         00:00:00:00:00:00     *        eth0\n";
+        assert_eq!(
+            parse_proc_arp(arp),
+            vec![("192.168.1.20".parse().unwrap(), MacAddr::parse("aa:bb:cc:dd:ee:01").unwrap())]
+        );
+
+        let neigh = "192.168.1.1 dev eth0 lladdr aa:bb:cc:dd:ee:02 REACHABLE\n\
+                     fe80::1 dev eth0 lladdr aa:bb:cc:dd:ee:03 router STALE\n\
+                     192.168.1.9 dev eth0  FAILED\n\
+                     2001:db8::9 dev eth0 INCOMPLETE\n";
+        let entries = parse_ip_neigh(neigh);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1], ("fe80::1".parse().unwrap(), MacAddr::parse("aa:bb:cc:dd:ee:03").unwrap()));
+    }
+
+    #[test]
+    fn test_edns_mac_formats() {
+        let raw = vec![0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x04];
+        let expected = MacAddr::parse("aa:bb:cc:dd:ee:04");
+        for data in [raw.clone(), b"qrvM3e4E".to_vec(), b"aa:bb:cc:dd:ee:04".to_vec()] {
+            let mut edns = Edns::new();
+            edns.options_mut().insert(EdnsOption::Unknown(EDNS_MAC_OPTION, data));
+            let mut msg = Message::new();
+            msg.set_edns(edns);
+            let msg = Message::from_vec(&msg.to_vec().unwrap()).unwrap();
+            assert_eq!(edns_mac(&msg), expected);
+        }
+        assert_eq!(edns_mac(&Message::new()), None);
+    }
+}
@@ -0,0 +1,84 @@
+//! Per-query-type policies.
+//!
+//! A policy maps record types to an action: `refuse` (REFUSED), `empty`
+//! (NOERROR without answers) or `allow` (normal processing; lets a group
+//! override a global refuse/empty).  Global policies live in the
+//! `qtype_policies` setting, group policies in `client_groups.qtype_policies`;
+//! both are JSON objects such as `{"ANY": "refuse", "HTTPS": "empty"}`.
+
+use hickory_proto::rr::RecordType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QtypeAction {
+    Refuse,
+    Empty,
+    Allow,
+}
+
+impl QtypeAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QtypeAction::Refuse => "refuse",
+            QtypeAction::Empty => "empty",
+            QtypeAction::Allow => "allow",
+        }
+    }
+}
+
+pub type QtypePolicies = HashMap<RecordType, QtypeAction>;
+
+/// Validate a policy map from the API and return it keyed by canonical
+/// record-type names (`"https"` → `"HTTPS"`), ready to be stored as JSON.
+pub fn normalize(policies: &HashMap<String, QtypeAction>) -> Result<HashMap<String, QtypeAction>, String> {
+    policies
+        .iter()
+        .map(|(name, action)| {
+            let qtype = RecordType::from_str(&name.trim().to_uppercase())
+                .map_err(|_| format!("Unknown record type: {}", name))?;
+            Ok((qtype.to_string(), *action))
+        })
+        .collect()
+}
+
+/// Parse stored policies; invalid entries are skipped.
+pub fn parse(json: &str) -> QtypePolicies {
+    serde_json::from_str::<HashMap<String, QtypeAction>>(json)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(name, action)| RecordType::from_str(&name).ok().map(|t| (t, action)))
+        .collect()
+}
+
+/// Log reason recorded for a query answered by a policy.
+pub fn block_reason(action: QtypeAction) -> String {
+    format!("qtype_policy:{}", action.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_and_parse() {
+        let input: HashMap<String, QtypeAction> =
+            serde_json::from_str(r#"{"any": "refuse", "HTTPS": "empty", "svcb": "empty", "AAAA": "allow"}"#).unwrap();
+        let normalized = normalize(&input).unwrap();
+        assert_eq!(normalized.get("ANY"), Some(&QtypeAction::Refuse));
+
+        let parsed = parse(&serde_json::to_string(&normalized).unwrap());
+        assert_eq!(parsed.get(&RecordType::ANY), Some(&QtypeAction::Refuse));
+        assert_eq!(parsed.get(&RecordType::HTTPS), Some(&QtypeAction::Empty));
+        assert_eq!(parsed.get(&RecordType::SVCB), Some(&QtypeAction::Empty));
+        assert_eq!(parsed.get(&RecordType::AAAA), Some(&QtypeAction::Allow));
+    }
+
+    #[test]
+    fn test_unknown_type_rejected() {
+        let input = HashMap::from([("NOTATYPE".to_string(), QtypeAction::Refuse)]);
+        assert!(normalize(&input).is_err());
+    }
+}
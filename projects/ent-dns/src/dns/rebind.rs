@@ -0,0 +1,106 @@
+//! DNS rebinding protection.
+//!
+//! A public name answering with a private, loopback or link-local address lets a
+//! malicious page talk to internal services (including this server's own API).
+//! When enabled, such answers are blocked unless the queried name is on the
+//! allowlist of legitimate split-horizon domains.  Configured through the
+//! `rebind_protection_enabled` / `rebind_protection_allowlist` settings.
+
+use std::net::{IpAddr, Ipv4Addr};
+
+/// Log reason recorded for answers dropped by rebinding protection.
+pub const BLOCK_REASON: &str = "rebind_protection";
+
+#[derive(Debug, Clone, Default)]
+pub struct RebindPolicy {
+    pub enabled: bool,
+    /// Domains (and their subdomains) allowed to resolve to internal addresses.
+    pub allowlist: Vec<String>,
+}
+
+impl RebindPolicy {
+    /// The first internal address in `ips` if answering `domain` with it would be
+    /// a rebinding violation, None when allowed or when protection is disabled.
+    pub fn check(&self, domain: &str, ips: &[IpAddr]) -> Option<IpAddr> {
+        if !self.enabled {
+            return None;
+        }
+        let internal = ips.iter().find(|ip| is_internal(ip))?;
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        let allowed = self.allowlist.iter().any(|entry| {
+            domain == *entry
+                || domain
+                    .strip_suffix(entry.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        });
+        (!allowed).then_some(*internal)
+    }
+}
+
+/// Normalize an allowlist entry (`*.corp.example.` → `corp.example`).
+pub fn normalize_allowlist_entry(entry: &str) -> String {
+    let entry = entry.trim().trim_end_matches('.').to_lowercase();
+    entry.strip_prefix("*.").map(str::to_string).unwrap_or(entry)
+}
+
+/// Private, loopback, link-local, shared (CGNAT) or unspecified addresses.
+pub fn is_internal(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_internal_v4(v4),
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_internal_v4(&v4);
+            }
+            let first = v6.segments()[0];
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || (first & 0xfe00) == 0xfc00 // fc00::/7 unique local
+                || (first & 0xffc0) == 0xfe80 // fe80::/10 link local
+        }
+    }
+}
+
+fn is_internal_v4(ip: &Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64) // 100.64.0.0/10 shared address space
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ips(list: &[&str]) -> Vec<IpAddr> {
+        list.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_internal_ranges() {
+        for ip in ["10.1.2.3", "172.16.0.1", "192.168.1.1", "127.0.0.1", "169.254.1.1", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:192.168.1.1"] {
+            assert!(is_internal(&ip.parse().unwrap()), "{} should be internal", ip);
+        }
+        for ip in ["8.8.8.8", "172.32.0.1", "100.128.0.1", "2606:4700::1111"] {
+            assert!(!is_internal(&ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = RebindPolicy {
+            enabled: true,
+            allowlist: vec![normalize_allowlist_entry("*.corp.example.")],
+        };
+        assert_eq!(policy.check("evil.example.", &ips(&["93.184.216.34", "192.168.1.1"])), Some("192.168.1.1".parse().unwrap()));
+        assert_eq!(policy.check("evil.example", &ips(&["93.184.216.34"])), None);
+        assert_eq!(policy.check("intranet.corp.example", &ips(&["10.0.0.5"])), None);
+        assert_eq!(policy.check("corp.example", &ips(&["10.0.0.5"])), None);
+        assert!(policy.check("notcorp.example", &ips(&["10.0.0.5"])).is_some());
+
+        let disabled = RebindPolicy::default();
+        assert_eq!(disabled.check("evil.example", &ips(&["127.0.0.1"])), None);
+    }
+}
@@ -1,14 +1,22 @@
 use anyhow::Result;
-use hickory_proto::op::{Message, MessageType, OpCode, ResponseCode};
-use hickory_proto::rr::RecordType;
+use hickory_proto::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use hickory_proto::rr::{Name, RecordType};
+use hickory_proto::xfer::{DnsHandle, DnsRequest, DnsRequestOptions, FirstAnswer};
 use hickory_resolver::TokioAsyncResolver;
 use hickory_resolver::config::{ResolverConfig, ResolverOpts, NameServerConfig, Protocol};
 use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::name_server::{GenericNameServerPool, TokioConnectionProvider, TokioRuntimeProvider};
 use std::net::SocketAddr;
+use std::str::FromStr;
+use super::ecs::EcsSubnet;
 use crate::config::Config;
 
 pub struct DnsResolver {
     inner: TokioAsyncResolver,
+    /// Same name servers, for queries the resolver API cannot express
+    /// (EDNS Client Subnet).
+    pool: GenericNameServerPool<TokioRuntimeProvider>,
+    name_servers: Vec<SocketAddr>,
 }
 
 impl DnsResolver {
@@ -24,13 +32,36 @@ impl DnsResolver {
         // DNSSEC validation disabled for compatibility with Cloudflare DoH
         opts.validate = false;
 
-        let resolver = TokioAsyncResolver::tokio(ResolverConfig::cloudflare(), opts);
-
         tracing::info!(
             "DNS resolver initialized without DNSSEC validation, upstreams: {:?}",
             cfg.dns.upstreams
         );
-        Ok(Self { inner: resolver })
+        Ok(Self::build(ResolverConfig::cloudflare(), opts))
+    }
+
+    fn build(config: ResolverConfig, opts: ResolverOpts) -> Self {
+        let pool = GenericNameServerPool::from_config(
+            config.name_servers().to_vec().into(),
+            opts.clone(),
+            TokioConnectionProvider::default(),
+        );
+        let mut name_servers: Vec<SocketAddr> = Vec::new();
+        for ns in config.name_servers() {
+            if !name_servers.contains(&ns.socket_addr) {
+                name_servers.push(ns.socket_addr);
+            }
+        }
+        Self {
+            inner: TokioAsyncResolver::tokio(config, opts),
+            pool,
+            name_servers,
+        }
+    }
+
+    /// Addresses of the name servers queried, once per address (UDP and TCP
+    /// share one).
+    pub fn name_servers(&self) -> &[SocketAddr] {
+        &self.name_servers
     }
 
     /// Create a resolver using custom upstream IPs (plain UDP port 53).
@@ -64,14 +95,10 @@ impl DnsResolver {
         if added == 0 {
             // Fall back to Cloudflare if no valid upstreams provided
             tracing::warn!("No valid custom upstreams, falling back to Cloudflare");
-            return Ok(Self {
-                inner: TokioAsyncResolver::tokio(ResolverConfig::cloudflare(), opts),
-            });
+            return Ok(Self::build(ResolverConfig::cloudflare(), opts));
         }
 
-        Ok(Self {
-            inner: TokioAsyncResolver::tokio(config, opts),
-        })
+        Ok(Self::build(config, opts))
     }
 
     /// Resolve a DNS query.  Returns the serialised DNS wire format response
@@ -147,4 +174,89 @@ impl DnsResolver {
 
         Ok((response.to_vec()?, min_ttl))
     }
+
+    /// Resolve a DNS query carrying an EDNS Client Subnet option.  Like
+    /// `resolve`, but also returns the scope prefix of the upstream's answer
+    /// (0 when the answer is valid for every subnet).
+    pub async fn resolve_with_subnet(
+        &self,
+        domain: &str,
+        qtype: RecordType,
+        request: &Message,
+        subnet: EcsSubnet,
+    ) -> Result<(Vec<u8>, Option<u32>, u8)> {
+        let mut query = Message::new();
+        query.set_id(request.id());
+        query.set_message_type(MessageType::Query);
+        query.set_op_code(OpCode::Query);
+        query.set_recursion_desired(true);
+        query.add_query(Query::query(Name::from_str(domain)?, qtype));
+        let mut edns = Edns::new();
+        edns.set_max_payload(1232);
+        edns.options_mut().insert(subnet.to_option());
+        query.set_edns(edns);
+
+        let mut response = Message::new();
+        response.set_id(request.id());
+        response.set_message_type(MessageType::Response);
+        response.set_op_code(OpCode::Query);
+        response.set_recursion_desired(request.recursion_desired());
+        response.set_recursion_available(true);
+        for query in request.queries() {
+            response.add_query(query.clone());
+        }
+
+        let mut options = DnsRequestOptions::default();
+        options.use_edns = true;
+        let mut min_ttl: Option<u32> = None;
+        let mut scope = 0;
+        match self.pool.send(DnsRequest::new(query, options)).first_answer().await {
+            Ok(upstream) => {
+                response.set_response_code(upstream.response_code());
+                for record in upstream.answers() {
+                    min_ttl = Some(min_ttl.map_or(record.ttl(), |t| t.min(record.ttl())));
+                    response.add_answer(record.clone());
+                }
+                // No ECS in the answer means it is not tailored to the subnet;
+                // one echoing a different subnet is treated as most specific
+                if let Some(answer) = EcsSubnet::from_message(&upstream) {
+                    scope = if answer.network() == subnet.network() {
+                        answer.scope_prefix.min(subnet.source_prefix)
+                    } else {
+                        subnet.source_prefix
+                    };
+                }
+                tracing::debug!(
+                    "Resolved {} {:?} for {}: {} records, min_ttl={:?}, scope={}",
+                    domain,
+                    qtype,
+                    subnet.network(),
+                    response.answer_count(),
+                    min_ttl,
+                    scope,
+                );
+            }
+            Err(e) => {
+                tracing::warn!("Upstream resolver error for {} {:?}: {}", domain, qtype, e);
+                response.set_response_code(ResponseCode::ServFail);
+            }
+        }
+
+        Ok((response.to_vec()?, min_ttl, scope))
+    }
+
+    /// Host name of `ip` from its PTR record, without the trailing dot.
+    /// `None` when the upstream has no PTR record for it.
+    pub async fn reverse_lookup(&self, ip: std::net::IpAddr) -> Result<Option<String>> {
+        match self.inner.reverse_lookup(ip).await {
+            Ok(lookup) => Ok(lookup
+                .iter()
+                .next()
+                .map(|name| name.to_utf8().trim_end_matches('.').to_string())),
+            Err(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { .. } => Ok(None),
+                _ => Err(e.into()),
+            },
+        }
+    }
 }
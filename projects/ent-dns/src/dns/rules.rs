@@ -7,10 +7,21 @@
 //!   `127.0.0.1 example.com`   — hosts-format redirect (treated as block for now)
 //!   `example.com`             — plain domain block (exact + subdomains)
 //!   `*.example.com`           — wildcard subdomain block
+//!   `198.51.100.7` / `||198.51.100.7^` / `198.51.100.0/24`
+//!                             — block responses whose answers contain the address
+//!   `@@198.51.100.7`          — allowlist a response address
+//!   `||example.com^$dnsrewrite=NOERROR`
+//!                             — block with an empty answer (NODATA) instead of NXDOMAIN
+//!   `||example.com^$dnsrewrite=192.0.2.1`
+//!                             — answer A/AAAA queries with local data
 //!   `# comment` / `! comment` — ignored
 #![allow(dead_code)]
 
-use std::collections::HashSet;
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::client_index::prefix_table;
 
 #[derive(Debug, Clone)]
 pub struct RuleSet {
@@ -18,6 +29,15 @@ pub struct RuleSet {
     blocked: HashSet<String>,
     /// Domains in allow list. Allow overrides block.
     allowed: HashSet<String>,
+    /// Response-address rules: an answer containing a matching address is blocked.
+    blocked_ips: IpMatcher,
+    /// Response addresses exempt from `blocked_ips`.
+    allowed_ips: IpMatcher,
+    /// Blocked domains answered with NOERROR/no data rather than NXDOMAIN
+    /// (a subset of `blocked`).
+    nodata: HashSet<String>,
+    /// Local data: domain → address answered for A/AAAA queries.
+    local_data: HashMap<String, Vec<IpAddr>>,
 }
 
 impl RuleSet {
@@ -25,6 +45,10 @@ impl RuleSet {
         Self {
             blocked: HashSet::new(),
             allowed: HashSet::new(),
+            blocked_ips: IpMatcher::default(),
+            allowed_ips: IpMatcher::default(),
+            nodata: HashSet::new(),
+            local_data: HashMap::new(),
         }
     }
 
@@ -43,8 +67,12 @@ impl RuleSet {
             return false;
         }
 
-        // Allowlist: @@||domain^ or @@domain
+        // Allowlist: @@||domain^ or @@domain (or a response address)
         if let Some(rest) = line.strip_prefix("@@") {
+            if let Some(net) = parse_ip_rule(rest) {
+                self.allowed_ips.insert(net);
+                return true;
+            }
             if let Some(domain) = parse_adguard_domain(rest) {
                 self.allowed.insert(domain);
                 return true;
@@ -52,6 +80,38 @@ impl RuleSet {
             return false;
         }
 
+        // $dnsrewrite: NXDOMAIN / NOERROR (no data) / local address
+        if let Some((pattern, value)) = line.split_once("$dnsrewrite=") {
+            let Some(domain) = parse_adguard_domain(pattern) else {
+                return false;
+            };
+            match value.trim() {
+                "NXDOMAIN" | "REFUSED" => {
+                    self.blocked.insert(domain);
+                }
+                "NOERROR" => {
+                    self.nodata.insert(domain.clone());
+                    self.blocked.insert(domain);
+                }
+                other => match other.parse::<IpAddr>() {
+                    Ok(ip) => {
+                        let addrs = self.local_data.entry(domain).or_default();
+                        if !addrs.contains(&ip) {
+                            addrs.push(ip);
+                        }
+                    }
+                    Err(_) => return false,
+                },
+            }
+            return true;
+        }
+
+        // Response address: 198.51.100.7, ||198.51.100.7^ or 198.51.100.0/24
+        if let Some(net) = parse_ip_rule(line) {
+            self.blocked_ips.insert(net);
+            return true;
+        }
+
         // AdGuard format: ||domain^  or ||domain^$options
         if let Some(domain) = parse_adguard_domain(line) {
             self.blocked.insert(domain);
@@ -102,6 +162,55 @@ impl RuleSet {
         self.matches_set(&domain, &self.blocked)
     }
 
+    /// Check if a response address is blocked (considering allowed addresses).
+    pub fn is_ip_blocked(&self, ip: &IpAddr) -> bool {
+        self.is_ip_blocklisted(ip) && !self.is_ip_allowlisted(ip)
+    }
+
+    /// True if a response-address block rule matches `ip` (ignores allow rules).
+    pub fn is_ip_blocklisted(&self, ip: &IpAddr) -> bool {
+        self.blocked_ips.contains(*ip)
+    }
+
+    /// True if a response-address allow rule matches `ip`.
+    pub fn is_ip_allowlisted(&self, ip: &IpAddr) -> bool {
+        self.allowed_ips.contains(*ip)
+    }
+
+    /// True if an allow rule matches `domain` (ignores block rules).
+    pub fn is_allowlisted(&self, domain: &str) -> bool {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        self.matches_set(&domain, &self.allowed)
+    }
+
+    /// True if a block rule matches `domain` (ignores allow rules).
+    pub fn is_blocklisted(&self, domain: &str) -> bool {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        self.matches_set(&domain, &self.blocked)
+    }
+
+    /// True if `domain` is blocked by a no-data rule (answer NOERROR without
+    /// records instead of NXDOMAIN).
+    pub fn is_nodata(&self, domain: &str) -> bool {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        self.matches_set(&domain, &self.nodata)
+    }
+
+    /// Local-data addresses for `domain` or its closest parent with any.
+    pub fn local_data(&self, domain: &str) -> Option<&[IpAddr]> {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        let mut current = domain.as_str();
+        loop {
+            if let Some(addrs) = self.local_data.get(current) {
+                return Some(addrs);
+            }
+            match current.find('.') {
+                Some(pos) => current = &current[pos + 1..],
+                None => return None,
+            }
+        }
+    }
+
     /// Returns true if `domain` or any of its parent domains is in `set`.
     fn matches_set(&self, domain: &str, set: &HashSet<String>) -> bool {
         // Walk from most-specific to least-specific
@@ -125,6 +234,67 @@ impl RuleSet {
     pub fn allowed_count(&self) -> usize {
         self.allowed.len()
     }
+
+    pub fn ip_rule_count(&self) -> usize {
+        self.blocked_ips.len() + self.allowed_ips.len()
+    }
+
+    pub fn local_data_count(&self) -> usize {
+        self.local_data.len()
+    }
+}
+
+/// Addresses and networks of response-address rules.  Single addresses go
+/// into an exact-match set, networks into per-prefix-length tables searched
+/// longest prefix first, so a lookup costs one hash probe per prefix length.
+#[derive(Debug, Clone, Default)]
+struct IpMatcher {
+    exact: HashSet<IpAddr>,
+    v4: Vec<(u8, HashSet<Ipv4Addr>)>,
+    v6: Vec<(u8, HashSet<Ipv6Addr>)>,
+}
+
+impl IpMatcher {
+    fn insert(&mut self, net: IpNet) {
+        match net {
+            IpNet::V4(n) if n.prefix_len() == 32 => self.exact.insert(IpAddr::V4(n.addr())),
+            IpNet::V6(n) if n.prefix_len() == 128 => self.exact.insert(IpAddr::V6(n.addr())),
+            IpNet::V4(n) => prefix_table(&mut self.v4, n.prefix_len()).insert(n.network()),
+            IpNet::V6(n) => prefix_table(&mut self.v6, n.prefix_len()).insert(n.network()),
+        };
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        if self.exact.contains(&ip) {
+            return true;
+        }
+        match ip {
+            IpAddr::V4(v4) => self.v4.iter().any(|(len, nets)| {
+                Ipv4Net::new(v4, *len).is_ok_and(|net| nets.contains(&net.network()))
+            }),
+            IpAddr::V6(v6) => self.v6.iter().any(|(len, nets)| {
+                Ipv6Net::new(v6, *len).is_ok_and(|net| nets.contains(&net.network()))
+            }),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.exact.len()
+            + self.v4.iter().map(|(_, t)| t.len()).sum::<usize>()
+            + self.v6.iter().map(|(_, t)| t.len()).sum::<usize>()
+    }
+}
+
+/// Parse a response-address rule: a bare IP, a CIDR, or `||IP^`.
+fn parse_ip_rule(rule: &str) -> Option<IpNet> {
+    let rule = rule
+        .strip_prefix("||")
+        .map(|r| r.trim_end_matches('^'))
+        .unwrap_or(rule);
+    if let Ok(ip) = rule.parse::<IpAddr>() {
+        return Some(IpNet::from(ip));
+    }
+    rule.parse::<IpNet>().ok().map(|net| net.trunc())
 }
 
 /// Parse `||domain^`, `||domain^$options`, `|domain|`, `||domain`
@@ -268,6 +438,42 @@ mod tests {
         assert!(rs.is_blocked("example.com."));
     }
 
+    #[test]
+    fn test_response_ip_rules() {
+        let mut rs = RuleSet::new();
+        assert!(rs.add_rule("198.51.100.0/24"));
+        assert!(rs.add_rule("||203.0.113.9^"));
+        assert!(rs.add_rule("2001:db8::/32"));
+        assert!(rs.add_rule("@@198.51.100.53"));
+        assert_eq!(rs.ip_rule_count(), 4);
+        // IP rules never block by domain name
+        assert_eq!(rs.blocked_count(), 0);
+
+        assert!(rs.is_ip_blocked(&"198.51.100.7".parse().unwrap()));
+        assert!(rs.is_ip_blocked(&"203.0.113.9".parse().unwrap()));
+        assert!(rs.is_ip_blocked(&"2001:db8::1".parse().unwrap()));
+        assert!(!rs.is_ip_blocked(&"198.51.100.53".parse().unwrap()));
+        assert!(!rs.is_ip_blocked(&"203.0.113.10".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_response_ip_rules_nested_prefixes() {
+        let mut rs = RuleSet::new();
+        for rule in ["10.0.0.0/8", "10.1.0.0/16", "10.1.2.0/24", "10.1.2.3/32", "fd00::/8", "fd00:1::/32"] {
+            assert!(rs.add_rule(rule));
+        }
+        assert!(rs.add_rule("@@10.1.2.0/25"));
+        assert_eq!(rs.ip_rule_count(), 7);
+        for ip in ["10.200.0.1", "10.1.200.1", "10.1.2.200", "fd00:1::1", "fd99::1"] {
+            assert!(rs.is_ip_blocked(&ip.parse().unwrap()), "{ip}");
+        }
+        // The allowed /25 exempts addresses inside any of the blocked networks
+        assert!(rs.is_ip_blocklisted(&"10.1.2.3".parse().unwrap()));
+        assert!(!rs.is_ip_blocked(&"10.1.2.3".parse().unwrap()));
+        assert!(!rs.is_ip_blocked(&"11.0.0.1".parse().unwrap()));
+        assert!(!rs.is_ip_blocked(&"fe80::1".parse().unwrap()));
+    }
+
     // --- 新增扩展测试用例 ---
 
     #[test]
@@ -373,4 +579,25 @@ mod tests {
         assert!(rs.is_blocked("a.b.c.d.evil.com"));
         assert!(!rs.is_blocked("notevil.com"));
     }
+
+    #[test]
+    fn test_dnsrewrite_rules() {
+        // $dnsrewrite：NOERROR 为空应答拦截，IP 为本地数据
+        let mut rs = RuleSet::new();
+        assert!(rs.add_rule("||nodata.example^$dnsrewrite=NOERROR"));
+        assert!(rs.add_rule("||nx.example^$dnsrewrite=NXDOMAIN"));
+        assert!(rs.add_rule("||local.example^$dnsrewrite=192.0.2.10"));
+        assert!(rs.add_rule("||local.example^$dnsrewrite=2001:db8::10"));
+        assert!(!rs.add_rule("||bad.example^$dnsrewrite=garbage"));
+
+        assert!(rs.is_blocked("a.nodata.example"));
+        assert!(rs.is_nodata("a.nodata.example"));
+        assert!(rs.is_blocked("nx.example"));
+        assert!(!rs.is_nodata("nx.example"));
+        assert!(!rs.is_blocked("local.example"));
+        let v4: IpAddr = "192.0.2.10".parse().unwrap();
+        let v6: IpAddr = "2001:db8::10".parse().unwrap();
+        assert_eq!(rs.local_data("www.local.example"), Some(&[v4, v6][..]));
+        assert_eq!(rs.local_data("other.example"), None);
+    }
 }
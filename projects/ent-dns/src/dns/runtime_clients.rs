@@ -0,0 +1,261 @@
+//! Runtime clients: every source address seen in the query log, whether or
+//! not it belongs to a configured client.
+//!
+//! The query log writer records first/last query time and a query count per
+//! address.  Names come from periodic reverse lookups and from DHCP lease
+//! files (dnsmasq, ISC Kea memfile CSV, odhcpd) imported through the API; a
+//! lease name takes precedence over a PTR name.
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::Deserialize;
+use std::net::IpAddr;
+
+use super::handler::DnsHandler;
+use super::neighbors::MacAddr;
+use crate::db::DbPool;
+
+/// Addresses looked up per `resolve_hostnames` run.
+const RDNS_BATCH: i64 = 64;
+/// Reverse lookups are repeated after this long.
+const RDNS_RECHECK_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaseFormat {
+    /// `dnsmasq.leases`: `expiry mac ip hostname client-id`
+    Dnsmasq,
+    /// Kea memfile (`kea-leases4.csv` / `kea-leases6.csv`), with header row
+    Kea,
+    /// odhcpd state file: `# iface duid|mac iaid|ipv4 hostname valid id prefix addr/len...`
+    Odhcpd,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    pub ip: IpAddr,
+    pub mac: Option<MacAddr>,
+    pub hostname: Option<String>,
+}
+
+/// Lease host name, `None` for the placeholders the servers write.
+fn lease_hostname(s: &str) -> Option<String> {
+    let s = s.trim().trim_end_matches('.');
+    (!matches!(s, "" | "*" | "-")).then(|| s.to_string())
+}
+
+impl LeaseFormat {
+    /// Leases in `content`, skipping lines that do not parse.  Later lines
+    /// win when an address appears more than once.
+    pub fn parse(self, content: &str) -> Vec<Lease> {
+        match self {
+            Self::Dnsmasq => parse_dnsmasq(content),
+            Self::Kea => parse_kea(content),
+            Self::Odhcpd => parse_odhcpd(content),
+        }
+    }
+}
+
+fn parse_dnsmasq(content: &str) -> Vec<Lease> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 4 || fields[0] == "duid" {
+                return None;
+            }
+            // DHCPv6 leases carry the IAID instead of a MAC
+            Some(Lease {
+                ip: fields[2].parse().ok()?,
+                mac: MacAddr::parse(fields[1]),
+                hostname: lease_hostname(fields[3]),
+            })
+        })
+        .collect()
+}
+
+fn parse_kea(content: &str) -> Vec<Lease> {
+    let mut lines = content.lines();
+    let Some(header) = lines.next() else {
+        return Vec::new();
+    };
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    let column = |name: &str| columns.iter().position(|c| *c == name);
+    let (Some(address), hwaddr, hostname, state) =
+        (column("address"), column("hwaddr"), column("hostname"), column("state"))
+    else {
+        return Vec::new();
+    };
+    lines
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            let field = |i: Option<usize>| i.and_then(|i| fields.get(i)).copied();
+            // 0 = assigned; declined and expired-reclaimed leases are skipped
+            if field(state).is_some_and(|s| s.trim() != "0") {
+                return None;
+            }
+            Some(Lease {
+                ip: field(Some(address))?.trim().parse().ok()?,
+                mac: field(hwaddr).and_then(MacAddr::parse),
+                hostname: field(hostname).and_then(lease_hostname),
+            })
+        })
+        .collect()
+}
+
+fn parse_odhcpd(content: &str) -> Vec<Lease> {
+    content
+        .lines()
+        .filter_map(|line| line.strip_prefix("# "))
+        .flat_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 {
+                return Vec::new();
+            }
+            // DHCPv4 leases have "ipv4" in place of the IAID and a MAC in place of the DUID
+            let mac = if fields[2] == "ipv4" { MacAddr::parse(fields[1]) } else { None };
+            let hostname = lease_hostname(fields[3]);
+            fields[7..]
+                .iter()
+                .filter_map(|addr| addr.split('/').next()?.parse().ok())
+                .map(|ip| Lease { ip, mac, hostname: hostname.clone() })
+                .collect()
+        })
+        .collect()
+}
+
+/// Store lease names and MACs, adding addresses that have not queried yet.
+pub async fn import_leases(db: &DbPool, leases: &[Lease]) -> Result<usize> {
+    let now = Utc::now().to_rfc3339();
+    let mut tx = db.begin().await?;
+    for lease in leases {
+        sqlx::query(
+            "INSERT INTO runtime_clients (ip, dhcp_hostname, mac, lease_updated_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(ip) DO UPDATE SET
+                 dhcp_hostname = excluded.dhcp_hostname,
+                 mac = COALESCE(excluded.mac, mac),
+                 lease_updated_at = excluded.lease_updated_at"
+        )
+        .bind(lease.ip.to_string())
+        .bind(&lease.hostname)
+        .bind(lease.mac.map(|m| m.to_string()))
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(leases.len())
+}
+
+/// Reverse-look up runtime clients never checked, or last checked more than
+/// `RDNS_RECHECK_HOURS` ago, most recently seen first.  A failed lookup keeps
+/// the previous name until the next check.
+pub async fn resolve_hostnames(db: &DbPool, handler: &DnsHandler) -> Result<usize> {
+    let cutoff = (Utc::now() - chrono::Duration::hours(RDNS_RECHECK_HOURS)).to_rfc3339();
+    let ips: Vec<String> = sqlx::query_scalar(
+        "SELECT ip FROM runtime_clients
+         WHERE query_count > 0 AND (rdns_checked_at IS NULL OR rdns_checked_at < ?)
+         ORDER BY last_seen DESC LIMIT ?"
+    )
+    .bind(&cutoff)
+    .bind(RDNS_BATCH)
+    .fetch_all(db)
+    .await?;
+
+    let mut resolved = 0;
+    for ip in &ips {
+        let Ok(addr) = ip.parse::<IpAddr>() else {
+            continue;
+        };
+        let now = Utc::now().to_rfc3339();
+        match handler.reverse_lookup(addr).await {
+            Ok(hostname) => {
+                resolved += hostname.is_some() as usize;
+                sqlx::query("UPDATE runtime_clients SET rdns_hostname = ?, rdns_checked_at = ? WHERE ip = ?")
+                    .bind(&hostname)
+                    .bind(&now)
+                    .bind(ip)
+                    .execute(db)
+                    .await?;
+            }
+            Err(e) => {
+                tracing::debug!("Reverse lookup of {} failed: {}", ip, e);
+                sqlx::query("UPDATE runtime_clients SET rdns_checked_at = ? WHERE ip = ?")
+                    .bind(&now)
+                    .bind(ip)
+                    .execute(db)
+                    .await?;
+            }
+        }
+    }
+    if !ips.is_empty() {
+        tracing::debug!("Reverse lookups: {} of {} runtime clients named", resolved, ips.len());
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lease(ip: &str, mac: Option<&str>, hostname: Option<&str>) -> Lease {
+        Lease {
+            ip: ip.parse().unwrap(),
+            mac: mac.map(|m| MacAddr::parse(m).unwrap()),
+            hostname: hostname.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_parse_dnsmasq_leases() {
+        let content = "1700000000 aa:bb:cc:dd:ee:01 192.168.1.20 laptop 01:aa:bb:cc:dd:ee:01\n\
+                       1700000100 aa:bb:cc:dd:ee:02 192.168.1.21 * *\n\
+                       duid 00:01:00:01:2c:aa:bb:cc:dd:ee:ff:00:11:22\n\
+                       1700000200 305419896 fd00::20 phone 00:01:00:01:2c:aa\n";
+        assert_eq!(
+            LeaseFormat::Dnsmasq.parse(content),
+            vec![
+                lease("192.168.1.20", Some("aa:bb:cc:dd:ee:01"), Some("laptop")),
+                lease("192.168.1.21", Some("aa:bb:cc:dd:ee:02"), None),
+                lease("fd00::20", None, Some("phone")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_kea_leases() {
+        let v4 = "address,hwaddr,client_id,valid_lifetime,expire,subnet_id,fqdn_fwd,fqdn_rev,hostname,state,user_context\n\
+                  192.168.1.30,aa:bb:cc:dd:ee:03,,3600,1700003600,1,0,0,printer.lan.,0,\n\
+                  192.168.1.31,aa:bb:cc:dd:ee:04,,3600,1700003600,1,0,0,old,2,\n\
+                  192.168.1.32,aa:bb:cc:dd:ee:05,,3600,1700003600,1,0,0,,0,\n";
+        assert_eq!(
+            LeaseFormat::Kea.parse(v4),
+            vec![
+                lease("192.168.1.30", Some("aa:bb:cc:dd:ee:03"), Some("printer.lan")),
+                lease("192.168.1.32", Some("aa:bb:cc:dd:ee:05"), None),
+            ]
+        );
+
+        let v6 = "address,duid,valid_lifetime,expire,subnet_id,pref_lifetime,lease_type,iaid,prefix_len,fqdn_fwd,fqdn_rev,hostname,hwaddr,state\n\
+                  fd00::30,00:01:00:01,3600,1700003600,1,1800,0,1,128,0,0,nas,aa:bb:cc:dd:ee:06,0\n";
+        assert_eq!(LeaseFormat::Kea.parse(v6), vec![lease("fd00::30", Some("aa:bb:cc:dd:ee:06"), Some("nas"))]);
+        assert_eq!(LeaseFormat::Kea.parse("not,a,lease,file\n1,2,3,4\n"), Vec::new());
+    }
+
+    #[test]
+    fn test_parse_odhcpd_leases() {
+        let content = "# br-lan 00010001290f2e8aaabbccddee07 2f9a1b3c tv 1700007200 3f 128 fd00::3f/128 2001:db8::3f/128\n\
+                       # br-lan aabbccddee08 ipv4 desktop 1700007200 a2 32 192.168.1.162/32\n\
+                       # br-lan aabbccddee09 ipv4 - 1700007200 a3 32 192.168.1.163/32\n\
+                       fd00::3f tv.lan\n";
+        assert_eq!(
+            LeaseFormat::Odhcpd.parse(content),
+            vec![
+                lease("fd00::3f", None, Some("tv")),
+                lease("2001:db8::3f", None, Some("tv")),
+                lease("192.168.1.162", Some("aa:bb:cc:dd:ee:08"), Some("desktop")),
+                lease("192.168.1.163", Some("aa:bb:cc:dd:ee:09"), None),
+            ]
+        );
+    }
+}
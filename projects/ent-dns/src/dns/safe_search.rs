@@ -0,0 +1,48 @@
+//! SafeSearch: queries for search engines (and YouTube) are answered with a
+//! CNAME to the engine's restricted host, which serves filtered results.
+
+/// Restricted host for a search domain, if it is one.
+pub fn safe_host(domain: &str) -> Option<&'static str> {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    let host = domain.strip_prefix("www.").unwrap_or(&domain);
+    match host {
+        "bing.com" => Some("strict.bing.com"),
+        "duckduckgo.com" | "start.duckduckgo.com" | "html.duckduckgo.com" => Some("safe.duckduckgo.com"),
+        "youtube.com" | "m.youtube.com" | "youtubei.googleapis.com" | "youtube.googleapis.com"
+        | "youtube-nocookie.com" => Some("restrict.youtube.com"),
+        _ if is_google_search(host) => Some("forcesafesearch.google.com"),
+        _ => None,
+    }
+}
+
+/// `google.com` and its country domains (`google.de`, `google.co.uk`, `google.com.au`).
+fn is_google_search(host: &str) -> bool {
+    let Some(suffix) = host.strip_prefix("google.") else {
+        return false;
+    };
+    let is_cc = |label: &str| label.len() == 2 && label.bytes().all(|b| b.is_ascii_lowercase());
+    match suffix.split('.').collect::<Vec<_>>().as_slice() {
+        ["com"] => true,
+        [cc] => is_cc(cc),
+        ["co" | "com", cc] => is_cc(cc),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_host() {
+        for domain in ["google.com", "www.google.com.", "WWW.Google.DE", "www.google.co.uk", "google.com.au"] {
+            assert_eq!(safe_host(domain), Some("forcesafesearch.google.com"), "{domain}");
+        }
+        assert_eq!(safe_host("www.bing.com"), Some("strict.bing.com"));
+        assert_eq!(safe_host("duckduckgo.com"), Some("safe.duckduckgo.com"));
+        assert_eq!(safe_host("m.youtube.com"), Some("restrict.youtube.com"));
+        for domain in ["mail.google.com", "google.example.com", "notgoogle.com", "google.co.example", "example.com"] {
+            assert_eq!(safe_host(domain), None, "{domain}");
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Time-based schedules.
+//!
+//! A schedule is a timezone, a set of weekdays and one or more local time
+//! ranges (`HH:MM`, end exclusive).  A range whose end is not after its start
+//! wraps past midnight and belongs to the weekday it starts on, so
+//! `22:00–06:00` on Friday covers Friday night into Saturday morning, and
+//! `00:00–00:00` covers the whole day.
+//!
+//! Schedules are stored in the `schedules` table and attached to group rule
+//! bindings (`client_group_rules.schedule_id`), to a client's blocked services
+//! (`clients.blocked_services_schedule_id`) or to a client's whole filtering
+//! (`clients.filter_schedule_id`).  The DNS handler evaluates them per query.
+
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// How far ahead `next_transition` searches (one week plus a day of slack for DST).
+const TRANSITION_HORIZON_MINUTES: i64 = 8 * 24 * 60;
+
+/// A local time range as stored in `schedules.time_ranges`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub id: String,
+    tz: Tz,
+    weekdays: Vec<Weekday>,
+    /// (start, end) in minutes since local midnight.
+    ranges: Vec<(u32, u32)>,
+}
+
+impl Schedule {
+    /// Validate and compile a schedule.  Errors are user-facing messages.
+    pub fn new(id: &str, timezone: &str, weekdays: &[String], ranges: &[TimeRange]) -> Result<Self, String> {
+        let tz: Tz = timezone
+            .parse()
+            .map_err(|_| format!("Unknown timezone: {}", timezone))?;
+
+        if weekdays.is_empty() {
+            return Err("At least one weekday is required".to_string());
+        }
+        let mut days = Vec::with_capacity(weekdays.len());
+        for day in weekdays {
+            let wd: Weekday = day
+                .parse()
+                .map_err(|_| format!("Invalid weekday: {}", day))?;
+            if !days.contains(&wd) {
+                days.push(wd);
+            }
+        }
+
+        if ranges.is_empty() {
+            return Err("At least one time range is required".to_string());
+        }
+        let ranges = ranges
+            .iter()
+            .map(|r| Ok((parse_hhmm(&r.start)?, parse_hhmm(&r.end)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self { id: id.to_string(), tz, weekdays: days, ranges })
+    }
+
+    /// Compile a schedule from its stored row (JSON-encoded weekdays and ranges).
+    pub fn from_row(id: &str, timezone: &str, weekdays_json: &str, ranges_json: &str) -> Result<Self, String> {
+        let weekdays: Vec<String> = serde_json::from_str(weekdays_json)
+            .map_err(|e| format!("Invalid weekdays: {}", e))?;
+        let ranges: Vec<TimeRange> = serde_json::from_str(ranges_json)
+            .map_err(|e| format!("Invalid time ranges: {}", e))?;
+        Self::new(id, timezone, &weekdays, &ranges)
+    }
+
+    /// Normalised weekday names (`mon` … `sun`) for storage.
+    pub fn weekday_names(&self) -> Vec<String> {
+        self.weekdays.iter().map(|d| d.to_string().to_lowercase()).collect()
+    }
+
+    pub fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.tz);
+        let day = local.weekday();
+        let minute = local.hour() * 60 + local.minute();
+
+        self.ranges.iter().any(|&(start, end)| {
+            if start < end {
+                self.weekdays.contains(&day) && minute >= start && minute < end
+            } else {
+                // Wraps past midnight: tail of today's range or spill-over from yesterday's
+                (self.weekdays.contains(&day) && minute >= start)
+                    || (self.weekdays.contains(&day.pred()) && minute < end)
+            }
+        })
+    }
+
+    /// The next instant after `now` at which `is_active_at` flips, or None if the
+    /// schedule never changes state (always or never active).
+    pub fn next_transition(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let current = self.is_active_at(now);
+        let base = now.with_second(0)?.with_nanosecond(0)?;
+        (1..=TRANSITION_HORIZON_MINUTES)
+            .map(|m| base + Duration::minutes(m))
+            .find(|t| self.is_active_at(*t) != current)
+    }
+}
+
+fn parse_hhmm(s: &str) -> Result<u32, String> {
+    let err = || format!("Invalid time '{}' (expected HH:MM)", s);
+    let (h, m) = s.trim().split_once(':').ok_or_else(err)?;
+    let h: u32 = h.parse().map_err(|_| err())?;
+    let m: u32 = m.parse().map_err(|_| err())?;
+    if h > 23 || m > 59 {
+        return Err(err());
+    }
+    Ok(h * 60 + m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn weekdays() -> Vec<String> {
+        ["mon", "tue", "wed", "thu", "fri"].iter().map(|s| s.to_string()).collect()
+    }
+
+    fn range(start: &str, end: &str) -> TimeRange {
+        TimeRange { start: start.to_string(), end: end.to_string() }
+    }
+
+    #[test]
+    fn test_school_hours() {
+        let s = Schedule::new("s", "UTC", &weekdays(), &[range("08:00", "15:00")]).unwrap();
+        // 2026-10-19 is a Monday
+        assert!(s.is_active_at(Utc.with_ymd_and_hms(2026, 10, 19, 8, 0, 0).unwrap()));
+        assert!(s.is_active_at(Utc.with_ymd_and_hms(2026, 10, 19, 14, 59, 0).unwrap()));
+        assert!(!s.is_active_at(Utc.with_ymd_and_hms(2026, 10, 19, 15, 0, 0).unwrap()));
+        assert!(!s.is_active_at(Utc.with_ymd_and_hms(2026, 10, 18, 10, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_timezone_applied() {
+        let s = Schedule::new("s", "Europe/Berlin", &weekdays(), &[range("08:00", "15:00")]).unwrap();
+        // 06:30 UTC = 08:30 CEST
+        assert!(s.is_active_at(Utc.with_ymd_and_hms(2026, 7, 6, 6, 30, 0).unwrap()));
+        assert!(!s.is_active_at(Utc.with_ymd_and_hms(2026, 7, 6, 13, 30, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_range_wraps_midnight() {
+        let days = vec!["fri".to_string()];
+        let s = Schedule::new("s", "UTC", &days, &[range("22:00", "06:00")]).unwrap();
+        // 2026-10-23 is a Friday
+        assert!(s.is_active_at(Utc.with_ymd_and_hms(2026, 10, 23, 23, 0, 0).unwrap()));
+        assert!(s.is_active_at(Utc.with_ymd_and_hms(2026, 10, 24, 5, 59, 0).unwrap()));
+        assert!(!s.is_active_at(Utc.with_ymd_and_hms(2026, 10, 24, 22, 30, 0).unwrap()));
+        assert!(!s.is_active_at(Utc.with_ymd_and_hms(2026, 10, 23, 5, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_next_transition() {
+        let s = Schedule::new("s", "UTC", &weekdays(), &[range("08:00", "15:00")]).unwrap();
+        let friday_noon = Utc.with_ymd_and_hms(2026, 10, 23, 12, 0, 0).unwrap();
+        assert_eq!(s.next_transition(friday_noon), Some(Utc.with_ymd_and_hms(2026, 10, 23, 15, 0, 0).unwrap()));
+        let friday_evening = Utc.with_ymd_and_hms(2026, 10, 23, 18, 0, 0).unwrap();
+        assert_eq!(s.next_transition(friday_evening), Some(Utc.with_ymd_and_hms(2026, 10, 26, 8, 0, 0).unwrap()));
+
+        let always = Schedule::new("a", "UTC", &["mon", "tue", "wed", "thu", "fri", "sat", "sun"].map(String::from), &[range("00:00", "00:00")]).unwrap();
+        assert_eq!(always.next_transition(friday_noon), None);
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(Schedule::new("s", "Mars/Olympus", &weekdays(), &[range("08:00", "15:00")]).is_err());
+        assert!(Schedule::new("s", "UTC", &["funday".to_string()], &[range("08:00", "15:00")]).is_err());
+        assert!(Schedule::new("s", "UTC", &weekdays(), &[range("24:00", "15:00")]).is_err());
+        assert!(Schedule::new("s", "UTC", &weekdays(), &[]).is_err());
+        assert!(Schedule::new("s", "UTC", &[], &[range("08:00", "15:00")]).is_err());
+    }
+}
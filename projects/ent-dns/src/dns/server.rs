@@ -3,9 +3,12 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{UdpSocket, TcpListener};
 use std::sync::Arc;
 use super::handler::DnsHandler;
+use crate::proxy_protocol::{self, TrustedProxies};
 
 /// Start the DNS server (UDP + TCP) using the provided shared handler.
-pub async fn run(handler: Arc<DnsHandler>, bind_addr: String) -> Result<()> {
+/// Connections and datagrams from `proxies` carry a PROXY protocol header
+/// naming the real client.
+pub async fn run(handler: Arc<DnsHandler>, bind_addr: String, proxies: Arc<TrustedProxies>) -> Result<()> {
 
     // ── UDP server ──────────────────────────────────────────────
     let udp_socket = Arc::new(UdpSocket::bind(&bind_addr).await?);
@@ -16,13 +19,21 @@ pub async fn run(handler: Arc<DnsHandler>, bind_addr: String) -> Result<()> {
     tracing::info!("DNS TCP listening on {}", bind_addr);
 
     let handler_tcp = handler.clone();
+    let proxies_tcp = proxies.clone();
     tokio::spawn(async move {
         loop {
             match tcp_listener.accept().await {
                 Ok((mut stream, peer)) => {
                     let h = handler_tcp.clone();
-                    let client_ip = peer.ip().to_string();
+                    let proxies = proxies_tcp.clone();
                     tokio::spawn(async move {
+                        let client_ip = match proxy_protocol::accept_client(&mut stream, peer, &proxies).await {
+                            Ok(client) => client.ip().to_string(),
+                            Err(e) => {
+                                tracing::debug!("Dropping DNS TCP connection from proxy {}: {}", peer, e);
+                                return;
+                            }
+                        };
                         // DNS/TCP: 2-byte big-endian length prefix before each message
                         let mut len_buf = [0u8; 2];
                         if stream.read_exact(&mut len_buf).await.is_err() { return; }
@@ -52,10 +63,24 @@ pub async fn run(handler: Arc<DnsHandler>, bind_addr: String) -> Result<()> {
         let mut buf = vec![0u8; 4096];
         match udp_socket.recv_from(&mut buf).await {
             Ok((len, peer)) => {
-                let data = buf[..len].to_vec();
+                // PROXY protocol v2 prefixes each datagram from a trusted proxy;
+                // the response goes back to the proxy without a header
+                let (data, client_ip) = if proxies.contains(peer.ip()) {
+                    match proxy_protocol::parse_v2(&buf[..len]) {
+                        Ok((header_len, source)) => (
+                            buf[header_len..len].to_vec(),
+                            source.unwrap_or(peer).ip().to_string(),
+                        ),
+                        Err(e) => {
+                            tracing::debug!("Dropping DNS UDP datagram from proxy {}: {}", peer, e);
+                            continue;
+                        }
+                    }
+                } else {
+                    (buf[..len].to_vec(), peer.ip().to_string())
+                };
                 let handler = handler.clone();
                 let socket = udp_socket.clone();
-                let client_ip = peer.ip().to_string();
 
                 // Spawn task for DNS processing
                 tokio::spawn(async move {
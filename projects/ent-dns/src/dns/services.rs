@@ -0,0 +1,192 @@
+//! Built-in catalog of blockable services.
+//!
+//! Each service is a named bundle of AdGuard-syntax rules covering the domains
+//! the service needs to work.  Services are selected per client
+//! (`clients.blocked_services`) or per client group (`client_group_rules` rows
+//! with `rule_type = 'blocked_service'`).  Rule sets are compiled once on first use.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use super::rules::RuleSet;
+
+/// A blockable service from the built-in catalog.
+#[derive(Debug, Clone, Copy)]
+pub struct Service {
+    /// Stable identifier used in bindings and log reasons.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub rules: &'static [&'static str],
+}
+
+pub const SERVICES: &[Service] = &[
+    Service {
+        id: "tiktok",
+        name: "TikTok",
+        rules: &["||tiktok.com^", "||tiktokv.com^", "||tiktokcdn.com^", "||tiktokcdn-us.com^", "||byteoversea.com^", "||ibytedtos.com^", "||musical.ly^"],
+    },
+    Service {
+        id: "discord",
+        name: "Discord",
+        rules: &["||discord.com^", "||discord.gg^", "||discord.media^", "||discordapp.com^", "||discordapp.net^", "||discord.co^"],
+    },
+    Service {
+        id: "steam",
+        name: "Steam",
+        rules: &["||steampowered.com^", "||steamcommunity.com^", "||steamstatic.com^", "||steamcontent.com^", "||steamserver.net^", "||steamgames.com^"],
+    },
+    Service {
+        id: "youtube",
+        name: "YouTube",
+        rules: &["||youtube.com^", "||youtu.be^", "||googlevideo.com^", "||ytimg.com^", "||youtube-nocookie.com^", "||youtubei.googleapis.com^"],
+    },
+    Service {
+        id: "facebook",
+        name: "Facebook",
+        rules: &["||facebook.com^", "||facebook.net^", "||fbcdn.net^", "||fb.com^", "||fb.me^", "||fbsbx.com^", "||messenger.com^"],
+    },
+    Service {
+        id: "instagram",
+        name: "Instagram",
+        rules: &["||instagram.com^", "||cdninstagram.com^", "||ig.me^", "||instagr.am^"],
+    },
+    Service {
+        id: "twitter",
+        name: "X (Twitter)",
+        rules: &["||twitter.com^", "||x.com^", "||twimg.com^", "||t.co^", "||twttr.com^"],
+    },
+    Service {
+        id: "snapchat",
+        name: "Snapchat",
+        rules: &["||snapchat.com^", "||snap.com^", "||snapkit.com^", "||sc-cdn.net^", "||sc-static.net^", "||snapads.com^"],
+    },
+    Service {
+        id: "whatsapp",
+        name: "WhatsApp",
+        rules: &["||whatsapp.com^", "||whatsapp.net^", "||wa.me^"],
+    },
+    Service {
+        id: "telegram",
+        name: "Telegram",
+        rules: &["||telegram.org^", "||telegram.me^", "||t.me^", "||telegra.ph^", "||tdesktop.com^"],
+    },
+    Service {
+        id: "reddit",
+        name: "Reddit",
+        rules: &["||reddit.com^", "||redd.it^", "||redditmedia.com^", "||redditstatic.com^"],
+    },
+    Service {
+        id: "twitch",
+        name: "Twitch",
+        rules: &["||twitch.tv^", "||twitchcdn.net^", "||twitchsvc.net^", "||ttvnw.net^", "||jtvnw.net^"],
+    },
+    Service {
+        id: "netflix",
+        name: "Netflix",
+        rules: &["||netflix.com^", "||netflix.net^", "||nflxext.com^", "||nflximg.com^", "||nflximg.net^", "||nflxso.net^", "||nflxvideo.net^"],
+    },
+    Service {
+        id: "spotify",
+        name: "Spotify",
+        rules: &["||spotify.com^", "||scdn.co^", "||spotifycdn.com^", "||spotify.net^", "||audio-ak-spotify-com.akamaized.net^"],
+    },
+    Service {
+        id: "roblox",
+        name: "Roblox",
+        rules: &["||roblox.com^", "||rbxcdn.com^", "||rbx.com^", "||robloxlabs.com^"],
+    },
+    Service {
+        id: "epic_games",
+        name: "Epic Games / Fortnite",
+        rules: &["||epicgames.com^", "||epicgames.dev^", "||unrealengine.com^", "||fortnite.com^", "||easyanticheat.net^"],
+    },
+    Service {
+        id: "minecraft",
+        name: "Minecraft",
+        rules: &["||minecraft.net^", "||mojang.com^", "||minecraftservices.com^"],
+    },
+    Service {
+        id: "pinterest",
+        name: "Pinterest",
+        rules: &["||pinterest.com^", "||pinimg.com^", "||pin.it^"],
+    },
+    Service {
+        id: "tinder",
+        name: "Tinder",
+        rules: &["||tinder.com^", "||gotinder.com^", "||tindersparks.com^"],
+    },
+    Service {
+        id: "9gag",
+        name: "9GAG",
+        rules: &["||9gag.com^", "||9cache.com^"],
+    },
+];
+
+/// Compiled rule set per service id.
+static COMPILED: LazyLock<HashMap<&'static str, RuleSet>> = LazyLock::new(|| {
+    SERVICES
+        .iter()
+        .map(|s| {
+            let mut rs = RuleSet::new();
+            for rule in s.rules {
+                rs.add_rule(rule);
+            }
+            (s.id, rs)
+        })
+        .collect()
+});
+
+/// Look up a service by id.
+pub fn find(id: &str) -> Option<&'static Service> {
+    SERVICES.iter().find(|s| s.id == id)
+}
+
+/// Return the first of `service_ids` whose rules block `domain`.
+pub fn check(domain: &str, service_ids: &[String]) -> Option<&'static str> {
+    service_ids.iter().find_map(|id| {
+        let (key, rs) = COMPILED.get_key_value(id.as_str())?;
+        rs.is_blocked(domain).then_some(*key)
+    })
+}
+
+/// Log reason recorded for a query blocked by `service_id`.
+pub fn block_reason(service_id: &str) -> String {
+    format!("service:{}", service_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_ids_unique() {
+        for (i, a) in SERVICES.iter().enumerate() {
+            for b in &SERVICES[i + 1..] {
+                assert_ne!(a.id, b.id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_all_service_rules_valid() {
+        for service in SERVICES {
+            let mut rs = RuleSet::new();
+            for rule in service.rules {
+                assert!(rs.add_rule(rule), "invalid rule {} in service {}", rule, service.id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_matches_selected_services_only() {
+        let selected = vec!["discord".to_string(), "steam".to_string()];
+        assert_eq!(check("cdn.discordapp.com", &selected), Some("discord"));
+        assert_eq!(check("store.steampowered.com", &selected), Some("steam"));
+        assert_eq!(check("www.tiktok.com", &selected), None);
+        assert_eq!(check("cdn.discordapp.com", &[]), None);
+    }
+
+    #[test]
+    fn test_check_ignores_unknown_ids() {
+        assert_eq!(check("discord.com", &["unknown".to_string()]), None);
+    }
+}
@@ -1,176 +1,550 @@
 //! Remote filter list subscription module.
 //!
-//! Handles downloading and parsing remote filter lists in:
-//! - AdGuard filter syntax (||domain^, @@domain, etc.)
-//! - Hosts file format (IP domain)
+//! Handles fetching filter lists and storing their rules.  Sources are
+//! `http(s)://` URLs, `file:///path` URLs (re-synced when the file's mtime
+//! changes, see [`sync_changed_local_lists`]) and inline bodies uploaded
+//! through the API (see [`import_filter_content`]).  Parsing lives in
+//! [`list_formats`](super::list_formats).
+//!
+//! Remote lists are fetched with conditional requests (ETag/Last-Modified)
+//! and parsed line by line while the body streams in.  Before fetched rules
+//! replace the active ones they pass sanity checks (see [`check_update`]); a
+//! rejected update keeps the previous rules.  Every sync attempt is recorded
+//! in `filter_sync_history` and its outcome in `filter_lists.sync_status`.
 
 use anyhow::{Context, Result};
 use chrono::Utc;
-use regex::Regex;
-use std::sync::LazyLock;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncBufReadExt;
 use tracing::info;
 
 use crate::db::DbPool;
+use super::list_formats::{ListFormat, ListParser, ParsedList};
+use super::list_versions;
+
+pub use super::list_formats::{parse_adguard_rules, parse_hosts_rules};
 
 /// HTTP client timeout for fetching remote lists
-const FETCH_TIMEOUT_SECS: u64 = 30;
-/// Maximum response size (10 MB)
-const MAX_RESPONSE_SIZE: usize = 10 * 1024 * 1024;
+const FETCH_TIMEOUT_SECS: u64 = 120;
+/// Maximum size of an uploaded list body (10 MB); uploads are held in memory
+pub const MAX_LIST_SIZE: usize = 10 * 1024 * 1024;
+/// Maximum size of a streamed list (remote or local)
+const MAX_STREAM_SIZE: u64 = 256 * 1024 * 1024;
+/// Sync history entries kept per list
+const HISTORY_PER_LIST: i64 = 50;
+/// URL scheme of local filter lists
+pub const FILE_SCHEME: &str = "file://";
+/// Leading bytes of a list kept for HTML sniffing
+const SNIFF_BYTES: usize = 1024;
+
+/// Stored cache validators and format of a list.
+#[derive(sqlx::FromRow, Default)]
+struct ListSource {
+    format: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// A fetched and parsed list.
+pub struct Fetched {
+    pub format: ListFormat,
+    pub parsed: ParsedList,
+    pub bytes: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub content_type: Option<String>,
+    /// The body starts like an HTML document (typically an error page).
+    pub html: bool,
+}
 
-/// AdGuard rule patterns
-static ADGUARD_DOMAIN_RULE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"^\|\|([a-zA-Z0-9][a-zA-Z0-9_.-]*[a-zA-Z0-9])\^?$").expect("Invalid regex")
-});
+/// A fetched update failed the sanity checks; the previous rules stay active.
+#[derive(Debug, thiserror::Error)]
+#[error("Update rejected: {0}")]
+pub struct SyncRejected(pub String);
+
+/// Sanity-check thresholds (`filter_sync_*` settings).
+#[derive(Debug, Clone, Copy)]
+pub struct SyncGuard {
+    /// Fewer rules than this reject the update (0 disables the check).
+    pub min_rules: i64,
+    /// A drop of more than this percentage of the last good version's rules
+    /// rejects the update (100 disables the check).
+    pub max_shrink_percent: i64,
+}
 
-static ADGUARD_EXCEPTION: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"^@@\|\|([a-zA-Z0-9][a-zA-Z0-9_.-]*[a-zA-Z0-9])\^?$").expect("Invalid regex")
-});
+impl Default for SyncGuard {
+    fn default() -> Self {
+        Self { min_rules: 1, max_shrink_percent: 50 }
+    }
+}
 
-/// Fetch remote filter list content
-pub async fn fetch_remote_filter(url: &str) -> Result<String> {
+impl SyncGuard {
+    async fn load(pool: &DbPool) -> Self {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT key, value FROM settings WHERE key LIKE 'filter_sync_%'"
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default();
+        let mut guard = Self::default();
+        for (key, value) in rows {
+            match key.as_str() {
+                "filter_sync_min_rules" => guard.min_rules = value.parse().unwrap_or(guard.min_rules),
+                "filter_sync_max_shrink_percent" => {
+                    guard.max_shrink_percent = value.parse().unwrap_or(guard.max_shrink_percent)
+                }
+                _ => {}
+            }
+        }
+        guard
+    }
+}
+
+/// Check a fetched list before it replaces the active rules.  `baseline` is
+/// the rule count of the last good version.
+pub fn check_update(guard: &SyncGuard, fetched: &Fetched, baseline: Option<i64>) -> Result<(), SyncRejected> {
+    if fetched.content_type.as_deref().is_some_and(|ct| ct.to_ascii_lowercase().starts_with("text/html")) {
+        return Err(SyncRejected("served as text/html (likely an error page)".to_string()));
+    }
+    if fetched.html {
+        return Err(SyncRejected("body is an HTML document, not a filter list".to_string()));
+    }
+    let count = fetched.parsed.rules.len() as i64;
+    if count < guard.min_rules {
+        return Err(SyncRejected(format!("{} rules, minimum is {}", count, guard.min_rules)));
+    }
+    if let Some(base) = baseline.filter(|b| *b > 0 && count < *b) {
+        let shrink = (base - count) * 100 / base;
+        if shrink > guard.max_shrink_percent {
+            return Err(SyncRejected(format!(
+                "rule count dropped {}% ({} → {}), limit is {}%",
+                shrink, base, count, guard.max_shrink_percent
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// True if the leading bytes of a body look like an HTML document.
+fn looks_like_html(head: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(head).to_ascii_lowercase();
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    text.starts_with("<!doctype html")
+        || text.starts_with("<html")
+        || text.starts_with("<?xml")
+        || text.contains("<head>")
+        || text.contains("<body")
+}
+
+pub enum FetchResult {
+    /// 304: the list is unchanged since the stored validators.
+    NotModified,
+    Fetched(Fetched),
+}
+
+/// Outcome of one sync, as recorded in the history.
+struct SyncOutcome {
+    status: &'static str,
+    bytes: u64,
+    rule_count: i64,
+}
+
+/// Fetch and parse a remote list.  `etag`/`last_modified` from the previous
+/// sync make the request conditional.
+pub async fn fetch_remote_filter(
+    url: &str,
+    format: ListFormat,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> Result<FetchResult> {
     let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(FETCH_TIMEOUT_SECS))
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
         .user_agent("Ent-DNS/1.0")
         .build()
         .context("Failed to create HTTP client")?;
 
-    let response = client
-        .get(url)
-        .send()
-        .await
-        .context("Failed to fetch filter list")?;
+    let mut request = client.get(url);
+    if let Some(etag) = etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = last_modified {
+        request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+    }
+    let mut response = request.send().await.context("Failed to fetch filter list")?;
 
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(FetchResult::NotModified);
+    }
     if !response.status().is_success() {
         anyhow::bail!("HTTP error: {}", response.status());
     }
 
     // Reject early using Content-Length header before reading any body (M-3 fix)
     if let Some(len) = response.content_length() {
-        if len > MAX_RESPONSE_SIZE as u64 {
+        if len > MAX_STREAM_SIZE {
             anyhow::bail!("Response too large: {} bytes (Content-Length)", len);
         }
     }
+    let header = |name| {
+        response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+    };
+    let etag = header(reqwest::header::ETAG);
+    let last_modified = header(reqwest::header::LAST_MODIFIED);
+    let content_type = header(reqwest::header::CONTENT_TYPE);
+
+    // Parse complete lines as chunks arrive; only a partial line is buffered
+    let mut parser = ListParser::new(format);
+    let mut buf: Vec<u8> = Vec::new();
+    let mut head: Vec<u8> = Vec::new();
+    let mut bytes = 0u64;
+    while let Some(chunk) = response.chunk().await.context("Failed to read response body")? {
+        bytes += chunk.len() as u64;
+        if bytes > MAX_STREAM_SIZE {
+            anyhow::bail!("Response too large: more than {} bytes", MAX_STREAM_SIZE);
+        }
+        if head.len() < SNIFF_BYTES {
+            head.extend_from_slice(&chunk[..chunk.len().min(SNIFF_BYTES - head.len())]);
+        }
+        buf.extend_from_slice(&chunk);
+        if let Some(end) = buf.iter().rposition(|&b| b == b'\n') {
+            push_lines(&mut parser, &buf[..end]);
+            buf.drain(..=end);
+        }
+    }
+    push_lines(&mut parser, &buf);
+
+    let (format, parsed) = parser.finish();
+    Ok(FetchResult::Fetched(Fetched {
+        format,
+        parsed,
+        bytes,
+        etag,
+        last_modified,
+        content_type,
+        html: looks_like_html(&head),
+    }))
+}
 
-    // Read body as raw bytes to enforce size limit before UTF-8 decoding
-    let bytes = response
-        .bytes()
-        .await
-        .context("Failed to read response body")?;
+fn push_lines(parser: &mut ListParser, data: &[u8]) {
+    for line in data.split(|&b| b == b'\n') {
+        parser.push_line(String::from_utf8_lossy(line).trim_end_matches('\r'));
+    }
+}
+
+/// Path of a `file:///path` URL.
+pub fn local_path(url: &str) -> Option<&Path> {
+    url.strip_prefix(FILE_SCHEME).map(Path::new)
+}
 
-    if bytes.len() > MAX_RESPONSE_SIZE {
-        anyhow::bail!("Response too large: {} bytes", bytes.len());
+/// Read and parse a local filter list line by line.
+pub async fn read_local_filter(path: &Path, format: ListFormat) -> Result<Fetched> {
+    let meta = tokio::fs::metadata(path).await
+        .with_context(|| format!("Failed to stat {}", path.display()))?;
+    if meta.len() > MAX_STREAM_SIZE {
+        anyhow::bail!("File too large: {} bytes", meta.len());
     }
+    let file = tokio::fs::File::open(path).await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut reader = tokio::io::BufReader::new(file);
+    let mut parser = ListParser::new(format);
+    let mut line = Vec::new();
+    let mut head: Vec<u8> = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        if head.len() < SNIFF_BYTES {
+            head.extend_from_slice(&line[..line.len().min(SNIFF_BYTES - head.len())]);
+        }
+        push_lines(&mut parser, line.strip_suffix(b"\n").unwrap_or(&line));
+    }
+    let (format, parsed) = parser.finish();
+    Ok(Fetched {
+        format,
+        parsed,
+        bytes: meta.len(),
+        etag: None,
+        last_modified: None,
+        content_type: None,
+        html: looks_like_html(&head),
+    })
+}
 
-    let content = String::from_utf8(bytes.to_vec())
-        .context("Filter list response is not valid UTF-8")?;
+/// Modification time of a local list, as stored in `filter_lists.source_mtime`.
+async fn file_mtime(path: &Path) -> Option<String> {
+    let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+    Some(chrono::DateTime::<Utc>::from(modified).to_rfc3339())
+}
 
-    Ok(content)
+async fn list_source(pool: &DbPool, filter_id: &str) -> Result<ListSource> {
+    let source: Option<ListSource> = sqlx::query_as(
+        "SELECT format, etag, last_modified FROM filter_lists WHERE id = ?"
+    )
+    .bind(filter_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to read filter list")?;
+    Ok(source.unwrap_or_default())
 }
 
-/// Parse AdGuard filter rules from content
-pub fn parse_adguard_rules(content: &str) -> (Vec<String>, Vec<String>) {
-    let mut block_rules = Vec::new();
-    let mut allow_rules = Vec::new();
+fn configured_format(source: &ListSource) -> ListFormat {
+    ListFormat::parse(&source.format).unwrap_or(ListFormat::Auto)
+}
 
-    for line in content.lines() {
-        let line = line.trim();
+/// Sync a filter list from its URL (remote or `file://`): fetch, parse, and store rules
+pub async fn sync_filter_list(pool: &DbPool, filter_id: &str, url: &str) -> Result<i64> {
+    sync_with(pool, filter_id, url, false).await
+}
 
-        // Skip empty lines and comments
-        if line.is_empty() || line.starts_with('!') || line.starts_with('#') {
-            continue;
-        }
+/// Like [`sync_filter_list`], but applies the update even if it fails the
+/// sanity checks (for an admin confirming an intended change).
+pub async fn force_sync_filter_list(pool: &DbPool, filter_id: &str, url: &str) -> Result<i64> {
+    sync_with(pool, filter_id, url, true).await
+}
 
-        // Skip CSS selectors and script rules
-        if line.contains("##") || line.contains("#@#") || line.contains("#%#") {
-            continue;
-        }
+async fn sync_with(pool: &DbPool, filter_id: &str, url: &str, force: bool) -> Result<i64> {
+    info!("Syncing filter list {} from {}", filter_id, url);
+    let started_at = Utc::now().to_rfc3339();
+    let started = Instant::now();
+    let result = sync_source(pool, filter_id, url, force).await;
+    record_history(pool, filter_id, &started_at, started, &result).await;
+    record_status(pool, filter_id, &result).await;
+    result.map(|outcome| outcome.rule_count)
+}
 
-        // Skip regex rules (too complex for now)
-        if line.starts_with('/') && line.ends_with('/') {
-            continue;
-        }
+/// Reject `fetched` if it fails the sanity checks (unless forced).
+async fn guard_update(pool: &DbPool, filter_id: &str, fetched: &Fetched, force: bool) -> Result<()> {
+    if force {
+        return Ok(());
+    }
+    let baseline: Option<i64> = sqlx::query_scalar(
+        "SELECT rule_count FROM filter_list_versions WHERE filter_id = ? ORDER BY id DESC LIMIT 1"
+    )
+    .bind(filter_id)
+    .fetch_optional(pool)
+    .await?;
+    check_update(&SyncGuard::load(pool).await, fetched, baseline)?;
+    Ok(())
+}
 
-        // Parse exception rules (@@||domain^)
-        if let Some(caps) = ADGUARD_EXCEPTION.captures(line) {
-            if let Some(domain) = caps.get(1) {
-                // Bug fix: append `^` so the rule matches AdGuard syntax expected by RuleSet
-                allow_rules.push(format!("@@||{}^", domain.as_str()));
-            }
-            continue;
-        }
+async fn sync_source(pool: &DbPool, filter_id: &str, url: &str, force: bool) -> Result<SyncOutcome> {
+    let source = list_source(pool, filter_id).await?;
+    let format = configured_format(&source);
+
+    if let Some(path) = local_path(url) {
+        // Take the mtime before reading so a write during the read triggers another sync
+        let mtime = file_mtime(path).await;
+        let fetched = read_local_filter(path, format).await?;
+        // A rejected file is not retried until it changes again
+        sqlx::query("UPDATE filter_lists SET source_mtime = ? WHERE id = ?")
+            .bind(&mtime)
+            .bind(filter_id)
+            .execute(pool)
+            .await
+            .context("Failed to update filter list mtime")?;
+        guard_update(pool, filter_id, &fetched, force).await?;
+        let bytes = fetched.bytes;
+        let rule_count = store_rules(pool, filter_id, fetched).await?;
+        return Ok(SyncOutcome { status: "updated", bytes, rule_count });
+    }
 
-        // Parse blocking rules (||domain^ or ||domain)
-        if let Some(caps) = ADGUARD_DOMAIN_RULE.captures(line) {
-            if let Some(domain) = caps.get(1) {
-                block_rules.push(format!("||{}^", domain.as_str()));
-            }
-            continue;
+    let fetched = fetch_remote_filter(url, format, source.etag.as_deref(), source.last_modified.as_deref())
+        .await
+        .context("Failed to fetch remote filter list")?;
+    match fetched {
+        FetchResult::NotModified => {
+            info!("Filter {} not modified", filter_id);
+            // Restart the refresh interval; rules stay as they are
+            let rule_count: i64 = sqlx::query_scalar(
+                "UPDATE filter_lists SET last_updated = ? WHERE id = ? RETURNING rule_count"
+            )
+            .bind(Utc::now().to_rfc3339())
+            .bind(filter_id)
+            .fetch_optional(pool)
+            .await?
+            .unwrap_or(0);
+            Ok(SyncOutcome { status: "not_modified", bytes: 0, rule_count })
         }
-
-        // Simple domain blocking (domain without special chars)
-        if !line.contains(['/', ':', '*', '^', '|']) {
-            // Check if it looks like a domain
-            if line.contains('.') && !line.starts_with('.') && !line.ends_with('.') {
-                block_rules.push(format!("||{}^", line));
-            }
+        FetchResult::Fetched(fetched) => {
+            guard_update(pool, filter_id, &fetched, force).await?;
+            let bytes = fetched.bytes;
+            let (etag, last_modified) = (fetched.etag.clone(), fetched.last_modified.clone());
+            let rule_count = store_rules(pool, filter_id, fetched).await?;
+            sqlx::query("UPDATE filter_lists SET etag = ?, last_modified = ? WHERE id = ?")
+                .bind(&etag)
+                .bind(&last_modified)
+                .bind(filter_id)
+                .execute(pool)
+                .await
+                .context("Failed to update filter list validators")?;
+            Ok(SyncOutcome { status: "updated", bytes, rule_count })
         }
     }
+}
 
-    (block_rules, allow_rules)
+/// Append a sync attempt to the list's history, keeping the newest entries.
+async fn record_history(
+    pool: &DbPool,
+    filter_id: &str,
+    started_at: &str,
+    started: Instant,
+    result: &Result<SyncOutcome>,
+) {
+    let (status, bytes, rule_count, error) = match result {
+        Ok(o) => (o.status, o.bytes as i64, Some(o.rule_count), None),
+        Err(e) => (failure_status(e), 0, None, Some(format!("{:#}", e))),
+    };
+    let inserted = sqlx::query(
+        "INSERT INTO filter_sync_history (filter_id, started_at, duration_ms, bytes, status, rule_count, error)
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(filter_id)
+    .bind(started_at)
+    .bind(started.elapsed().as_millis() as i64)
+    .bind(bytes)
+    .bind(status)
+    .bind(rule_count)
+    .bind(&error)
+    .execute(pool)
+    .await;
+    let pruned = sqlx::query(
+        "DELETE FROM filter_sync_history WHERE filter_id = ? AND id NOT IN
+            (SELECT id FROM filter_sync_history WHERE filter_id = ? ORDER BY id DESC LIMIT ?)"
+    )
+    .bind(filter_id)
+    .bind(filter_id)
+    .bind(HISTORY_PER_LIST)
+    .execute(pool)
+    .await;
+    if let Err(e) = inserted.and(pruned) {
+        tracing::warn!("Failed to record sync history for filter {}: {}", filter_id, e);
+    }
 }
 
-/// Parse hosts file format rules
-pub fn parse_hosts_rules(content: &str) -> Vec<String> {
-    let mut rules = Vec::new();
+fn failure_status(e: &anyhow::Error) -> &'static str {
+    if e.downcast_ref::<SyncRejected>().is_some() { "rejected" } else { "failed" }
+}
 
-    for line in content.lines() {
-        let line = line.trim();
+/// Show the outcome of the latest sync on the list itself.
+async fn record_status(pool: &DbPool, filter_id: &str, result: &Result<SyncOutcome>) {
+    let (status, error) = match result {
+        Ok(_) => ("ok", None),
+        Err(e) => {
+            tracing::warn!("Filter {} sync {}: {:#}", filter_id, failure_status(e), e);
+            (failure_status(e), Some(format!("{:#}", e)))
+        }
+    };
+    let updated = sqlx::query("UPDATE filter_lists SET sync_status = ?, sync_error = ? WHERE id = ?")
+        .bind(status)
+        .bind(&error)
+        .bind(filter_id)
+        .execute(pool)
+        .await;
+    if let Err(e) = updated {
+        tracing::warn!("Failed to record sync status for filter {}: {}", filter_id, e);
+    }
+}
 
-        // Skip empty lines and comments
-        if line.is_empty() || line.starts_with('#') {
+/// Re-sync enabled `file://` lists whose file changed since the last sync.
+/// Returns the number of lists synced.
+pub async fn sync_changed_local_lists(pool: &DbPool) -> Result<usize> {
+    let lists: Vec<(String, String, Option<String>)> = sqlx::query_as(
+        "SELECT id, url, source_mtime FROM filter_lists WHERE is_enabled = 1 AND url LIKE 'file://%'"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut synced = 0;
+    for (id, url, last_mtime) in lists {
+        let Some(path) = local_path(&url) else { continue };
+        let mtime = file_mtime(path).await;
+        if mtime.is_none() || mtime == last_mtime {
             continue;
         }
-
-        // Parse "IP domain" format
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 2 {
-            let domain = parts[1];
-            // Validate domain format
-            if domain.contains('.')
-                && !domain.starts_with('.')
-                && !domain.ends_with('.')
-                && domain.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '-' || c == '_')
-            {
-                // Create AdGuard-style blocking rule
-                rules.push(format!("||{}^", domain));
+        match sync_filter_list(pool, &id, &url).await {
+            Ok(n) => {
+                info!("Local filter {} changed: {} rules", id, n);
+                synced += 1;
             }
+            Err(e) => tracing::warn!("Local filter {}: {}", id, e),
         }
     }
+    Ok(synced)
+}
 
-    rules
+/// Parse an uploaded list body in the list's format and replace its stored
+/// rules; returns the rule count.
+pub async fn import_filter_content(pool: &DbPool, filter_id: &str, content: &str) -> Result<i64> {
+    let started_at = Utc::now().to_rfc3339();
+    let started = Instant::now();
+    let result = async {
+        let format = configured_format(&list_source(pool, filter_id).await?);
+        let mut parser = ListParser::new(format);
+        for line in content.lines() {
+            parser.push_line(line);
+        }
+        let (format, parsed) = parser.finish();
+        let fetched = Fetched {
+            format,
+            parsed,
+            bytes: content.len() as u64,
+            etag: None,
+            last_modified: None,
+            content_type: None,
+            html: false,
+        };
+        let rule_count = store_rules(pool, filter_id, fetched).await?;
+        Ok(SyncOutcome { status: "updated", bytes: content.len() as u64, rule_count })
+    }
+    .await;
+    record_history(pool, filter_id, &started_at, started, &result).await;
+    result.map(|outcome| outcome.rule_count)
 }
 
-/// Sync a remote filter list: download, parse, and store rules
-pub async fn sync_filter_list(pool: &DbPool, filter_id: &str, url: &str) -> Result<i64> {
-    info!("Syncing filter list {} from {}", filter_id, url);
+/// Store freshly parsed rules: record them as a version and, unless the list
+/// is pinned to an earlier version, make them the active rules.
+async fn store_rules(pool: &DbPool, filter_id: &str, fetched: Fetched) -> Result<i64> {
+    let Fetched { format, parsed, .. } = fetched;
+    info!("Parsed {} rules for filter {} ({} format, {} unsupported entries skipped)",
+          parsed.rules.len(), filter_id, format.as_str(), parsed.unsupported);
 
-    // Fetch content
-    let content = fetch_remote_filter(url).await
-        .context("Failed to fetch remote filter list")?;
+    let version = list_versions::record_version(pool, filter_id, &parsed.rules).await?;
+    let pinned: Option<i64> = sqlx::query_scalar("SELECT pinned_version FROM filter_lists WHERE id = ?")
+        .bind(filter_id)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
 
-    // Detect format and parse
-    let (block_rules, allow_rules) = if is_hosts_format(&content) {
-        info!("Detected hosts file format for filter {}", filter_id);
-        (parse_hosts_rules(&content), Vec::new())
-    } else {
-        info!("Detected AdGuard filter format for filter {}", filter_id);
-        parse_adguard_rules(&content)
+    let now = Utc::now().to_rfc3339();
+    let rule_count = match pinned {
+        Some(pinned) => {
+            info!("Filter {} is pinned to version {}; version {} recorded but not applied",
+                  filter_id, pinned, version);
+            sqlx::query_scalar("SELECT rule_count FROM filter_lists WHERE id = ?")
+                .bind(filter_id)
+                .fetch_one(pool)
+                .await?
+        }
+        None => replace_rules(pool, filter_id, &parsed.rules).await?,
     };
 
-    let total_rules = block_rules.len() + allow_rules.len();
-    info!("Parsed {} rules for filter {} ({} block, {} allow)",
-          total_rules, filter_id, block_rules.len(), allow_rules.len());
+    // Update filter list metadata (outside the transaction — non-critical metadata)
+    sqlx::query("UPDATE filter_lists SET last_updated = ?, expires_hours = ? WHERE id = ?")
+        .bind(&now)
+        .bind(parsed.expires_hours)
+        .bind(filter_id)
+        .execute(pool)
+        .await
+        .context("Failed to update filter list metadata")?;
 
+    info!("Successfully synced filter {}: {} rules", filter_id, rule_count);
+    Ok(rule_count)
+}
+
+/// Replace the active rules of a list and update its `rule_count`.
+pub async fn replace_rules(pool: &DbPool, filter_id: &str, rules: &[String]) -> Result<i64> {
     // Wrap DELETE + INSERT in a transaction so a crash mid-sync never leaves rules empty (H-4 fix)
     let filter_prefix = format!("filter:{}", filter_id);
     let now = Utc::now().to_rfc3339();
@@ -184,15 +558,14 @@ pub async fn sync_filter_list(pool: &DbPool, filter_id: &str, url: &str) -> Resu
         .await
         .context("Failed to delete old rules")?;
 
-    // Insert new blocking rules
-    for rule in block_rules {
+    for rule in rules {
         let id = uuid::Uuid::new_v4().to_string();
         let result = sqlx::query(
             "INSERT INTO custom_rules (id, rule, comment, is_enabled, created_by, created_at)
              VALUES (?, ?, NULL, 1, ?, ?)"
         )
         .bind(&id)
-        .bind(&rule)
+        .bind(rule)
         .bind(&filter_prefix)
         .bind(&now)
         .execute(&mut *tx)
@@ -203,70 +576,17 @@ pub async fn sync_filter_list(pool: &DbPool, filter_id: &str, url: &str) -> Resu
         }
     }
 
-    // Insert new allow rules
-    for rule in allow_rules {
-        let id = uuid::Uuid::new_v4().to_string();
-        let result = sqlx::query(
-            "INSERT INTO custom_rules (id, rule, comment, is_enabled, created_by, created_at)
-             VALUES (?, ?, NULL, 1, ?, ?)"
-        )
-        .bind(&id)
-        .bind(&rule)
-        .bind(&filter_prefix)
-        .bind(&now)
+    sqlx::query("UPDATE filter_lists SET rule_count = ? WHERE id = ?")
+        .bind(inserted)
+        .bind(filter_id)
         .execute(&mut *tx)
-        .await;
-
-        if result.is_ok() {
-            inserted += 1;
-        }
-    }
+        .await
+        .context("Failed to update rule count")?;
 
     tx.commit().await.context("Failed to commit filter sync transaction")?;
-
-    // Update filter list metadata (outside the transaction — non-critical metadata)
-    sqlx::query(
-        "UPDATE filter_lists SET rule_count = ?, last_updated = ? WHERE id = ?"
-    )
-    .bind(inserted)
-    .bind(&now)
-    .bind(filter_id)
-    .execute(pool)
-    .await
-    .context("Failed to update filter list metadata")?;
-
-    info!("Successfully synced filter {}: {} rules", filter_id, inserted);
     Ok(inserted)
 }
 
-/// Check if content appears to be hosts file format
-fn is_hosts_format(content: &str) -> bool {
-    let mut hosts_lines = 0;
-    let mut total_lines = 0;
-
-    for line in content.lines().take(100) {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-
-        total_lines += 1;
-
-        // Check if line starts with an IP address
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 2 {
-            let first = parts[0];
-            // Check if first part looks like an IP address
-            if first.parse::<std::net::IpAddr>().is_ok() {
-                hosts_lines += 1;
-            }
-        }
-    }
-
-    // If more than 50% of lines are in hosts format, treat as hosts file
-    total_lines > 0 && (hosts_lines as f64 / total_lines as f64) > 0.5
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -301,4 +621,33 @@ mod tests {
         assert!(rules.contains(&"||ads.example.org^".to_string()));
         assert!(rules.contains(&"||tracker.net^".to_string()));
     }
+
+    fn fetched(content: &str, content_type: Option<&str>) -> Fetched {
+        let (format, parsed) = super::super::list_formats::parse(content, ListFormat::Auto);
+        Fetched {
+            format,
+            parsed,
+            bytes: content.len() as u64,
+            etag: None,
+            last_modified: None,
+            content_type: content_type.map(str::to_string),
+            html: looks_like_html(content.as_bytes()),
+        }
+    }
+
+    #[test]
+    fn test_check_update() {
+        let guard = SyncGuard { min_rules: 2, max_shrink_percent: 50 };
+        let list = "||a.example^\n||b.example^\n||c.example^\n";
+        assert!(check_update(&guard, &fetched(list, Some("text/plain")), Some(4)).is_ok());
+
+        // Shrinking from 10 to 3 rules (70%) exceeds the limit
+        assert!(check_update(&guard, &fetched(list, None), Some(10)).is_err());
+        // Too few rules
+        assert!(check_update(&guard, &fetched("||a.example^\n", None), None).is_err());
+        // Error pages
+        let page = "<!DOCTYPE html>\n<html><body>503 Service Unavailable</body></html>\n";
+        assert!(check_update(&guard, &fetched(page, None), None).is_err());
+        assert!(check_update(&guard, &fetched(list, Some("text/html; charset=utf-8")), None).is_err());
+    }
 }
@@ -0,0 +1,293 @@
+//! Threat-intelligence feeds and security events.
+//!
+//! Filter lists tagged with a `threat_category` (malware, phishing, c2, ...)
+//! and a `severity` are threat feeds.  Their rules are kept apart from the
+//! global blocklist; a hit blocks the query and produces a `SecurityEvent`
+//! that is stored in `security_events`, broadcast on its own WebSocket channel,
+//! counted in metrics and optionally pushed to an alert webhook.
+
+use chrono::Utc;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, RwLock};
+
+use super::rules::RuleSet;
+use crate::db::DbPool;
+use crate::metrics::DnsMetrics;
+
+pub const THREAT_CATEGORIES: &[&str] = &["malware", "phishing", "c2", "other"];
+
+/// An alert for the same client and domain is sent at most once per cooldown.
+const ALERT_COOLDOWN: Duration = Duration::from_secs(600);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "low" => Some(Severity::Low),
+            "medium" => Some(Severity::Medium),
+            "high" => Some(Severity::High),
+            "critical" => Some(Severity::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+pub fn is_threat_category(category: &str) -> bool {
+    THREAT_CATEGORIES.contains(&category)
+}
+
+/// A compiled threat feed.
+pub struct ThreatFeed {
+    pub filter_id: String,
+    pub name: String,
+    pub category: String,
+    pub severity: Severity,
+    pub rules: RuleSet,
+}
+
+/// The feed that matched a query.
+#[derive(Debug, Clone)]
+pub struct ThreatMatch {
+    pub filter_id: String,
+    pub feed_name: String,
+    pub category: String,
+    pub severity: Severity,
+}
+
+impl ThreatMatch {
+    /// Query-log reason for a blocked threat.
+    pub fn reason(&self) -> String {
+        format!("threat:{}", self.category)
+    }
+}
+
+/// Highest-severity feed blocking `domain`.
+pub fn check_feeds(feeds: &[ThreatFeed], domain: &str) -> Option<ThreatMatch> {
+    feeds
+        .iter()
+        .filter(|f| f.rules.is_blocked(domain))
+        .max_by_key(|f| f.severity)
+        .map(|f| ThreatMatch {
+            filter_id: f.filter_id.clone(),
+            feed_name: f.name.clone(),
+            category: f.category.clone(),
+            severity: f.severity,
+        })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityEvent {
+    pub time: String,
+    pub client_ip: String,
+    pub domain: String,
+    pub qtype: String,
+    pub filter_id: String,
+    pub feed_name: String,
+    pub threat_category: String,
+    pub severity: Severity,
+    pub alerted: bool,
+}
+
+/// Alert settings (`security_alert_*`).
+#[derive(Debug, Clone)]
+pub struct AlertConfig {
+    pub min_severity: Severity,
+    pub webhook_url: Option<String>,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self { min_severity: Severity::High, webhook_url: None }
+    }
+}
+
+/// Records security events: database, WebSocket broadcast, metrics and alerts.
+pub struct SecurityEvents {
+    db: DbPool,
+    metrics: Arc<DnsMetrics>,
+    tx: broadcast::Sender<serde_json::Value>,
+    alerts: RwLock<AlertConfig>,
+    /// (client_ip, domain) → last alert, for the alert cooldown.
+    last_alert: DashMap<(String, String), Instant>,
+    http: reqwest::Client,
+}
+
+impl SecurityEvents {
+    pub fn new(db: DbPool, metrics: Arc<DnsMetrics>) -> Self {
+        let (tx, _) = broadcast::channel(256);
+        Self {
+            db,
+            metrics,
+            tx,
+            alerts: RwLock::new(AlertConfig::default()),
+            last_alert: DashMap::new(),
+            http: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .user_agent("Ent-DNS/1.0")
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Channel carrying every recorded event (WebSocket streaming).
+    pub fn sender(&self) -> broadcast::Sender<serde_json::Value> {
+        self.tx.clone()
+    }
+
+    /// Re-read the `security_alert_*` settings.
+    pub async fn reload(&self) -> anyhow::Result<()> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT key, value FROM settings WHERE key LIKE 'security_alert_%'"
+        )
+        .fetch_all(&self.db)
+        .await?;
+        let mut config = AlertConfig::default();
+        for (key, value) in rows {
+            match key.as_str() {
+                "security_alert_min_severity" => {
+                    config.min_severity = Severity::parse(&value).unwrap_or(config.min_severity)
+                }
+                "security_alert_webhook_url" => {
+                    config.webhook_url = Some(value).filter(|v| !v.trim().is_empty())
+                }
+                _ => {}
+            }
+        }
+        *self.alerts.write().await = config;
+        Ok(())
+    }
+
+    /// Record a threat hit.  Never blocks the DNS path: the database write and
+    /// the webhook call run in background tasks.
+    pub async fn record(&self, client_ip: &str, domain: &str, qtype: &str, threat: &ThreatMatch) {
+        let alerts = self.alerts.read().await.clone();
+        let alerted = threat.severity >= alerts.min_severity && self.take_alert_slot(client_ip, domain);
+
+        let event = SecurityEvent {
+            time: Utc::now().to_rfc3339(),
+            client_ip: client_ip.to_string(),
+            domain: domain.to_string(),
+            qtype: qtype.to_string(),
+            filter_id: threat.filter_id.clone(),
+            feed_name: threat.feed_name.clone(),
+            threat_category: threat.category.clone(),
+            severity: threat.severity,
+            alerted,
+        };
+        self.metrics.inc_security_event(&event.threat_category, event.severity.as_str());
+        if alerted {
+            tracing::warn!(
+                "Security alert: {} {} threat {} from {} (feed {})",
+                event.severity.as_str(), event.threat_category, event.domain, event.client_ip, event.feed_name
+            );
+        }
+
+        if let Ok(json) = serde_json::to_value(&event) {
+            let _ = self.tx.send(json.clone());
+            if let (true, Some(url)) = (alerted, alerts.webhook_url) {
+                let http = self.http.clone();
+                tokio::spawn(async move {
+                    let payload = serde_json::json!({ "type": "security_event", "event": json });
+                    let request = http
+                        .post(&url)
+                        .header(reqwest::header::CONTENT_TYPE, "application/json")
+                        .body(payload.to_string());
+                    if let Err(e) = request.send().await.and_then(|r| r.error_for_status()) {
+                        tracing::warn!("Security alert webhook failed: {}", e);
+                    }
+                });
+            }
+        }
+
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            let result = sqlx::query(
+                "INSERT INTO security_events
+                    (time, client_ip, domain, qtype, filter_id, feed_name, threat_category, severity, alerted)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&event.time)
+            .bind(&event.client_ip)
+            .bind(&event.domain)
+            .bind(&event.qtype)
+            .bind(&event.filter_id)
+            .bind(&event.feed_name)
+            .bind(&event.threat_category)
+            .bind(event.severity.as_str())
+            .bind(event.alerted)
+            .execute(&db)
+            .await;
+            if let Err(e) = result {
+                tracing::warn!("Failed to store security event: {}", e);
+            }
+        });
+    }
+
+    /// True when no alert for this client and domain was sent within the cooldown.
+    fn take_alert_slot(&self, client_ip: &str, domain: &str) -> bool {
+        let now = Instant::now();
+        if self.last_alert.len() > 10_000 {
+            self.last_alert.retain(|_, t| now.duration_since(*t) < ALERT_COOLDOWN);
+        }
+        let key = (client_ip.to_string(), domain.to_string());
+        match self.last_alert.get(&key) {
+            Some(t) if now.duration_since(*t) < ALERT_COOLDOWN => false,
+            _ => {
+                self.last_alert.insert(key, now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(id: &str, severity: Severity, rule: &str) -> ThreatFeed {
+        let mut rules = RuleSet::new();
+        rules.add_rule(rule);
+        ThreatFeed {
+            filter_id: id.to_string(),
+            name: id.to_string(),
+            category: "malware".to_string(),
+            severity,
+            rules,
+        }
+    }
+
+    #[test]
+    fn test_highest_severity_wins() {
+        let feeds = vec![
+            feed("low", Severity::Low, "||bad.example^"),
+            feed("critical", Severity::Critical, "||bad.example^"),
+            feed("medium", Severity::Medium, "||other.example^"),
+        ];
+        let hit = check_feeds(&feeds, "cdn.bad.example").unwrap();
+        assert_eq!(hit.filter_id, "critical");
+        assert_eq!(hit.reason(), "threat:malware");
+        assert!(check_feeds(&feeds, "good.example").is_none());
+        assert!(Severity::parse("high").unwrap() > Severity::Medium);
+    }
+}
@@ -0,0 +1,155 @@
+//! DNS-over-TLS listener (RFC 7858).
+//!
+//! Each connection carries any number of length-prefixed DNS messages, like
+//! DNS/TCP, and is closed after `IDLE_TIMEOUT` without a query.  When
+//! `dns.client_id_domain` is set, a TLS server name of the form
+//! `{client_id}.{client_id_domain}` identifies the client; the SNI parsing is
+//! transport-agnostic so a DoQ listener can share it.  Connections from
+//! trusted proxies start with a PROXY protocol header, before the handshake.
+
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::{self, pki_types::{CertificateDer, PrivateKeyDer}};
+use tokio_rustls::TlsAcceptor;
+
+use super::client_index::is_valid_client_id;
+use super::handler::DnsHandler;
+use crate::proxy_protocol::{self, TrustedProxies};
+
+/// RFC 7858 §3.4: servers may close idle connections.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Server TLS config from PEM certificate chain and private key files.
+pub fn load_tls_config(cert_path: &str, key_path: &str) -> Result<Arc<rustls::ServerConfig>> {
+    let cert_pem = std::fs::read(cert_path).with_context(|| format!("reading {}", cert_path))?;
+    let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut cert_pem.as_slice())
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("parsing {}", cert_path))?;
+    if certs.is_empty() {
+        anyhow::bail!("{} contains no certificate", cert_path);
+    }
+    let key_pem = std::fs::read(key_path).with_context(|| format!("reading {}", key_path))?;
+    let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut key_pem.as_slice())
+        .with_context(|| format!("parsing {}", key_path))?
+        .with_context(|| format!("{} contains no private key", key_path))?;
+
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"dot".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Client ID carried as the first label of the TLS server name, e.g.
+/// `kiosk-1` in `kiosk-1.dns.example.com` for domain `dns.example.com`.
+pub fn client_id_from_sni(server_name: &str, domain: &str) -> Option<String> {
+    let server_name = server_name.trim_end_matches('.').to_ascii_lowercase();
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    let label = server_name.strip_suffix(&domain)?.strip_suffix('.')?;
+    is_valid_client_id(label).then(|| label.to_string())
+}
+
+/// Accept DoT connections until the listener fails.
+pub async fn run(
+    handler: Arc<DnsHandler>,
+    listener: TcpListener,
+    config: Arc<rustls::ServerConfig>,
+    client_id_domain: Option<String>,
+    proxies: Arc<TrustedProxies>,
+) -> Result<()> {
+    let acceptor = TlsAcceptor::from(config);
+    let client_id_domain = client_id_domain.map(Arc::<str>::from);
+    loop {
+        let (mut tcp, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::error!("DNS TLS accept error: {}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let handler = handler.clone();
+        let client_id_domain = client_id_domain.clone();
+        let proxies = proxies.clone();
+        tokio::spawn(async move {
+            let client = match proxy_protocol::accept_client(&mut tcp, peer, &proxies).await {
+                Ok(client) => client,
+                Err(e) => {
+                    tracing::debug!("Dropping DNS TLS connection from proxy {}: {}", peer, e);
+                    return;
+                }
+            };
+            let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(tcp)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    tracing::debug!("DNS TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+                Err(_) => return,
+            };
+            let client_id = match (&client_id_domain, stream.get_ref().1.server_name()) {
+                (Some(domain), Some(sni)) => client_id_from_sni(sni, domain),
+                _ => None,
+            };
+            serve_connection(stream, &handler, client.ip().to_string(), client_id.as_deref()).await;
+        });
+    }
+}
+
+/// Answer length-prefixed queries on one connection, in order.
+async fn serve_connection<S>(mut stream: S, handler: &DnsHandler, client_ip: String, client_id: Option<&str>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        let mut len_buf = [0u8; 2];
+        match tokio::time::timeout(IDLE_TIMEOUT, stream.read_exact(&mut len_buf)).await {
+            Ok(Ok(_)) => {}
+            _ => break,
+        }
+        let msg_len = u16::from_be_bytes(len_buf) as usize;
+        if msg_len == 0 {
+            break;
+        }
+        let mut data = vec![0u8; msg_len];
+        if stream.read_exact(&mut data).await.is_err() {
+            break;
+        }
+
+        match handler.handle_with_client_id(data, client_ip.clone(), client_id).await {
+            Ok(response) => {
+                let len = (response.len() as u16).to_be_bytes();
+                if stream.write_all(&len).await.is_err() || stream.write_all(&response).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                tracing::warn!("DNS TLS handler error from {}: {}", client_ip, e);
+                break;
+            }
+        }
+    }
+    let _ = stream.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_id_from_sni() {
+        let domain = "dns.example.com";
+        assert_eq!(client_id_from_sni("kiosk-1.dns.example.com", domain).as_deref(), Some("kiosk-1"));
+        assert_eq!(client_id_from_sni("Kiosk-1.DNS.example.com.", domain).as_deref(), Some("kiosk-1"));
+        assert_eq!(client_id_from_sni("dns.example.com", domain), None);
+        assert_eq!(client_id_from_sni("a.b.dns.example.com", domain), None);
+        assert_eq!(client_id_from_sni("kiosk-1.xdns.example.com", domain), None);
+        assert_eq!(client_id_from_sni("kiosk-1.other.com", domain), None);
+    }
+}
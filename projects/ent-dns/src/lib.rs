@@ -9,3 +9,4 @@ pub mod db;
 pub mod dns;
 pub mod error;
 pub mod metrics;
+pub mod proxy_protocol;
@@ -41,7 +41,8 @@ async fn main() -> Result<()> {
     // Broadcast channel for real-time query log push (WebSocket)
     let (query_log_tx, _) = broadcast::channel::<serde_json::Value>(256);
 
-    // Background: auto-refresh filter lists based on each list's update_interval_hours
+    // Background: auto-refresh filter lists based on each list's update_interval_hours,
+    // falling back to the interval announced by the list (`! Expires:`)
     {
         let db = db_pool.clone();
         let filter_engine = filter.clone();
@@ -53,8 +54,9 @@ async fn main() -> Result<()> {
                 tracing::info!("Auto-refresh: checking filter lists...");
 
                 let lists: Vec<(String, String, Option<i64>, Option<String>)> = match sqlx::query_as(
-                    "SELECT id, url, update_interval_hours, last_updated
-                     FROM filter_lists WHERE is_enabled = 1 AND url != '' AND url IS NOT NULL"
+                    "SELECT id, url, COALESCE(update_interval_hours, expires_hours), last_updated
+                     FROM filter_lists
+                     WHERE is_enabled = 1 AND url != '' AND url IS NOT NULL AND url NOT LIKE 'file://%'"
                 ).fetch_all(&db).await {
                     Ok(r) => r,
                     Err(e) => { tracing::warn!("Auto-refresh DB error: {}", e); continue; }
@@ -88,6 +90,27 @@ async fn main() -> Result<()> {
         });
     }
 
+    // Background: re-sync file:// filter lists when their file changes (mtime poll)
+    {
+        let db = db_pool.clone();
+        let filter_engine = filter.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(tokio::time::Duration::from_secs(30));
+            loop {
+                ticker.tick().await;
+                match dns::subscription::sync_changed_local_lists(&db).await {
+                    Ok(0) => {}
+                    Ok(_) => {
+                        if let Err(e) = filter_engine.reload().await {
+                            tracing::warn!("Filter reload after local list change: {}", e);
+                        }
+                    }
+                    Err(e) => tracing::warn!("Local filter list check: {}", e),
+                }
+            }
+        });
+    }
+
     // Background: auto-cleanup query log based on query_log_retention_days setting
     // Rotates logs daily to prevent database from growing indefinitely
     {
@@ -131,6 +154,21 @@ async fn main() -> Result<()> {
     // API server (DoH endpoint).  Both use the same filter, cache, and log writer.
     let dns_handler = dns::build_handler(&cfg, db_pool.clone(), filter.clone(), metrics.clone(), query_log_tx.clone()).await?;
 
+    // Background: name runtime clients by reverse lookup
+    {
+        let db = db_pool.clone();
+        let handler = dns_handler.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(tokio::time::Duration::from_secs(60));
+            loop {
+                ticker.tick().await;
+                if let Err(e) = dns::runtime_clients::resolve_hostnames(&db, &handler).await {
+                    tracing::warn!("Runtime client reverse lookups: {}", e);
+                }
+            }
+        });
+    }
+
     tokio::try_join!(
         dns::serve(dns_handler.clone(), &cfg),
         api::serve(cfg.clone(), db_pool.clone(), filter.clone(), metrics.clone(), query_log_tx, dns_handler),
@@ -1,3 +1,4 @@
+use dashmap::DashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Global DNS query counters shared between the DNS server and the API.
@@ -7,6 +8,12 @@ pub struct DnsMetrics {
     pub queries_blocked: AtomicU64,
     pub queries_allowed: AtomicU64,
     pub queries_cached: AtomicU64,
+    /// Queries per record type ("A", "AAAA", "HTTPS", ...).
+    pub queries_by_qtype: DashMap<String, AtomicU64>,
+    /// Queries answered by a query-type policy, keyed by (qtype, action).
+    pub qtype_policy_hits: DashMap<(String, &'static str), AtomicU64>,
+    /// Threat-feed hits, keyed by (threat category, severity).
+    pub security_events: DashMap<(String, &'static str), AtomicU64>,
 }
 
 impl DnsMetrics {
@@ -25,6 +32,31 @@ impl DnsMetrics {
         self.queries_cached.fetch_add(1, Ordering::Relaxed);
     }
 
+    pub fn inc_qtype(&self, qtype: &str) {
+        if let Some(counter) = self.queries_by_qtype.get(qtype) {
+            counter.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.queries_by_qtype
+            .entry(qtype.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_qtype_policy(&self, qtype: &str, action: &'static str) {
+        self.qtype_policy_hits
+            .entry((qtype.to_string(), action))
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_security_event(&self, category: &str, severity: &'static str) {
+        self.security_events
+            .entry((category.to_string(), severity))
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
     /// Serialize to Prometheus text exposition format.
     pub fn to_prometheus_text(&self) -> String {
         let total = self.queries_total.load(Ordering::Relaxed);
@@ -32,13 +64,61 @@ impl DnsMetrics {
         let allowed = self.queries_allowed.load(Ordering::Relaxed);
         let cached = self.queries_cached.load(Ordering::Relaxed);
 
-        format!(
+        let mut text = format!(
             "# HELP ent_dns_queries_total Total DNS queries processed\n\
              # TYPE ent_dns_queries_total counter\n\
              ent_dns_queries_total{{status=\"blocked\"}} {blocked}\n\
              ent_dns_queries_total{{status=\"allowed\"}} {allowed}\n\
              ent_dns_queries_total{{status=\"cached\"}} {cached}\n\
              ent_dns_queries_total{{status=\"total\"}} {total}\n"
-        )
+        );
+
+        let mut by_qtype: Vec<(String, u64)> = self
+            .queries_by_qtype
+            .iter()
+            .map(|e| (e.key().clone(), e.value().load(Ordering::Relaxed)))
+            .collect();
+        by_qtype.sort();
+        text.push_str(
+            "# HELP ent_dns_queries_by_qtype_total DNS queries per record type\n\
+             # TYPE ent_dns_queries_by_qtype_total counter\n",
+        );
+        for (qtype, count) in by_qtype {
+            text.push_str(&format!("ent_dns_queries_by_qtype_total{{qtype=\"{qtype}\"}} {count}\n"));
+        }
+
+        let mut hits: Vec<((String, &'static str), u64)> = self
+            .qtype_policy_hits
+            .iter()
+            .map(|e| (e.key().clone(), e.value().load(Ordering::Relaxed)))
+            .collect();
+        hits.sort();
+        text.push_str(
+            "# HELP ent_dns_qtype_policy_total DNS queries answered by a query-type policy\n\
+             # TYPE ent_dns_qtype_policy_total counter\n",
+        );
+        for ((qtype, action), count) in hits {
+            text.push_str(&format!(
+                "ent_dns_qtype_policy_total{{qtype=\"{qtype}\",action=\"{action}\"}} {count}\n"
+            ));
+        }
+
+        let mut events: Vec<((String, &'static str), u64)> = self
+            .security_events
+            .iter()
+            .map(|e| (e.key().clone(), e.value().load(Ordering::Relaxed)))
+            .collect();
+        events.sort();
+        text.push_str(
+            "# HELP ent_dns_security_events_total DNS queries matching a threat-intelligence feed\n\
+             # TYPE ent_dns_security_events_total counter\n",
+        );
+        for ((category, severity), count) in events {
+            text.push_str(&format!(
+                "ent_dns_security_events_total{{category=\"{category}\",severity=\"{severity}\"}} {count}\n"
+            ));
+        }
+
+        text
     }
 }
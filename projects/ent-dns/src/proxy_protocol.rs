@@ -0,0 +1,336 @@
+//! PROXY protocol (v1 and v2) and `X-Forwarded-For` for listeners behind a
+//! load balancer.
+//!
+//! Only peers in a configured list of trusted proxies may speak the PROXY
+//! protocol, and for them it is mandatory: a connection (or UDP datagram) from
+//! a trusted proxy without a valid header is dropped.  Everything else is
+//! served as before, with the peer address as client address.  A `LOCAL`
+//! (health check) or `UNKNOWN` header keeps the proxy's own address.
+
+use anyhow::Context;
+use ipnet::IpNet;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// Time a trusted proxy has to send the header after connecting.
+pub const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// "PROXY TCP6 " + two maximal IPv6 addresses + two ports + CRLF.
+const V1_MAX_LEN: usize = 107;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Addresses or CIDRs of trusted proxies.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(Vec<IpNet>);
+
+impl TrustedProxies {
+    pub fn parse(entries: &[String]) -> anyhow::Result<Self> {
+        entries
+            .iter()
+            .map(|entry| {
+                let entry = entry.trim();
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .with_context(|| format!("invalid trusted proxy: {}", entry))
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+        self.0.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Client address of an HTTP request from `peer`: `X-Forwarded-For` hops
+    /// are followed right to left while the hop they came from is trusted.
+    /// Unparsable hops stop the walk.
+    pub fn forwarded_client<'a>(&self, peer: IpAddr, forwarded_for: impl IntoIterator<Item = &'a str>) -> IpAddr {
+        let hops: Vec<&str> = forwarded_for.into_iter().flat_map(|v| v.split(',')).collect();
+        let mut client = peer;
+        for hop in hops.iter().rev() {
+            if !self.contains(client) {
+                break;
+            }
+            match hop.trim().parse::<IpAddr>() {
+                Ok(ip) => client = ip,
+                Err(_) => break,
+            }
+        }
+        client
+    }
+}
+
+/// Parse a v1 header line, including its CRLF.  `None` for `UNKNOWN`.
+fn parse_v1(line: &[u8]) -> io::Result<Option<SocketAddr>> {
+    let line = std::str::from_utf8(line)
+        .ok()
+        .and_then(|l| l.strip_suffix("\r\n"))
+        .ok_or_else(|| invalid("malformed PROXY v1 header"))?;
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", proto @ ("TCP4" | "TCP6"), src, _dst, sport, _dport] => {
+            let ip: IpAddr = src.parse().map_err(|_| invalid("bad PROXY v1 source address"))?;
+            if ip.is_ipv4() != (*proto == "TCP4") {
+                return Err(invalid("PROXY v1 address family mismatch"));
+            }
+            let port: u16 = sport.parse().map_err(|_| invalid("bad PROXY v1 source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("malformed PROXY v1 header")),
+    }
+}
+
+/// Parse a v2 header at the start of `buf`.  Returns the header length and
+/// the source address (`None` for `LOCAL` or unsupported families).
+pub fn parse_v2(buf: &[u8]) -> io::Result<(usize, Option<SocketAddr>)> {
+    if buf.len() < 16 || buf[..12] != V2_SIGNATURE {
+        return Err(invalid("missing PROXY v2 header"));
+    }
+    let (version, command) = (buf[12] >> 4, buf[12] & 0x0f);
+    if version != 2 || command > 1 {
+        return Err(invalid("unsupported PROXY v2 version or command"));
+    }
+    let len = 16 + u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    let addrs = buf.get(16..len).ok_or_else(|| invalid("truncated PROXY v2 header"))?;
+    if command == 0 {
+        return Ok((len, None));
+    }
+    // Family in the high nibble, transport (stream / datagram) in the low one
+    let source = match buf[13] >> 4 {
+        1 if addrs.len() >= 12 => {
+            let ip = Ipv4Addr::new(addrs[0], addrs[1], addrs[2], addrs[3]);
+            Some(SocketAddr::new(IpAddr::V4(ip), u16::from_be_bytes([addrs[8], addrs[9]])))
+        }
+        2 if addrs.len() >= 36 => {
+            let octets: [u8; 16] = addrs[..16].try_into().expect("16 bytes");
+            let port = u16::from_be_bytes([addrs[32], addrs[33]]);
+            Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port))
+        }
+        1 | 2 => return Err(invalid("truncated PROXY v2 addresses")),
+        _ => None,
+    };
+    Ok((len, source))
+}
+
+/// Read a v1 or v2 header from the start of a stream, consuming exactly the
+/// header.  `None` when the header carries no client address.
+pub async fn read_header<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<SocketAddr>> {
+    let mut head = [0u8; 16];
+    stream.read_exact(&mut head[..5]).await?;
+    if &head[..5] == b"PROXY" {
+        let mut line = head[..5].to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() >= V1_MAX_LEN {
+                return Err(invalid("PROXY v1 header too long"));
+            }
+            line.push(stream.read_u8().await?);
+        }
+        return parse_v1(&line);
+    }
+    stream.read_exact(&mut head[5..]).await?;
+    if head[..12] != V2_SIGNATURE {
+        return Err(invalid("missing PROXY header"));
+    }
+    let mut header = head.to_vec();
+    header.resize(16 + u16::from_be_bytes([head[14], head[15]]) as usize, 0);
+    stream.read_exact(&mut header[16..]).await?;
+    parse_v2(&header).map(|(_, source)| source)
+}
+
+/// Client address of a new connection: from the PROXY header when the peer
+/// is a trusted proxy, else the peer itself.  Errors mean the connection
+/// should be dropped.
+pub async fn accept_client<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    peer: SocketAddr,
+    trusted: &TrustedProxies,
+) -> io::Result<SocketAddr> {
+    if !trusted.contains(peer.ip()) {
+        return Ok(peer);
+    }
+    match tokio::time::timeout(HEADER_TIMEOUT, read_header(stream)).await {
+        Ok(header) => Ok(header?.unwrap_or(peer)),
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "no PROXY header")),
+    }
+}
+
+/// HTTP listener accepting PROXY headers from trusted proxies.  Headers are
+/// read off the accept path, so a slow proxy connection does not hold up
+/// other clients.
+pub struct ProxyProtocolListener {
+    connections: mpsc::Receiver<(TcpStream, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl ProxyProtocolListener {
+    pub fn new(listener: TcpListener, trusted: TrustedProxies) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (tx, connections) = mpsc::channel(64);
+        let trusted = std::sync::Arc::new(trusted);
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, peer) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        tracing::error!("API accept error: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let tx = tx.clone();
+                let trusted = trusted.clone();
+                tokio::spawn(async move {
+                    match accept_client(&mut stream, peer, &trusted).await {
+                        Ok(client) => {
+                            let _ = tx.send((stream, client)).await;
+                        }
+                        Err(e) => tracing::debug!("Dropping connection from proxy {}: {}", peer, e),
+                    }
+                });
+            }
+        });
+        Ok(Self { connections, local_addr })
+    }
+}
+
+impl axum::serve::Listener for ProxyProtocolListener {
+    type Io = TcpStream;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.connections.recv().await {
+            Some(conn) => conn,
+            // The accept loop never exits
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2_header(command: u8, family: u8, addrs: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.push(0x20 | command);
+        header.push(family);
+        header.extend_from_slice(&(addrs.len() as u16).to_be_bytes());
+        header.extend_from_slice(addrs);
+        header
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        assert_eq!(
+            parse_v1(b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 53\r\n").unwrap(),
+            Some("203.0.113.7:51234".parse().unwrap())
+        );
+        assert_eq!(
+            parse_v1(b"PROXY TCP6 2001:db8::7 2001:db8::1 51234 443\r\n").unwrap(),
+            Some("[2001:db8::7]:51234".parse().unwrap())
+        );
+        assert_eq!(parse_v1(b"PROXY UNKNOWN\r\n").unwrap(), None);
+        assert!(parse_v1(b"PROXY TCP4 2001:db8::7 10.0.0.1 1 2\r\n").is_err());
+        assert!(parse_v1(b"PROXY TCP4 203.0.113.7 10.0.0.1 1\r\n").is_err());
+        assert!(parse_v1(b"PROXY TCP4 203.0.113.7 10.0.0.1 1 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        // TCP over IPv4 with a trailing TLV, followed by payload
+        let mut addrs = vec![203, 0, 113, 7, 10, 0, 0, 1, 0xc8, 0x22, 0, 53];
+        addrs.extend_from_slice(&[0x04, 0, 1, 0]);
+        let mut buf = v2_header(1, 0x11, &addrs);
+        let header_len = buf.len();
+        buf.extend_from_slice(b"payload");
+        assert_eq!(parse_v2(&buf).unwrap(), (header_len, Some("203.0.113.7:51234".parse().unwrap())));
+
+        // UDP over IPv6
+        let mut addrs = "2001:db8::7".parse::<Ipv6Addr>().unwrap().octets().to_vec();
+        addrs.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        addrs.extend_from_slice(&[0xc8, 0x22, 0, 53]);
+        let buf = v2_header(1, 0x22, &addrs);
+        assert_eq!(parse_v2(&buf).unwrap().1, Some("[2001:db8::7]:51234".parse().unwrap()));
+
+        // LOCAL (health check)
+        assert_eq!(parse_v2(&v2_header(0, 0, &[])).unwrap(), (16, None));
+
+        assert!(parse_v2(b"\x12\x34 plain DNS").is_err());
+        assert!(parse_v2(&v2_header(1, 0x11, &[203, 0, 113])).is_err());
+        let mut truncated = v2_header(1, 0x11, &[0; 12]);
+        truncated.truncate(20);
+        assert!(parse_v2(&truncated).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_header_consumes_only_header() {
+        let mut stream: &[u8] = b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 53\r\nGET / HTTP/1.1\r\n";
+        assert_eq!(read_header(&mut stream).await.unwrap(), Some("203.0.113.7:51234".parse().unwrap()));
+        assert_eq!(stream, b"GET / HTTP/1.1\r\n");
+
+        let mut buf = v2_header(1, 0x11, &[203, 0, 113, 7, 10, 0, 0, 1, 0xc8, 0x22, 0, 53]);
+        buf.extend_from_slice(b"rest");
+        let mut stream = buf.as_slice();
+        assert_eq!(read_header(&mut stream).await.unwrap(), Some("203.0.113.7:51234".parse().unwrap()));
+        assert_eq!(stream, b"rest");
+
+        let mut stream: &[u8] = b"GET / HTTP/1.1\r\nHost: x\r\n";
+        assert!(read_header(&mut stream).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_accept_client_only_trusts_proxies() {
+        let trusted = TrustedProxies::parse(&["10.0.0.0/24".to_string()]).unwrap();
+        let header: &[u8] = b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 53\r\n";
+
+        let untrusted: SocketAddr = "192.0.2.1:4000".parse().unwrap();
+        let mut stream = header;
+        assert_eq!(accept_client(&mut stream, untrusted, &trusted).await.unwrap(), untrusted);
+        assert_eq!(stream, header, "untrusted peers' data is left alone");
+
+        let proxy: SocketAddr = "10.0.0.9:4000".parse().unwrap();
+        let mut stream = header;
+        assert_eq!(accept_client(&mut stream, proxy, &trusted).await.unwrap(), "203.0.113.7:51234".parse().unwrap());
+        let mut stream: &[u8] = b"\x00\x1c plain DNS over TCP";
+        assert!(accept_client(&mut stream, proxy, &trusted).await.is_err());
+    }
+
+    #[test]
+    fn test_forwarded_client() {
+        let trusted = TrustedProxies::parse(&["10.0.0.0/8".to_string(), "192.0.2.10".to_string()]).unwrap();
+        let lb: IpAddr = "10.0.0.1".parse().unwrap();
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        assert_eq!(trusted.forwarded_client(lb, ["203.0.113.7"]), ip("203.0.113.7"));
+        // A client-supplied hop left of the first untrusted one is ignored
+        assert_eq!(trusted.forwarded_client(lb, ["198.51.100.1, 203.0.113.7, 10.1.1.1"]), ip("203.0.113.7"));
+        assert_eq!(trusted.forwarded_client(lb, ["198.51.100.1", "203.0.113.7"]), ip("203.0.113.7"));
+        assert_eq!(trusted.forwarded_client(lb, ["garbage"]), lb);
+        assert_eq!(trusted.forwarded_client(lb, []), lb);
+        // Untrusted peers cannot spoof
+        assert_eq!(trusted.forwarded_client(ip("203.0.113.9"), ["198.51.100.1"]), ip("203.0.113.9"));
+        assert!(TrustedProxies::parse(&["not-an-ip".to_string()]).is_err());
+    }
+}
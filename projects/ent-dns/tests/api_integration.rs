@@ -119,11 +119,18 @@ async fn build_test_app() -> (axum::Router, Arc<AppState>) {
             upstreams: vec!["https://1.1.1.1/dns-query".to_string()],
             doh_enabled: false,
             dot_enabled: false,
+            dot_port: 0,
+            tls_cert_path: None,
+            tls_key_path: None,
+            client_id_domain: None,
+            proxy_protocol_from: vec![],
         },
         api: ent_dns::config::ApiConfig {
             port: 18099,
             bind: "127.0.0.1".to_string(),
             cors_allowed_origins: vec!["http://localhost:5173".to_string()],
+            proxy_protocol_from: vec![],
+            forwarded_for_from: vec![],
         },
         database: ent_dns::config::DatabaseConfig {
             path: ":memory:".to_string(),
@@ -163,6 +170,7 @@ async fn build_test_app() -> (axum::Router, Arc<AppState>) {
                 .build(),
         ),
         client_config_cache: None,
+        forwarded_for_proxies: Default::default(),
     });
 
     // CORS 层用空配置（测试中不需要）
@@ -605,3 +613,1044 @@ async fn test_query_log_pagination_limit() {
     assert_eq!(json["total"], 5, "Total should be 5");
     assert_eq!(json["limit"], 2);
 }
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Filtering pauses
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_pause_create_list_resume() {
+    let (app, state) = build_test_app().await;
+
+    let token = ent_dns::auth::jwt::generate(
+        "test-user-id", "admin", "super_admin",
+        &state.jwt_secret, 1,
+    ).expect("Should generate token");
+
+    // 非法 scope
+    let bad_req = Request::builder()
+        .method("POST")
+        .uri("/api/v1/pauses")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"scope":"everything","duration_minutes":10}"#))
+        .unwrap();
+    let bad_resp = app.clone().oneshot(bad_req).await.unwrap();
+    assert_eq!(bad_resp.status(), StatusCode::BAD_REQUEST);
+
+    // 全局暂停 10 分钟
+    let create_req = Request::builder()
+        .method("POST")
+        .uri("/api/v1/pauses")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"scope":"global","duration_minutes":10,"reason":"troubleshooting"}"#))
+        .unwrap();
+    let create_resp = app.clone().oneshot(create_req).await.unwrap();
+    assert_eq!(create_resp.status(), StatusCode::OK);
+    let created = body_json(create_resp.into_body()).await;
+    assert_eq!(created["scope"], "global");
+    let remaining = created["remaining_seconds"].as_i64().unwrap();
+    assert!(remaining > 590 && remaining <= 600, "remaining_seconds = {}", remaining);
+    let pause_id = created["id"].as_str().unwrap().to_string();
+
+    // 列表中可见
+    let list_req = Request::builder()
+        .method("GET")
+        .uri("/api/v1/pauses")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let list_json = body_json(app.clone().oneshot(list_req).await.unwrap().into_body()).await;
+    assert_eq!(list_json["total"], 1);
+
+    // 提前恢复
+    let delete_req = Request::builder()
+        .method("DELETE")
+        .uri(format!("/api/v1/pauses/{}", pause_id))
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let delete_resp = app.oneshot(delete_req).await.unwrap();
+    assert_eq!(delete_resp.status(), StatusCode::OK);
+
+    // 审计日志为后台写入（顺序不确定），稍等片刻
+    let mut actions: Vec<String> = Vec::new();
+    for _ in 0..20 {
+        actions = sqlx::query_scalar("SELECT action FROM audit_log ORDER BY action")
+            .fetch_all(&state.db)
+            .await
+            .unwrap();
+        if actions.len() >= 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(actions, vec!["pause_filtering", "resume_filtering"]);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Suspicious-domain heuristics
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_dashboard_suspicious_domains() {
+    let (app, state) = build_test_app().await;
+
+    // 两条 DGA 记录（一条被拦截）与一条普通记录
+    let now = chrono::Utc::now().to_rfc3339();
+    for (question, status, reason) in [
+        ("xjw3kq9vzt7p.com", "blocked", Some("suspicious:dga")),
+        ("xjw3kq9vzt7p.com", "allowed", Some("suspicious:dga")),
+        ("example.com", "allowed", None),
+    ] {
+        sqlx::query(
+            "INSERT INTO query_log (time, client_ip, question, qtype, status, reason, elapsed_ms)
+             VALUES (?, '192.168.1.1', ?, 'A', ?, ?, 1)"
+        )
+        .bind(&now)
+        .bind(question)
+        .bind(status)
+        .bind(reason)
+        .execute(&state.db)
+        .await
+        .expect("Failed to insert test log");
+    }
+
+    let token = ent_dns::auth::jwt::generate(
+        "test-user-id", "admin", "super_admin",
+        &state.jwt_secret, 1,
+    ).expect("Should generate token");
+
+    let req = Request::builder()
+        .method("GET")
+        .uri("/api/v1/dashboard/suspicious-domains?hours=24")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp.into_body()).await;
+    assert_eq!(json["by_kind"][0]["kind"], "dga");
+    assert_eq!(json["by_kind"][0]["count"], 2);
+    assert_eq!(json["by_kind"][0]["blocked"], 1);
+    let top = json["top_domains"].as_array().unwrap();
+    assert_eq!(top.len(), 1);
+    assert_eq!(top[0]["domain"], "xjw3kq9vzt7p.com");
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Threat-intelligence feeds
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_threat_feed_and_security_events() {
+    let (app, state) = build_test_app().await;
+
+    let token = ent_dns::auth::jwt::generate(
+        "test-user-id", "admin", "super_admin",
+        &state.jwt_secret, 1,
+    ).expect("Should generate token");
+
+    // 未知威胁类别
+    let bad_req = Request::builder()
+        .method("POST")
+        .uri("/api/v1/filters")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"name":"feed","threat_category":"adware"}"#))
+        .unwrap();
+    let bad_resp = app.clone().oneshot(bad_req).await.unwrap();
+    assert_eq!(bad_resp.status(), StatusCode::BAD_REQUEST);
+
+    // 创建威胁情报源，默认严重级别为 high
+    let create_req = Request::builder()
+        .method("POST")
+        .uri("/api/v1/filters")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"name":"Malware feed","threat_category":"malware"}"#))
+        .unwrap();
+    let created = body_json(app.clone().oneshot(create_req).await.unwrap().into_body()).await;
+    assert_eq!(created["threat_category"], "malware");
+    assert_eq!(created["severity"], "high");
+
+    // 安全事件按最低严重级别过滤
+    let now = chrono::Utc::now().to_rfc3339();
+    for severity in ["low", "critical"] {
+        sqlx::query(
+            "INSERT INTO security_events (time, client_ip, domain, qtype, filter_id, feed_name, threat_category, severity)
+             VALUES (?, '192.168.1.1', 'evil.example', 'A', 'f', 'Malware feed', 'malware', ?)"
+        )
+        .bind(&now)
+        .bind(severity)
+        .execute(&state.db)
+        .await
+        .expect("Failed to insert security event");
+    }
+
+    let req = Request::builder()
+        .method("GET")
+        .uri("/api/v1/security-events?min_severity=high")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp.into_body()).await;
+    assert_eq!(json["total"], 1);
+    assert_eq!(json["data"][0]["severity"], "critical");
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Local and inline filter lists
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// 构造 multipart/form-data 请求体
+fn multipart_body(boundary: &str, fields: &[(&str, &str)]) -> String {
+    let mut body = String::new();
+    for (name, value) in fields {
+        let filename = if *name == "file" { "; filename=\"list.txt\"" } else { "" };
+        body.push_str(&format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"{filename}\r\n\r\n{value}\r\n"
+        ));
+    }
+    body.push_str(&format!("--{boundary}--\r\n"));
+    body
+}
+
+#[tokio::test]
+async fn test_inline_filter_upload() {
+    let (app, state) = build_test_app().await;
+
+    let token = ent_dns::auth::jwt::generate(
+        "test-user-id", "admin", "super_admin",
+        &state.jwt_secret, 1,
+    ).expect("Should generate token");
+
+    // 上传内联列表（dnsmasq 格式，自动识别）
+    let boundary = "ent-dns-boundary";
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/v1/filters/upload")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary))
+        .body(Body::from(multipart_body(boundary, &[
+            ("name", "Air-gapped list"),
+            ("file", "address=/ads.example/0.0.0.0\naddress=/tracker.example/\n"),
+        ])))
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let created = body_json(resp.into_body()).await;
+    assert_eq!(created["rule_count"], 2);
+    assert!(created["url"].is_null());
+    assert!(state.filter.is_blocked("ads.example").await);
+
+    // 替换内联列表内容
+    let id = created["id"].as_str().unwrap();
+    let req = Request::builder()
+        .method("PUT")
+        .uri(format!("/api/v1/filters/{}/content", id))
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary))
+        .body(Body::from(multipart_body(boundary, &[("file", "address=/other.example/0.0.0.0\n")])))
+        .unwrap();
+    let replaced = body_json(app.clone().oneshot(req).await.unwrap().into_body()).await;
+    assert_eq!(replaced["rule_count"], 1);
+    assert!(!state.filter.is_blocked("ads.example").await);
+    assert!(state.filter.is_blocked("other.example").await);
+
+    // 不支持的 URL 协议
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/v1/filters")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"name":"ftp","url":"ftp://lists.example/list.txt"}"#))
+        .unwrap();
+    assert_eq!(app.oneshot(req).await.unwrap().status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_local_file_filter_list() {
+    let (_, state) = build_test_app().await;
+
+    let path = std::env::temp_dir().join(format!("ent-dns-list-{}.txt", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "local-block.example\n").unwrap();
+    let url = format!("file://{}", path.display());
+    sqlx::query(
+        "INSERT INTO filter_lists (id, name, url, is_enabled, created_at) VALUES ('local', 'Local', ?, 1, datetime('now'))"
+    )
+    .bind(&url)
+    .execute(&state.db)
+    .await
+    .unwrap();
+
+    // 首次检查：未同步过，立即同步
+    let synced = ent_dns::dns::subscription::sync_changed_local_lists(&state.db).await.unwrap();
+    assert_eq!(synced, 1);
+    let count: i64 = sqlx::query_scalar("SELECT rule_count FROM filter_lists WHERE id = 'local'")
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+
+    // 文件未变化：不重复同步
+    assert_eq!(ent_dns::dns::subscription::sync_changed_local_lists(&state.db).await.unwrap(), 0);
+
+    // 修改文件（显式推进 mtime）后重新同步
+    std::fs::write(&path, "local-block.example\nsecond.example\n").unwrap();
+    let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+    std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+    assert_eq!(ent_dns::dns::subscription::sync_changed_local_lists(&state.db).await.unwrap(), 1);
+    state.filter.reload().await.unwrap();
+    assert!(state.filter.is_blocked("second.example").await);
+
+    std::fs::remove_file(&path).ok();
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Conditional downloads and sync history
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_conditional_sync_and_history() {
+    use axum::http::HeaderMap;
+    use axum::response::IntoResponse;
+
+    let (app, state) = build_test_app().await;
+
+    // 本地列表服务器：ETag "v1"，If-None-Match 命中时返回 304
+    let list_app = axum::Router::new().route(
+        "/list.txt",
+        axum::routing::get(|headers: HeaderMap| async move {
+            if headers.get(header::IF_NONE_MATCH).is_some_and(|v| v == "\"v1\"") {
+                return StatusCode::NOT_MODIFIED.into_response();
+            }
+            (
+                [(header::ETAG, "\"v1\"")],
+                "! Title: Test\n! Expires: 12 hours\n||ads.example^\n||tracker.example^\n",
+            )
+                .into_response()
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, list_app).await.unwrap() });
+    let url = format!("http://{}/list.txt", addr);
+
+    sqlx::query(
+        "INSERT INTO filter_lists (id, name, url, is_enabled, created_at) VALUES ('remote', 'Remote', ?, 1, datetime('now'))"
+    )
+    .bind(&url)
+    .execute(&state.db)
+    .await
+    .unwrap();
+
+    // 首次同步：完整下载并记录 ETag 与 Expires
+    let n = ent_dns::dns::subscription::sync_filter_list(&state.db, "remote", &url).await.unwrap();
+    assert_eq!(n, 2);
+    let (etag, expires): (Option<String>, Option<i64>) =
+        sqlx::query_as("SELECT etag, expires_hours FROM filter_lists WHERE id = 'remote'")
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+    assert_eq!(etag.as_deref(), Some("\"v1\""));
+    assert_eq!(expires, Some(12));
+
+    // 再次同步：条件请求返回 304，规则保持不变
+    let n = ent_dns::dns::subscription::sync_filter_list(&state.db, "remote", &url).await.unwrap();
+    assert_eq!(n, 2);
+
+    let token = ent_dns::auth::jwt::generate(
+        "test-user-id", "admin", "super_admin",
+        &state.jwt_secret, 1,
+    ).expect("Should generate token");
+    let req = Request::builder()
+        .method("GET")
+        .uri("/api/v1/filters/remote/history")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = body_json(resp.into_body()).await;
+    assert_eq!(json["total"], 2);
+    assert_eq!(json["data"][0]["status"], "not_modified");
+    assert_eq!(json["data"][0]["bytes"], 0);
+    assert_eq!(json["data"][1]["status"], "updated");
+    assert!(json["data"][1]["bytes"].as_i64().unwrap() > 0);
+    assert_eq!(json["data"][1]["rule_count"], 2);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Filter list versions, diff and rollback
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_filter_versions_diff_and_pin() {
+    let (app, state) = build_test_app().await;
+
+    let token = ent_dns::auth::jwt::generate(
+        "test-user-id", "admin", "super_admin",
+        &state.jwt_secret, 1,
+    ).expect("Should generate token");
+    let boundary = "ent-dns-boundary";
+    let send = |method: &str, uri: String, body: Option<&str>| {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token));
+        let body = match body {
+            Some(list) => {
+                req = req.header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary));
+                Body::from(multipart_body(boundary, &[("name", "Versioned"), ("file", list)]))
+            }
+            None => Body::empty(),
+        };
+        let app = app.clone();
+        let req = req.body(body).unwrap();
+        async move {
+            let resp = app.oneshot(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            body_json(resp.into_body()).await
+        }
+    };
+
+    // 版本 1 与版本 2
+    let created = send("POST", "/api/v1/filters/upload".into(), Some("a.example\nb.example\n")).await;
+    let id = created["id"].as_str().unwrap().to_string();
+    send("PUT", format!("/api/v1/filters/{id}/content"), Some("a.example\nc.example\n")).await;
+
+    let versions = send("GET", format!("/api/v1/filters/{id}/versions"), None).await;
+    assert_eq!(versions["total"], 2);
+    let v2 = versions["data"][0]["id"].as_i64().unwrap();
+    let v1 = versions["data"][1]["id"].as_i64().unwrap();
+    assert_eq!(versions["data"][0]["added"], 1);
+    assert_eq!(versions["data"][0]["removed"], 1);
+
+    // 差异：默认与上一版本比较
+    let diff = send("GET", format!("/api/v1/filters/{id}/versions/{v2}/diff"), None).await;
+    assert_eq!(diff["base"], v1);
+    assert_eq!(diff["added"], serde_json::json!(["||c.example^"]));
+    assert_eq!(diff["removed"], serde_json::json!(["||b.example^"]));
+
+    // 回滚并固定到版本 1；固定期间的新内容只记录不生效
+    let pinned = send("POST", format!("/api/v1/filters/{id}/versions/{v1}/pin"), None).await;
+    assert_eq!(pinned["pinned_version"], v1);
+    assert!(state.filter.is_blocked("b.example").await);
+    assert!(!state.filter.is_blocked("c.example").await);
+
+    send("PUT", format!("/api/v1/filters/{id}/content"), Some("d.example\n")).await;
+    assert!(state.filter.is_blocked("b.example").await);
+    assert!(!state.filter.is_blocked("d.example").await);
+    let versions = send("GET", format!("/api/v1/filters/{id}/versions"), None).await;
+    assert_eq!(versions["total"], 3);
+
+    // 取消固定：最新版本生效
+    let unpinned = send("DELETE", format!("/api/v1/filters/{id}/pin"), None).await;
+    assert!(unpinned["pinned_version"].is_null());
+    assert_eq!(unpinned["rule_count"], 1);
+    assert!(state.filter.is_blocked("d.example").await);
+    assert!(!state.filter.is_blocked("b.example").await);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Filter list update sanity checks
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_broken_list_update_rejected() {
+    use ent_dns::dns::subscription;
+
+    let (_, state) = build_test_app().await;
+
+    let path = std::env::temp_dir().join(format!("ent-dns-guard-{}.txt", uuid::Uuid::new_v4()));
+    let url = format!("file://{}", path.display());
+    sqlx::query(
+        "INSERT INTO filter_lists (id, name, url, is_enabled, created_at) VALUES ('guarded', 'Guarded', ?, 1, datetime('now'))"
+    )
+    .bind(&url)
+    .execute(&state.db)
+    .await
+    .unwrap();
+    let status = || async {
+        let row: (i64, Option<String>, Option<String>) = sqlx::query_as(
+            "SELECT rule_count, sync_status, sync_error FROM filter_lists WHERE id = 'guarded'"
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        row
+    };
+
+    // 正常同步 10 条规则
+    let list: String = (0..10).map(|i| format!("host{i}.example\n")).collect();
+    std::fs::write(&path, &list).unwrap();
+    assert_eq!(subscription::sync_filter_list(&state.db, "guarded", &url).await.unwrap(), 10);
+    assert_eq!(status().await.1.as_deref(), Some("ok"));
+
+    // 规则数骤减 80%：拒绝更新，保留原规则
+    std::fs::write(&path, "host0.example\nhost1.example\n").unwrap();
+    assert!(subscription::sync_filter_list(&state.db, "guarded", &url).await.is_err());
+    let (count, sync_status, error) = status().await;
+    assert_eq!(count, 10);
+    assert_eq!(sync_status.as_deref(), Some("rejected"));
+    assert!(error.unwrap().contains("dropped 80%"));
+
+    // 返回 HTML 错误页：同样拒绝
+    std::fs::write(&path, "<!DOCTYPE html>\n<html><head><title>502</title></head></html>\n").unwrap();
+    assert!(subscription::sync_filter_list(&state.db, "guarded", &url).await.is_err());
+    assert_eq!(status().await.0, 10);
+
+    let statuses: Vec<String> = sqlx::query_scalar(
+        "SELECT status FROM filter_sync_history WHERE filter_id = 'guarded' ORDER BY id"
+    )
+    .fetch_all(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(statuses, ["updated", "rejected", "rejected"]);
+
+    // 管理员确认后强制应用
+    std::fs::write(&path, "host0.example\nhost1.example\n").unwrap();
+    assert_eq!(subscription::force_sync_filter_list(&state.db, "guarded", &url).await.unwrap(), 2);
+    assert_eq!(status().await.1.as_deref(), Some("ok"));
+
+    std::fs::remove_file(&path).ok();
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Shared group rule sets
+// ═══════════════════════════════════════════════════════════════════════════════
+
+fn dns_query(domain: &str) -> Vec<u8> {
+    use hickory_proto::op::{Message, Query};
+    use hickory_proto::rr::{Name, RecordType};
+    use std::str::FromStr;
+
+    let mut msg = Message::new();
+    msg.set_id(7);
+    msg.set_recursion_desired(true);
+    msg.add_query(Query::query(Name::from_str(&format!("{domain}.")).unwrap(), RecordType::A));
+    msg.to_vec().unwrap()
+}
+
+#[tokio::test]
+async fn test_group_rules_shared_and_invalidated() {
+    use hickory_proto::op::{Message, ResponseCode};
+
+    let (app, state) = build_test_app().await;
+
+    let token = ent_dns::auth::jwt::generate(
+        "test-user-id", "admin", "super_admin",
+        &state.jwt_secret, 1,
+    ).expect("Should generate token");
+    let send = |method: &str, uri: String, body: Value| {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let app = app.clone();
+        async move {
+            let resp = app.oneshot(req).await.unwrap();
+            assert!(resp.status().is_success(), "{}", resp.status());
+            body_json(resp.into_body()).await
+        }
+    };
+    let resolve = |domain: &str, ip: &str| {
+        let handler = state.dns_handler.clone();
+        let (query, ip) = (dns_query(domain), ip.to_string());
+        async move {
+            let resp = handler.handle(query, ip).await.expect("DNS handle should not return Err");
+            Message::from_vec(&resp).unwrap().response_code()
+        }
+    };
+
+    // 一个 /16 网段的客户端加入分组，分组绑定规则 A
+    let rule_a = send("POST", "/api/v1/rules".into(), serde_json::json!({"rule": "||shared-a.invalid^"})).await;
+    let group = send("POST", "/api/v1/client-groups".into(), serde_json::json!({"name": "Lab"})).await;
+    let group_id = group["id"].as_i64().unwrap();
+    let client = send("POST", "/api/v1/clients".into(), serde_json::json!({"name": "Lab net", "identifiers": ["10.9.0.0/16"]})).await;
+    send("POST", format!("/api/v1/client-groups/{group_id}/members"), serde_json::json!({"client_ids": [client["id"]]})).await;
+    send("POST", format!("/api/v1/client-groups/{group_id}/rules"),
+        serde_json::json!({"rules": [{"rule_id": rule_a["id"], "rule_type": "custom_rule"}]})).await;
+
+    // 多个成员共享同一份编译结果
+    let before = state.dns_handler.group_rule_compilations();
+    for ip in ["10.9.0.1", "10.9.0.2", "10.9.200.7"] {
+        assert_eq!(resolve("shared-a.invalid", ip).await, ResponseCode::NXDomain);
+    }
+    assert_eq!(state.dns_handler.group_rule_compilations() - before, 1);
+
+    // 绑定新规则立即生效，无需等待缓存过期
+    let rule_b = send("POST", "/api/v1/rules".into(), serde_json::json!({"rule": "||shared-b.invalid^"})).await;
+    send("POST", format!("/api/v1/client-groups/{group_id}/rules"),
+        serde_json::json!({"rules": [{"rule_id": rule_b["id"], "rule_type": "custom_rule"}]})).await;
+    assert_eq!(resolve("shared-b.invalid", "10.9.0.1").await, ResponseCode::NXDomain);
+    assert_eq!(resolve("shared-b.invalid", "10.9.0.2").await, ResponseCode::NXDomain);
+    assert_eq!(state.dns_handler.group_rule_compilations() - before, 2);
+
+    // 禁用已绑定的规则同样使分组重新编译
+    send("POST", "/api/v1/rules/bulk".into(), serde_json::json!({"ids": [rule_a["id"]], "action": "disable"})).await;
+    assert_eq!(resolve("shared-b.invalid", "10.9.0.1").await, ResponseCode::NXDomain);
+    assert_eq!(state.dns_handler.group_rule_compilations() - before, 3);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// DoH client IDs
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_doh_client_id_path() {
+    use hickory_proto::op::{Message, ResponseCode};
+
+    let (base_url, state) = start_test_server().await;
+    let now = chrono::Utc::now().to_rfc3339();
+    // 127.0.0.1 属于 desk；kiosk-1 是 laptop 的客户端 ID
+    for (id, identifiers, services) in [
+        ("desk", r#"["127.0.0.1"]"#, r#"["youtube"]"#),
+        ("laptop", r#"["kiosk-1"]"#, r#"["tiktok"]"#),
+    ] {
+        sqlx::query(
+            "INSERT INTO clients (id, name, identifiers, filter_enabled, blocked_services, created_at, updated_at)
+             VALUES (?, ?, ?, 1, ?, ?, ?)"
+        )
+        .bind(id).bind(id).bind(identifiers).bind(services).bind(&now).bind(&now)
+        .execute(&state.db).await.unwrap();
+    }
+    state.dns_handler.reload_clients().await;
+
+    let client = reqwest::Client::new();
+    let post = |path: &str, domain: &str| {
+        let req = client
+            .post(format!("{base_url}{path}"))
+            .header("content-type", "application/dns-message")
+            .body(dns_query(domain));
+        async move {
+            let resp = req.send().await.expect("Request failed");
+            assert_eq!(resp.status().as_u16(), 200);
+            Message::from_vec(&resp.bytes().await.unwrap()).unwrap().response_code()
+        }
+    };
+
+    // 路径中的客户端 ID 优先于来源 IP
+    assert_eq!(post("/dns-query/kiosk-1", "www.tiktok.com").await, ResponseCode::NXDomain);
+    assert_eq!(post("/dns-query/Laptop", "www.tiktok.com").await, ResponseCode::NXDomain);
+    // 未知客户端 ID 回退到 IP 匹配
+    assert_eq!(post("/dns-query/unknown", "www.youtube.com").await, ResponseCode::NXDomain);
+    assert_eq!(post("/dns-query", "www.youtube.com").await, ResponseCode::NXDomain);
+
+    // GET 同样支持
+    use base64::Engine;
+    let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(dns_query("www.tiktok.com"));
+    let resp = client
+        .get(format!("{base_url}/dns-query/kiosk-1?dns={encoded}"))
+        .send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    let rcode = Message::from_vec(&resp.bytes().await.unwrap()).unwrap().response_code();
+    assert_eq!(rcode, ResponseCode::NXDomain);
+
+    // 非法客户端 ID
+    let resp = client
+        .post(format!("{base_url}/dns-query/bad_id"))
+        .body(dns_query("www.tiktok.com"))
+        .send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 400);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// DoH 位于负载均衡之后：按 PROXY 协议头部识别客户端
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_doh_behind_trusted_proxy() {
+    use axum::serve::ListenerExt;
+    use ent_dns::proxy_protocol::{ProxyProtocolListener, TrustedProxies};
+    use hickory_proto::op::{Message, ResponseCode};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (_, state) = build_test_app().await;
+    let now = chrono::Utc::now().to_rfc3339();
+    sqlx::query(
+        "INSERT INTO clients (id, name, identifiers, filter_enabled, blocked_services, created_at, updated_at)
+         VALUES ('tablet', 'Tablet', '[\"203.0.113.7\"]', 1, '[\"tiktok\"]', ?, ?)"
+    )
+    .bind(&now).bind(&now)
+    .execute(&state.db).await.unwrap();
+    state.dns_handler.reload_clients().await;
+
+    // PROXY 协议：只信任 127.0.0.0/8 发来的头部
+    let app = ent_dns::api::build_app(state.clone(), tower_http::cors::CorsLayer::new());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let trusted = TrustedProxies::parse(&["127.0.0.0/8".to_string()]).unwrap();
+    let listener = ProxyProtocolListener::new(listener, trusted).unwrap().tap_io(|_| {});
+    tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.ok();
+    });
+
+    let body = dns_query("www.tiktok.com");
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let mut request = b"PROXY TCP4 203.0.113.7 127.0.0.1 40000 443\r\n".to_vec();
+    request.extend_from_slice(
+        format!(
+            "POST /dns-query HTTP/1.1\r\nHost: dns.example.test\r\nContent-Type: application/dns-message\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .as_bytes(),
+    );
+    request.extend_from_slice(&body);
+    stream.write_all(&request).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n").expect("HTTP response") + 4;
+    assert!(response.starts_with(b"HTTP/1.1 200"));
+    let rcode = Message::from_vec(&response[split..]).unwrap().response_code();
+    assert_eq!(rcode, ResponseCode::NXDomain, "识别为 PROXY 头部中的客户端");
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// 运行时客户端：自动发现、DHCP 租约导入与转为持久客户端
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_runtime_clients_discovery_and_promotion() {
+    use ent_dns::db::query_log_writer::{self, QueryLogEntry};
+
+    let (app, state) = build_test_app().await;
+    let token = ent_dns::auth::jwt::generate(
+        "test-user-id", "admin", "super_admin",
+        &state.jwt_secret, 1,
+    ).expect("Should generate token");
+    let send = |method: &str, uri: String, body: Value| {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let app = app.clone();
+        async move {
+            let resp = app.oneshot(req).await.unwrap();
+            let status = resp.status().as_u16();
+            (status, body_json(resp.into_body()).await)
+        }
+    };
+
+    // 查询日志写入时记录来源地址
+    let tx = query_log_writer::spawn(state.db.clone());
+    for (time, ip) in [
+        ("2026-10-18T08:00:00+00:00", "192.168.1.20"),
+        ("2026-10-18T09:00:00+00:00", "192.168.1.20"),
+        ("2026-10-18T08:30:00+00:00", "192.168.1.20"),
+        ("2026-10-18T07:00:00+00:00", "192.168.1.21"),
+    ] {
+        tx.send(QueryLogEntry {
+            time: time.into(),
+            client_ip: ip.into(),
+            question: "example.com".into(),
+            qtype: "A".into(),
+            status: "allowed".into(),
+            reason: None,
+            elapsed_ms: 1,
+        }).unwrap();
+    }
+    drop(tx);
+    let mut runtime = Value::Null;
+    for _ in 0..50 {
+        let (_, body) = send("GET", "/api/v1/clients/runtime".into(), Value::Null).await;
+        if body["total"] == 2 {
+            runtime = body;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(runtime["total"], 2, "查询日志刷新后应出现两个运行时客户端");
+    let laptop = &runtime["data"][0];
+    assert_eq!(laptop["ip"], "192.168.1.20");
+    assert_eq!(laptop["query_count"], 3);
+    assert_eq!(laptop["first_seen"], "2026-10-18T08:00:00+00:00");
+    assert_eq!(laptop["last_seen"], "2026-10-18T09:00:00+00:00");
+    assert_eq!(laptop["hostname"], Value::Null);
+
+    // 导入 dnsmasq 租约为其命名；仅有租约的地址也会出现
+    let leases = "1700000000 aa:bb:cc:dd:ee:01 192.168.1.20 laptop *\n\
+                  1700000000 aa:bb:cc:dd:ee:03 192.168.1.30 printer *\n";
+    let (status, body) = send("POST", "/api/v1/clients/runtime/leases".into(),
+        serde_json::json!({"format": "dnsmasq", "content": leases})).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["imported"], 2);
+    let (status, _) = send("POST", "/api/v1/clients/runtime/leases".into(),
+        serde_json::json!({"format": "kea", "content": "garbage"})).await;
+    assert_eq!(status, 400);
+
+    let (_, runtime) = send("GET", "/api/v1/clients/runtime".into(), Value::Null).await;
+    assert_eq!(runtime["total"], 3);
+    let laptop = &runtime["data"][0];
+    assert_eq!(laptop["hostname"], "laptop");
+    assert_eq!(laptop["hostname_source"], "dhcp");
+    assert_eq!(laptop["mac"], "aa:bb:cc:dd:ee:01");
+    assert_eq!(laptop["client_id"], Value::Null);
+    let printer = &runtime["data"][2];
+    assert_eq!(printer["ip"], "192.168.1.30");
+    assert_eq!(printer["query_count"], 0);
+
+    // 一键转为持久客户端：名称取租约主机名，标识包含 IP 与 MAC
+    let (status, client) = send("POST", "/api/v1/clients/runtime/192.168.1.20/promote".into(),
+        serde_json::json!({})).await;
+    assert_eq!(status, 200);
+    assert_eq!(client["name"], "laptop");
+    assert_eq!(client["identifiers"], serde_json::json!(["192.168.1.20", "aa:bb:cc:dd:ee:01"]));
+
+    let (_, runtime) = send("GET", "/api/v1/clients/runtime".into(), Value::Null).await;
+    assert_eq!(runtime["data"][0]["client_id"], client["id"]);
+
+    // 已归属客户端、未知地址、非法地址
+    let (status, _) = send("POST", "/api/v1/clients/runtime/192.168.1.20/promote".into(), serde_json::json!({})).await;
+    assert_eq!(status, 409);
+    let (status, _) = send("POST", "/api/v1/clients/runtime/192.168.1.99/promote".into(), serde_json::json!({})).await;
+    assert_eq!(status, 404);
+    let (status, _) = send("POST", "/api/v1/clients/runtime/not-an-ip/promote".into(), serde_json::json!({})).await;
+    assert_eq!(status, 400);
+
+    // 自定义名称
+    let (status, client) = send("POST", "/api/v1/clients/runtime/192.168.1.21/promote".into(),
+        serde_json::json!({"name": "Guest"})).await;
+    assert_eq!(status, 200);
+    assert_eq!(client["name"], "Guest");
+    assert_eq!(client["identifiers"], serde_json::json!(["192.168.1.21"]));
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// 客户端/分组设置覆盖：按分组优先级继承，API 展示生效配置及来源
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_client_settings_overrides() {
+    use hickory_proto::op::{Message, ResponseCode};
+
+    let (app, state) = build_test_app().await;
+    let token = ent_dns::auth::jwt::generate(
+        "test-user-id", "admin", "super_admin",
+        &state.jwt_secret, 1,
+    ).expect("Should generate token");
+    let send = |method: &str, uri: String, body: Value| {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let app = app.clone();
+        async move {
+            let resp = app.oneshot(req).await.unwrap();
+            let status = resp.status().as_u16();
+            // 请求体反序列化失败时 axum 返回纯文本
+            let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+            (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+        }
+    };
+
+    // 两个分组：优先级高（数值小）的分组覆盖同名字段
+    let (_, low) = send("POST", "/api/v1/client-groups".into(), serde_json::json!({
+        "name": "Office", "priority": 10,
+        "settings": {"blocking_mode": "nodata", "rate_limit": 50, "cache_enabled": false},
+    })).await;
+    let (status, high) = send("POST", "/api/v1/client-groups".into(), serde_json::json!({
+        "name": "Executives", "priority": 1,
+        "settings": {"blocking_mode": "refused", "query_log_enabled": false},
+    })).await;
+    assert_eq!(status, 200);
+    assert_eq!(high["settings"]["query_log_enabled"], false);
+
+    let (status, client) = send("POST", "/api/v1/clients".into(), serde_json::json!({
+        "name": "CEO laptop", "identifiers": ["10.8.0.5"], "settings": {"safe_search": true},
+    })).await;
+    assert_eq!(status, 200);
+    let client_id = client["id"].as_str().unwrap().to_string();
+    for group in [&low, &high] {
+        let (status, _) = send("POST", format!("/api/v1/client-groups/{}/members", group["id"]),
+            serde_json::json!({"client_ids": [client_id]})).await;
+        assert_eq!(status, 200);
+    }
+
+    let (_, clients) = send("GET", "/api/v1/clients".into(), Value::Null).await;
+    let effective = &clients["data"][0]["effective_settings"];
+    assert_eq!(clients["data"][0]["settings"], serde_json::json!({"safe_search": true}));
+    assert_eq!(effective["blocking_mode"], "refused");
+    assert_eq!(effective["sources"]["blocking_mode"], format!("group:{}", high["id"]));
+    assert_eq!(effective["rate_limit"], 50);
+    assert_eq!(effective["sources"]["rate_limit"], format!("group:{}", low["id"]));
+    assert_eq!(effective["safe_search"], true);
+    assert_eq!(effective["sources"]["safe_search"], "client");
+    assert_eq!(effective["query_log_enabled"], false);
+    assert_eq!(effective["cache_enabled"], false);
+    assert_eq!(effective["sources"]["upstreams"], "global");
+
+    // 生效配置立即作用于 DNS 查询
+    sqlx::query("INSERT INTO custom_rules (id, rule, is_enabled, created_by, created_at) VALUES ('ads', '||ads.invalid^', 1, 'test', datetime('now'))")
+        .execute(&state.db)
+        .await
+        .unwrap();
+    state.filter.reload().await.unwrap();
+    let resp = state.dns_handler.handle(dns_query("ads.invalid"), "10.8.0.5".into()).await.unwrap();
+    assert_eq!(Message::from_vec(&resp).unwrap().response_code(), ResponseCode::Refused);
+
+    // 全局默认值变化反映在未覆盖的字段中
+    let (status, _) = send("PUT", "/api/v1/settings/dns".into(), serde_json::json!({"blocking_mode": "null_ip"})).await;
+    assert_eq!(status, 200);
+    let (status, group) = send("PUT", format!("/api/v1/client-groups/{}", high["id"]),
+        serde_json::json!({"settings": {"query_log_enabled": false}})).await;
+    assert_eq!(status, 200, "{group}");
+    let (_, clients) = send("GET", "/api/v1/clients".into(), Value::Null).await;
+    let effective = &clients["data"][0]["effective_settings"];
+    assert_eq!(effective["blocking_mode"], "nodata");
+    assert_eq!(effective["sources"]["blocking_mode"], format!("group:{}", low["id"]));
+
+    // 非法设置被拒绝
+    for settings in [
+        serde_json::json!({"blocking_mode": "drop"}),
+        serde_json::json!({"rate_limit": 10_000_000}),
+        serde_json::json!({"upstreams": ["1.1.1.1"]}),
+        serde_json::json!({"unknown": 1}),
+    ] {
+        let (status, _) = send("PUT", format!("/api/v1/clients/{client_id}"),
+            serde_json::json!({"settings": settings})).await;
+        assert!(status == 400 || status == 422, "{settings}: {status}");
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// 生效配置：匹配的客户端与标识、分组优先级、上游、过滤状态及每条规则的来源
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_client_effective_config() {
+    let (app, state) = build_test_app().await;
+    let token = ent_dns::auth::jwt::generate(
+        "test-user-id", "admin", "super_admin",
+        &state.jwt_secret, 1,
+    ).expect("Should generate token");
+    let send = |method: &str, uri: String, body: Value| {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let app = app.clone();
+        async move {
+            let resp = app.oneshot(req).await.unwrap();
+            let status = resp.status().as_u16();
+            let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+            (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+        }
+    };
+
+    // 全局规则与改写
+    let (_, global_rule) = send("POST", "/api/v1/rules".into(), serde_json::json!({"rule": "||global.invalid^"})).await;
+    let (_, kids_rule) = send("POST", "/api/v1/rules".into(), serde_json::json!({"rule": "@@||homework.invalid^"})).await;
+    let (status, _) = send("POST", "/api/v1/rewrites".into(), serde_json::json!({"domain": "nas.lan", "answer": "10.0.0.2"})).await;
+    assert_eq!(status, 200);
+
+    // 两个分组：Staff 提供上游，Kids 优先级更高并绑定规则
+    let (_, staff) = send("POST", "/api/v1/client-groups".into(), serde_json::json!({
+        "name": "Staff", "priority": 5, "settings": {"upstreams": ["9.9.9.9"]},
+    })).await;
+    let (_, kids) = send("POST", "/api/v1/client-groups".into(), serde_json::json!({"name": "Kids", "priority": 1})).await;
+    send("POST", format!("/api/v1/client-groups/{}/rules", kids["id"]),
+        serde_json::json!({"rules": [{"rule_id": kids_rule["id"], "rule_type": "custom_rule"}]})).await;
+    send("POST", format!("/api/v1/client-groups/{}/rules", staff["id"]),
+        serde_json::json!({"rules": [{"rule_id": "youtube", "rule_type": "blocked_service"}]})).await;
+
+    let (_, client) = send("POST", "/api/v1/clients".into(), serde_json::json!({
+        "name": "Laptop", "identifiers": ["10.7.0.0/16", "AA-BB-CC-DD-EE-FF"], "blocked_services": ["tiktok"],
+    })).await;
+    let client_id = client["id"].as_str().unwrap().to_string();
+    for group in [&staff, &kids] {
+        send("POST", format!("/api/v1/client-groups/{}/members", group["id"]),
+            serde_json::json!({"client_ids": [client_id]})).await;
+    }
+
+    let (status, config) = send("GET", format!("/api/v1/clients/{client_id}/effective-config"), Value::Null).await;
+    assert_eq!(status, 200, "{config}");
+    assert_eq!(config["client"]["name"], "Laptop");
+    assert_eq!(config["matched_identifier"], Value::Null);
+    let group_names: Vec<&str> = config["groups"].as_array().unwrap().iter().map(|g| g["name"].as_str().unwrap()).collect();
+    assert_eq!(group_names, ["Kids", "Staff"], "按优先级排列");
+    assert_eq!(config["upstreams"], serde_json::json!({"servers": ["9.9.9.9"], "source": format!("group:{}", staff["id"])}));
+    assert_eq!(config["filtering"]["active"], true);
+    assert_eq!(config["settings"]["blocking_mode"], "nxdomain");
+
+    // 规则按 客户端 → 分组（优先级）→ 全局 排列
+    let rules: Vec<(String, String, String)> = config["rules"].as_array().unwrap().iter().map(|r| (
+        r["source"].as_str().unwrap().to_string(),
+        r["rule_type"].as_str().unwrap().to_string(),
+        r["id"].as_str().unwrap().to_string(),
+    )).collect();
+    let kids_rule_id = kids_rule["id"].as_str().unwrap().to_string();
+    assert_eq!(rules[..3], [
+        ("client".into(), "blocked_service".into(), "tiktok".into()),
+        ("group".into(), "custom_rule".into(), kids_rule_id.clone()),
+        ("group".into(), "blocked_service".into(), "youtube".into()),
+    ]);
+    // 分组自定义规则取代全局规则，全局规则不再列出；改写仍然生效
+    assert_eq!(config["filtering"]["group_rules_only"], true);
+    assert!(!rules.iter().any(|(source, rule_type, _)| source == "global" && rule_type == "custom_rule"));
+    assert_eq!(config["total_rules"], rules.len());
+    let bound = &config["rules"][1];
+    assert_eq!(bound["group_name"], "Kids");
+    assert_eq!(bound["action"], "allow");
+    assert_eq!(bound["pattern"], "@@||homework.invalid^");
+    let rewrite = config["rules"].as_array().unwrap().iter().find(|r| r["rule_type"] == "rewrite").unwrap();
+    assert_eq!(rewrite["domain"], "nas.lan");
+    assert_eq!(rewrite["replacement"], "10.0.0.2");
+
+    // 按来源地址：与 DNS 服务相同的客户端识别
+    let (status, by_ip) = send("GET", "/api/v1/clients/effective-config?ip=10.7.3.4".into(), Value::Null).await;
+    assert_eq!(status, 200);
+    assert_eq!(by_ip["ip"], "10.7.3.4");
+    assert_eq!(by_ip["client"]["id"], client_id.as_str());
+    assert_eq!(by_ip["matched_identifier"], serde_json::json!({"value": "10.7.0.0/16", "kind": "cidr"}));
+    assert_eq!(by_ip["rules"], config["rules"]);
+    let (_, by_mac) = send("GET", "/api/v1/clients/effective-config?ip=192.168.5.5&mac=aa:bb:cc:dd:ee:ff".into(), Value::Null).await;
+    assert_eq!(by_mac["matched_identifier"], serde_json::json!({"value": "aa:bb:cc:dd:ee:ff", "kind": "mac"}));
+
+    // 未识别的地址只适用全局配置
+    let (_, unknown) = send("GET", "/api/v1/clients/effective-config?ip=192.0.2.9".into(), Value::Null).await;
+    assert_eq!(unknown["client"], Value::Null);
+    assert_eq!(unknown["matched_identifier"], Value::Null);
+    assert_eq!(unknown["groups"], serde_json::json!([]));
+    assert_eq!(unknown["upstreams"]["source"], "global");
+    assert!(!unknown["upstreams"]["servers"].as_array().unwrap().is_empty());
+    assert!(unknown["rules"].as_array().unwrap().iter().all(|r| r["source"] == "global"));
+    assert!(unknown["rules"].as_array().unwrap().iter().any(|r| r["id"] == global_rule["id"]));
+
+    // 关闭过滤、解绑规则后立即反映
+    send("PUT", format!("/api/v1/clients/{client_id}"), serde_json::json!({"filter_enabled": false})).await;
+    let (status, _) = send("DELETE", format!("/api/v1/client-groups/{}/rules?rule_type=custom_rule", kids["id"]),
+        serde_json::json!({"rule_ids": [kids_rule_id], "rule_type": "custom_rule"})).await;
+    assert_eq!(status, 200);
+    let (_, config) = send("GET", format!("/api/v1/clients/{client_id}/effective-config"), Value::Null).await;
+    assert_eq!(config["filtering"]["enabled"], false);
+    assert_eq!(config["filtering"]["active"], false);
+    assert!(config["rules"].as_array().unwrap().iter().all(|r| r["source"] != "group" || r["rule_type"] != "custom_rule"));
+    assert_eq!(config["filtering"]["group_rules_only"], false);
+    assert!(config["rules"].as_array().unwrap().iter().any(|r| r["id"] == global_rule["id"]));
+
+    // 错误处理
+    let (status, _) = send("GET", "/api/v1/clients/no-such-client/effective-config".into(), Value::Null).await;
+    assert_eq!(status, 404);
+    let (status, _) = send("GET", "/api/v1/clients/effective-config?ip=not-an-ip".into(), Value::Null).await;
+    assert_eq!(status, 400);
+    let (status, _) = send("GET", "/api/v1/clients/effective-config?ip=10.7.0.1&mac=zz".into(), Value::Null).await;
+    assert_eq!(status, 400);
+}
@@ -64,11 +64,18 @@ async fn build_test_state() -> Arc<AppState> {
             upstreams: vec!["https://1.1.1.1/dns-query".to_string()],
             doh_enabled: false,
             dot_enabled: false,
+            dot_port: 0,
+            tls_cert_path: None,
+            tls_key_path: None,
+            client_id_domain: None,
+            proxy_protocol_from: vec![],
         },
         api: ent_dns::config::ApiConfig {
             port: 18101,
             bind: "127.0.0.1".to_string(),
             cors_allowed_origins: vec![],
+            proxy_protocol_from: vec![],
+            forwarded_for_from: vec![],
         },
         database: ent_dns::config::DatabaseConfig {
             path: ":memory:".to_string(),
@@ -101,6 +108,7 @@ async fn build_test_state() -> Arc<AppState> {
                 .build(),
         ),
         client_config_cache: None,
+        forwarded_for_proxies: Default::default(),
     })
 }
 
@@ -336,3 +344,526 @@ async fn test_group_rules_replace_global_filter() {
         "Group client should get NXDOMAIN for the group-specific blocked domain"
     );
 }
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Test 4: Parental-control category selected by a group
+//
+// Binding a category to a group blocks the bundled category domains for group
+// members only; clients outside the group are not affected.
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_group_category_blocking() {
+    let state = build_test_state().await;
+    let db = &state.db;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let client_id = uuid::Uuid::new_v4().to_string();
+    sqlx::query(
+        "INSERT INTO clients (id, name, identifiers, filter_enabled, created_at, updated_at)
+         VALUES (?, 'Category Client', '[\"192.168.150.1\"]', 1, ?, ?)"
+    )
+    .bind(&client_id).bind(&now).bind(&now)
+    .execute(db).await.expect("Insert client");
+
+    let group_id = sqlx::query(
+        "INSERT INTO client_groups (name, priority, created_at, updated_at)
+         VALUES ('Students', 1, ?, ?)"
+    )
+    .bind(&now).bind(&now)
+    .execute(db).await.expect("Insert group")
+    .last_insert_rowid();
+
+    sqlx::query(
+        "INSERT INTO client_group_memberships (client_id, group_id, created_at) VALUES (?, ?, ?)"
+    )
+    .bind(&client_id).bind(group_id).bind(&now)
+    .execute(db).await.expect("Insert membership");
+
+    sqlx::query(
+        "INSERT INTO client_group_rules (group_id, rule_id, rule_type, priority, created_at)
+         VALUES (?, 'gambling', 'category', 0, ?)"
+    )
+    .bind(group_id).bind(&now)
+    .execute(db).await.expect("Bind category");
+
+    let resp = state.dns_handler
+        .handle(build_dns_query("www.bet365.com"), "192.168.150.1".to_string())
+        .await
+        .expect("DNS handle should not return Err");
+    assert_eq!(
+        decode_rcode(&resp),
+        ResponseCode::NXDomain,
+        "Group member should be blocked by the group's gambling category"
+    );
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Test 5: Global parental control
+//
+// With parental_control_enabled, the configured categories apply to every
+// client, including ones without any client/group configuration.
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_global_parental_control_blocks_category() {
+    let state = build_test_state().await;
+    let db = &state.db;
+
+    sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('parental_control_enabled', 'true')")
+        .execute(db).await.expect("Enable parental control");
+    sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES ('parental_control_categories', '[\"dating\"]')")
+        .execute(db).await.expect("Set categories");
+    state.filter.reload().await.expect("FilterEngine::reload");
+
+    let resp = state.dns_handler
+        .handle(build_dns_query("tinder.com"), "10.0.0.77".to_string())
+        .await
+        .expect("DNS handle should not return Err");
+    assert_eq!(
+        decode_rcode(&resp),
+        ResponseCode::NXDomain,
+        "Globally enabled category should block for any client"
+    );
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Test 6: Blocked services per client and per group
+//
+// A service selected on the client itself and a service bound to one of its
+// groups are both enforced; unselected services still resolve normally.
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_blocked_services_client_and_group() {
+    let state = build_test_state().await;
+    let db = &state.db;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let client_id = uuid::Uuid::new_v4().to_string();
+    sqlx::query(
+        "INSERT INTO clients (id, name, identifiers, filter_enabled, blocked_services, created_at, updated_at)
+         VALUES (?, 'Service Client', '[\"192.168.160.1\"]', 1, '[\"tiktok\"]', ?, ?)"
+    )
+    .bind(&client_id).bind(&now).bind(&now)
+    .execute(db).await.expect("Insert client");
+
+    let group_id = sqlx::query(
+        "INSERT INTO client_groups (name, priority, created_at, updated_at)
+         VALUES ('No Games', 1, ?, ?)"
+    )
+    .bind(&now).bind(&now)
+    .execute(db).await.expect("Insert group")
+    .last_insert_rowid();
+
+    sqlx::query(
+        "INSERT INTO client_group_memberships (client_id, group_id, created_at) VALUES (?, ?, ?)"
+    )
+    .bind(&client_id).bind(group_id).bind(&now)
+    .execute(db).await.expect("Insert membership");
+
+    sqlx::query(
+        "INSERT INTO client_group_rules (group_id, rule_id, rule_type, priority, created_at)
+         VALUES (?, 'steam', 'blocked_service', 0, ?)"
+    )
+    .bind(group_id).bind(&now)
+    .execute(db).await.expect("Bind service");
+
+    for domain in ["www.tiktok.com", "store.steampowered.com"] {
+        let resp = state.dns_handler
+            .handle(build_dns_query(domain), "192.168.160.1".to_string())
+            .await
+            .expect("DNS handle should not return Err");
+        assert_eq!(
+            decode_rcode(&resp),
+            ResponseCode::NXDomain,
+            "{} should be blocked by the selected service",
+            domain
+        );
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Test 7: Scheduled group bindings
+//
+// A binding only applies while its schedule is active.  One schedule covers
+// every day around the clock; the other starts two hours from now, so it is
+// inactive for the duration of the test.
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_scheduled_group_bindings() {
+    let state = build_test_state().await;
+    let db = &state.db;
+    let now = chrono::Utc::now();
+    let now_str = now.to_rfc3339();
+    let all_days = r#"["mon","tue","wed","thu","fri","sat","sun"]"#;
+
+    let later = |h: i64| (now + chrono::Duration::hours(h)).format("%H:%M").to_string();
+    let inactive_ranges = format!(r#"[{{"start":"{}","end":"{}"}}]"#, later(2), later(3));
+    for (id, ranges) in [("always", r#"[{"start":"00:00","end":"00:00"}]"#.to_string()), ("later", inactive_ranges)] {
+        sqlx::query(
+            "INSERT INTO schedules (id, name, timezone, weekdays, time_ranges, created_at, updated_at)
+             VALUES (?, ?, 'UTC', ?, ?, ?, ?)"
+        )
+        .bind(id).bind(id).bind(all_days).bind(&ranges).bind(&now_str).bind(&now_str)
+        .execute(db).await.expect("Insert schedule");
+    }
+
+    let client_id = uuid::Uuid::new_v4().to_string();
+    sqlx::query(
+        "INSERT INTO clients (id, name, identifiers, filter_enabled, created_at, updated_at)
+         VALUES (?, 'Scheduled Client', '[\"192.168.170.1\"]', 1, ?, ?)"
+    )
+    .bind(&client_id).bind(&now_str).bind(&now_str)
+    .execute(db).await.expect("Insert client");
+
+    let group_id = sqlx::query(
+        "INSERT INTO client_groups (name, priority, created_at, updated_at)
+         VALUES ('Students', 1, ?, ?)"
+    )
+    .bind(&now_str).bind(&now_str)
+    .execute(db).await.expect("Insert group")
+    .last_insert_rowid();
+
+    sqlx::query(
+        "INSERT INTO client_group_memberships (client_id, group_id, created_at) VALUES (?, ?, ?)"
+    )
+    .bind(&client_id).bind(group_id).bind(&now_str)
+    .execute(db).await.expect("Insert membership");
+
+    for (service, schedule) in [("steam", "always"), ("discord", "later")] {
+        sqlx::query(
+            "INSERT INTO client_group_rules (group_id, rule_id, rule_type, priority, schedule_id, created_at)
+             VALUES (?, ?, 'blocked_service', 0, ?, ?)"
+        )
+        .bind(group_id).bind(service).bind(schedule).bind(&now_str)
+        .execute(db).await.expect("Bind service");
+    }
+
+    let resp = state.dns_handler
+        .handle(build_dns_query("store.steampowered.com"), "192.168.170.1".to_string())
+        .await
+        .expect("DNS handle should not return Err");
+    assert_eq!(
+        decode_rcode(&resp),
+        ResponseCode::NXDomain,
+        "Binding with an active schedule should block"
+    );
+
+    // Outside its schedule the binding is skipped and the query reaches the
+    // resolver.  The upstream answer is environment-dependent, so check the
+    // logged status instead of the RCODE.
+    let mut log_rx = state.query_log_tx.subscribe();
+    let _ = state.dns_handler
+        .handle(build_dns_query("discord.com"), "192.168.170.1".to_string())
+        .await;
+    let event = log_rx.try_recv().expect("query should be logged");
+    assert_ne!(
+        event["status"], "blocked",
+        "Binding with an inactive schedule should not block"
+    );
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Test 8: Filtering pauses
+//
+// A pause on the client's group suspends blocking for that client; an expired
+// pause has no effect.  Pauses are read from the database, so they survive a
+// restart (here: a fresh reload_pauses()).
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_group_pause_suspends_blocking() {
+    let state = build_test_state().await;
+    let db = &state.db;
+    let now = chrono::Utc::now();
+    let now_str = now.to_rfc3339();
+
+    sqlx::query(
+        "INSERT INTO custom_rules (id, rule, comment, is_enabled, created_by, created_at)
+         VALUES ('pause-rule', '||ent-dns-paused.invalid^', 'Pause test', 1, 'test', ?)"
+    )
+    .bind(&now_str)
+    .execute(db).await.expect("Insert global rule");
+    state.filter.reload().await.expect("FilterEngine::reload");
+
+    for (client_id, ip) in [("paused-client", "192.168.180.1"), ("other-client", "192.168.180.2")] {
+        sqlx::query(
+            "INSERT INTO clients (id, name, identifiers, filter_enabled, created_at, updated_at)
+             VALUES (?, ?, ?, 1, ?, ?)"
+        )
+        .bind(client_id).bind(client_id).bind(format!("[\"{}\"]", ip)).bind(&now_str).bind(&now_str)
+        .execute(db).await.expect("Insert client");
+    }
+
+    let group_id = sqlx::query(
+        "INSERT INTO client_groups (name, priority, created_at, updated_at)
+         VALUES ('Helpdesk', 1, ?, ?)"
+    )
+    .bind(&now_str).bind(&now_str)
+    .execute(db).await.expect("Insert group")
+    .last_insert_rowid();
+    sqlx::query(
+        "INSERT INTO client_group_memberships (client_id, group_id, created_at) VALUES ('paused-client', ?, ?)"
+    )
+    .bind(group_id).bind(&now_str)
+    .execute(db).await.expect("Insert membership");
+
+    let expired = (now - chrono::Duration::minutes(1)).to_rfc3339();
+    let active = (now + chrono::Duration::minutes(10)).to_rfc3339();
+    sqlx::query(
+        "INSERT INTO filter_pauses (id, scope, target_id, expires_at, created_by, created_at)
+         VALUES ('p1', 'group', ?, ?, 'test', ?), ('p2', 'client', 'other-client', ?, 'test', ?)"
+    )
+    .bind(group_id.to_string()).bind(&active).bind(&now_str).bind(&expired).bind(&now_str)
+    .execute(db).await.expect("Insert pauses");
+    state.dns_handler.reload_pauses().await.expect("reload_pauses");
+
+    let mut log_rx = state.query_log_tx.subscribe();
+    let _ = state.dns_handler
+        .handle(build_dns_query("ent-dns-paused.invalid"), "192.168.180.1".to_string())
+        .await;
+    let event = log_rx.try_recv().expect("query should be logged");
+    assert_ne!(event["status"], "blocked", "Paused group member should not be filtered");
+
+    let resp = state.dns_handler
+        .handle(build_dns_query("ent-dns-paused.invalid"), "192.168.180.2".to_string())
+        .await
+        .expect("DNS handle should not return Err");
+    assert_eq!(
+        decode_rcode(&resp),
+        ResponseCode::NXDomain,
+        "An expired pause should not suspend filtering"
+    );
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Test 9: Filter lists bound to groups
+//
+// A list bound to a group applies to that group's members only (in addition to
+// their own rules); unbound lists stay global.
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_group_filter_lists() {
+    let state = build_test_state().await;
+    let db = &state.db;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    for (list_id, domain) in [("strict", "ent-dns-strict.invalid"), ("light", "ent-dns-light.invalid")] {
+        sqlx::query(
+            "INSERT INTO filter_lists (id, name, is_enabled, rule_count, created_at) VALUES (?, ?, 1, 1, ?)"
+        )
+        .bind(list_id).bind(list_id).bind(&now)
+        .execute(db).await.expect("Insert filter list");
+        sqlx::query(
+            "INSERT INTO custom_rules (id, rule, is_enabled, created_by, created_at) VALUES (?, ?, 1, ?, ?)"
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(format!("||{}^", domain))
+        .bind(format!("filter:{}", list_id))
+        .bind(&now)
+        .execute(db).await.expect("Insert list rule");
+    }
+
+    for (client_id, ip) in [("kiosk", "192.168.190.1"), ("laptop", "192.168.190.2")] {
+        sqlx::query(
+            "INSERT INTO clients (id, name, identifiers, filter_enabled, created_at, updated_at)
+             VALUES (?, ?, ?, 1, ?, ?)"
+        )
+        .bind(client_id).bind(client_id).bind(format!("[\"{}\"]", ip)).bind(&now).bind(&now)
+        .execute(db).await.expect("Insert client");
+    }
+    let group_id = sqlx::query(
+        "INSERT INTO client_groups (name, priority, created_at, updated_at) VALUES ('Kiosks', 1, ?, ?)"
+    )
+    .bind(&now).bind(&now)
+    .execute(db).await.expect("Insert group")
+    .last_insert_rowid();
+    sqlx::query(
+        "INSERT INTO client_group_memberships (client_id, group_id, created_at) VALUES ('kiosk', ?, ?)"
+    )
+    .bind(group_id).bind(&now)
+    .execute(db).await.expect("Insert membership");
+    sqlx::query(
+        "INSERT INTO client_group_rules (group_id, rule_id, rule_type, priority, created_at)
+         VALUES (?, 'strict', 'filter_list', 0, ?)"
+    )
+    .bind(group_id).bind(&now)
+    .execute(db).await.expect("Bind filter list");
+    state.filter.reload().await.expect("FilterEngine::reload");
+
+    // Group member: bound list plus the global one
+    for domain in ["ent-dns-strict.invalid", "ent-dns-light.invalid"] {
+        let resp = state.dns_handler
+            .handle(build_dns_query(domain), "192.168.190.1".to_string())
+            .await
+            .expect("DNS handle should not return Err");
+        assert_eq!(decode_rcode(&resp), ResponseCode::NXDomain, "{} should be blocked for the kiosk", domain);
+    }
+
+    // Other clients: the bound list no longer applies globally
+    let mut log_rx = state.query_log_tx.subscribe();
+    let _ = state.dns_handler
+        .handle(build_dns_query("ent-dns-strict.invalid"), "192.168.190.2".to_string())
+        .await;
+    let event = log_rx.try_recv().expect("query should be logged");
+    assert_ne!(event["status"], "blocked", "Group-bound list should not block other clients");
+
+    let resp = state.dns_handler
+        .handle(build_dns_query("ent-dns-light.invalid"), "192.168.190.2".to_string())
+        .await
+        .expect("DNS handle should not return Err");
+    assert_eq!(decode_rcode(&resp), ResponseCode::NXDomain, "Unbound list stays global");
+    let event = log_rx.try_recv().expect("query should be logged");
+    assert_eq!(event["reason"], "filter_rule");
+
+    let _ = state.dns_handler
+        .handle(build_dns_query("ent-dns-strict.invalid"), "192.168.190.1".to_string())
+        .await;
+    let event = log_rx.try_recv().expect("query should be logged");
+    assert_eq!(event["reason"], "filter_list:strict");
+}
+
+/// Start a DoT listener with the fixture certificate (`*.dns.example.test`).
+async fn start_dot_server(state: &AppState, client_id_domain: Option<&str>, proxies: &[&str]) -> std::net::SocketAddr {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+    let tls_config = ent_dns::dns::tls::load_tls_config(&format!("{dir}/cert.pem"), &format!("{dir}/key.pem"))
+        .expect("load TLS config");
+    let proxies: Vec<String> = proxies.iter().map(|p| p.to_string()).collect();
+    let proxies = ent_dns::proxy_protocol::TrustedProxies::parse(&proxies).unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(ent_dns::dns::tls::run(
+        state.dns_handler.clone(),
+        listener,
+        tls_config,
+        client_id_domain.map(str::to_string),
+        Arc::new(proxies),
+    ));
+    addr
+}
+
+/// TLS client trusting the fixture CA.
+fn dot_connector() -> tokio_rustls::TlsConnector {
+    use tokio_rustls::rustls;
+
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+    let mut roots = rustls::RootCertStore::empty();
+    let ca_pem = std::fs::read(format!("{dir}/ca.pem")).unwrap();
+    for cert in rustls_pemfile::certs(&mut ca_pem.as_slice()) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let client_config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    tokio_rustls::TlsConnector::from(Arc::new(client_config))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Test 10: Client ID in the DoT server name
+//
+// `{client_id}.dns.example.test` identifies the client ahead of the source
+// address; an unknown client ID falls back to address matching.
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_dot_sni_client_id() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls;
+
+    let state = build_test_state().await;
+    let now = chrono::Utc::now().to_rfc3339();
+    sqlx::query(
+        "INSERT INTO clients (id, name, identifiers, filter_enabled, blocked_services, created_at, updated_at)
+         VALUES ('laptop', 'Roaming Laptop', '[\"kiosk-1\"]', 1, '[\"tiktok\"]', ?, ?)"
+    )
+    .bind(&now).bind(&now)
+    .execute(&state.db).await.expect("Insert client");
+    sqlx::query(
+        "INSERT INTO clients (id, name, identifiers, filter_enabled, blocked_services, created_at, updated_at)
+         VALUES ('desk', 'Desk', '[\"127.0.0.1\"]', 1, '[\"youtube\"]', ?, ?)"
+    )
+    .bind(&now).bind(&now)
+    .execute(&state.db).await.expect("Insert client");
+    state.dns_handler.reload_clients().await;
+
+    let addr = start_dot_server(&state, Some("dns.example.test"), &[]).await;
+    let connector = dot_connector();
+
+    let query = |server_name: &'static str, domain: &'static str| {
+        let connector = connector.clone();
+        async move {
+            let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let name = rustls::pki_types::ServerName::try_from(server_name).unwrap();
+            let mut tls = connector.connect(name, tcp).await.expect("TLS handshake");
+            let mut rcodes = Vec::new();
+            // Several queries over one connection
+            for _ in 0..2 {
+                let msg = build_dns_query(domain);
+                tls.write_all(&(msg.len() as u16).to_be_bytes()).await.unwrap();
+                tls.write_all(&msg).await.unwrap();
+                let mut len = [0u8; 2];
+                tls.read_exact(&mut len).await.unwrap();
+                let mut resp = vec![0u8; u16::from_be_bytes(len) as usize];
+                tls.read_exact(&mut resp).await.unwrap();
+                rcodes.push(decode_rcode(&resp));
+            }
+            rcodes
+        }
+    };
+
+    // 127.0.0.1 is the desk, which does not block tiktok
+    assert_eq!(query("kiosk-1.dns.example.test", "www.tiktok.com").await, vec![ResponseCode::NXDomain; 2]);
+    // The client's own id works as client ID too
+    assert_eq!(query("laptop.dns.example.test", "www.tiktok.com").await, vec![ResponseCode::NXDomain; 2]);
+    assert_eq!(query("other.dns.example.test", "www.youtube.com").await, vec![ResponseCode::NXDomain; 2]);
+    assert_eq!(query("dns.example.test", "www.youtube.com").await, vec![ResponseCode::NXDomain; 2]);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Test 11: DoT behind a load balancer
+//
+// A trusted proxy prefixes the connection with a PROXY header naming the real
+// client; without one its connections are dropped.
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[tokio::test]
+async fn test_dot_proxy_protocol() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::pki_types::ServerName;
+
+    let state = build_test_state().await;
+    let now = chrono::Utc::now().to_rfc3339();
+    sqlx::query(
+        "INSERT INTO clients (id, name, identifiers, filter_enabled, blocked_services, created_at, updated_at)
+         VALUES ('tablet', 'Tablet', '[\"203.0.113.7\"]', 1, '[\"tiktok\"]', ?, ?)"
+    )
+    .bind(&now).bind(&now)
+    .execute(&state.db).await.expect("Insert client");
+
+    let addr = start_dot_server(&state, None, &["127.0.0.0/8"]).await;
+    let connector = dot_connector();
+    let name = ServerName::try_from("dns.example.test").unwrap();
+
+    let mut tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+    tcp.write_all(b"PROXY TCP4 203.0.113.7 127.0.0.1 40000 853\r\n").await.unwrap();
+    let mut tls = connector.connect(name.clone(), tcp).await.expect("TLS handshake");
+    let msg = build_dns_query("www.tiktok.com");
+    tls.write_all(&(msg.len() as u16).to_be_bytes()).await.unwrap();
+    tls.write_all(&msg).await.unwrap();
+    let mut len = [0u8; 2];
+    tls.read_exact(&mut len).await.unwrap();
+    let mut resp = vec![0u8; u16::from_be_bytes(len) as usize];
+    tls.read_exact(&mut resp).await.unwrap();
+    assert_eq!(decode_rcode(&resp), ResponseCode::NXDomain);
+
+    // The ClientHello is not a PROXY header
+    let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+    assert!(connector.connect(name, tcp).await.is_err());
+}
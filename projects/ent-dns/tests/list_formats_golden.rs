@@ -0,0 +1,106 @@
+//! Golden-file tests for filter list formats.
+//!
+//! Each `tests/fixtures/lists/<format>.txt` is parsed both with its explicit
+//! format and with auto-detection; the resulting rules must equal
+//! `<format>.expected` line by line.
+
+use std::path::PathBuf;
+
+use ent_dns::dns::list_formats::{self, ListFormat, ListParser};
+use ent_dns::dns::rules::RuleSet;
+
+fn fixture(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/lists").join(name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("read {}: {}", path.display(), e))
+}
+
+fn check_golden(format: ListFormat) {
+    let name = format.as_str();
+    let content = fixture(&format!("{name}.txt"));
+    let expected: Vec<String> = fixture(&format!("{name}.expected")).lines().map(str::to_string).collect();
+
+    let (used, parsed) = list_formats::parse(&content, format);
+    assert_eq!(used, format);
+    assert_eq!(parsed.rules, expected, "explicit {name}");
+
+    assert_eq!(list_formats::detect_format(&content), format, "detected format of {name}.txt");
+    let (_, detected) = list_formats::parse(&content, ListFormat::Auto);
+    assert_eq!(detected.rules, expected, "auto-detected {name}");
+
+    // Every produced rule must be accepted by the rule engine
+    let mut rules = RuleSet::new();
+    for rule in &parsed.rules {
+        assert!(rules.add_rule(rule), "RuleSet rejected {rule:?} from {name}");
+    }
+}
+
+#[test]
+fn golden_adguard() {
+    check_golden(ListFormat::Adguard);
+    let (_, parsed) = list_formats::parse(&fixture("adguard.txt"), ListFormat::Auto);
+    // `! Expires: 4 days`
+    assert_eq!(parsed.expires_hours, Some(96));
+}
+
+#[test]
+fn golden_hosts() {
+    check_golden(ListFormat::Hosts);
+}
+
+#[test]
+fn golden_domains() {
+    check_golden(ListFormat::Domains);
+}
+
+#[test]
+fn golden_dnsmasq() {
+    check_golden(ListFormat::Dnsmasq);
+}
+
+#[test]
+fn golden_unbound() {
+    check_golden(ListFormat::Unbound);
+}
+
+#[test]
+fn golden_rpz() {
+    check_golden(ListFormat::Rpz);
+    let (_, parsed) = list_formats::parse(&fixture("rpz.txt"), ListFormat::Rpz);
+    // rpz-tcp-only, CNAME local data and the IP trigger
+    assert_eq!(parsed.unsupported, 3);
+}
+
+#[test]
+fn rpz_actions_in_rule_engine() {
+    let (_, parsed) = list_formats::parse(&fixture("rpz.txt"), ListFormat::Rpz);
+    let mut rules = RuleSet::new();
+    for rule in &parsed.rules {
+        rules.add_rule(rule);
+    }
+    // NXDOMAIN covers subdomains
+    assert!(rules.is_blocked("x.ads.example.com"));
+    // NODATA: blocked with an empty answer
+    assert!(rules.is_blocked("nodata.example.net"));
+    assert!(rules.is_nodata("nodata.example.net"));
+    assert!(!rules.is_nodata("ads.example.com"));
+    // PASSTHRU
+    assert!(!rules.is_blocked("allowed.example.com"));
+    // Local data
+    let addrs: Vec<std::net::IpAddr> = vec!["192.0.2.10".parse().unwrap(), "2001:db8::10".parse().unwrap()];
+    assert_eq!(rules.local_data("portal.example.com"), Some(&addrs[..]));
+}
+
+#[test]
+fn streamed_detection_past_sample() {
+    // More significant lines than the detection sample: buffered lines are
+    // replayed once the format is known, later lines are parsed directly
+    let mut parser = ListParser::new(ListFormat::Auto);
+    parser.push_line("# Hosts");
+    for i in 0..500 {
+        parser.push_line(&format!("0.0.0.0 host{i}.example.com"));
+    }
+    let (format, parsed) = parser.finish();
+    assert_eq!(format, ListFormat::Hosts);
+    assert_eq!(parsed.rules.len(), 500);
+    assert_eq!(parsed.rules[499], "||host499.example.com^");
+}